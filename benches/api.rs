@@ -0,0 +1,74 @@
+// 32 concurrent API requests whose handler awaits for 100µs, through a registry behind a Mutex
+// (as `get_api_registry` used to be) and behind an RwLock (as it is now).
+// Run with `cargo bench --bench api`.
+use async_trait::async_trait;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rustnext::{ApiError, ApiHandler, ApiRegistry, ApiResponse, Request};
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Mutex, RwLock};
+
+const CONCURRENT: usize = 32;
+
+struct Slow;
+
+#[async_trait]
+impl ApiHandler for Slow {
+    async fn handle(&self, req: Request) -> Result<ApiResponse, ApiError> {
+        tokio::time::sleep(Duration::from_micros(100)).await;
+        Ok(ApiResponse::ok(json!({ "id": req.param("id") })))
+    }
+}
+
+fn registry() -> ApiRegistry {
+    let mut registry = ApiRegistry::new();
+    for i in 0..200 {
+        registry.add_route(hyper::Method::GET, &format!("/api/resource{}/:id", i), Slow);
+    }
+    registry.add_route(hyper::Method::GET, "/api/projects/:id", Slow);
+    registry
+}
+
+async fn request() -> Request {
+    let req = hyper::Request::builder().uri("/api/projects/42").body(hyper::Body::empty()).unwrap();
+    Request::from_hyper(req).await.unwrap()
+}
+
+fn concurrent(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+    let mutex = Arc::new(Mutex::new(registry()));
+    let rwlock = Arc::new(RwLock::new(registry()));
+
+    let mut group = c.benchmark_group("32 concurrent API requests");
+    group.bench_function("Mutex<ApiRegistry>", |b| {
+        b.iter(|| {
+            runtime.block_on(async {
+                let tasks: Vec<_> = (0..CONCURRENT).map(|_| {
+                    let registry = mutex.clone();
+                    tokio::spawn(async move { registry.lock().await.handle_request(request().await).await })
+                }).collect();
+                for task in tasks {
+                    black_box(task.await.unwrap());
+                }
+            })
+        })
+    });
+    group.bench_function("RwLock<ApiRegistry>", |b| {
+        b.iter(|| {
+            runtime.block_on(async {
+                let tasks: Vec<_> = (0..CONCURRENT).map(|_| {
+                    let registry = rwlock.clone();
+                    tokio::spawn(async move { registry.read().await.handle_request(request().await).await })
+                }).collect();
+                for task in tasks {
+                    black_box(task.await.unwrap());
+                }
+            })
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, concurrent);
+criterion_main!(benches);
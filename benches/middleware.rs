@@ -0,0 +1,104 @@
+// Requests through five stacked middleware (three global, one scoped, one on the route): the
+// Router's chains, composed once per route, against folding the chain into new handlers on every
+// request, as the Router did before. Run with `cargo bench --bench middleware`.
+use async_trait::async_trait;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rustnext::{AppError, Handler, Middleware, Request, Response, Router};
+use std::sync::Arc;
+
+struct PassThrough;
+
+#[async_trait]
+impl Middleware for PassThrough {
+    async fn handle(
+        &self,
+        req: Request,
+        next: Arc<dyn Handler>,
+    ) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+        next.handle(req).await
+    }
+}
+
+// One link of a per-request chain, like the Router's own
+struct Link {
+    middleware: Arc<dyn Middleware>,
+    next: Arc<dyn Handler>,
+}
+
+#[async_trait]
+impl Handler for Link {
+    async fn handle(&self, req: Request) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+        self.middleware.handle(req, self.next.clone()).await
+    }
+}
+
+fn handler() -> impl Handler {
+    |_req: Request| async { Ok::<_, AppError>(Response::new()) }
+}
+
+type Scoped = (Option<&'static str>, Arc<dyn Middleware>);
+
+// The five middleware with the scope each applies to (None for everywhere), folded around the
+// route's handler on every request, as the Router used to
+#[derive(Clone)]
+struct PerRequest {
+    middleware: Arc<Vec<Scoped>>,
+    handler: Arc<dyn Handler>,
+}
+
+impl PerRequest {
+    async fn handle(self, req: Request) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+        let path = req.uri.path().to_string();
+        let chain: Vec<&Arc<dyn Middleware>> = self.middleware.iter()
+            .filter(|(scope, _)| scope.is_none_or(|prefix| path.starts_with(prefix)))
+            .map(|(_, middleware)| middleware)
+            .collect();
+        let handler = chain.into_iter().rev().fold(self.handler.clone(), |next, middleware| {
+            Arc::new(Link { middleware: middleware.clone(), next })
+        });
+        handler.handle(req).await
+    }
+}
+
+async fn request() -> Request {
+    let req = hyper::Request::builder().uri("/items/42").body(hyper::Body::empty()).unwrap();
+    Request::from_hyper(req).await.unwrap()
+}
+
+fn stacked(c: &mut Criterion) {
+    let router = Router::new()
+        .use_middleware(PassThrough)
+        .use_middleware(PassThrough)
+        .use_middleware(PassThrough)
+        .use_middleware_at("/items", PassThrough)
+        .route("/items/:id")
+        .middleware(PassThrough)
+        .get(handler());
+
+    // The same route and middleware, but with the chain built inside the route's handler, so
+    // both go through the same routing and differ only in when the chain is composed
+    let per_request = PerRequest {
+        middleware: Arc::new(vec![
+            (None, Arc::new(PassThrough)),
+            (None, Arc::new(PassThrough)),
+            (None, Arc::new(PassThrough)),
+            (Some("/items"), Arc::new(PassThrough)),
+            (Some("/items"), Arc::new(PassThrough)),
+        ]),
+        handler: Arc::new(handler()),
+    };
+    let baseline = Router::new().get("/items/:id", move |req: Request| per_request.clone().handle(req));
+    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+
+    let mut group = c.benchmark_group("request through 5 middleware");
+    group.bench_function("composed per request", |b| {
+        b.iter(|| runtime.block_on(async { black_box(baseline.handle_request(request().await).await.unwrap()) }))
+    });
+    group.bench_function("composed once", |b| {
+        b.iter(|| runtime.block_on(async { black_box(router.handle_request(request().await).await.unwrap()) }))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, stacked);
+criterion_main!(benches);
@@ -0,0 +1,58 @@
+// Route lookup with 500 routes: the Router's RegexSet table against trying each route's regex in
+// registration order, as the Router did before. Run with `cargo bench --bench routing`.
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use hyper::Method;
+use rustnext::{AppError, Handler, Request, Response, Route, Router};
+use std::sync::Arc;
+
+const ROUTES: usize = 500;
+
+fn handler() -> impl Handler {
+    |_req: Request| async { Ok::<_, AppError>(Response::new()) }
+}
+
+// A mix of static, parameter and wildcard routes, like a large app's
+fn paths() -> Vec<String> {
+    (0..ROUTES)
+        .map(|i| match i % 4 {
+            0 => format!("/section{}/items", i),
+            1 => format!("/section{}/items/:id", i),
+            2 => format!("/section{}/items/:id/comments/:comment_id", i),
+            _ => format!("/section{}/files/*", i),
+        })
+        .collect()
+}
+
+fn lookup(c: &mut Criterion) {
+    let paths = paths();
+    let router = paths.iter().fold(Router::new(), |router, path| router.get(path, handler()));
+    let routes: Vec<Route> = paths.iter().map(|path| Route::new(Method::GET, path, Arc::new(handler()))).collect();
+
+    let requests = [
+        "/section0/items",
+        "/section249/items/42",
+        "/section498/items/42/comments/7",
+        "/section499/files/a/b.css",
+        "/not/found",
+    ];
+
+    let mut group = c.benchmark_group("route lookup, 500 routes");
+    group.bench_function("linear scan", |b| {
+        b.iter(|| {
+            for path in requests {
+                black_box(routes.iter().find_map(|route| route.matches(&Method::GET, black_box(path))));
+            }
+        })
+    });
+    group.bench_function("route table", |b| {
+        b.iter(|| {
+            for path in requests {
+                black_box(router.match_route(&Method::GET, black_box(path)));
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, lookup);
+criterion_main!(benches);
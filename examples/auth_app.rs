@@ -0,0 +1,74 @@
+use rustnext::*;
+use rustnext::auth::{JwtAuth, LoginHandler, LogoutHandler, MemoryUserStore, OptionalAuth, RefreshHandler};
+use rustnext::middleware::auth_guard::{AuthGuard, RoleHierarchy};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+// Forwards a request to the API registry
+async fn dispatch_api(req: Request) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+    match get_api_registry().read().await.handle_request(req).await {
+        Some(response) => Ok(response),
+        None => Ok(Response::new().status(StatusCode::NOT_FOUND).json(&json!({"error": "Not found"}))?),
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let users = Arc::new(MemoryUserStore::new());
+    users.add_user("alice", "wonderland", vec!["admin".to_string()]).await?;
+    users.add_user("bob", "builder", vec!["user".to_string()]).await?;
+
+    let jwt = Arc::new(JwtAuth::new("change-me-in-production"));
+    let roles = Arc::new(RoleHierarchy::new().role("admin", &["editor"]).role("editor", &["user"]));
+
+    api_route!(
+        Method::POST,
+        "/api/login",
+        LoginHandler::jwt(users.clone(), jwt.clone()).max_failed_attempts(5, Duration::from_secs(300))
+    ).await?;
+    api_route!(Method::POST, "/api/refresh", RefreshHandler::new(jwt.clone())).await?;
+    api_route!(Method::POST, "/api/logout", LogoutHandler::jwt(jwt.clone())).await?;
+
+    let router = Router::new()
+        .post("/api/login", dispatch_api)
+        .post("/api/refresh", dispatch_api)
+        .post("/api/logout", dispatch_api)
+        .route("/api/greeting")
+        .middleware(OptionalAuth::new(jwt.clone()))
+        .get(|req: Request| async move {
+            let message = match &req.user_id {
+                Some(user_id) => format!("Welcome back, {}", user_id),
+                None => "Hello, guest".to_string(),
+            };
+            Json(json!({"message": message}))
+        })
+        .route("/api/me")
+        .middleware(AuthGuard::new().from_jwt(jwt.clone()))
+        .get(|req: Request| async move {
+            Json(json!({
+                "user_id": req.user_id,
+                "roles": req.user_roles,
+            }))
+        })
+        .route("/api/drafts")
+        .middleware(AuthGuard::new().from_jwt(jwt.clone()).require_role("editor").with_hierarchy(roles.clone()))
+        .get(|_req: Request| async move {
+            Json(json!({"drafts": []}))
+        })
+        .route("/api/admin")
+        .middleware(AuthGuard::new().from_jwt(jwt.clone()).require_role("admin"))
+        .get(|_req: Request| async move {
+            Json(json!({"message": "Welcome, admin"}))
+        });
+
+    let app = App::new().router(router);
+    let addr: SocketAddr = "127.0.0.1:3000".parse()?;
+    let server = Server::new(app, addr);
+
+    println!("🔐 RustNext auth example running at http://127.0.0.1:3000");
+    println!("   curl -X POST localhost:3000/api/login -H 'Content-Type: application/json' -d '{{\"username\":\"alice\",\"password\":\"wonderland\"}}'");
+    println!("   curl localhost:3000/api/me -H 'Authorization: Bearer <access_token>'");
+
+    server.run().await
+}
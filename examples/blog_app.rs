@@ -1,5 +1,4 @@
 use rustnext::*;
-use rustnext::ui::*;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::net::SocketAddr;
@@ -41,7 +40,7 @@ fn get_blog_posts() -> Vec<BlogPost> {
 }
 
 // Blog Layout Component
-component!(blog_layout, props => {
+component!(BlogLayout, props => {
     let title = props.get("title").and_then(|v| v.as_str()).unwrap_or("RustNext Blog");
     let children = props.get("children").cloned().unwrap_or_default();
     
@@ -74,7 +73,7 @@ component!(blog_layout, props => {
                 )
         )
         .child(
-            main()
+            rustnext::ui::main()
                 .class("main")
                 .child(
                     div()
@@ -99,7 +98,7 @@ component!(blog_layout, props => {
 });
 
 // Blog Post Card Component
-component!(blog_post_card, props => {
+component!(BlogPostCard, props => {
     let post_id = props.get("id").and_then(|v| v.as_u64()).unwrap_or(0);
     let title = props.get("title").and_then(|v| v.as_str()).unwrap_or("Untitled");
     let content = props.get("content").and_then(|v| v.as_str()).unwrap_or("");
@@ -142,7 +141,7 @@ component!(blog_post_card, props => {
 });
 
 // Home Page
-page!(HomePage, req => {
+page!(HomePage, _req => {
     let posts = get_blog_posts();
     let mut post_cards = Vec::new();
     
@@ -154,9 +153,7 @@ page!(HomePage, req => {
         post_props.insert("author".to_string(), json!(post.author));
         post_props.insert("created_at".to_string(), json!(post.created_at));
         
-        if let Some(card) = get_component_registry().render("blog_post_card", &post_props) {
-            post_cards.push(card);
-        }
+        post_cards.push(get_component_registry().lock().await.render("blog_post_card", &post_props).await.unwrap_or_else(RenderError::into_element));
     }
     
     let mut layout_props = HashMap::new();
@@ -170,9 +167,7 @@ page!(HomePage, req => {
     
     layout_props.insert("children".to_string(), json!(get_renderer().render_to_html(&content)));
     
-    get_component_registry().render("blog_layout", &layout_props).unwrap_or_else(|| {
-        div().child(text("Error rendering page"))
-    })
+    get_component_registry().lock().await.render("blog_layout", &layout_props).await.unwrap_or_else(RenderError::into_element)
 });
 
 // Individual Post Page
@@ -230,13 +225,11 @@ page!(PostPage, req => {
     ));
     layout_props.insert("children".to_string(), json!(get_renderer().render_to_html(&content)));
     
-    get_component_registry().render("blog_layout", &layout_props).unwrap_or_else(|| {
-        div().child(text("Error rendering page"))
-    })
+    get_component_registry().lock().await.render("blog_layout", &layout_props).await.unwrap_or_else(RenderError::into_element)
 });
 
 // About Page
-page!(AboutPage, req => {
+page!(AboutPage, _req => {
     let content = section()
         .child(
             h2().child(text("About RustNext Blog"))
@@ -267,40 +260,38 @@ page!(AboutPage, req => {
     layout_props.insert("title".to_string(), json!("About"));
     layout_props.insert("children".to_string(), json!(get_renderer().render_to_html(&content)));
     
-    get_component_registry().render("blog_layout", &layout_props).unwrap_or_else(|| {
-        div().child(text("Error rendering page"))
-    })
+    get_component_registry().lock().await.render("blog_layout", &layout_props).await.unwrap_or_else(RenderError::into_element)
 });
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     // Register components
-    register_component!("blog_layout", blog_layout);
-    register_component!("blog_post_card", blog_post_card);
+    register_component!("blog_layout", BlogLayout).await?;
+    register_component!("blog_post_card", BlogPostCard).await?;
     
     // Register pages
-    register_page!("/", HomePage);
-    register_page!("/post/:id", PostPage);
-    register_page!("/about", AboutPage);
+    register_page!("/", HomePage).await?;
+    register_page!("/post/:id", PostPage).await?;
+    register_page!("/about", AboutPage).await?;
     
     // Create router with page handler
     let router = Router::new()
         .get("/", |req| async move {
-            if let Some(element) = get_page_registry().render_page("/", &req) {
+            if let Some(element) = get_page_registry().lock().await.render_page("/", &req).await {
                 get_renderer().render_to_response(&element)
             } else {
                 Ok(Response::new().status(StatusCode::NOT_FOUND).text("Page not found"))
             }
         })
         .get("/post/:id", |req| async move {
-            if let Some(element) = get_page_registry().render_page("/post/:id", &req) {
+            if let Some(element) = get_page_registry().lock().await.render_page("/post/:id", &req).await {
                 get_renderer().render_to_response(&element)
             } else {
                 Ok(Response::new().status(StatusCode::NOT_FOUND).text("Page not found"))
             }
         })
         .get("/about", |req| async move {
-            if let Some(element) = get_page_registry().render_page("/about", &req) {
+            if let Some(element) = get_page_registry().lock().await.render_page("/about", &req).await {
                 get_renderer().render_to_response(&element)
             } else {
                 Ok(Response::new().status(StatusCode::NOT_FOUND).text("Page not found"))
@@ -1,13 +1,12 @@
 use rustnext::*;
 use rustnext::ui::{Element, div, header, nav, a, text, main as main_element, h1, form, input, button, section, h2, ul, li, span, article, p, get_component_registry, get_renderer};
-use rustnext::middleware::auth_guard::RateLimiter;
+use rustnext::middleware::RateLimiter;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::{Mutex, Arc};
 use log::{info, error};
 use once_cell::sync::Lazy;
-use urlencoding;
 
 // In-memory storage for blog posts (for demonstration without a database)
 static BLOG_POSTS: Lazy<Mutex<Vec<BlogPost>>> = Lazy::new(|| Mutex::new(vec![
@@ -49,13 +48,17 @@ struct CreatePostHandler;
 #[async_trait]
 impl ApiHandler for CreatePostHandler {
     async fn handle(&self, mut req: Request) -> Result<ApiResponse, ApiError> {
-        let content_type = req.headers.get(hyper::header::CONTENT_TYPE)
-            .and_then(|v| v.to_str().ok())
-            .unwrap_or("");
-
-        let form_data = if content_type.starts_with("application/x-www-form-urlencoded") {
-            req.form().await.map_err(|e| ApiError::bad_request(&format!("Failed to parse form data: {}", e)))?
-        } else if content_type.starts_with("multipart/form-data") {
+        let form_data = if req.is_form() {
+            req.form().await?.clone()
+        } else if req.is_json() {
+            let body: CreatePostRequest = serde_json::from_value(req.json().await?)
+                .map_err(|e| ApiError::bad_request(&format!("Invalid post: {}", e)))?;
+            HashMap::from([
+                ("title".to_string(), body.title),
+                ("content".to_string(), body.content),
+                ("author".to_string(), body.author),
+            ])
+        } else if req.is_multipart() {
             return Err(ApiError::bad_request("Multipart form data not fully supported in this example. Please use application/x-www-form-urlencoded."));
         } else {
             return Err(ApiError::bad_request("Unsupported Content-Type for post creation."));
@@ -82,10 +85,7 @@ impl ApiHandler for CreatePostHandler {
         posts.push(new_post.clone());
         info!("New post created: {:?}", new_post);
 
-        Ok(ApiResponse::ok(json!({"message": "Post created successfully"}))
-            .header("Location", "/")
-            .header("HX-Redirect", "/")
-            .with_status(StatusCode::SEE_OTHER))
+        Ok(ApiResponse::redirect("/"))
     }
 }
 
@@ -277,9 +277,7 @@ page!(CreatePostPage, req => {
     
     let component_registry = get_component_registry().lock().await;
     let rendered_element = component_registry.render("enhanced_blog_layout", &layout_props).await;
-    rendered_element.unwrap_or_else(|| {
-        div().child(text("Error rendering page"))
-    })
+    rendered_element.unwrap_or_else(RenderError::into_element)
 });
 
 // Individual Post Page
@@ -351,9 +349,7 @@ page!(PostPage, req => {
     
     let component_registry = get_component_registry().lock().await;
     let rendered_element = component_registry.render("enhanced_blog_layout", &layout_props).await;
-    rendered_element.unwrap_or_else(|| {
-        div().child(text("Error rendering page"))
-    })
+    rendered_element.unwrap_or_else(RenderError::into_element)
 });
 
 // About Page
@@ -396,9 +392,7 @@ page!(AboutPage, _req => {
     
     let component_registry = get_component_registry().lock().await;
     let rendered_element = component_registry.render("enhanced_blog_layout", &layout_props).await;
-    rendered_element.unwrap_or_else(|| {
-        div().child(text("Error rendering page"))
-    })
+    rendered_element.unwrap_or_else(RenderError::into_element)
 });
 
 // Enhanced Home Page with API integration info
@@ -420,9 +414,7 @@ page!(EnhancedHomePage, _req => {
         post_props.insert("created_at".to_string(), json!(post.created_at));
         
         let component_registry = get_component_registry().lock().await;
-        if let Some(card) = component_registry.render("blog_post_card", &post_props).await {
-            post_cards.push(card);
-        }
+        post_cards.push(component_registry.render("blog_post_card", &post_props).await.unwrap_or_else(RenderError::into_element));
     }
 
     let content = section()
@@ -451,9 +443,7 @@ page!(EnhancedHomePage, _req => {
     
     let component_registry = get_component_registry().lock().await;
     let rendered_element = component_registry.render("enhanced_blog_layout", &layout_props).await;
-    rendered_element.unwrap_or_else(|| {
-        div().child(text("Error rendering page"))
-    })
+    rendered_element.unwrap_or_else(RenderError::into_element)
 });
 
 #[tokio::main]
@@ -462,7 +452,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     init_logging();
 
     // Initialize configuration from file (if exists) or environment
-    let config = Config::load(Some("config.toml"));
+    let config = Config::load_or_default(Some("config.toml"));
     init_config(config.clone());
     
     info!("🔧 Configuration loaded:");
@@ -533,7 +523,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
             }
         })
         .get("/api/posts", |req| async move {
-            let api_registry = get_api_registry().lock().await;
+            let api_registry = get_api_registry().read().await;
             if let Some(response) = api_registry.handle_request(req).await {
                 Ok(response)
             } else {
@@ -541,7 +531,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
             }
         })
         .post("/api/posts", |req| async move {
-            let api_registry = get_api_registry().lock().await;
+            let api_registry = get_api_registry().read().await;
             match api_registry.handle_request(req).await {
                 Some(response) => {
                     if response.status == StatusCode::SEE_OTHER {
@@ -550,10 +540,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
                         let body_bytes = hyper::body::to_bytes(response.body).await.map_err(|e| Box::new(AppError::Internal(e.to_string())) as Box<dyn std::error::Error + Send + Sync>)?;
                         let error_json: serde_json::Value = serde_json::from_slice(&body_bytes).map_err(|e| Box::new(AppError::BadRequest(e.to_string())) as Box<dyn std::error::Error + Send + Sync>)?;
                         let error_msg = error_json["error"].as_str().unwrap_or("Unknown error").to_string();
-                        Ok(Response::new()
-                            .status(StatusCode::SEE_OTHER)
-                            .header("Location", format!("/create?error={}", urlencoding::encode(&error_msg)))
-                            .header("HX-Redirect", format!("/create?error={}", urlencoding::encode(&error_msg))))
+                        Ok(Response::see_other(&format!("/create?error={}", urlencoding::encode(&error_msg)))
+                            .hx_redirect(&format!("/create?error={}", urlencoding::encode(&error_msg))))
                     }
                 }
                 None => Err(Box::new(AppError::NotFound("API endpoint /api/posts (POST) not found".to_string())) as Box<dyn std::error::Error + Send + Sync>),
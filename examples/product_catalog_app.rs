@@ -1,13 +1,12 @@
 use rustnext::*;
 use rustnext::ui::{Element, div, header, nav, a, text, main as main_element, h1, form, input, button, section, h2, ul, li, span, article, p, label, get_component_registry, get_renderer};
-use rustnext::middleware::auth_guard::RateLimiter;
+use rustnext::middleware::RateLimiter;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::{Mutex, Arc};
 use log::{info, error};
 use once_cell::sync::Lazy;
-use urlencoding;
 
 // In-memory storage for products
 static PRODUCTS: Lazy<Mutex<Vec<Product>>> = Lazy::new(|| Mutex::new(vec![
@@ -52,9 +51,9 @@ struct GetProductsHandler;
 
 #[async_trait]
 impl ApiHandler for GetProductsHandler {
-    async fn handle(&self, _req: Request) -> Result<ApiResponse, ApiError> {
-        let products = PRODUCTS.lock().unwrap().clone();
-        Ok(ApiResponse::ok(serde_json::to_value(products).unwrap()))
+    async fn handle(&self, req: Request) -> Result<ApiResponse, ApiError> {
+        let products = PRODUCTS.lock().unwrap();
+        Ok(Paginated::from_slice(&products, Pagination::from_request(&req)).respond(&req))
     }
 }
 
@@ -70,7 +69,11 @@ impl ApiHandler for GetProductHandler {
 
         let products = PRODUCTS.lock().unwrap();
         if let Some(product) = products.iter().find(|p| p.id == product_id) {
-            Ok(ApiResponse::ok(serde_json::to_value(product).unwrap()))
+            let etag = etag_for(product);
+            if let Some(not_modified) = req.not_modified(&etag) {
+                return Ok(not_modified);
+            }
+            Ok(ApiResponse::ok(serde_json::to_value(product).unwrap()).with_etag(&etag))
         } else {
             Err(ApiError::not_found(&format!("Product with ID {} not found", product_id)))
         }
@@ -80,43 +83,44 @@ impl ApiHandler for GetProductHandler {
 // API Handler for creating products
 struct CreateProductHandler;
 
-#[async_trait]
-impl ApiHandler for CreateProductHandler {
-    async fn handle(&self, mut req: Request) -> Result<ApiResponse, ApiError> {
-        let form_data = req.form().await.map_err(|e| ApiError::bad_request(&format!("Failed to parse form data: {}", e)))?;
-        
-        let name = form_data.get("name").map(|s| s.trim()).filter(|s| !s.is_empty());
-        let description = form_data.get("description").map(|s| s.trim()).filter(|s| !s.is_empty());
-        let price_str = form_data.get("price").map(|s| s.trim()).filter(|s| !s.is_empty());
-        let category = form_data.get("category").map(|s| s.trim()).filter(|s| !s.is_empty());
-
-        let price: f64 = price_str
-            .ok_or_else(|| ApiError::bad_request("Price is required."))?
-            .parse()
-            .map_err(|_| ApiError::bad_request("Invalid price format."))?;
+// A non-numeric price is rejected while parsing the form
+#[derive(Deserialize, Serialize)]
+struct NewProduct {
+    name: String,
+    description: String,
+    price: f64,
+    category: String,
+}
 
-        if name.is_none() || description.is_none() || category.is_none() {
-            return Err(ApiError::bad_request("Name, description, price, and category are required."));
-        }
+#[async_trait]
+impl TypedApiHandler for CreateProductHandler {
+    type Body = NewProduct;
+
+    fn validate(&self, body: &NewProduct) -> Result<(), ValidationErrors> {
+        let required = [ValidationRule::Required];
+        validate_request(body, &Rules::new()
+            .field("name", &required)
+            .field("description", &required)
+            .field("price", &[ValidationRule::Range(0.0, f64::MAX)])
+            .field("category", &required))
+    }
 
+    async fn handle(&self, _req: Request, body: NewProduct) -> Result<ApiResponse, ApiError> {
         let mut products = PRODUCTS.lock().unwrap();
         let new_id = products.iter().map(|p| p.id).max().unwrap_or(0) + 1;
 
         let new_product = Product {
             id: new_id,
-            name: name.unwrap().to_string(),
-            description: description.unwrap().to_string(),
-            price,
-            category: category.unwrap().to_string(),
+            name: body.name.trim().to_string(),
+            description: body.description.trim().to_string(),
+            price: body.price,
+            category: body.category.trim().to_string(),
             created_at: chrono::Utc::now().format("%Y-%m-%d").to_string(),
         };
         products.push(new_product.clone());
         info!("New product created: {:?}", new_product);
 
-        Ok(ApiResponse::ok(json!({"message": "Product created successfully", "product_id": new_product.id}))
-            .header("Location", "/")
-            .header("HX-Redirect", "/")
-            .with_status(StatusCode::SEE_OTHER))
+        Ok(ApiResponse::redirect("/"))
     }
 }
 
@@ -127,10 +131,10 @@ struct UpdateProductHandler;
 impl ApiHandler for UpdateProductHandler {
     async fn handle(&self, mut req: Request) -> Result<ApiResponse, ApiError> {
         let product_id: u32 = req.param("id")
-            .and_then(|id| id.parse().ok())
-            .ok_or_else(|| ApiError::bad_request("Invalid product ID"))?;
+            .ok_or_else(|| ApiError::bad_request("Missing product ID"))?
+            .parse()?;
 
-        let form_data = req.form().await.map_err(|e| ApiError::bad_request(&format!("Failed to parse form data: {}", e)))?;
+        let form_data = req.form().await?.clone();
         
         let name = form_data.get("name").map(|s| s.trim()).filter(|s| !s.is_empty());
         let description = form_data.get("description").map(|s| s.trim()).filter(|s| !s.is_empty());
@@ -144,13 +148,17 @@ impl ApiHandler for UpdateProductHandler {
 
         let mut products = PRODUCTS.lock().unwrap();
         if let Some(product) = products.iter_mut().find(|p| p.id == product_id) {
+            // Clients that fetched the product with its ETag can send If-Match to avoid lost updates
+            req.if_match_satisfied(&etag_for(&*product))?;
+
             if let Some(n) = name { product.name = n.to_string(); }
             if let Some(d) = description { product.description = d.to_string(); }
             product.price = price;
             if let Some(c) = category { product.category = c.to_string(); }
             
             info!("Product {} updated: {:?}", product_id, product);
-            Ok(ApiResponse::ok(json!({"message": "Product updated successfully", "product": product})))
+            Ok(ApiResponse::ok(json!({"message": "Product updated successfully", "product": product}))
+                .with_etag(&etag_for(&*product)))
         } else {
             Err(ApiError::not_found(&format!("Product with ID {} not found", product_id)))
         }
@@ -380,10 +388,10 @@ page!(ProductListingPage, req => {
         product_props.insert("price".to_string(), json!(product.price));
         product_props.insert("category".to_string(), json!(product.category));
         
-        let component_registry_arc = get_component_registry().clone();
+        let component_registry_arc = get_component_registry();
         product_cards_futures.push(async move {
             let component_registry = component_registry_arc.lock().await;
-            component_registry.render("product_card", &product_props).await.unwrap_or_else(|| div())
+            component_registry.render("product_card", &product_props).await.unwrap_or_else(RenderError::into_element)
         });
     }
 
@@ -411,9 +419,7 @@ page!(ProductListingPage, req => {
     
     let component_registry = get_component_registry().lock().await;
     let rendered_element = component_registry.render("catalog_layout", &layout_props).await;
-    rendered_element.unwrap_or_else(|| {
-        div().child(text("Error rendering page"))
-    })
+    rendered_element.unwrap_or_else(RenderError::into_element)
 });
 
 // New Product Page
@@ -422,7 +428,7 @@ page!(NewProductPage, req => {
     layout_props.insert("title".to_string(), json!("Add New Product"));
     let product_form_element = {
         let component_registry = get_component_registry().lock().await;
-        component_registry.render("product_form", &HashMap::new()).await.unwrap_or_else(|| div())
+        component_registry.render("product_form", &HashMap::new()).await.unwrap_or_else(RenderError::into_element)
     };
     layout_props.insert("children_html".to_string(), json!(get_renderer().render_to_html(&product_form_element)));
     layout_props.insert("active_path".to_string(), json!(req.uri.path()));
@@ -436,9 +442,7 @@ page!(NewProductPage, req => {
     
     let component_registry = get_component_registry().lock().await;
     let rendered_element = component_registry.render("catalog_layout", &layout_props).await;
-    rendered_element.unwrap_or_else(|| {
-        div().child(text("Error rendering page"))
-    })
+    rendered_element.unwrap_or_else(RenderError::into_element)
 });
 
 // Product Detail Page
@@ -503,9 +507,7 @@ page!(ProductDetailPage, req => {
     
     let component_registry = get_component_registry().lock().await;
     let rendered_element = component_registry.render("catalog_layout", &layout_props).await;
-    rendered_element.unwrap_or_else(|| {
-        div().child(text("Error rendering page"))
-    })
+    rendered_element.unwrap_or_else(RenderError::into_element)
 });
 
 // Edit Product Page
@@ -527,7 +529,7 @@ page!(EditProductPage, req => {
         form_props.insert("category".to_string(), json!(product.category));
         
         let component_registry = get_component_registry().lock().await;
-        component_registry.render("product_form", &form_props).await.unwrap_or_else(|| div())
+        component_registry.render("product_form", &form_props).await.unwrap_or_else(RenderError::into_element)
     } else {
         div()
             .class("card")
@@ -552,9 +554,7 @@ page!(EditProductPage, req => {
     
     let component_registry = get_component_registry().lock().await;
     let rendered_element = component_registry.render("catalog_layout", &layout_props).await;
-    rendered_element.unwrap_or_else(|| {
-        div().child(text("Error rendering page"))
-    })
+    rendered_element.unwrap_or_else(RenderError::into_element)
 });
 
 
@@ -595,9 +595,7 @@ page!(AboutPage, req => {
 
     let component_registry = get_component_registry().lock().await;
     let rendered_element = component_registry.render("catalog_layout", &layout_props).await;
-    rendered_element.unwrap_or_else(|| {
-        div().child(text("Error rendering page"))
-    })
+    rendered_element.unwrap_or_else(RenderError::into_element)
 });
 
 
@@ -607,7 +605,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     init_logging();
 
     // Initialize configuration from file (if exists) or environment
-    let config = Config::load(Some("config.toml"));
+    let config = Config::load_or_default(Some("config.toml"));
     init_config(config.clone());
 
     info!("🔧 Configuration loaded:");
@@ -696,8 +694,20 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
                 Err(Box::new(AppError::NotFound("About page not found".to_string())) as Box<dyn std::error::Error + Send + Sync>)
             }
         })
+        .get("/api/products", |req: Request| async move {
+            match get_api_registry().read().await.handle_request(req).await {
+                Some(response) => Ok(response),
+                None => Err(Box::new(AppError::NotFound("API endpoint /api/products (GET) not found".to_string())) as Box<dyn std::error::Error + Send + Sync>),
+            }
+        })
+        .get("/api/products/:id", |req: Request| async move {
+            match get_api_registry().read().await.handle_request(req).await {
+                Some(response) => Ok(response),
+                None => Err(Box::new(AppError::NotFound("API endpoint /api/products/:id (GET) not found".to_string())) as Box<dyn std::error::Error + Send + Sync>),
+            }
+        })
         .post("/api/products", |req: Request| async move {
-            let api_registry = get_api_registry().lock().await;
+            let api_registry = get_api_registry().read().await;
             match api_registry.handle_request(req).await {
                 Some(response) => {
                     if response.status == StatusCode::SEE_OTHER {
@@ -706,17 +716,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
                         let body_bytes = hyper::body::to_bytes(response.body).await.map_err(|e| Box::new(AppError::Internal(e.to_string())) as Box<dyn std::error::Error + Send + Sync>)?;
                         let error_json: serde_json::Value = serde_json::from_slice(&body_bytes).map_err(|e| Box::new(AppError::BadRequest(e.to_string())) as Box<dyn std::error::Error + Send + Sync>)?;
                         let error_msg = error_json["error"].as_str().unwrap_or("Unknown error").to_string();
-                        Ok(Response::new()
-                            .status(StatusCode::SEE_OTHER)
-                            .header("Location", &format!("/products/new?error={}", urlencoding::encode(&error_msg)))
-                            .header("HX-Redirect", &format!("/products/new?error={}", urlencoding::encode(&error_msg))))
+                        Ok(Response::see_other(&format!("/products/new?error={}", urlencoding::encode(&error_msg)))
+                            .hx_redirect(&format!("/products/new?error={}", urlencoding::encode(&error_msg))))
                     }
                 }
                 None => Err(Box::new(AppError::NotFound("API endpoint /api/products (POST) not found".to_string())) as Box<dyn std::error::Error + Send + Sync>),
             }
         })
         .post("/api/products/:id/update", |req: Request| async move {
-            let api_registry = get_api_registry().lock().await;
+            let api_registry = get_api_registry().read().await;
             let product_id_str = req.param("id").cloned().unwrap_or_default();
             match api_registry.handle_request(req).await {
                 Some(response) => {
@@ -726,21 +734,19 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
                         let body_bytes = hyper::body::to_bytes(response.body).await.map_err(|e| Box::new(AppError::Internal(e.to_string())) as Box<dyn std::error::Error + Send + Sync>)?;
                         let error_json: serde_json::Value = serde_json::from_slice(&body_bytes).map_err(|e| Box::new(AppError::BadRequest(e.to_string())) as Box<dyn std::error::Error + Send + Sync>)?;
                         let error_msg = error_json["error"].as_str().unwrap_or("Unknown error").to_string();
-                        Ok(Response::new()
-                            .status(StatusCode::SEE_OTHER)
-                            .header("Location", &format!("/products/{}?error={}", product_id_str, urlencoding::encode(&error_msg)))
-                            .header("HX-Redirect", &format!("/products/{}?error={}", product_id_str, urlencoding::encode(&error_msg))))
+                        Ok(Response::see_other(&format!("/products/{}?error={}", product_id_str, urlencoding::encode(&error_msg)))
+                            .hx_redirect(&format!("/products/{}?error={}", product_id_str, urlencoding::encode(&error_msg))))
                     }
                 }
                 None => Err(Box::new(AppError::NotFound("API endpoint /api/products/:id/update (POST) not found".to_string())) as Box<dyn std::error::Error + Send + Sync>),
             }
         })
         .post("/api/products/:id/delete", |req: Request| async move {
-            let api_registry = get_api_registry().lock().await;
+            let api_registry = get_api_registry().read().await;
             match api_registry.handle_request(req).await {
                 Some(_response) => {
                     // After delete, redirect back to product listing to show updated list
-                    Ok(Response::new().status(StatusCode::SEE_OTHER).header("Location", "/?success=Product%20deleted%20successfully"))
+                    Ok(Response::see_other("/?success=Product%20deleted%20successfully"))
                 }
                 None => Err(Box::new(AppError::NotFound("API endpoint /api/products/:id/delete (POST) not found".to_string())) as Box<dyn std::error::Error + Send + Sync>),
             }
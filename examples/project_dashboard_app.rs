@@ -1,13 +1,12 @@
 use rustnext::*;
 use rustnext::ui::{Element, div, header, nav, a, text, main as main_element, h1, form, input, button, section, h2, ul, li, span, article, p, label, get_component_registry, get_renderer};
-use rustnext::middleware::auth_guard::RateLimiter;
+use rustnext::middleware::RateLimiter;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::net::SocketAddr;
-use std::sync::{Mutex, Arc};
+use std::sync::Mutex;
 use log::{info, error};
 use once_cell::sync::Lazy;
-use urlencoding;
 
 // In-memory storage for projects and tasks
 static PROJECTS: Lazy<Mutex<Vec<Project>>> = Lazy::new(|| Mutex::new(vec![
@@ -57,6 +56,7 @@ struct Task {
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[allow(dead_code)]
 struct CreateProjectRequest {
     name: String,
     description: String,
@@ -64,6 +64,7 @@ struct CreateProjectRequest {
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[allow(dead_code)]
 struct CreateTaskRequest {
     name: String,
     description: String,
@@ -75,9 +76,34 @@ struct GetProjectsHandler;
 
 #[async_trait]
 impl ApiHandler for GetProjectsHandler {
-    async fn handle(&self, _req: Request) -> Result<ApiResponse, ApiError> {
-        let projects = PROJECTS.lock().unwrap().clone();
-        Ok(ApiResponse::ok(serde_json::to_value(projects).unwrap()))
+    async fn handle(&self, req: Request) -> Result<ApiResponse, ApiError> {
+        // `?format=csv` / `?format=ndjson` export every project (tasks stay nested as JSON)
+        let export = |format: &str| {
+            let projects = PROJECTS.lock().unwrap();
+            let rows = projects.iter().map(|p| serde_json::to_value(p).unwrap());
+            if format == "csv" { ApiResponse::csv(rows.collect()) } else { ApiResponse::ndjson(rows) }
+        };
+        match req.query.get("format").map(String::as_str) {
+            Some(format @ ("csv" | "ndjson")) => return Ok(export(format)),
+            Some("json") | None => {}
+            Some(other) => return Err(ApiError::bad_request(&format!("Unsupported format: {}", other))),
+        }
+
+        let pagination = Pagination::from_request(&req);
+        let (page_items, total) = {
+            let projects = PROJECTS.lock().unwrap();
+            (pagination.slice(&projects).to_vec(), projects.len())
+        };
+        Ok(ApiResponse::paginated(page_items, pagination.page, pagination.per_page, total)
+            .pagination_links(&req))
+    }
+
+    fn describe(&self) -> OperationMeta {
+        OperationMeta::new()
+            .summary("List projects (paginated, or ?format=csv|ndjson)")
+            .tag("projects")
+            .response(200, "A page of projects")
+            .response(400, "Unsupported export format")
     }
 }
 
@@ -98,82 +124,98 @@ impl ApiHandler for GetProjectHandler {
             Err(ApiError::not_found(&format!("Project with ID {} not found", project_id)))
         }
     }
+
+    fn describe(&self) -> OperationMeta {
+        OperationMeta::new()
+            .summary("Get a project with its tasks")
+            .tag("projects")
+            .response(200, "The project")
+            .response(404, "No project with this ID")
+    }
 }
 
 // API Handler for creating projects
 struct CreateProjectHandler;
 
-#[async_trait]
-impl ApiHandler for CreateProjectHandler {
-    async fn handle(&self, mut req: Request) -> Result<ApiResponse, ApiError> {
-        let form_data = req.form().await.map_err(|e| ApiError::bad_request(&format!("Failed to parse form data: {}", e)))?;
-        
-        let name = form_data.get("name").map(|s| s.trim()).filter(|s| !s.is_empty());
-        let description = form_data.get("description").map(|s| s.trim()).filter(|s| !s.is_empty());
-        let status = form_data.get("status").map(|s| s.trim()).filter(|s| !s.is_empty());
+#[derive(Deserialize, Serialize)]
+struct NewProject {
+    name: String,
+    description: String,
+    status: String,
+}
 
-        if name.is_none() || description.is_none() || status.is_none() {
-            return Err(ApiError::bad_request("Name, description, and status are required."));
-        }
+#[async_trait]
+impl TypedApiHandler for CreateProjectHandler {
+    type Body = NewProject;
+
+    fn validate(&self, body: &NewProject) -> Result<(), ValidationErrors> {
+        let required = [ValidationRule::Required];
+        validate_request(body, &Rules::new()
+            .field("name", &required)
+            .field("description", &required)
+            .field("status", &required))
+    }
 
+    async fn handle(&self, _req: Request, body: NewProject) -> Result<ApiResponse, ApiError> {
         let mut projects = PROJECTS.lock().unwrap();
         let new_id = projects.iter().map(|p| p.id).max().unwrap_or(0) + 1;
 
         let new_project = Project {
             id: new_id,
-            name: name.unwrap().to_string(),
-            description: description.unwrap().to_string(),
-            status: status.unwrap().to_string(),
+            name: body.name.trim().to_string(),
+            description: body.description.trim().to_string(),
+            status: body.status.trim().to_string(),
             created_at: chrono::Utc::now().format("%Y-%m-%d").to_string(),
             tasks: Vec::new(),
         };
         projects.push(new_project.clone());
         info!("New project created: {:?}", new_project);
 
-        Ok(ApiResponse::ok(json!({"message": "Project created successfully", "project_id": new_project.id}))
-            .header("Location", &format!("/projects/{}", new_project.id))
-            .header("HX-Redirect", &format!("/projects/{}", new_project.id))
-            .with_status(StatusCode::SEE_OTHER))
+        Ok(ApiResponse::redirect(&format!("/projects/{}", new_project.id)))
     }
 }
 
 // API Handler for creating tasks within a project
 struct CreateTaskHandler;
 
+#[derive(Deserialize, Serialize)]
+struct NewTask {
+    task_name: String,
+    task_description: String,
+    due_date: String,
+}
+
 #[async_trait]
-impl ApiHandler for CreateTaskHandler {
-    async fn handle(&self, mut req: Request) -> Result<ApiResponse, ApiError> {
+impl TypedApiHandler for CreateTaskHandler {
+    type Body = NewTask;
+
+    fn validate(&self, body: &NewTask) -> Result<(), ValidationErrors> {
+        let required = [ValidationRule::Required];
+        validate_request(body, &Rules::new()
+            .field("task_name", &required)
+            .field("task_description", &required)
+            .field("due_date", &required))
+    }
+
+    async fn handle(&self, req: Request, body: NewTask) -> Result<ApiResponse, ApiError> {
         let project_id: u32 = req.param("id")
             .and_then(|id| id.parse().ok())
             .ok_or_else(|| ApiError::bad_request("Invalid project ID"))?;
 
-        let form_data = req.form().await.map_err(|e| ApiError::bad_request(&format!("Failed to parse form data: {}", e)))?;
-        
-        let name = form_data.get("task_name").map(|s| s.trim()).filter(|s| !s.is_empty());
-        let description = form_data.get("task_description").map(|s| s.trim()).filter(|s| !s.is_empty());
-        let due_date = form_data.get("due_date").map(|s| s.trim()).filter(|s| !s.is_empty());
-
-        if name.is_none() || description.is_none() || due_date.is_none() {
-            return Err(ApiError::bad_request("Task name, description, and due date are required."));
-        }
-
         let mut projects = PROJECTS.lock().unwrap();
         if let Some(project) = projects.iter_mut().find(|p| p.id == project_id) {
             let new_task_id = project.tasks.iter().map(|t| t.id).max().unwrap_or(0) + 1;
             let new_task = Task {
                 id: new_task_id,
-                name: name.unwrap().to_string(),
-                description: description.unwrap().to_string(),
+                name: body.task_name.trim().to_string(),
+                description: body.task_description.trim().to_string(),
                 completed: false,
-                due_date: due_date.unwrap().to_string(),
+                due_date: body.due_date.trim().to_string(),
             };
             project.tasks.push(new_task.clone());
             info!("New task created for project {}: {:?}", project_id, new_task);
 
-            Ok(ApiResponse::ok(json!({"message": "Task created successfully", "task_id": new_task.id}))
-                .header("Location", &format!("/projects/{}", project_id))
-                .header("HX-Redirect", &format!("/projects/{}", project_id))
-                .with_status(StatusCode::SEE_OTHER))
+            Ok(ApiResponse::redirect(&format!("/projects/{}", project_id)))
         } else {
             Err(ApiError::not_found(&format!("Project with ID {} not found", project_id)))
         }
@@ -242,7 +284,6 @@ impl ApiHandler for DeleteTaskHandler {
 // Dashboard Layout Component
 component!(DashboardLayout, props => {
     let title = props.get("title").and_then(|v| v.as_str()).unwrap_or("RustNext Dashboard");
-    let children_html = props.get("children_html").and_then(|v| v.as_str()).unwrap_or("");
     let error_message = props.get("error_message").and_then(|v| v.as_str()).unwrap_or("");
     let success_message = props.get("success_message").and_then(|v| v.as_str()).unwrap_or("");
     let active_path = props.get("active_path").and_then(|v| v.as_str()).unwrap_or("/");
@@ -288,9 +329,7 @@ component!(DashboardLayout, props => {
                                 div()
                             }
                         )
-                        .child(
-                            div().prop("_raw_html", children_html)
-                        )
+                        .child(Element::slot("main"))
                 )
         )
         .child(
@@ -304,14 +343,20 @@ component!(DashboardLayout, props => {
         )
 });
 
-// Project Card Component
-component!(ProjectCard, props => {
-    let project_id = props.get("id").and_then(|v| v.as_u64()).unwrap_or(0);
-    let name = props.get("name").and_then(|v| v.as_str()).unwrap_or("Untitled Project");
-    let description = props.get("description").and_then(|v| v.as_str()).unwrap_or("");
-    let status = props.get("status").and_then(|v| v.as_str()).unwrap_or("Unknown");
-    let created_at = props.get("created_at").and_then(|v| v.as_str()).unwrap_or("");
-    
+// Project Card Component, with props checked against ProjectCardProps
+#[derive(Deserialize)]
+pub struct ProjectCardProps {
+    id: u64,
+    name: String,
+    #[serde(default)]
+    description: String,
+    status: String,
+    created_at: String,
+}
+
+typed_component!(ProjectCard, props: ProjectCardProps => {
+    let project_id = props.id;
+
     article()
         .class("card")
         .child(
@@ -319,23 +364,23 @@ component!(ProjectCard, props => {
                 .child(
                     a()
                         .prop("href", format!("/projects/{}", project_id))
-                        .child(text(name))
+                        .child(text(&props.name))
                 )
         )
         .child(
             p().class("text-gray-700 text-sm mt-2")
-                .child(text(description))
+                .child(text(&props.description))
         )
         .child(
             div()
                 .class("flex justify-between items-center mt-4 text-sm")
                 .child(
                     span().class("text-gray-600")
-                        .child(text(&format!("Status: {}", status)))
+                        .child(text(&format!("Status: {}", props.status)))
                 )
                 .child(
                     span().class("text-gray-500")
-                        .child(text(&format!("Created: {}", created_at)))
+                        .child(text(&format!("Created: {}", props.created_at)))
                 )
         )
         .child(
@@ -405,60 +450,49 @@ component!(TaskItem, props => {
         )
 });
 
-// Project Form Component
-component!(ProjectForm, _props => {
-    form()
-        .prop("method", "POST")
-        .prop("action", "/api/projects")
-        .class("mt-4 p-4 border border-gray-200 rounded-md bg-gray-50")
-        .child(
-            div()
-                .class("form-group")
-                .child(label().prop("for", "name").child(text("Project Name")))
-                .child(
-                    input()
-                        .prop("type", "text")
-                        .prop("name", "name")
-                        .prop("placeholder", "e.g., Website Redesign")
-                        .prop("required", "true")
-                        .class("form-control")
-                )
+// Project form definition, shared by the page and the create handler's validation
+fn project_form() -> Form {
+    Form::new()
+        .action("/api/projects")
+        .submit_label("Create Project")
+        .field(
+            FormField::new("name", "text")
+                .label("Project Name")
+                .placeholder("e.g., Website Redesign")
+                .required()
+                .max_length(100)
         )
-        .child(
-            div()
-                .class("form-group")
-                .child(label().prop("for", "description").child(text("Description")))
-                .child(
-                    Element::new("textarea")
-                        .prop("name", "description")
-                        .prop("placeholder", "Brief description of the project...")
-                        .prop("required", "true")
-                        .prop("rows", "3")
-                        .class("form-control")
-                )
+        .field(
+            FormField::new("description", "textarea")
+                .label("Description")
+                .placeholder("Brief description of the project...")
+                .required()
         )
-        .child(
-            div()
-                .class("form-group")
-                .child(label().prop("for", "status").child(text("Status")))
-                .child(
-                    Element::new("select")
-                        .prop("name", "status")
-                        .prop("required", "true")
-                        .class("form-control")
-                        .child(Element::new("option").prop("value", "").child(text("Select Status")))
-                        .child(Element::new("option").prop("value", "Planned").child(text("Planned")))
-                        .child(Element::new("option").prop("value", "In Progress").child(text("In Progress")))
-                        .child(Element::new("option").prop("value", "Completed").child(text("Completed")))
-                        .child(Element::new("option").prop("value", "On Hold").child(text("On Hold")))
-                )
-        )
-        .child(
-            button()
-                .prop("type", "submit")
-                .class("btn mt-2")
-                .child(text("Create Project"))
+        .field(
+            FormField::new("status", "select")
+                .label("Status")
+                .placeholder("Select Status")
+                .option("Planned", "Planned")
+                .option("In Progress", "In Progress")
+                .option("Completed", "Completed")
+                .option("On Hold", "On Hold")
+                .required()
+                .one_of(&["Planned", "In Progress", "Completed", "On Hold"])
         )
+}
+
+// Project Form Component; pass submitted `values` to re-render with them and their errors
+component!(ProjectForm, props => {
+    let mut project_form = project_form();
+    if let Some(values) = props.get("values").and_then(|v| v.as_object()) {
+        let values: HashMap<String, String> = values.iter()
+            .map(|(k, v)| (k.clone(), v.as_str().unwrap_or_default().to_string()))
+            .collect();
+        project_form.populate_from_form_data(&values);
+        project_form.validate();
+    }
+    project_form.to_element()
+        .class("mt-4 p-4 border border-gray-200 rounded-md bg-gray-50")
 });
 
 // Task Form Component
@@ -517,6 +551,16 @@ component!(TaskForm, props => {
         )
 });
 
+// Friendly page for unknown routes, registered with `ErrorPages` below
+component!(NotFoundPage, props => {
+    let path = props.get("path").and_then(|v| v.as_str()).unwrap_or("");
+    div()
+        .class("container")
+        .child(h1().child(text("Page not found")))
+        .child(p().child(text(&format!("Nothing lives at {}.", path))))
+        .child(a().prop("href", "/").child(text("Back to the dashboard")))
+});
+
 
 // Dashboard Home Page
 page!(ProjectDashboardPage, req => {
@@ -533,10 +577,10 @@ page!(ProjectDashboardPage, req => {
         project_props.insert("status".to_string(), json!(project.status));
         project_props.insert("created_at".to_string(), json!(project.created_at));
         
-        let component_registry_arc = get_component_registry().clone();
+        let component_registry_arc = get_component_registry();
         project_cards_futures.push(async move {
             let component_registry = component_registry_arc.lock().await;
-            component_registry.render("project_card", &project_props).await.unwrap_or_else(|| div())
+            component_registry.render("project_card", &project_props).await.unwrap_or_else(RenderError::into_element)
         });
     }
 
@@ -553,7 +597,6 @@ page!(ProjectDashboardPage, req => {
 
     let mut layout_props = HashMap::new();
     layout_props.insert("title".to_string(), json!("Project Dashboard"));
-    layout_props.insert("children_html".to_string(), json!(get_renderer().render_to_html(&content)));
     layout_props.insert("active_path".to_string(), json!(req.uri.path()));
 
     // Check for messages after redirect
@@ -565,21 +608,23 @@ page!(ProjectDashboardPage, req => {
     }
     
     let component_registry = get_component_registry().lock().await;
-    let rendered_element = component_registry.render("dashboard_layout", &layout_props).await;
-    rendered_element.unwrap_or_else(|| {
-        div().child(text("Error rendering page"))
-    })
+    let rendered_element = component_registry.render_with_slots("dashboard_layout", &layout_props, HashMap::from([("main".to_string(), vec![content])])).await;
+    rendered_element.unwrap_or_else(RenderError::into_element)
 });
 
 // New Project Page
 page!(NewProjectPage, req => {
     let mut layout_props = HashMap::new();
     layout_props.insert("title".to_string(), json!("Create New Project"));
+    // After a failed submit the parsed form body is still on the request
+    let mut project_form_props = HashMap::new();
+    if let Some(values) = &req.form_body {
+        project_form_props.insert("values".to_string(), json!(values));
+    }
     let project_form_element = {
         let component_registry = get_component_registry().lock().await;
-        component_registry.render("project_form", &HashMap::new()).await.unwrap_or_else(|| div())
+        component_registry.render("project_form", &project_form_props).await.unwrap_or_else(RenderError::into_element)
     };
-    layout_props.insert("children_html".to_string(), json!(get_renderer().render_to_html(&project_form_element)));
     layout_props.insert("active_path".to_string(), json!(req.uri.path()));
 
     // Check for messages after redirect
@@ -591,10 +636,8 @@ page!(NewProjectPage, req => {
     }
     
     let component_registry = get_component_registry().lock().await;
-    let rendered_element = component_registry.render("dashboard_layout", &layout_props).await;
-    rendered_element.unwrap_or_else(|| {
-        div().child(text("Error rendering page"))
-    })
+    let rendered_element = component_registry.render_with_slots("dashboard_layout", &layout_props, HashMap::from([("main".to_string(), vec![project_form_element])])).await;
+    rendered_element.unwrap_or_else(RenderError::into_element)
 });
 
 // Project Detail Page
@@ -618,10 +661,10 @@ page!(ProjectDetailPage, req => {
             task_props.insert("completed".to_string(), json!(task.completed));
             task_props.insert("due_date".to_string(), json!(task.due_date));
 
-            let component_registry_arc = get_component_registry().clone();
+            let component_registry_arc = get_component_registry();
             task_items_futures.push(async move {
                 let component_registry = component_registry_arc.lock().await;
-                component_registry.render("task_item", &task_props).await.unwrap_or_else(|| div())
+                component_registry.render("task_item", &task_props).await.unwrap_or_else(RenderError::into_element)
             });
         }
         let task_items = futures::future::join_all(task_items_futures).await;
@@ -630,9 +673,7 @@ page!(ProjectDetailPage, req => {
         task_form_props.insert("project_id".to_string(), json!(project.id));
         let task_form_element = {
             let component_registry = get_component_registry().lock().await;
-            component_registry.render("task_form", &task_form_props).await.unwrap_or_else(|| {
-                div().child(text("Error rendering task form"))
-            })
+            component_registry.render("task_form", &task_form_props).await.unwrap_or_else(RenderError::into_element)
         };
 
         div()
@@ -674,7 +715,6 @@ page!(ProjectDetailPage, req => {
     layout_props.insert("title".to_string(), json!(
         project_option.map(|p| p.name).unwrap_or_else(|| "Project Not Found".to_string())
     ));
-    layout_props.insert("children_html".to_string(), json!(get_renderer().render_to_html(&content)));
     layout_props.insert("active_path".to_string(), json!(req.uri.path()));
 
     // Check for messages after redirect
@@ -686,10 +726,8 @@ page!(ProjectDetailPage, req => {
     }
     
     let component_registry = get_component_registry().lock().await;
-    let rendered_element = component_registry.render("dashboard_layout", &layout_props).await;
-    rendered_element.unwrap_or_else(|| {
-        div().child(text("Error rendering page"))
-    })
+    let rendered_element = component_registry.render_with_slots("dashboard_layout", &layout_props, HashMap::from([("main".to_string(), vec![content])])).await;
+    rendered_element.unwrap_or_else(RenderError::into_element)
 });
 
 // About Page for Dashboard App
@@ -724,14 +762,11 @@ page!(AboutPage, req => {
 
     let mut layout_props = HashMap::new();
     layout_props.insert("title".to_string(), json!("About Project Dashboard"));
-    layout_props.insert("children_html".to_string(), json!(get_renderer().render_to_html(&content)));
     layout_props.insert("active_path".to_string(), json!(req.uri.path()));
 
     let component_registry = get_component_registry().lock().await;
-    let rendered_element = component_registry.render("dashboard_layout", &layout_props).await;
-    rendered_element.unwrap_or_else(|| {
-        div().child(text("Error rendering page"))
-    })
+    let rendered_element = component_registry.render_with_slots("dashboard_layout", &layout_props, HashMap::from([("main".to_string(), vec![content])])).await;
+    rendered_element.unwrap_or_else(RenderError::into_element)
 });
 
 
@@ -741,7 +776,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     init_logging();
 
     // Initialize configuration from file (if exists) or environment
-    let config = Config::load(Some("config.toml"));
+    let config = Config::load_or_default(Some("config.toml"));
     init_config(config.clone());
 
     info!("🔧 Configuration loaded:");
@@ -763,6 +798,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     register_component!("project_form", ProjectForm).await?;
     register_component!("task_item", TaskItem).await?;
     register_component!("task_form", TaskForm).await?;
+    register_component!("not_found", NotFoundPage).await?;
     
     // Register pages
     register_page!("/", ProjectDashboardPage).await?;
@@ -780,6 +816,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
 
     // Create router
     let router = Router::new()
+        .openapi("Project Dashboard API", "1.0.0")
         .use_middleware(RateLimiter::new(100, 60))
         .get("/", |req| async move {
             let page_registry = get_page_registry().lock().await;
@@ -817,8 +854,24 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
                 Err(Box::new(AppError::NotFound("About page not found".to_string())) as Box<dyn std::error::Error + Send + Sync>)
             }
         })
-        .post("/api/projects", |req: Request| async move {
-            let api_registry = get_api_registry().lock().await;
+        .get("/api/projects", |req: Request| async move {
+            match get_api_registry().read().await.handle_request(req).await {
+                Some(response) => Ok(response),
+                None => Err(Box::new(AppError::NotFound("API endpoint /api/projects (GET) not found".to_string())) as Box<dyn std::error::Error + Send + Sync>),
+            }
+        })
+        .post("/api/projects", |mut req: Request| async move {
+            // Validate up front so a failed submit re-renders the form with field errors
+            let mut submitted = project_form();
+            submitted.populate_from_form_data(req.form().await?);
+            if !submitted.validate() {
+                let page_registry = get_page_registry().lock().await;
+                let element = page_registry.render_page("/projects/new", &req).await
+                    .ok_or_else(|| Box::new(AppError::NotFound("New project page not found".to_string())) as Box<dyn std::error::Error + Send + Sync>)?;
+                return Ok(get_renderer().render_to_response(&element)?.status(StatusCode::UNPROCESSABLE_ENTITY));
+            }
+
+            let api_registry = get_api_registry().read().await;
             match api_registry.handle_request(req).await {
                 Some(response) => {
                     if response.status == StatusCode::SEE_OTHER {
@@ -827,17 +880,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
                         let body_bytes = hyper::body::to_bytes(response.body).await.map_err(|e| Box::new(AppError::Internal(e.to_string())) as Box<dyn std::error::Error + Send + Sync>)?;
                         let error_json: serde_json::Value = serde_json::from_slice(&body_bytes).map_err(|e| Box::new(AppError::BadRequest(e.to_string())) as Box<dyn std::error::Error + Send + Sync>)?;
                         let error_msg = error_json["error"].as_str().unwrap_or("Unknown error").to_string();
-                        Ok(Response::new()
-                            .status(StatusCode::SEE_OTHER)
-                            .header("Location", &format!("/projects/new?error={}", urlencoding::encode(&error_msg)))
-                            .header("HX-Redirect", &format!("/projects/new?error={}", urlencoding::encode(&error_msg))))
+                        Ok(Response::see_other(&format!("/projects/new?error={}", urlencoding::encode(&error_msg)))
+                            .hx_redirect(&format!("/projects/new?error={}", urlencoding::encode(&error_msg))))
                     }
                 }
                 None => Err(Box::new(AppError::NotFound("API endpoint /api/projects (POST) not found".to_string())) as Box<dyn std::error::Error + Send + Sync>),
             }
         })
         .post("/api/projects/:id/tasks", |req: Request| async move {
-            let api_registry = get_api_registry().lock().await;
+            let api_registry = get_api_registry().read().await;
             let project_id_str = req.param("id").cloned().unwrap_or_default();
             match api_registry.handle_request(req).await {
                 Some(response) => {
@@ -847,47 +898,46 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
                         let body_bytes = hyper::body::to_bytes(response.body).await.map_err(|e| Box::new(AppError::Internal(e.to_string())) as Box<dyn std::error::Error + Send + Sync>)?;
                         let error_json: serde_json::Value = serde_json::from_slice(&body_bytes).map_err(|e| Box::new(AppError::BadRequest(e.to_string())) as Box<dyn std::error::Error + Send + Sync>)?;
                         let error_msg = error_json["error"].as_str().unwrap_or("Unknown error").to_string();
-                        Ok(Response::new()
-                            .status(StatusCode::SEE_OTHER)
-                            .header("Location", &format!("/projects/{}?error={}", project_id_str, urlencoding::encode(&error_msg)))
-                            .header("HX-Redirect", &format!("/projects/{}?error={}", project_id_str, urlencoding::encode(&error_msg))))
+                        Ok(Response::see_other(&format!("/projects/{}?error={}", project_id_str, urlencoding::encode(&error_msg)))
+                            .hx_redirect(&format!("/projects/{}?error={}", project_id_str, urlencoding::encode(&error_msg))))
                     }
                 }
                 None => Err(Box::new(AppError::NotFound("API endpoint /api/projects/:id/tasks (POST) not found".to_string())) as Box<dyn std::error::Error + Send + Sync>),
             }
         })
         .post("/api/projects/:project_id/tasks/:task_id/toggle", |req: Request| async move {
-            let api_registry = get_api_registry().lock().await;
+            let api_registry = get_api_registry().read().await;
             let project_id_str = req.param("project_id").cloned().unwrap_or_default();
             match api_registry.handle_request(req).await {
                 Some(_response) => {
                     // After toggle, redirect back to project detail to show updated list
-                    Ok(Response::new().status(StatusCode::SEE_OTHER).header("Location", &format!("/projects/{}", project_id_str)))
+                    Ok(Response::see_other(&format!("/projects/{}", project_id_str)))
                 }
                 None => Err(Box::new(AppError::NotFound("API endpoint /api/projects/:project_id/tasks/:task_id/toggle (POST) not found".to_string())) as Box<dyn std::error::Error + Send + Sync>),
             }
         })
         .post("/api/projects/:project_id/tasks/:task_id/delete", |req: Request| async move {
-            let api_registry = get_api_registry().lock().await;
+            let api_registry = get_api_registry().read().await;
             let project_id_str = req.param("project_id").cloned().unwrap_or_default();
             match api_registry.handle_request(req).await {
                 Some(_response) => {
                     // After delete, redirect back to project detail to show updated list
-                    Ok(Response::new().status(StatusCode::SEE_OTHER).header("Location", &format!("/projects/{}", project_id_str)))
+                    Ok(Response::see_other(&format!("/projects/{}", project_id_str)))
                 }
                 None => Err(Box::new(AppError::NotFound("API endpoint /api/projects/:project_id/tasks/:task_id/delete (POST) not found".to_string())) as Box<dyn std::error::Error + Send + Sync>),
             }
         });
 
     // Define a custom error handler for the App
-    let custom_error_handler = Arc::new(|err: AppError| {
-        error!("Application Error: {}", err);
-        err.into_response()
+    let custom_error_handler = ErrorHandler::new(|ctx: &ErrorContext, err: AppError| {
+        error!("Application Error on {} {} (request {}): {}", ctx.method, ctx.path, ctx.request_id.as_deref().unwrap_or("-"), err);
+        err.respond_to(ctx)
     });
 
     // Create and run server
     let app = App::new()
         .router(router)
+        .error_pages(ErrorPages::new().component(StatusCode::NOT_FOUND, "not_found"))
         .error_handler(custom_error_handler);
 
     let addr: SocketAddr = format!("{}:{}", config.server.host, config.server.port).parse()?;
@@ -1,12 +1,11 @@
 use rustnext::*;
-use rustnext::ui::{Element, div, header, nav, a, text, main as main_element, h1, form, input, button, section, h2, ul, li, span, get_component_registry, get_renderer};
+use rustnext::ui::{div, header, nav, a, text, main as main_element, h1, form, input, button, section, h2, ul, li, span, get_component_registry, get_renderer};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::{Mutex, Arc};
 use log::{info, error};
 use once_cell::sync::Lazy;
-use urlencoding;
 
 // In-memory storage for todos
 static TODOS: Lazy<Mutex<Vec<Todo>>> = Lazy::new(|| Mutex::new(vec![
@@ -48,32 +47,32 @@ impl ApiHandler for GetTodosHandler {
 // API Handler for creating todos
 struct CreateTodoHandler;
 
+#[derive(Deserialize, Serialize)]
+struct NewTodo {
+    task: String,
+}
+
 #[async_trait]
-impl ApiHandler for CreateTodoHandler {
-    async fn handle(&self, mut req: Request) -> Result<ApiResponse, ApiError> {
-        let form_data = req.form().await.map_err(|e| ApiError::bad_request(&format!("Failed to parse form data: {}", e)))?;
-        
-        let task = form_data.get("task").map(|s| s.trim()).filter(|s| !s.is_empty());
+impl TypedApiHandler for CreateTodoHandler {
+    type Body = NewTodo;
 
-        if task.is_none() {
-            return Err(ApiError::bad_request("Task cannot be empty."));
-        }
+    fn validate(&self, body: &NewTodo) -> Result<(), ValidationErrors> {
+        validate_request(body, &Rules::new().field("task", &[ValidationRule::Required]))
+    }
 
+    async fn handle(&self, _req: Request, body: NewTodo) -> Result<ApiResponse, ApiError> {
         let mut todos = TODOS.lock().unwrap();
         let new_id = todos.iter().map(|t| t.id).max().unwrap_or(0) + 1;
 
         let new_todo = Todo {
             id: new_id,
-            task: task.unwrap().to_string(),
+            task: body.task.trim().to_string(),
             completed: false,
         };
         todos.push(new_todo.clone());
         info!("New todo created: {:?}", new_todo);
 
-        Ok(ApiResponse::ok(json!({"message": "Todo created successfully"}))
-            .header("Location", "/")
-            .header("HX-Redirect", "/") // For HTMX if used
-            .with_status(StatusCode::SEE_OTHER))
+        Ok(ApiResponse::redirect("/"))
     }
 }
 
@@ -174,6 +173,8 @@ component!(TodoLayout, props => {
                         .child(text("© 2024 RustNext Todo App. Built with Rust."))
                 )
         )
+        // htmx swaps the todo list in place when a todo is toggled or deleted
+        .child(Element::new("script").prop("src", "https://unpkg.com/htmx.org@1.9.12"))
 });
 
 // Todo Item Component
@@ -184,24 +185,23 @@ component!(TodoItem, props => {
 
     let task_class = if completed { "line-through text-gray-500" } else { "text-gray-800" };
 
+    // Toggling swaps in the list the toggle route returns for htmx requests
+    let mut checkbox = input()
+        .prop("type", "checkbox")
+        .prop("hx-post", format!("/api/todos/{}/toggle", id))
+        .prop("hx-target", "#todo-list")
+        .prop("hx-swap", "outerHTML")
+        .class("form-checkbox h-5 w-5 text-blue-600 mr-3");
+    if completed {
+        checkbox = checkbox.prop("checked", "checked");
+    }
+
     li()
         .class("flex items-center justify-between p-3 border-b border-gray-200 last:border-b-0")
         .child(
             div()
                 .class("flex items-center")
-                .child(
-                    a() // Link to toggle completion
-                        .prop("href", format!("/api/todos/{}/toggle", id))
-                        .prop("method", "POST") // Use POST for state change
-                        .class("mr-3")
-                        .child(
-                            input()
-                                .prop("type", "checkbox")
-                                .prop("checked", completed)
-                                .prop("disabled", "true") // Disable direct interaction, link handles it
-                                .class("form-checkbox h-5 w-5 text-blue-600")
-                        )
-                )
+                .child(checkbox)
                 .child(
                     span()
                         .class(task_class)
@@ -209,11 +209,24 @@ component!(TodoItem, props => {
                 )
         )
         .child(
-            a() // Link to delete todo
-                .prop("href", format!("/api/todos/{}/delete", id))
-                .prop("method", "POST") // Use POST for state change
-                .class("text-red-500 hover:text-red-700 text-sm")
-                .child(text("Delete"))
+            form() // Forms can only POST; MethodOverride turns this into a DELETE without htmx
+                .prop("method", "POST")
+                .prop("action", format!("/api/todos/{}", id))
+                .prop("hx-delete", format!("/api/todos/{}", id))
+                .prop("hx-target", "#todo-list")
+                .prop("hx-swap", "outerHTML")
+                .child(
+                    input()
+                        .prop("type", "hidden")
+                        .prop("name", "_method")
+                        .prop("value", "DELETE")
+                )
+                .child(
+                    button()
+                        .prop("type", "submit")
+                        .class("text-red-500 hover:text-red-700 text-sm")
+                        .child(text("Delete"))
+                )
         )
 });
 
@@ -243,8 +256,8 @@ component!(TodoForm, _props => {
         )
 });
 
-// Home Page for Todos
-page!(HomePage, req => {
+// The todo list on its own: the fragment htmx swaps in after a toggle or delete
+async fn todo_list() -> Element {
     let todos_cloned = {
         TODOS.lock().unwrap().clone()
     };
@@ -257,23 +270,22 @@ page!(HomePage, req => {
         todo_props.insert("completed".to_string(), json!(todo.completed));
         
         let component_registry = get_component_registry().lock().await;
-        if let Some(item) = component_registry.render("todo_item", &todo_props).await {
-            todo_items.push(item);
-        }
+        todo_items.push(component_registry.render("todo_item", &todo_props).await.unwrap_or_else(RenderError::into_element));
     }
 
+    ul().id("todo-list").children(todo_items)
+}
+
+// The home page around `todo_list`
+async fn home_page(req: &Request, todo_list: Element) -> Element {
     let todo_list_section = section()
         .class("card mt-4")
         .child(h2().class("text-xl font-bold mb-4").child(text("My Todos")))
-        .child(
-            ul().children(todo_items)
-        );
+        .child(todo_list);
 
     let todo_form_element = {
         let component_registry = get_component_registry().lock().await;
-        component_registry.render("todo_form", &HashMap::new()).await.unwrap_or_else(|| {
-            div().child(text("Error rendering todo form"))
-        })
+        component_registry.render("todo_form", &HashMap::new()).await.unwrap_or_else(RenderError::into_element)
     };
 
     let content = div()
@@ -291,9 +303,12 @@ page!(HomePage, req => {
 
     let component_registry = get_component_registry().lock().await;
     let rendered_element = component_registry.render("todo_layout", &layout_props).await;
-    rendered_element.unwrap_or_else(|| {
-        div().child(text("Error rendering page"))
-    })
+    rendered_element.unwrap_or_else(RenderError::into_element)
+}
+
+// Home Page for Todos
+page!(HomePage, req => {
+    home_page(req, todo_list().await).await
 });
 
 // About Page for Todo App
@@ -329,9 +344,7 @@ page!(AboutPage, _req => {
 
     let component_registry = get_component_registry().lock().await;
     let rendered_element = component_registry.render("todo_layout", &layout_props).await;
-    rendered_element.unwrap_or_else(|| {
-        div().child(text("Error rendering page"))
-    })
+    rendered_element.unwrap_or_else(RenderError::into_element)
 });
 
 
@@ -341,7 +354,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     init_logging();
 
     // Initialize configuration from file (if exists) or environment
-    let config = Config::load(Some("config.toml"));
+    let config = Config::load_or_default(Some("config.toml"));
     init_config(config.clone());
 
     info!("🔧 Configuration loaded:");
@@ -361,18 +374,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     api_route!(hyper::Method::GET, "/api/todos", GetTodosHandler).await?;
     api_route!(hyper::Method::POST, "/api/todos", CreateTodoHandler).await?;
     api_route!(hyper::Method::POST, "/api/todos/:id/toggle", UpdateTodoHandler).await?; // Using POST for toggle
-    api_route!(hyper::Method::POST, "/api/todos/:id/delete", DeleteTodoHandler).await?; // Using POST for delete
+    api_route!(hyper::Method::DELETE, "/api/todos/:id", DeleteTodoHandler).await?;
 
     // Create router
     let router = Router::new()
         .get("/", |req| async move {
-            let page_registry = get_page_registry().lock().await;
-            let element_option = page_registry.render_page("/", &req).await;
-            if let Some(element) = element_option {
-                get_renderer().render_to_response(&element)
-            } else {
-                Err(Box::new(AppError::NotFound("Home page not found".to_string())) as Box<dyn std::error::Error + Send + Sync>)
-            }
+            // `hx-get="/"` gets just the list back
+            render_partial_or_full(&req, todo_list().await, |list| home_page(&req, list)).await
         })
         .get("/about", |req| async move {
             let page_registry = get_page_registry().lock().await;
@@ -384,7 +392,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
             }
         })
         .post("/api/todos", |req| async move {
-            let api_registry = get_api_registry().lock().await;
+            let api_registry = get_api_registry().read().await;
             match api_registry.handle_request(req).await {
                 Some(response) => {
                     // If API call was successful and resulted in a redirect, return it
@@ -395,33 +403,35 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
                         let body_bytes = hyper::body::to_bytes(response.body).await.map_err(|e| Box::new(AppError::Internal(e.to_string())) as Box<dyn std::error::Error + Send + Sync>)?;
                         let error_json: serde_json::Value = serde_json::from_slice(&body_bytes).map_err(|e| Box::new(AppError::BadRequest(e.to_string())) as Box<dyn std::error::Error + Send + Sync>)?;
                         let error_msg = error_json["error"].as_str().unwrap_or("Unknown error").to_string();
-                        Ok(Response::new()
-                            .status(StatusCode::SEE_OTHER)
-                            .header("Location", format!("/?error={}", urlencoding::encode(&error_msg)))
-                            .header("HX-Redirect", format!("/?error={}", urlencoding::encode(&error_msg))))
+                        Ok(Response::see_other(&format!("/?error={}", urlencoding::encode(&error_msg)))
+                            .hx_redirect(&format!("/?error={}", urlencoding::encode(&error_msg))))
                     }
                 }
                 None => Err(Box::new(AppError::NotFound("API endpoint /api/todos (POST) not found".to_string())) as Box<dyn std::error::Error + Send + Sync>),
             }
         })
-        .post("/api/todos/:id/toggle", |req| async move {
-            let api_registry = get_api_registry().lock().await;
+        .post("/api/todos/:id/toggle", |req: Request| async move {
+            let is_htmx = req.is_htmx();
+            let api_registry = get_api_registry().read().await;
             match api_registry.handle_request(req).await {
-                Some(response) => {
+                Some(_response) if is_htmx => Ok(render_fragment(&todo_list().await)),
+                Some(_response) => {
                     // After toggle, redirect back to home to show updated list
-                    Ok(Response::new().status(StatusCode::SEE_OTHER).header("Location", "/"))
+                    Ok(Response::see_other("/"))
                 }
                 None => Err(Box::new(AppError::NotFound("API endpoint /api/todos/:id/toggle (POST) not found".to_string())) as Box<dyn std::error::Error + Send + Sync>),
             }
         })
-        .post("/api/todos/:id/delete", |req| async move {
-            let api_registry = get_api_registry().lock().await;
+        .delete("/api/todos/:id", |req: Request| async move {
+            let is_htmx = req.is_htmx();
+            let api_registry = get_api_registry().read().await;
             match api_registry.handle_request(req).await {
-                Some(response) => {
+                Some(_response) if is_htmx => Ok(render_fragment(&todo_list().await)),
+                Some(_response) => {
                     // After delete, redirect back to home to show updated list
-                    Ok(Response::new().status(StatusCode::SEE_OTHER).header("Location", "/"))
+                    Ok(Response::see_other("/"))
                 }
-                None => Err(Box::new(AppError::NotFound("API endpoint /api/todos/:id/delete (POST) not found".to_string())) as Box<dyn std::error::Error + Send + Sync>),
+                None => Err(Box::new(AppError::NotFound("API endpoint /api/todos/:id (DELETE) not found".to_string())) as Box<dyn std::error::Error + Send + Sync>),
             }
         });
 
@@ -437,7 +447,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         .error_handler(custom_error_handler);
 
     let addr: SocketAddr = format!("{}:{}", config.server.host, config.server.port).parse()?;
-    let server = Server::new(app, addr);
+    let server = Server::new(app, addr).wrap(MethodOverride::new());
     
     info!("🚀 RustNext Todo App running at http://{}:{}", config.server.host, config.server.port);
     info!("📝 Available routes:");
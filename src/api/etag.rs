@@ -0,0 +1,128 @@
+use super::{ApiError, ApiResponse};
+use crate::Request;
+use serde::Serialize;
+use std::collections::HashMap;
+
+// Strong ETag for an entity: the MD5 of its JSON serialization. Objects are serialized with their
+// keys sorted (serde_json's default map), so equal data gives the same tag regardless of struct
+// field order or HashMap iteration order.
+pub fn etag_for<T: Serialize>(value: &T) -> String {
+    let json = serde_json::to_value(value)
+        .map(|value| value.to_string())
+        .unwrap_or_default();
+    format!("\"{:x}\"", md5::compute(json.as_bytes()))
+}
+
+impl ApiResponse {
+    pub fn with_etag(self, etag: &str) -> Self {
+        self.header("ETag", etag)
+    }
+
+    // 304 with no body, for a matching `If-None-Match`
+    pub fn not_modified(etag: &str) -> Self {
+        let mut headers = HashMap::new();
+        headers.insert("ETag".to_string(), etag.to_string());
+        ApiResponse {
+            status: hyper::StatusCode::NOT_MODIFIED,
+            data: serde_json::Value::Null,
+            headers,
+            negotiate: false,
+            body: Some(Vec::new()),
+        }
+    }
+}
+
+impl ApiError {
+    pub fn precondition_failed(message: &str) -> Self {
+        ApiError::new(hyper::StatusCode::PRECONDITION_FAILED, message)
+    }
+
+    pub fn precondition_required(message: &str) -> Self {
+        ApiError::new(hyper::StatusCode::PRECONDITION_REQUIRED, message)
+    }
+}
+
+impl Request {
+    // For updates: passes when there is no `If-Match` or it lists `current_etag` (or `*`),
+    // otherwise 412. Weak tags (`W/"..."`) never match, as If-Match uses strong comparison.
+    pub fn if_match_satisfied(&self, current_etag: &str) -> Result<(), ApiError> {
+        match self.header_etags(hyper::header::IF_MATCH) {
+            None => Ok(()),
+            Some(tags) if tags.iter().any(|tag| tag == "*" || tag == current_etag) => Ok(()),
+            Some(_) => Err(ApiError::precondition_failed("Resource has been modified")),
+        }
+    }
+
+    // Like `if_match_satisfied`, but a missing `If-Match` is a 428
+    pub fn require_if_match(&self, current_etag: &str) -> Result<(), ApiError> {
+        if self.headers.get(hyper::header::IF_MATCH).is_none() {
+            return Err(ApiError::precondition_required("If-Match header is required"));
+        }
+        self.if_match_satisfied(current_etag)
+    }
+
+    // For reads: a 304 response when `If-None-Match` lists `current_etag` (weak comparison) or `*`
+    pub fn not_modified(&self, current_etag: &str) -> Option<ApiResponse> {
+        let tags = self.header_etags(hyper::header::IF_NONE_MATCH)?;
+        let current = current_etag.trim_start_matches("W/");
+        tags.iter()
+            .any(|tag| tag == "*" || tag.trim_start_matches("W/") == current)
+            .then(|| ApiResponse::not_modified(current_etag))
+    }
+
+    fn header_etags(&self, name: hyper::header::HeaderName) -> Option<Vec<String>> {
+        let value = self.headers.get(name)?.to_str().ok()?;
+        Some(value.split(',')
+            .map(|tag| tag.trim().to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    async fn request(headers: &[(&str, &str)]) -> Request {
+        let mut builder = hyper::Request::builder().uri("/api/items/1");
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        Request::from_hyper(builder.body(hyper::Body::empty()).unwrap()).await.unwrap()
+    }
+
+    #[test]
+    fn equal_data_gets_the_same_tag_whatever_the_key_order() {
+        let a: HashMap<&str, u32> = [("x", 1), ("y", 2)].into_iter().collect();
+        let b: HashMap<&str, u32> = [("y", 2), ("x", 1)].into_iter().collect();
+        assert_eq!(etag_for(&a), etag_for(&b));
+        assert_ne!(etag_for(&json!({"x": 1})), etag_for(&json!({"x": 2})));
+        assert!(etag_for(&a).starts_with('"') && etag_for(&a).ends_with('"'));
+    }
+
+    #[tokio::test]
+    async fn if_match_uses_strong_comparison() {
+        let etag = "\"abc\"";
+        assert!(request(&[]).await.if_match_satisfied(etag).is_ok());
+        assert!(request(&[("if-match", "\"old\", \"abc\"")]).await.if_match_satisfied(etag).is_ok());
+        assert!(request(&[("if-match", "*")]).await.if_match_satisfied(etag).is_ok());
+
+        let weak = request(&[("if-match", "W/\"abc\"")]).await.if_match_satisfied(etag).unwrap_err();
+        assert_eq!(weak.status, hyper::StatusCode::PRECONDITION_FAILED);
+        let missing = request(&[]).await.require_if_match(etag).unwrap_err();
+        assert_eq!(missing.status, hyper::StatusCode::PRECONDITION_REQUIRED);
+    }
+
+    #[tokio::test]
+    async fn if_none_match_uses_weak_comparison() {
+        let etag = "\"abc\"";
+        let response = request(&[("if-none-match", "W/\"abc\"")]).await.not_modified(etag).unwrap();
+        assert_eq!(response.status, hyper::StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers["ETag"], etag);
+        assert_eq!(response.body.as_deref(), Some(&[][..]));
+
+        assert!(request(&[("if-none-match", "\"other\"")]).await.not_modified(etag).is_none());
+        assert!(request(&[]).await.not_modified(etag).is_none());
+    }
+}
@@ -0,0 +1,150 @@
+use super::ApiResponse;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+impl ApiResponse {
+    // RFC 4180 CSV with a header row; see `to_csv` for how rows become columns
+    pub fn csv(rows: Vec<Value>) -> Self {
+        ApiResponse::raw("text/csv; charset=utf-8", to_csv(&rows).into_bytes())
+    }
+
+    // One compact JSON document per line
+    pub fn ndjson(values: impl IntoIterator<Item = Value>) -> Self {
+        ApiResponse::raw("application/x-ndjson", to_ndjson(values).into_bytes())
+    }
+
+    pub fn text(text: &str) -> Self {
+        ApiResponse::raw("text/plain; charset=utf-8", text.as_bytes().to_vec())
+    }
+
+    // Binary content such as an image or a PDF
+    pub fn bytes(content_type: &str, body: Vec<u8>) -> Self {
+        ApiResponse::raw(content_type, body)
+    }
+
+    // A pre-encoded body sent as-is; the registry skips JSON serialization and negotiation
+    pub fn raw(content_type: &str, body: Vec<u8>) -> Self {
+        let mut headers = HashMap::new();
+        headers.insert("Content-Type".to_string(), content_type.to_string());
+        ApiResponse {
+            status: hyper::StatusCode::OK,
+            data: Value::Null,
+            headers,
+            negotiate: false,
+            body: Some(body),
+        }
+    }
+}
+
+// Columns are the union of the rows' keys in first-seen order. Nested objects are flattened one
+// level into `parent.child` columns; deeper values and arrays are written as JSON, null as empty.
+// Non-object rows go in a single `value` column.
+pub fn to_csv(rows: &[Value]) -> String {
+    let flattened: Vec<Map<String, Value>> = rows.iter().map(flatten_row).collect();
+
+    let mut columns: Vec<&String> = Vec::new();
+    for row in &flattened {
+        for key in row.keys() {
+            if !columns.contains(&key) {
+                columns.push(key);
+            }
+        }
+    }
+
+    let mut out = String::new();
+    push_record(&mut out, columns.iter().map(|c| c.to_string()));
+    for row in &flattened {
+        push_record(&mut out, columns.iter().map(|c| row.get(*c).map(cell).unwrap_or_default()));
+    }
+    out
+}
+
+pub fn to_ndjson(values: impl IntoIterator<Item = Value>) -> String {
+    values.into_iter()
+        .map(|value| value.to_string() + "\n")
+        .collect()
+}
+
+fn flatten_row(row: &Value) -> Map<String, Value> {
+    let mut flat = Map::new();
+    match row {
+        Value::Object(map) => {
+            for (key, value) in map {
+                match value {
+                    Value::Object(nested) => {
+                        for (nested_key, nested_value) in nested {
+                            flat.insert(format!("{}.{}", key, nested_key), nested_value.clone());
+                        }
+                    }
+                    other => {
+                        flat.insert(key.clone(), other.clone());
+                    }
+                }
+            }
+        }
+        other => {
+            flat.insert("value".to_string(), other.clone());
+        }
+    }
+    flat
+}
+
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn push_record(out: &mut String, fields: impl Iterator<Item = String>) {
+    let fields: Vec<String> = fields.map(|field| escape_field(&field)).collect();
+    out.push_str(&fields.join(","));
+    out.push_str("\r\n");
+}
+
+// Quotes fields containing a comma, quote or line break, doubling embedded quotes
+fn escape_field(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn csv_takes_the_union_of_columns_and_flattens_one_level() {
+        let rows = vec![
+            json!({"id": 1, "name": "Ada", "address": {"city": "London"}}),
+            json!({"id": 2, "tags": ["a", "b"], "name": null}),
+        ];
+        assert_eq!(
+            to_csv(&rows),
+            "address.city,id,name,tags\r\nLondon,1,Ada,\r\n,2,,\"[\"\"a\"\",\"\"b\"\"]\"\r\n"
+        );
+    }
+
+    #[test]
+    fn csv_quotes_fields_with_separators_and_line_breaks() {
+        let rows = vec![json!({"note": "a, \"quoted\"\nline"}), json!("plain")];
+        assert_eq!(to_csv(&rows), "note,value\r\n\"a, \"\"quoted\"\"\nline\",\r\n,plain\r\n");
+    }
+
+    #[test]
+    fn ndjson_writes_one_document_per_line() {
+        assert_eq!(to_ndjson(vec![json!({"a": 1}), json!([2])]), "{\"a\":1}\n[2]\n");
+    }
+
+    #[test]
+    fn raw_bodies_skip_negotiation() {
+        let response = ApiResponse::csv(vec![json!({"a": 1})]);
+        assert!(!response.negotiate);
+        assert_eq!(response.headers["Content-Type"], "text/csv; charset=utf-8");
+        assert_eq!(response.body.as_deref(), Some(&b"a\r\n1\r\n"[..]));
+    }
+}
@@ -0,0 +1,241 @@
+use super::{ApiHandler, ApiRegistry, ApiRoute};
+use crate::middleware::Middleware;
+use std::sync::Arc;
+
+// Routes registered under a shared path prefix, with middleware and optionally an API version
+// applied to all of them, e.g.
+//
+//     let list = Arc::new(ListProjects);
+//     registry.group("/api/v1", |g| {
+//         g.add_route(Method::GET, "/projects", list.clone());
+//     });
+//     registry.group("/api/v2", |g| {
+//         g.middleware(AuthGuard::new(auth.clone()));
+//         g.add_route(Method::GET, "/projects", list.clone());
+//         g.group("/admin", |admin| {
+//             admin.add_route(Method::DELETE, "/projects/:id", DeleteProject);
+//         });
+//     });
+//
+// Middleware added to a group runs around the routes of its nested groups too, outside their
+// own, whether it was added before or after them.
+pub struct ApiGroup {
+    prefix: String,
+    version: Option<String>,
+    middleware: Vec<Arc<dyn Middleware>>,
+    routes: Vec<ApiRoute>,
+}
+
+impl ApiGroup {
+    fn new(prefix: String, version: Option<String>) -> Self {
+        ApiGroup { prefix, version, middleware: Vec::new(), routes: Vec::new() }
+    }
+
+    pub fn middleware<M: Middleware + 'static>(&mut self, middleware: M) -> &mut Self {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    // The version of the group's routes, and of its nested groups unless they set their own
+    pub fn version(&mut self, version: &str) -> &mut Self {
+        self.version = Some(version.to_string());
+        self
+    }
+
+    pub fn add_route<H>(&mut self, method: hyper::Method, path: &str, handler: H) -> &mut Self
+    where
+        H: ApiHandler + 'static,
+    {
+        self.routes.push(ApiRoute::new(method, &join_path(&self.prefix, path), handler));
+        self
+    }
+
+    pub fn group<F>(&mut self, prefix: &str, build: F) -> &mut Self
+    where
+        F: FnOnce(&mut ApiGroup),
+    {
+        let mut group = ApiGroup::new(join_path(&self.prefix, prefix), self.version.clone());
+        build(&mut group);
+        self.routes.extend(group.into_routes());
+        self
+    }
+
+    fn into_routes(self) -> Vec<ApiRoute> {
+        let ApiGroup { version, middleware, routes, .. } = self;
+        routes.into_iter().map(|mut route| {
+            route.middleware.splice(0..0, middleware.iter().cloned());
+            if route.version.is_none() {
+                route.version = version.clone();
+            }
+            route
+        }).collect()
+    }
+}
+
+impl ApiRegistry {
+    pub fn group<F>(&mut self, prefix: &str, build: F)
+    where
+        F: FnOnce(&mut ApiGroup),
+    {
+        let mut group = ApiGroup::new(join_path("", prefix), None);
+        build(&mut group);
+        for route in group.into_routes() {
+            self.push_route(route);
+        }
+    }
+}
+
+// "/api/v1" + "/projects" is "/api/v1/projects"; "" or "/" adds nothing
+fn join_path(prefix: &str, path: &str) -> String {
+    let prefix = prefix.trim_end_matches('/');
+    let path = path.trim_start_matches('/');
+    match (prefix.is_empty(), path.is_empty()) {
+        (true, true) => "/".to_string(),
+        (_, true) => prefix.to_string(),
+        (true, false) => format!("/{}", path),
+        (false, false) => format!("{}/{}", prefix, path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{ApiError, ApiResponse, VersionStrategy};
+    use crate::{Handler, Request, Response};
+    use async_trait::async_trait;
+    use serde_json::{json, Value};
+    use std::sync::Mutex;
+
+    type Trace = Arc<Mutex<Vec<&'static str>>>;
+
+    // Records its name on the way in
+    struct Tag(&'static str, Trace);
+
+    #[async_trait]
+    impl Middleware for Tag {
+        async fn handle(&self, req: Request, next: Arc<dyn Handler>) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+            self.1.lock().unwrap().push(self.0);
+            next.handle(req).await
+        }
+    }
+
+    // Answers with the matched route
+    struct Echo;
+
+    #[async_trait]
+    impl ApiHandler for Echo {
+        async fn handle(&self, req: Request) -> Result<ApiResponse, ApiError> {
+            Ok(ApiResponse::ok(json!({"route": req.route})))
+        }
+    }
+
+    async fn send(registry: &ApiRegistry, uri: &str, headers: &[(&str, &str)]) -> Option<Response> {
+        let mut builder = hyper::Request::builder().uri(uri);
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        let req = Request::from_hyper(builder.body(hyper::Body::empty()).unwrap()).await.unwrap();
+        registry.handle_request(req).await
+    }
+
+    async fn body_json(response: Response) -> Value {
+        serde_json::from_slice(&hyper::body::to_bytes(response.body).await.unwrap()).unwrap()
+    }
+
+    // Runs a request and returns the middleware it went through, in order
+    async fn trail(registry: &ApiRegistry, uri: &str, trace: &Trace) -> Vec<&'static str> {
+        trace.lock().unwrap().clear();
+        let response = send(registry, uri, &[]).await.expect("a response");
+        assert_eq!(response.status, hyper::StatusCode::OK);
+        trace.lock().unwrap().clone()
+    }
+
+    #[test]
+    fn join_path_handles_slashes_and_empty_parts() {
+        assert_eq!(join_path("/api/v1", "/projects"), "/api/v1/projects");
+        assert_eq!(join_path("/api/v1/", "projects"), "/api/v1/projects");
+        assert_eq!(join_path("/api/v1/", "/projects/"), "/api/v1/projects/");
+        assert_eq!(join_path("/api", ""), "/api");
+        assert_eq!(join_path("/api", "/"), "/api");
+        assert_eq!(join_path("", "projects"), "/projects");
+        assert_eq!(join_path("/", "/projects"), "/projects");
+        assert_eq!(join_path("", ""), "/");
+        assert_eq!(join_path("/", "/"), "/");
+    }
+
+    #[tokio::test]
+    async fn one_handler_serves_two_groups_with_their_own_middleware() {
+        let trace = Trace::default();
+        let echo = Arc::new(Echo);
+        let mut registry = ApiRegistry::new();
+        registry.group("/api/v1", |g| {
+            g.middleware(Tag("v1", trace.clone()));
+            g.add_route(hyper::Method::GET, "/projects", echo.clone());
+        });
+        registry.group("/api/v2", |g| {
+            g.middleware(Tag("v2", trace.clone()));
+            g.add_route(hyper::Method::GET, "/projects", echo.clone());
+        });
+
+        assert_eq!(trail(&registry, "/api/v1/projects", &trace).await, ["v1"]);
+        assert_eq!(trail(&registry, "/api/v2/projects", &trace).await, ["v2"]);
+        let response = send(&registry, "/api/v2/projects", &[]).await.unwrap();
+        assert_eq!(body_json(response).await, json!({"route": "/api/v2/projects"}));
+        assert!(send(&registry, "/api/v3/projects", &[]).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn outer_middleware_wraps_nested_groups_wherever_it_was_added() {
+        let trace = Trace::default();
+        let mut registry = ApiRegistry::new();
+        registry.group("/api", |api| {
+            api.middleware(Tag("outer-before", trace.clone()));
+            api.group("/admin", |admin| {
+                admin.middleware(Tag("inner", trace.clone()));
+                admin.add_route(hyper::Method::GET, "/projects", Echo);
+            });
+            api.middleware(Tag("outer-after", trace.clone()));
+            api.add_route(hyper::Method::GET, "/projects", Echo);
+        });
+
+        assert_eq!(
+            trail(&registry, "/api/admin/projects", &trace).await,
+            ["outer-before", "outer-after", "inner"]
+        );
+        assert_eq!(trail(&registry, "/api/projects", &trace).await, ["outer-before", "outer-after"]);
+    }
+
+    #[tokio::test]
+    async fn nested_groups_inherit_the_version_unless_they_set_their_own() {
+        let mut registry = ApiRegistry::new();
+        registry.set_version_strategy(VersionStrategy::Header { vendor: "myapp".to_string() });
+        registry.group("/api", |api| {
+            api.group("/early", |g| {
+                g.add_route(hyper::Method::GET, "/projects", Echo);
+            });
+            api.version("v2");
+            api.group("/late", |g| {
+                g.add_route(hyper::Method::GET, "/projects", Echo);
+            });
+            api.group("/own", |g| {
+                g.version("v3");
+                g.add_route(hyper::Method::GET, "/projects", Echo);
+            });
+        });
+
+        let versions: Vec<(&str, Option<&str>)> = registry.routes.iter()
+            .map(|route| (route.path.as_str(), route.version.as_deref()))
+            .collect();
+        assert_eq!(versions, [
+            ("/api/early/projects", Some("v2")),
+            ("/api/late/projects", Some("v2")),
+            ("/api/own/projects", Some("v3")),
+        ]);
+
+        // Only served to v2 clients
+        let v2 = [("accept", "application/vnd.myapp.v2+json")];
+        let response = send(&registry, "/api/early/projects", &v2).await.unwrap();
+        assert_eq!(response.status, hyper::StatusCode::OK);
+        assert!(send(&registry, "/api/early/projects", &[]).await.is_none());
+    }
+}
@@ -1,17 +1,45 @@
-use crate::{Request, Response};
+use crate::error::{AppError, IntoResponse};
+use crate::middleware::Middleware;
+use crate::router::{match_paths, normalize_request, MiddlewareHandler, RouteTable, TrailingSlash};
+use crate::{Handler, Request, Response};
 use async_trait::async_trait;
 use serde_json::Value;
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::sync::Arc;
 use once_cell::sync::OnceCell;
-use tokio::sync::Mutex;
+use tokio::sync::RwLock;
 use regex::Regex; // Add this import
 
+pub mod etag;
+pub mod export;
+pub mod group;
+pub mod negotiation;
+pub mod openapi;
+pub mod pagination;
+pub mod query;
+pub mod typed;
+pub mod versioning;
+
+pub use etag::etag_for;
+pub use group::ApiGroup;
+pub use negotiation::Formatter;
+pub use openapi::OperationMeta;
+pub use pagination::{Paginated, Pagination};
+pub use query::{ListQuery, SortOrder};
+pub use typed::{parse_body, TypedApiHandler};
+pub use versioning::VersionStrategy;
+
 pub struct ApiRoute {
     pub path: String,
     pub method: hyper::Method,
     pub regex: Regex, // Add regex field
     pub param_names: Vec<String>, // Add param_names field
-    pub handler: Box<dyn ApiHandler>,
+    pub handler: Arc<dyn ApiHandler>,
+    // None: the registry's default version
+    pub version: Option<String>,
+    // Run around the handler, outermost first (see `ApiRegistry::group`)
+    pub middleware: Vec<Arc<dyn Middleware>>,
 }
 
 impl ApiRoute {
@@ -19,57 +47,39 @@ impl ApiRoute {
     where
         H: ApiHandler + 'static,
     {
-        let (regex, param_names) = Self::path_to_regex(path);
+        let (regex, param_names) = crate::router::compile_path(path);
         ApiRoute {
             path: path.to_string(),
             method,
             regex,
             param_names,
-            handler: Box::new(handler),
-        }
-    }
-
-    // Copied from src/router.rs to enable regex matching for API routes
-    fn path_to_regex(path: &str) -> (Regex, Vec<String>) {
-        let mut regex_str = String::new();
-        let mut param_names = Vec::new();
-        let mut chars = path.chars().peekable();
-
-        regex_str.push('^');
-
-        while let Some(ch) = chars.next() {
-            match ch {
-                ':' => {
-                    let mut param_name = String::new();
-                    while let Some(&next_ch) = chars.peek() {
-                        if next_ch.is_alphanumeric() || next_ch == '_' {
-                            param_name.push(chars.next().unwrap());
-                        } else {
-                            break;
-                        }
-                    }
-                    param_names.push(param_name);
-                    regex_str.push_str("([^/]+)");
-                }
-                '*' => {
-                    regex_str.push_str("(.*)");
-                }
-                '.' | '+' | '?' | '^' | '$' | '{' | '}' | '[' | ']' | '|' | '(' | ')' | '\\' => {
-                    regex_str.push('\\');
-                    regex_str.push(ch);
-                }
-                _ => regex_str.push(ch),
-            }
+            handler: Arc::new(handler),
+            version: None,
+            middleware: Vec::new(),
         }
-
-        regex_str.push('$');
-        (Regex::new(&regex_str).unwrap(), param_names)
     }
 }
 
 #[async_trait]
 pub trait ApiHandler: Send + Sync {
     async fn handle(&self, req: Request) -> Result<ApiResponse, ApiError>;
+
+    // Documentation for the generated OpenAPI spec
+    fn describe(&self) -> OperationMeta {
+        OperationMeta::default()
+    }
+}
+
+// Lets one handler serve several routes, e.g. the same endpoint in two groups
+#[async_trait]
+impl<H: ApiHandler + ?Sized> ApiHandler for Arc<H> {
+    async fn handle(&self, req: Request) -> Result<ApiResponse, ApiError> {
+        (**self).handle(req).await
+    }
+
+    fn describe(&self) -> OperationMeta {
+        (**self).describe()
+    }
 }
 
 #[derive(Debug)]
@@ -77,6 +87,10 @@ pub struct ApiResponse {
     pub status: hyper::StatusCode,
     pub data: Value,
     pub headers: HashMap<String, String>,
+    // Whether the registry may render `data` as something other than JSON based on `Accept`
+    pub negotiate: bool,
+    // Pre-encoded body (CSV, NDJSON, ...) sent instead of `data`; set by `ApiResponse::raw`
+    pub body: Option<Vec<u8>>,
 }
 
 impl ApiResponse {
@@ -85,6 +99,8 @@ impl ApiResponse {
             status: hyper::StatusCode::OK,
             data,
             headers: HashMap::new(),
+            negotiate: true,
+            body: None,
         }
     }
 
@@ -93,6 +109,8 @@ impl ApiResponse {
             status: hyper::StatusCode::CREATED,
             data,
             headers: HashMap::new(),
+            negotiate: true,
+            body: None,
         }
     }
 
@@ -101,9 +119,19 @@ impl ApiResponse {
             status,
             data: serde_json::json!({"error": message}),
             headers: HashMap::new(),
+            negotiate: true,
+            body: None,
         }
     }
 
+    // 303 to `location`, also as `HX-Redirect` so htmx requests follow it
+    pub fn redirect(location: &str) -> Self {
+        ApiResponse::ok(serde_json::json!({"location": location}))
+            .with_status(hyper::StatusCode::SEE_OTHER)
+            .header("Location", location)
+            .header(crate::htmx::HX_REDIRECT, location)
+    }
+
     pub fn with_status(mut self, status: hyper::StatusCode) -> Self {
         self.status = status;
         self
@@ -113,107 +141,426 @@ impl ApiResponse {
         self.headers.insert(key.to_string(), value.to_string());
         self
     }
+
+    // `with_negotiation(false)` always answers with JSON, whatever the client accepts
+    pub fn with_negotiation(mut self, negotiate: bool) -> Self {
+        self.negotiate = negotiate;
+        self
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ApiError {
     pub status: hyper::StatusCode,
     pub message: String,
+    // Machine-readable, for clients to branch on (e.g. "validation_failed")
+    pub code: Option<String>,
+    // Anything else the client needs, e.g. the messages per field
+    pub details: Option<Value>,
+    // Sent with the error response, e.g. `Retry-After` or `WWW-Authenticate`. A Vec keeps
+    // `Result<_, ApiError>` small.
+    pub headers: Vec<(String, String)>,
 }
 
 impl ApiError {
-    pub fn bad_request(message: &str) -> Self {
+    pub fn new(status: hyper::StatusCode, message: &str) -> Self {
         ApiError {
-            status: hyper::StatusCode::BAD_REQUEST,
+            status,
             message: message.to_string(),
+            code: None,
+            details: None,
+            headers: Vec::new(),
         }
     }
 
+    pub fn bad_request(message: &str) -> Self {
+        Self::new(hyper::StatusCode::BAD_REQUEST, message)
+    }
+
     pub fn not_found(message: &str) -> Self {
-        ApiError {
-            status: hyper::StatusCode::NOT_FOUND,
-            message: message.to_string(),
-        }
+        Self::new(hyper::StatusCode::NOT_FOUND, message)
     }
 
     pub fn internal_error(message: &str) -> Self {
-        ApiError {
-            status: hyper::StatusCode::INTERNAL_SERVER_ERROR,
-            message: message.to_string(),
+        Self::new(hyper::StatusCode::INTERNAL_SERVER_ERROR, message)
+    }
+
+    pub fn unprocessable(message: &str) -> Self {
+        Self::new(hyper::StatusCode::UNPROCESSABLE_ENTITY, message)
+    }
+
+    // 422 with code "validation_failed", e.g. `ApiError::validation(json!({"email": ["is required"]}))`
+    pub fn validation(details: Value) -> Self {
+        Self::unprocessable("Validation failed")
+            .with_code("validation_failed")
+            .with_details(details)
+    }
+
+    pub fn with_code(mut self, code: &str) -> Self {
+        self.code = Some(code.to_string());
+        self
+    }
+
+    pub fn with_details(mut self, details: Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    pub fn header(mut self, key: &str, value: &str) -> Self {
+        self.headers.retain(|(existing, _)| !existing.eq_ignore_ascii_case(key));
+        self.headers.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    // `{"error": message, "code": .., "details": ..}`, without the fields that aren't set
+    pub fn to_json(&self) -> Value {
+        let mut json = serde_json::json!({"error": self.message});
+        if let Some(code) = &self.code {
+            json["code"] = Value::String(code.clone());
+        }
+        if let Some(details) = &self.details {
+            json["details"] = details.clone();
+        }
+        json
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.status, self.message)
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+        let response = Response::new().status(self.status).json(&self.to_json())?;
+        Ok(self.headers.into_iter().fold(response, |response, (key, value)| response.header(key, value)))
+    }
+}
+
+// The conversions below let ApiHandlers use `?`. Parse failures are the client's fault (400);
+// anything else is reported as the App's error handling would report it.
+impl From<serde_json::Error> for ApiError {
+    fn from(err: serde_json::Error) -> Self {
+        ApiError::bad_request(&format!("Invalid JSON: {}", err))
+    }
+}
+
+impl From<std::num::ParseIntError> for ApiError {
+    fn from(err: std::num::ParseIntError) -> Self {
+        ApiError::bad_request(&format!("Invalid number: {}", err))
+    }
+}
+
+impl From<crate::forms::ValidationErrors> for ApiError {
+    fn from(errors: crate::forms::ValidationErrors) -> Self {
+        ApiError::validation(errors.to_json())
+    }
+}
+
+impl From<AppError> for ApiError {
+    fn from(err: AppError) -> Self {
+        ApiError::new(err.status(), err.message())
+    }
+}
+
+// E.g. the errors of `Request::json` and `Request::form`
+impl From<Box<dyn std::error::Error + Send + Sync>> for ApiError {
+    fn from(err: Box<dyn std::error::Error + Send + Sync>) -> Self {
+        if let Some(api_err) = err.downcast_ref::<ApiError>() {
+            api_err.clone()
+        } else if let Some(json_err) = err.downcast_ref::<serde_json::Error>() {
+            ApiError::bad_request(&format!("Invalid JSON: {}", json_err))
+        } else if let Some(utf8_err) = err.downcast_ref::<std::string::FromUtf8Error>() {
+            ApiError::bad_request(&format!("Invalid UTF-8 in body: {}", utf8_err))
+        } else if let Some(errors) = err.downcast_ref::<crate::forms::ValidationErrors>() {
+            ApiError::validation(errors.to_json())
+        } else {
+            AppError::from_ref(err.as_ref()).into()
         }
     }
 }
 
 pub struct ApiRegistry {
     routes: Vec<ApiRoute>,
+    // Media types in preference order; the first one is the default for `*/*` or no `Accept`.
+    // Shared with requests in flight.
+    formatters: Arc<Vec<(String, Formatter)>>,
+    version_strategy: Option<VersionStrategy>,
+    default_version: String,
+    trailing_slash: TrailingSlash,
+    // Positions in `routes` by method, most specific path first, so `/projects/new` wins over
+    // `/projects/:id` in any order. Ties keep registration order.
+    index: HashMap<hyper::Method, Vec<IndexEntry>>,
+    // The index's routes matched in one pass; built on first use, and again after routes are added
+    table: OnceCell<(RouteTable, Vec<usize>)>,
+    // Each route's handler wrapped in its middleware, by position in `routes`; composed on first
+    // use, and again after routes or formatters change
+    chains: OnceCell<Vec<Arc<dyn Handler>>>,
+}
+
+struct IndexEntry {
+    specificity: Vec<u8>,
+    route: usize,
+}
+
+impl IndexEntry {
+    fn new(path: &str, route: usize) -> Self {
+        // Per segment: literal before `:param` before `*`
+        let specificity = path.split('/').map(|segment| {
+            if segment.contains('*') {
+                2
+            } else if segment.contains(':') {
+                1
+            } else {
+                0
+            }
+        }).collect();
+        IndexEntry { specificity, route }
+    }
 }
 
 impl ApiRegistry {
     pub fn new() -> Self {
+        let formatters: Vec<(String, Formatter)> = vec![
+            ("application/json".to_string(), Arc::new(|data: &Value| data.to_string())),
+            ("text/html".to_string(), Arc::new(negotiation::html_view)),
+            ("text/plain".to_string(), Arc::new(negotiation::text_view)),
+        ];
         ApiRegistry {
             routes: Vec::new(),
+            formatters: Arc::new(formatters),
+            version_strategy: None,
+            default_version: "v1".to_string(),
+            trailing_slash: TrailingSlash::default(),
+            index: HashMap::new(),
+            table: OnceCell::new(),
+            chains: OnceCell::new(),
         }
     }
 
+    // Adds a media type (e.g. `text/csv`) or replaces the formatter of an existing one
+    pub fn register_formatter<F>(&mut self, media_type: &str, formatter: F)
+    where
+        F: Fn(&Value) -> String + Send + Sync + 'static,
+    {
+        let formatter: Formatter = Arc::new(formatter);
+        let formatters = Arc::make_mut(&mut self.formatters);
+        match formatters.iter_mut().find(|(existing, _)| existing.eq_ignore_ascii_case(media_type)) {
+            Some(entry) => entry.1 = formatter,
+            None => formatters.push((media_type.to_string(), formatter)),
+        }
+        self.chains = OnceCell::new();
+    }
+
+    pub fn supported_media_types(&self) -> Vec<&str> {
+        self.formatters.iter().map(|(media_type, _)| media_type.as_str()).collect()
+    }
+
+    // Same policy and path normalization as `Router::trailing_slash`
+    pub fn set_trailing_slash(&mut self, policy: TrailingSlash) {
+        self.trailing_slash = policy;
+    }
+
     pub fn add_route<H>(&mut self, method: hyper::Method, path: &str, handler: H)
     where
         H: ApiHandler + 'static,
     {
-        self.routes.push(ApiRoute::new(method, path, handler)); // Use the new constructor
+        self.push_route(ApiRoute::new(method, path, handler)); // Use the new constructor
+    }
+
+    pub(crate) fn push_route(&mut self, route: ApiRoute) {
+        let entry = IndexEntry::new(&route.path, self.routes.len());
+        let entries = self.index.entry(route.method.clone()).or_default();
+        // After routes as specific, keeping registration order among equals
+        let at = entries.partition_point(|existing| existing.specificity <= entry.specificity);
+        entries.insert(at, entry);
+        self.routes.push(route);
+        self.table = OnceCell::new();
+        self.chains = OnceCell::new();
+    }
+
+    // Positions in `routes` of the routes of `method` matching any of `paths`, most specific first
+    fn matching_routes(&self, method: &hyper::Method, paths: &[Cow<str>]) -> Vec<usize> {
+        let (table, order) = self.table.get_or_init(|| {
+            let order: Vec<usize> = self.index.values().flatten().map(|entry| entry.route).collect();
+            let table = RouteTable::new(order.iter().map(|&i| (&self.routes[i].method, &self.routes[i].regex)));
+            (table, order)
+        });
+        let mut positions: Vec<usize> = paths.iter().flat_map(|path| table.matches(method, path)).collect();
+        positions.sort_unstable();
+        positions.dedup();
+        positions.into_iter().map(|position| order[position]).collect()
+    }
+
+    fn chain(&self, route: usize) -> &Arc<dyn Handler> {
+        let chains = self.chains.get_or_init(|| {
+            self.routes.iter().map(|route| {
+                let endpoint: Arc<dyn Handler> = Arc::new(Endpoint {
+                    handler: route.handler.clone(),
+                    formatters: self.formatters.clone(),
+                });
+                route.middleware.iter().rev().fold(endpoint, |next, middleware| {
+                    Arc::new(MiddlewareHandler { middleware: middleware.clone(), next })
+                })
+            }).collect()
+        });
+        &chains[route]
     }
 
     pub async fn handle_request(&self, mut req: Request) -> Option<Response> {
-        let req_path = req.uri.path().to_string(); // Clone path once for iteration
-        let req_method = req.method.clone(); // Clone method once for iteration
-
-        for route in &self.routes {
-            if route.method == req_method {
-                if let Some(captures) = route.regex.captures(&req_path) {
-                    // Found a match! Now, extract parameters and update the request.
-                    let mut params = HashMap::new();
-                    for (i, param_name) in route.param_names.iter().enumerate() {
-                        if let Some(capture) = captures.get(i + 1) {
-                            params.insert(param_name.clone(), capture.as_str().to_string());
-                        }
-                    }
-                    
-                    // Extend the request's parameters with the newly extracted ones
-                    req.params.extend(params);
-
-                    // Call the handler with the modified `req`
-                    match route.handler.handle(req).await { // `req` is moved here
-                        Ok(api_response) => {
-                            let mut response = Response::new()
-                                .status(api_response.status)
-                                .json(&api_response.data)
-                                .unwrap_or_else(|_| Response::new().status(hyper::StatusCode::INTERNAL_SERVER_ERROR));
-                            
-                            for (key, value) in api_response.headers {
-                                response.headers.insert(key, value);
-                            }
-                            
-                            return Some(response);
-                        }
-                        Err(api_error) => {
-                            return Some(
-                                Response::new()
-                                    .status(api_error.status)
-                                    .json(&serde_json::json!({"error": api_error.message}))
-                                    .unwrap_or_else(|_| Response::new().status(hyper::StatusCode::INTERNAL_SERVER_ERROR))
-                            );
-                        }
-                    }
-                }
+        match normalize_request(&mut req, self.trailing_slash) {
+            Ok(None) => {}
+            Ok(Some(redirect)) => return Some(redirect),
+            Err(e) => return Some(error_response(e.into())),
+        }
+        let (version, req_path) = self.resolve_version(&req);
+        if !self.has_version(&version) {
+            return Some(
+                Response::new()
+                    .status(hyper::StatusCode::NOT_FOUND)
+                    .json(&serde_json::json!({
+                        "error": format!("API version {} not found", version),
+                        "available_versions": self.versions(),
+                    }))
+                    .unwrap_or_else(|_| Response::new().status(hyper::StatusCode::NOT_FOUND))
+            );
+        }
+
+        // Only the winning route's params are copied out of the path
+        let paths = match_paths(&req_path, self.trailing_slash);
+        let matched = self.matching_routes(&req.method, &paths).into_iter().find_map(|i| {
+            let route = &self.routes[i];
+            if self.route_version(route) != version {
+                return None;
             }
+            let captures = paths.iter().find_map(|path| route.regex.captures(path))?;
+            let params: Vec<(String, String)> = route.param_names.iter().enumerate()
+                .filter_map(|(n, name)| Some((name.clone(), captures.get(n + 1)?.as_str().to_string())))
+                .collect();
+            Some((i, params))
+        });
+        if let Some((i, params)) = matched {
+            let route = &self.routes[i];
+            req.params.extend(params);
+            // More specific than the Router's route that dispatched here
+            req.route = Some(route.path.clone());
+            #[cfg(feature = "tracing")]
+            tracing::Span::current().record("http.route", route.path.as_str());
+
+            // Middleware errors (e.g. AuthGuard's) are answered like handler errors
+            return Some(self.chain(i).handle(req).await.unwrap_or_else(|e| error_response(e.into())));
+        }
+
+        // The path exists, just not for this method
+        let mut first_routes: Vec<(usize, &str)> = self.index.keys()
+            .filter_map(|method| {
+                let first = self.matching_routes(method, &paths).into_iter()
+                    .filter(|&i| self.route_version(&self.routes[i]) == version)
+                    .min()?;
+                Some((first, method.as_str()))
+            })
+            .collect();
+        first_routes.sort_by_key(|(first, _)| *first);
+        let allowed: Vec<&str> = first_routes.into_iter().map(|(_, method)| method).collect();
+        if !allowed.is_empty() {
+            return Some(
+                Response::new()
+                    .status(hyper::StatusCode::METHOD_NOT_ALLOWED)
+                    .header("Allow", allowed.join(", "))
+                    .json(&serde_json::json!({
+                        "error": format!("Method {} not allowed", req.method),
+                        "allowed_methods": allowed,
+                    }))
+                    .unwrap_or_else(|_| Response::new().status(hyper::StatusCode::METHOD_NOT_ALLOWED))
+            );
         }
         None // If no route matches after checking all, return None
     }
 }
 
-static GLOBAL_API_REGISTRY: OnceCell<Mutex<ApiRegistry>> = OnceCell::new();
+// A route's handler and the rendering of its result, as the innermost handler of its middleware
+struct Endpoint {
+    handler: Arc<dyn ApiHandler>,
+    formatters: Arc<Vec<(String, Formatter)>>,
+}
+
+#[async_trait]
+impl Handler for Endpoint {
+    async fn handle(&self, req: Request) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+        let accept = req.headers.get(hyper::header::ACCEPT)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
+        match self.handler.handle(req).await {
+            Ok(api_response) => Ok(render(&self.formatters, api_response, accept.as_deref())),
+            Err(api_error) => api_error.into_response(),
+        }
+    }
+}
 
-pub fn get_api_registry() -> &'static Mutex<ApiRegistry> {
-    GLOBAL_API_REGISTRY.get_or_init(|| Mutex::new(ApiRegistry::new()))
+fn error_response(error: ApiError) -> Response {
+    error.into_response()
+        .unwrap_or_else(|_| Response::new().status(hyper::StatusCode::INTERNAL_SERVER_ERROR))
+}
+
+fn render(formatters: &[(String, Formatter)], mut api_response: ApiResponse, accept: Option<&str>) -> Response {
+    if let Some(body) = api_response.body.take() {
+        let response = Response::new()
+            .status(api_response.status)
+            .body(hyper::Body::from(body));
+        return with_headers(response, api_response.headers);
+    }
+
+    let supported: Vec<&str> = formatters.iter().map(|(media_type, _)| media_type.as_str()).collect();
+    let media_type = if api_response.negotiate {
+        match negotiation::negotiate(accept, &supported) {
+            Some(media_type) => media_type,
+            None => {
+                return Response::new()
+                    .status(hyper::StatusCode::NOT_ACCEPTABLE)
+                    .json(&serde_json::json!({
+                        "error": "Not Acceptable",
+                        "supported": supported,
+                    }))
+                    .unwrap_or_else(|_| Response::new().status(hyper::StatusCode::NOT_ACCEPTABLE));
+            }
+        }
+    } else {
+        "application/json"
+    };
+
+    let body = match formatters.iter().find(|(existing, _)| existing == media_type) {
+        Some((_, formatter)) => formatter(&api_response.data),
+        None => api_response.data.to_string(),
+    };
+
+    let mut response = Response::new()
+        .status(api_response.status)
+        .body(hyper::Body::from(body))
+        .header("Content-Type", media_type);
+    if api_response.negotiate {
+        response = response.header(hyper::header::VARY, "Accept");
+    }
+    with_headers(response, api_response.headers)
+}
+
+fn with_headers(response: Response, headers: HashMap<String, String>) -> Response {
+    headers.into_iter().fold(response, |response, (key, value)| response.header(key, value))
+}
+
+impl Default for ApiRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+static GLOBAL_API_REGISTRY: OnceCell<RwLock<ApiRegistry>> = OnceCell::new();
+
+// Routes are registered (`write()`) at startup; requests only `read()`, so they run concurrently
+pub fn get_api_registry() -> &'static RwLock<ApiRegistry> {
+    GLOBAL_API_REGISTRY.get_or_init(|| RwLock::new(ApiRegistry::new()))
 }
 
 #[macro_export]
@@ -221,9 +568,352 @@ macro_rules! api_route {
     ($method:expr, $path:expr, $handler:expr) => {
         // This macro now expands to an async block that returns a Future
         async {
-            let mut registry = crate::api::get_api_registry().lock().await;
+            let mut registry = $crate::api::get_api_registry().write().await;
             registry.add_route($method, $path, $handler); // This will now call ApiRoute::new internally
             Ok::<(), Box<dyn std::error::Error + Send + Sync>>(()) // Return a Result
         }
     };
+    (version = $version:expr, $method:expr, $path:expr, $handler:expr) => {
+        async {
+            let mut registry = $crate::api::get_api_registry().write().await;
+            registry.add_versioned_route($version, $method, $path, $handler);
+            Ok::<(), Box<dyn std::error::Error + Send + Sync>>(())
+        }
+    };
+}
+
+// `api_group!("/api/v1", |g| { g.add_route(Method::GET, "/projects", ListProjects); }).await?`
+// registers a group on the global registry
+#[macro_export]
+macro_rules! api_group {
+    ($prefix:expr, $build:expr) => {
+        async {
+            let mut registry = $crate::api::get_api_registry().write().await;
+            registry.group($prefix, $build);
+            Ok::<(), Box<dyn std::error::Error + Send + Sync>>(())
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // Answers with the matched route and its parameters
+    struct Echo;
+
+    #[async_trait]
+    impl ApiHandler for Echo {
+        async fn handle(&self, req: Request) -> Result<ApiResponse, ApiError> {
+            Ok(ApiResponse::ok(json!({"route": req.route, "params": req.params})))
+        }
+    }
+
+    async fn send(registry: &ApiRegistry, method: &str, uri: &str, headers: &[(&str, &str)]) -> Option<Response> {
+        let mut builder = hyper::Request::builder().method(method).uri(uri);
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        let req = Request::from_hyper(builder.body(hyper::Body::empty()).unwrap()).await.unwrap();
+        registry.handle_request(req).await
+    }
+
+    async fn body_json(response: Response) -> Value {
+        let bytes = hyper::body::to_bytes(response.body).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn renders_the_negotiated_format() {
+        let mut registry = ApiRegistry::new();
+        registry.add_route(hyper::Method::GET, "/api/items", Echo);
+
+        let json = send(&registry, "GET", "/api/items", &[]).await.unwrap();
+        assert_eq!(json.headers.get("content-type").unwrap(), "application/json");
+        assert_eq!(json.headers.get("vary").unwrap(), "Accept");
+
+        let html = send(&registry, "GET", "/api/items", &[("accept", "text/html")]).await.unwrap();
+        assert_eq!(html.headers.get("content-type").unwrap(), "text/html");
+
+        let refused = send(&registry, "GET", "/api/items", &[("accept", "image/png")]).await.unwrap();
+        assert_eq!(refused.status, hyper::StatusCode::NOT_ACCEPTABLE);
+        assert_eq!(body_json(refused).await["supported"], json!(["application/json", "text/html", "text/plain"]));
+    }
+
+    #[tokio::test]
+    async fn registered_formatters_join_the_negotiation() {
+        let mut registry = ApiRegistry::new();
+        registry.register_formatter("text/csv", |_data: &Value| "a,b".to_string());
+        registry.add_route(hyper::Method::GET, "/api/items", Echo);
+
+        let csv = send(&registry, "GET", "/api/items", &[("accept", "text/csv")]).await.unwrap();
+        assert_eq!(csv.headers.get("content-type").unwrap(), "text/csv");
+        assert_eq!(hyper::body::to_bytes(csv.body).await.unwrap(), "a,b");
+    }
+
+    struct Export;
+
+    #[async_trait]
+    impl ApiHandler for Export {
+        async fn handle(&self, _req: Request) -> Result<ApiResponse, ApiError> {
+            Ok(ApiResponse::ndjson(vec![json!({"id": 1}), json!({"id": 2})]))
+        }
+    }
+
+    #[tokio::test]
+    async fn raw_bodies_are_sent_whatever_the_client_accepts() {
+        let mut registry = ApiRegistry::new();
+        registry.add_route(hyper::Method::GET, "/api/export", Export);
+
+        let response = send(&registry, "GET", "/api/export", &[("accept", "text/html")]).await.unwrap();
+        assert_eq!(response.status, hyper::StatusCode::OK);
+        assert_eq!(response.headers.get("content-type").unwrap(), "application/x-ndjson");
+        assert_eq!(hyper::body::to_bytes(response.body).await.unwrap(), "{\"id\":1}\n{\"id\":2}\n");
+    }
+
+    // Answers every request with the response it was built with
+    struct Responds(fn() -> Result<ApiResponse, ApiError>);
+
+    #[async_trait]
+    impl ApiHandler for Responds {
+        async fn handle(&self, _req: Request) -> Result<ApiResponse, ApiError> {
+            (self.0)()
+        }
+    }
+
+    async fn respond_with(respond: fn() -> Result<ApiResponse, ApiError>) -> Response {
+        let mut registry = ApiRegistry::new();
+        registry.add_route(hyper::Method::GET, "/api/thing", Responds(respond));
+        send(&registry, "GET", "/api/thing", &[]).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn text_and_bytes_are_sent_with_their_content_type() {
+        let response = respond_with(|| Ok(ApiResponse::text("plain words"))).await;
+        assert_eq!(response.status, hyper::StatusCode::OK);
+        assert_eq!(response.headers.get("content-type").unwrap(), "text/plain; charset=utf-8");
+        assert_eq!(hyper::body::to_bytes(response.body).await.unwrap(), "plain words");
+
+        let response = respond_with(|| Ok(ApiResponse::bytes("image/png", vec![0x89, b'P', b'N', b'G', 0]))).await;
+        assert_eq!(response.status, hyper::StatusCode::OK);
+        assert_eq!(response.headers.get("content-type").unwrap(), "image/png");
+        assert_eq!(hyper::body::to_bytes(response.body).await.unwrap(), &[0x89, b'P', b'N', b'G', 0][..]);
+    }
+
+    #[tokio::test]
+    async fn redirects_set_location_for_browsers_and_htmx() {
+        let response = respond_with(|| Ok(ApiResponse::redirect("/projects/5"))).await;
+        assert_eq!(response.status, hyper::StatusCode::SEE_OTHER);
+        assert_eq!(response.headers.get("location").unwrap(), "/projects/5");
+        assert_eq!(response.headers.get("hx-redirect").unwrap(), "/projects/5");
+        assert_eq!(response.headers.get("content-type").unwrap(), "application/json");
+        assert_eq!(body_json(response).await, json!({"location": "/projects/5"}));
+    }
+
+    #[tokio::test]
+    async fn response_and_error_headers_are_sent() {
+        let response = respond_with(|| Ok(ApiResponse::ok(json!({"id": 1})).header("X-Request-Id", "abc"))).await;
+        assert_eq!(response.status, hyper::StatusCode::OK);
+        assert_eq!(response.headers.get("x-request-id").unwrap(), "abc");
+        assert_eq!(response.headers.get("content-type").unwrap(), "application/json");
+        assert_eq!(body_json(response).await, json!({"id": 1}));
+
+        // A header set twice keeps the last value, whatever its case
+        let response = respond_with(|| {
+            Err(ApiError::new(hyper::StatusCode::UNAUTHORIZED, "Sign in first")
+                .header("WWW-Authenticate", "Basic")
+                .header("www-authenticate", "Bearer"))
+        }).await;
+        assert_eq!(response.status, hyper::StatusCode::UNAUTHORIZED);
+        let challenges: Vec<_> = response.headers.get_all("www-authenticate").iter().collect();
+        assert_eq!(challenges, ["Bearer"]);
+        assert_eq!(response.headers.get("content-type").unwrap(), "application/json");
+        assert_eq!(body_json(response).await, json!({"error": "Sign in first"}));
+    }
+
+    #[tokio::test]
+    async fn api_paths_compile_like_router_paths() {
+        let mut registry = ApiRegistry::new();
+        registry.add_route(hyper::Method::GET, "/api/reports/:id.csv", Echo);
+
+        let response = send(&registry, "GET", "/api/reports/9.csv", &[]).await.unwrap();
+        assert_eq!(body_json(response).await["params"], json!({"id": "9"}));
+        assert!(send(&registry, "GET", "/api/reports/9xcsv", &[]).await.is_none());
+        assert!(send(&registry, "GET", "/api/reports/9/x.csv", &[]).await.is_none());
+    }
+
+    #[test]
+    fn api_error_json_only_has_the_fields_that_are_set() {
+        assert_eq!(ApiError::not_found("No such post").to_json(), json!({"error": "No such post"}));
+
+        let err = ApiError::validation(json!({"email": ["is required"]}));
+        assert_eq!(err.status, hyper::StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            err.to_json(),
+            json!({"error": "Validation failed", "code": "validation_failed", "details": {"email": ["is required"]}})
+        );
+
+        let err = ApiError::bad_request("Slow down").header("Retry-After", "10").header("retry-after", "30");
+        assert_eq!(err.headers, [("retry-after".to_string(), "30".to_string())]);
+        assert_eq!(err.to_string(), "400 Bad Request: Slow down");
+    }
+
+    #[test]
+    fn conversions_pick_the_status() {
+        let json_err = serde_json::from_str::<Value>("{").unwrap_err();
+        assert_eq!(ApiError::from(json_err).status, hyper::StatusCode::BAD_REQUEST);
+        assert_eq!(ApiError::from("x".parse::<u32>().unwrap_err()).status, hyper::StatusCode::BAD_REQUEST);
+
+        let mut errors = crate::forms::ValidationErrors::new();
+        errors.add("title", "is too short".to_string());
+        let err = ApiError::from(errors.clone());
+        assert_eq!(err.code.as_deref(), Some("validation_failed"));
+        assert_eq!(err.details, Some(json!({"title": ["is too short"]})));
+
+        let err = ApiError::from(AppError::Forbidden("Not yours".to_string()));
+        assert_eq!((err.status, err.message.as_str()), (hyper::StatusCode::FORBIDDEN, "Not yours"));
+
+        // Boxed errors keep what they were
+        let boxed = |err: Box<dyn std::error::Error + Send + Sync>| ApiError::from(err);
+        let err = boxed(Box::new(ApiError::not_found("gone").with_code("post_missing")));
+        assert_eq!((err.status, err.code.as_deref()), (hyper::StatusCode::NOT_FOUND, Some("post_missing")));
+        assert_eq!(boxed(Box::new(serde_json::from_str::<Value>("[").unwrap_err())).status, hyper::StatusCode::BAD_REQUEST);
+        assert_eq!(boxed(Box::new(errors)).status, hyper::StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(boxed(Box::new(AppError::Unauthorized("login".to_string()))).status, hyper::StatusCode::UNAUTHORIZED);
+        assert_eq!(boxed("disk full".into()).status, hyper::StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    struct Limited;
+
+    #[async_trait]
+    impl ApiHandler for Limited {
+        async fn handle(&self, req: Request) -> Result<ApiResponse, ApiError> {
+            let id: u32 = req.params.get("id").unwrap().parse()?;
+            Err(ApiError::new(hyper::StatusCode::TOO_MANY_REQUESTS, &format!("Too many requests for {}", id))
+                .with_code("rate_limited")
+                .header("Retry-After", "5"))
+        }
+    }
+
+    #[tokio::test]
+    async fn handler_errors_become_json_responses() {
+        let mut registry = ApiRegistry::new();
+        registry.add_route(hyper::Method::GET, "/api/items/:id", Limited);
+
+        let response = send(&registry, "GET", "/api/items/3", &[]).await.unwrap();
+        assert_eq!(response.status, hyper::StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers.get("retry-after").unwrap(), "5");
+        assert_eq!(body_json(response).await, json!({"error": "Too many requests for 3", "code": "rate_limited"}));
+
+        let response = send(&registry, "GET", "/api/items/abc", &[]).await.unwrap();
+        assert_eq!(response.status, hyper::StatusCode::BAD_REQUEST);
+        assert!(body_json(response).await["error"].as_str().unwrap().starts_with("Invalid number"));
+    }
+
+    async fn matched_route(registry: &ApiRegistry, method: &str, uri: &str) -> Value {
+        body_json(send(registry, method, uri, &[]).await.unwrap()).await["route"].clone()
+    }
+
+    #[tokio::test]
+    async fn the_most_specific_route_wins_in_any_order() {
+        let mut registry = ApiRegistry::new();
+        registry.add_route(hyper::Method::GET, "/api/projects/*", Echo);
+        registry.add_route(hyper::Method::GET, "/api/projects/:id", Echo);
+        registry.add_route(hyper::Method::GET, "/api/:kind/new", Echo);
+        registry.add_route(hyper::Method::GET, "/api/projects/new", Echo);
+
+        assert_eq!(matched_route(&registry, "GET", "/api/projects/new").await, "/api/projects/new");
+        assert_eq!(matched_route(&registry, "GET", "/api/projects/5").await, "/api/projects/:id");
+        assert_eq!(matched_route(&registry, "GET", "/api/projects/5/files").await, "/api/projects/*");
+        assert_eq!(matched_route(&registry, "GET", "/api/tasks/new").await, "/api/:kind/new");
+
+        // Routes added later join the index
+        registry.add_route(hyper::Method::GET, "/api/projects/archived", Echo);
+        assert_eq!(matched_route(&registry, "GET", "/api/projects/archived").await, "/api/projects/archived");
+    }
+
+    #[tokio::test]
+    async fn equally_specific_routes_keep_registration_order_per_method() {
+        let mut registry = ApiRegistry::new();
+        registry.add_route(hyper::Method::POST, "/api/projects/new", Echo);
+        registry.add_route(hyper::Method::GET, "/api/projects/:id", Echo);
+        registry.add_route(hyper::Method::GET, "/api/projects/:slug", Echo);
+
+        // The POST route doesn't take GETs
+        assert_eq!(matched_route(&registry, "GET", "/api/projects/new").await, "/api/projects/:id");
+        assert_eq!(matched_route(&registry, "POST", "/api/projects/new").await, "/api/projects/new");
+    }
+
+    #[tokio::test]
+    async fn other_methods_on_a_known_path_are_not_allowed() {
+        let mut registry = ApiRegistry::new();
+        registry.add_route(hyper::Method::GET, "/api/projects/:id", Echo);
+        registry.add_route(hyper::Method::POST, "/api/projects/:id", Echo);
+
+        let response = send(&registry, "PUT", "/api/projects/5", &[]).await.unwrap();
+        assert_eq!(response.status, hyper::StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(response.headers.get("allow").unwrap(), "GET, POST");
+        assert_eq!(
+            body_json(response).await,
+            json!({"error": "Method PUT not allowed", "allowed_methods": ["GET", "POST"]})
+        );
+
+        // An unknown path is left for the Router's 404
+        assert!(send(&registry, "PUT", "/api/tasks/5", &[]).await.is_none());
+        assert!(send(&registry, "GET", "/api/tasks/5", &[]).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn routes_registered_globally_are_served_by_the_global_registry() {
+        // The registry is shared by every test in the process, so the path is unique to this one
+        crate::api_route!(hyper::Method::GET, "/api/global-registry-test/:id", Echo).await.unwrap();
+
+        // Readers don't wait for each other
+        let (first, second) = tokio::join!(get_api_registry().read(), get_api_registry().read());
+        let response = send(&first, "GET", "/api/global-registry-test/7", &[]).await.unwrap();
+        assert_eq!(
+            body_json(response).await,
+            json!({"route": "/api/global-registry-test/:id", "params": {"id": "7"}})
+        );
+        assert!(send(&second, "GET", "/api/global-registry-test", &[]).await.is_none());
+    }
+
+    #[test]
+    fn route_table_matches_like_each_route_regex() {
+        // The API routes of the examples, in registration order
+        let routes = [
+            ("POST", "/api/login"), ("GET", "/api/posts"), ("POST", "/api/posts"),
+            ("GET", "/api/products"), ("GET", "/api/products/:id"), ("POST", "/api/products"),
+            ("POST", "/api/products/:id/update"), ("POST", "/api/products/:id/delete"),
+            ("GET", "/api/projects"), ("POST", "/api/projects"), ("POST", "/api/projects/:id/tasks"),
+            ("POST", "/api/projects/:project_id/tasks/:task_id/toggle"),
+            ("POST", "/api/projects/:project_id/tasks/:task_id/delete"),
+            ("POST", "/api/todos"), ("POST", "/api/todos/:id/toggle"), ("DELETE", "/api/todos/:id"),
+            ("GET", "/api/files/*"), ("GET", "/api/:resource"),
+        ];
+        let mut registry = ApiRegistry::new();
+        for (method, path) in routes {
+            registry.add_route(method.parse().unwrap(), path, Echo);
+        }
+
+        let mut probes = vec!["/api".to_string(), "/api/".to_string(), "/nope".to_string()];
+        for (_, path) in routes {
+            let filled = path.replace(":id", "7").replace(":project_id", "1").replace(":task_id", "2")
+                .replace(":resource", "users").replace('*', "a/b.txt");
+            probes.push(format!("{}/extra", filled));
+            probes.push(format!("{}x", filled));
+            probes.push(filled);
+        }
+
+        for path in &probes {
+            for method in [hyper::Method::GET, hyper::Method::POST, hyper::Method::DELETE] {
+                let expected: Vec<usize> = registry.index.get(&method).into_iter().flatten()
+                    .map(|entry| entry.route)
+                    .filter(|&route| registry.routes[route].regex.is_match(path))
+                    .collect();
+                assert_eq!(registry.matching_routes(&method, &[Cow::Borrowed(path.as_str())]), expected, "{} {}", method, path);
+            }
+        }
+    }
 }
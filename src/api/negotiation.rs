@@ -0,0 +1,171 @@
+use crate::escape::escape_text;
+use serde_json::Value;
+use std::sync::Arc;
+
+// Renders a handler's JSON value as the body for one media type
+pub type Formatter = Arc<dyn Fn(&Value) -> String + Send + Sync>;
+
+// One entry of an `Accept` header, e.g. `text/html;q=0.9`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaRange {
+    pub main: String,
+    pub sub: String,
+    pub q: f32,
+}
+
+impl MediaRange {
+    // Matching specificity: 2 for an exact type, 1 for `type/*`, 0 for `*/*`, None if no match
+    fn specificity(&self, media_type: &str) -> Option<u8> {
+        let (main, sub) = media_type.split_once('/').unwrap_or((media_type, ""));
+        match (self.main.as_str(), self.sub.as_str()) {
+            ("*", "*") => Some(0),
+            (m, "*") if m.eq_ignore_ascii_case(main) => Some(1),
+            (m, s) if m.eq_ignore_ascii_case(main) && s.eq_ignore_ascii_case(sub) => Some(2),
+            // Structured suffixes: `application/vnd.myapp.v2+json` asks for JSON
+            (m, s) if m.eq_ignore_ascii_case(main)
+                && s.rsplit_once('+').is_some_and(|(_, suffix)| suffix.eq_ignore_ascii_case(sub)) => Some(2),
+            _ => None,
+        }
+    }
+}
+
+pub fn parse_accept(header: &str) -> Vec<MediaRange> {
+    header.split(',')
+        .filter_map(|part| {
+            let mut pieces = part.split(';');
+            let (main, sub) = pieces.next()?.trim().split_once('/')?;
+            let q = pieces
+                .filter_map(|param| param.split_once('='))
+                .find(|(key, _)| key.trim().eq_ignore_ascii_case("q"))
+                .and_then(|(_, value)| value.trim().parse::<f32>().ok())
+                .unwrap_or(1.0)
+                .clamp(0.0, 1.0);
+            Some(MediaRange {
+                main: main.trim().to_ascii_lowercase(),
+                sub: sub.trim().to_ascii_lowercase(),
+                q,
+            })
+        })
+        .collect()
+}
+
+// Picks the media type from `supported` (in preference order) that the client accepts with the
+// highest q-value. Each type takes the q of its most specific matching range, and ties go to the
+// earlier entry in `supported`. A missing or empty header accepts the first type.
+pub fn negotiate<'a>(accept: Option<&str>, supported: &[&'a str]) -> Option<&'a str> {
+    let ranges = accept.map(parse_accept).unwrap_or_default();
+    if ranges.is_empty() {
+        return supported.first().copied();
+    }
+
+    let mut best: Option<(&'a str, f32)> = None;
+    for &media_type in supported {
+        let q = ranges.iter()
+            .filter_map(|range| range.specificity(media_type).map(|s| (s, range.q)))
+            .max_by_key(|(specificity, _)| *specificity)
+            .map(|(_, q)| q)
+            .unwrap_or(0.0);
+        if q > 0.0 && best.is_none_or(|(_, best_q)| q > best_q) {
+            best = Some((media_type, q));
+        }
+    }
+    best.map(|(media_type, _)| media_type)
+}
+
+// Default `text/html` view: the value as nested lists/tables, escaped
+pub fn html_view(data: &Value) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>API response</title></head><body>{}</body></html>",
+        html_value(data)
+    )
+}
+
+fn html_value(value: &Value) -> String {
+    match value {
+        Value::Object(map) => {
+            let rows: String = map.iter()
+                .map(|(key, value)| format!(
+                    "<tr><th>{}</th><td>{}</td></tr>",
+                    escape_text(key),
+                    html_value(value)
+                ))
+                .collect();
+            format!("<table>{}</table>", rows)
+        }
+        Value::Array(items) => {
+            let items: String = items.iter()
+                .map(|item| format!("<li>{}</li>", html_value(item)))
+                .collect();
+            format!("<ol>{}</ol>", items)
+        }
+        Value::String(s) => escape_text(s).into_owned(),
+        other => other.to_string(),
+    }
+}
+
+// Default `text/plain` view: one `path: value` line per leaf, e.g. `data.0.name: Alice`
+pub fn text_view(data: &Value) -> String {
+    let mut lines = Vec::new();
+    text_lines(data, "", &mut lines);
+    lines.join("\n")
+}
+
+fn text_lines(value: &Value, path: &str, lines: &mut Vec<String>) {
+    let child_path = |key: &str| if path.is_empty() { key.to_string() } else { format!("{}.{}", path, key) };
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, value) in map {
+                text_lines(value, &child_path(key), lines);
+            }
+        }
+        Value::Array(items) if !items.is_empty() => {
+            for (i, item) in items.iter().enumerate() {
+                text_lines(item, &child_path(&i.to_string()), lines);
+            }
+        }
+        Value::String(s) if path.is_empty() => lines.push(s.clone()),
+        Value::String(s) => lines.push(format!("{}: {}", path, s)),
+        other if path.is_empty() => lines.push(other.to_string()),
+        other => lines.push(format!("{}: {}", path, other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const SUPPORTED: &[&str] = &["application/json", "text/html", "text/plain"];
+
+    #[test]
+    fn parses_q_values_and_ignores_malformed_entries() {
+        let ranges = parse_accept("text/html;q=0.5, garbage, application/*;level=1;q=2");
+        assert_eq!(ranges.len(), 2);
+        assert_eq!(ranges[0], MediaRange { main: "text".into(), sub: "html".into(), q: 0.5 });
+        assert_eq!(ranges[1].q, 1.0);
+    }
+
+    #[test]
+    fn picks_the_highest_q_with_ties_to_the_first_supported() {
+        assert_eq!(negotiate(None, SUPPORTED), Some("application/json"));
+        assert_eq!(negotiate(Some("text/html"), SUPPORTED), Some("text/html"));
+        assert_eq!(negotiate(Some("text/*, application/json;q=0.5"), SUPPORTED), Some("text/html"));
+        assert_eq!(negotiate(Some("*/*"), SUPPORTED), Some("application/json"));
+        assert_eq!(negotiate(Some("image/png"), SUPPORTED), None);
+    }
+
+    #[test]
+    fn the_most_specific_range_sets_the_q() {
+        // `text/*` would allow HTML, but the exact range rules it out
+        assert_eq!(negotiate(Some("text/*, text/html;q=0"), SUPPORTED), Some("text/plain"));
+        assert_eq!(negotiate(Some("application/vnd.app.v2+json"), SUPPORTED), Some("application/json"));
+    }
+
+    #[test]
+    fn views_render_nested_values() {
+        let data = json!({"name": "<b>Ada</b>", "tags": ["x", 1]});
+        assert_eq!(text_view(&data), "name: <b>Ada</b>\ntags.0: x\ntags.1: 1");
+        assert!(html_view(&data).contains("<tr><th>name</th><td>&lt;b&gt;Ada&lt;/b&gt;</td></tr>"));
+        assert!(html_view(&data).contains("<ol><li>x</li><li>1</li></ol>"));
+    }
+}
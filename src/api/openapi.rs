@@ -0,0 +1,259 @@
+use super::{get_api_registry, ApiRegistry};
+use crate::ui::{div, get_renderer, Element};
+use crate::{Json, Request, Router};
+use serde_json::{json, Map, Value};
+
+// What a handler tells the OpenAPI document about its route; every part is optional
+#[derive(Debug, Clone, Default)]
+pub struct OperationMeta {
+    pub summary: Option<String>,
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+    // JSON Schema of the request body
+    pub request_schema: Option<Value>,
+    // (status code, description)
+    pub responses: Vec<(u16, String)>,
+}
+
+impl OperationMeta {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn summary(mut self, summary: &str) -> Self {
+        self.summary = Some(summary.to_string());
+        self
+    }
+
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = Some(description.to_string());
+        self
+    }
+
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tags.push(tag.to_string());
+        self
+    }
+
+    pub fn request_schema(mut self, schema: Value) -> Self {
+        self.request_schema = Some(schema);
+        self
+    }
+
+    pub fn response(mut self, status: u16, description: &str) -> Self {
+        self.responses.push((status, description.to_string()));
+        self
+    }
+}
+
+// `/api/projects/:id` -> `/api/projects/{id}`; a `*` wildcard becomes `{path}`
+pub fn openapi_path(path: &str) -> (String, Vec<String>) {
+    let mut params = Vec::new();
+    let segments: Vec<String> = path.split('/')
+        .map(|segment| {
+            if let Some(name) = segment.strip_prefix(':') {
+                params.push(name.to_string());
+                format!("{{{}}}", name)
+            } else if segment == "*" {
+                params.push("path".to_string());
+                "{path}".to_string()
+            } else {
+                segment.to_string()
+            }
+        })
+        .collect();
+    (segments.join("/"), params)
+}
+
+fn operation_id(method: &str, path: &str) -> String {
+    let mut id = method.to_ascii_lowercase();
+    for part in path.split(|c: char| !c.is_ascii_alphanumeric()).filter(|p| !p.is_empty()) {
+        id.push('_');
+        id.push_str(part);
+    }
+    id
+}
+
+impl ApiRegistry {
+    // An OpenAPI 3.0 document for every registered route. Path parameters are strings and bodies
+    // are generic JSON objects unless the handler's `describe()` says otherwise. Under header-based
+    // versioning, versions share paths, so the last route registered per path and method wins.
+    pub fn openapi_spec(&self, title: &str, version: &str) -> Value {
+        let mut paths = Map::new();
+
+        for route in &self.routes {
+            let meta = route.handler.describe();
+            let public_path = self.public_path(route);
+            let (path, params) = openapi_path(&public_path);
+            let method = route.method.as_str().to_ascii_lowercase();
+
+            let mut operation = Map::new();
+            operation.insert("operationId".to_string(), json!(operation_id(&method, &public_path)));
+            if let Some(summary) = &meta.summary {
+                operation.insert("summary".to_string(), json!(summary));
+            }
+            if let Some(description) = &meta.description {
+                operation.insert("description".to_string(), json!(description));
+            }
+            if !meta.tags.is_empty() {
+                operation.insert("tags".to_string(), json!(meta.tags));
+            }
+            if !params.is_empty() {
+                let parameters: Vec<Value> = params.iter()
+                    .map(|name| json!({
+                        "name": name,
+                        "in": "path",
+                        "required": true,
+                        "schema": {"type": "string"},
+                    }))
+                    .collect();
+                operation.insert("parameters".to_string(), json!(parameters));
+            }
+            if matches!(method.as_str(), "post" | "put" | "patch") || meta.request_schema.is_some() {
+                let schema = meta.request_schema.clone().unwrap_or_else(|| json!({"type": "object"}));
+                operation.insert("requestBody".to_string(), json!({
+                    "content": {"application/json": {"schema": schema}},
+                }));
+            }
+
+            let mut responses = Map::new();
+            if meta.responses.is_empty() {
+                responses.insert("200".to_string(), json!({
+                    "description": "Successful response",
+                    "content": {"application/json": {"schema": {}}},
+                }));
+            }
+            for (status, description) in &meta.responses {
+                responses.insert(status.to_string(), json!({"description": description}));
+            }
+            responses.insert("default".to_string(), json!({
+                "description": "Error",
+                "content": {"application/json": {"schema": {"$ref": "#/components/schemas/Error"}}},
+            }));
+            operation.insert("responses".to_string(), Value::Object(responses));
+
+            let item = paths.entry(path).or_insert_with(|| json!({}));
+            item[method] = Value::Object(operation);
+        }
+
+        json!({
+            "openapi": "3.0.3",
+            "info": {"title": title, "version": version},
+            "paths": paths,
+            "components": {
+                "schemas": {
+                    "Error": {
+                        "type": "object",
+                        "properties": {"error": {"type": "string"}},
+                        "required": ["error"],
+                    }
+                }
+            }
+        })
+    }
+}
+
+// Swagger UI (loaded from a CDN) pointed at `spec_url`
+pub fn swagger_ui(spec_url: &str) -> Element {
+    let init = format!(
+        "window.onload = function() {{ SwaggerUIBundle({{ url: {}, dom_id: '#swagger-ui' }}); }};",
+        json!(spec_url)
+    );
+    div()
+        .child(
+            Element::new("link")
+                .prop("rel", "stylesheet")
+                .prop("href", "https://unpkg.com/swagger-ui-dist@5/swagger-ui.css"),
+        )
+        .child(div().id("swagger-ui"))
+        .child(Element::new("script").prop("src", "https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"))
+        .child(Element::new("script").prop("_raw_html", init))
+}
+
+impl Router {
+    // Serves the global registry's spec at `/api/openapi.json` and Swagger UI at `/api/docs`
+    pub fn openapi(self, title: &str, version: &str) -> Self {
+        let title = title.to_string();
+        let version = version.to_string();
+        self
+            .get("/api/openapi.json", move |_req: Request| {
+                let title = title.clone();
+                let version = version.clone();
+                async move {
+                    let spec = get_api_registry().read().await.openapi_spec(&title, &version);
+                    Json(spec)
+                }
+            })
+            .get("/api/docs", |_req: Request| async move {
+                get_renderer().render_to_response(&swagger_ui("/api/openapi.json"))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{ApiError, ApiHandler, ApiResponse};
+    use async_trait::async_trait;
+
+    struct CreateProject;
+
+    #[async_trait]
+    impl ApiHandler for CreateProject {
+        async fn handle(&self, _req: Request) -> Result<ApiResponse, ApiError> {
+            Ok(ApiResponse::created(json!({})))
+        }
+
+        fn describe(&self) -> OperationMeta {
+            OperationMeta::new()
+                .summary("Create a project")
+                .tag("projects")
+                .request_schema(json!({"type": "object", "required": ["name"]}))
+                .response(201, "Created")
+        }
+    }
+
+    struct GetTask;
+
+    #[async_trait]
+    impl ApiHandler for GetTask {
+        async fn handle(&self, _req: Request) -> Result<ApiResponse, ApiError> {
+            Ok(ApiResponse::ok(json!({})))
+        }
+    }
+
+    #[test]
+    fn converts_route_parameters_to_templates() {
+        assert_eq!(
+            openapi_path("/api/projects/:id/tasks/:task_id"),
+            ("/api/projects/{id}/tasks/{task_id}".to_string(), vec!["id".to_string(), "task_id".to_string()])
+        );
+        assert_eq!(openapi_path("/files/*").0, "/files/{path}");
+        assert_eq!(operation_id("get", "/api/projects/{id}"), "get_api_projects_id");
+    }
+
+    #[test]
+    fn documents_every_route_with_its_metadata() {
+        let mut registry = ApiRegistry::new();
+        registry.add_route(hyper::Method::POST, "/api/projects", CreateProject);
+        registry.add_route(hyper::Method::GET, "/api/projects/:id/tasks/:task_id", GetTask);
+
+        let spec = registry.openapi_spec("Projects", "1.0.0");
+        assert_eq!(spec["openapi"], "3.0.3");
+        assert_eq!(spec["info"], json!({"title": "Projects", "version": "1.0.0"}));
+
+        let create = &spec["paths"]["/api/projects"]["post"];
+        assert_eq!(create["summary"], "Create a project");
+        assert_eq!(create["tags"], json!(["projects"]));
+        assert_eq!(create["requestBody"]["content"]["application/json"]["schema"]["required"], json!(["name"]));
+        assert_eq!(create["responses"]["201"]["description"], "Created");
+        assert!(create["responses"]["200"].is_null());
+
+        let get = &spec["paths"]["/api/projects/{id}/tasks/{task_id}"]["get"];
+        assert_eq!(get["operationId"], "get_api_projects_id_tasks_task_id");
+        assert_eq!(get["parameters"][1]["name"], "task_id");
+        assert!(get["requestBody"].is_null());
+        assert!(get["responses"]["200"].is_object());
+        assert_eq!(get["responses"]["default"]["content"]["application/json"]["schema"]["$ref"], "#/components/schemas/Error");
+    }
+}
@@ -0,0 +1,265 @@
+use super::query::{DEFAULT_PAGE, DEFAULT_PER_PAGE, MAX_PER_PAGE};
+use super::ApiResponse;
+use crate::Request;
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use serde_json::json;
+use std::collections::HashMap;
+
+// `?page=&per_page=` for list endpoints; invalid or missing values fall back to defaults
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pagination {
+    pub page: usize,
+    pub per_page: usize,
+}
+
+impl Default for Pagination {
+    fn default() -> Self {
+        Pagination {
+            page: DEFAULT_PAGE,
+            per_page: DEFAULT_PER_PAGE,
+        }
+    }
+}
+
+impl Pagination {
+    pub fn from_request(req: &Request) -> Self {
+        Self::from_query(&req.query)
+    }
+
+    pub fn from_request_with_max(req: &Request, max_per_page: usize) -> Self {
+        Self::from_query_with_max(&req.query, max_per_page)
+    }
+
+    pub fn from_query(query: &HashMap<String, String>) -> Self {
+        Self::from_query_with_max(query, MAX_PER_PAGE)
+    }
+
+    pub fn from_query_with_max(query: &HashMap<String, String>, max_per_page: usize) -> Self {
+        let parse = |key: &str| {
+            query.get(key)
+                .and_then(|v| v.trim().parse::<usize>().ok())
+                .filter(|n| *n >= 1)
+        };
+
+        Pagination {
+            page: parse("page").unwrap_or(DEFAULT_PAGE),
+            per_page: parse("per_page").unwrap_or(DEFAULT_PER_PAGE).min(max_per_page.max(1)),
+        }
+    }
+
+    pub fn offset(&self) -> usize {
+        (self.page - 1).saturating_mul(self.per_page)
+    }
+
+    // The slice of `items` for this page (empty when the page is out of range)
+    pub fn slice<'a, T>(&self, items: &'a [T]) -> &'a [T] {
+        let start = self.offset().min(items.len());
+        let end = start.saturating_add(self.per_page).min(items.len());
+        &items[start..end]
+    }
+}
+
+pub fn total_pages(total: usize, per_page: usize) -> usize {
+    if per_page == 0 {
+        return 0;
+    }
+    total.div_ceil(per_page)
+}
+
+// One page of a collection, serialized the same way as `ApiResponse::paginated`:
+// `{ "data": [...], "meta": { page, per_page, total, total_pages } }`, e.g.
+//
+//     let pagination = Pagination::from_request(&req);
+//     Ok(Paginated::from_slice(&products, pagination).respond(&req))
+#[derive(Debug, Clone)]
+pub struct Paginated<T> {
+    pub items: Vec<T>,
+    pub total: usize,
+    pub page: usize,
+    pub per_page: usize,
+    pub total_pages: usize,
+}
+
+impl<T: Serialize> Paginated<T> {
+    // `items` is the page already fetched, `total` the size of the whole collection
+    pub fn new(items: Vec<T>, pagination: Pagination, total: usize) -> Self {
+        Paginated {
+            items,
+            total,
+            page: pagination.page,
+            per_page: pagination.per_page,
+            total_pages: total_pages(total, pagination.per_page),
+        }
+    }
+
+    // The page of a collection held in memory
+    pub fn from_slice(all: &[T], pagination: Pagination) -> Self
+    where
+        T: Clone,
+    {
+        Self::new(pagination.slice(all).to_vec(), pagination, all.len())
+    }
+
+    // A 200 with `X-Total-Count` and the `Link` header (see `pagination_links`)
+    pub fn respond(self, req: &Request) -> ApiResponse {
+        ApiResponse::paginated(self.items, self.page, self.per_page, self.total).pagination_links(req)
+    }
+}
+
+impl<T: Serialize> Serialize for Paginated<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut page = serializer.serialize_struct("Paginated", 2)?;
+        page.serialize_field("data", &self.items)?;
+        page.serialize_field("meta", &json!({
+            "page": self.page,
+            "per_page": self.per_page,
+            "total": self.total,
+            "total_pages": self.total_pages,
+        }))?;
+        page.end()
+    }
+}
+
+impl ApiResponse {
+    // `{ "data": [...], "meta": { page, per_page, total, total_pages } }` with `X-Total-Count`.
+    // Chain `.pagination_links(&req)` to add the `Link` header.
+    pub fn paginated<T: Serialize>(items: Vec<T>, page: usize, per_page: usize, total: usize) -> Self {
+        ApiResponse::ok(json!(Paginated::new(items, Pagination { page, per_page }, total)))
+            .header("X-Total-Count", &total.to_string())
+    }
+
+    // Sets an RFC 8288 (formerly 5988) `Link` header with first/prev/next/last, built from the
+    // request path and query and the `meta` written by `paginated`. Other query parameters are kept.
+    pub fn pagination_links(mut self, req: &Request) -> Self {
+        let meta = &self.data["meta"];
+        let (page, per_page, pages) = match (
+            meta["page"].as_u64(),
+            meta["per_page"].as_u64(),
+            meta["total_pages"].as_u64(),
+        ) {
+            (Some(page), Some(per_page), Some(pages)) => (page as usize, per_page as usize, pages as usize),
+            _ => return self,
+        };
+
+        let header = link_header(req, page, per_page, pages);
+        self.headers.insert("Link".to_string(), header);
+        self
+    }
+}
+
+fn link_header(req: &Request, page: usize, per_page: usize, pages: usize) -> String {
+    let last = pages.max(1);
+    let mut links = vec![(1, "first")];
+    if page > 1 {
+        // From past the end, "prev" points at the last real page
+        links.push(((page - 1).min(last), "prev"));
+    }
+    if page < pages {
+        links.push((page + 1, "next"));
+    }
+    links.push((last, "last"));
+
+    links.into_iter()
+        .map(|(target, rel)| format!("<{}>; rel=\"{}\"", page_url(req, target, per_page), rel))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn page_url(req: &Request, page: usize, per_page: usize) -> String {
+    // Sorted so the generated links are stable
+    let mut params: Vec<(&String, &String)> = req.query.iter()
+        .filter(|(key, _)| key.as_str() != "page" && key.as_str() != "per_page")
+        .collect();
+    params.sort();
+
+    let mut query = url::form_urlencoded::Serializer::new(String::new());
+    for (key, value) in params {
+        query.append_pair(key, value);
+    }
+    query.append_pair("page", &page.to_string());
+    query.append_pair("per_page", &per_page.to_string());
+
+    format!("{}?{}", req.uri.path(), query.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn request(uri: &str) -> Request {
+        Request::from_hyper(hyper::Request::builder().uri(uri).body(hyper::Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn respond_matches_api_response_paginated() {
+        let req = request("/products?page=2&per_page=2&sort=name").await;
+        let all: Vec<u32> = (1..=5).collect();
+
+        let response = Paginated::from_slice(&all, Pagination::from_request(&req)).respond(&req);
+        let expected = ApiResponse::paginated(vec![3, 4], 2, 2, 5).pagination_links(&req);
+
+        assert_eq!(response.data, expected.data);
+        assert_eq!(response.data["data"], json!([3, 4]));
+        assert_eq!(response.data["meta"]["total_pages"], 3);
+        assert_eq!(response.headers, expected.headers);
+        assert_eq!(response.headers["X-Total-Count"], "5");
+    }
+
+    #[tokio::test]
+    async fn link_header_keeps_other_parameters() {
+        let req = request("/products?page=2&per_page=2&sort=name").await;
+        let response = ApiResponse::paginated(vec![3, 4], 2, 2, 5).pagination_links(&req);
+
+        assert_eq!(
+            response.headers["Link"],
+            "</products?sort=name&page=1&per_page=2>; rel=\"first\", \
+             </products?sort=name&page=1&per_page=2>; rel=\"prev\", \
+             </products?sort=name&page=3&per_page=2>; rel=\"next\", \
+             </products?sort=name&page=3&per_page=2>; rel=\"last\""
+        );
+    }
+
+    #[test]
+    fn out_of_range_pages_are_empty() {
+        let pagination = Pagination { page: 4, per_page: 2 };
+        assert!(pagination.slice(&[1, 2, 3, 4, 5]).is_empty());
+        assert_eq!(Pagination::from_query(&HashMap::from([("per_page".to_string(), "0".to_string())])), Pagination::default());
+    }
+
+    #[test]
+    fn from_query_clamps_per_page_and_computes_offsets() {
+        let query = HashMap::from([("page".to_string(), "3".to_string()), ("per_page".to_string(), "50".to_string())]);
+        let pagination = Pagination::from_query_with_max(&query, 25);
+        assert_eq!(pagination, Pagination { page: 3, per_page: 25 });
+        assert_eq!(pagination.offset(), 50);
+        assert_eq!(total_pages(51, 25), 3);
+        assert_eq!(total_pages(0, 25), 0);
+    }
+
+    #[tokio::test]
+    async fn first_and_last_pages_omit_prev_and_next() {
+        let req = request("/items").await;
+
+        let first = ApiResponse::paginated(vec![1, 2], 1, 2, 4).pagination_links(&req);
+        assert_eq!(
+            first.headers["Link"],
+            "</items?page=1&per_page=2>; rel=\"first\", </items?page=2&per_page=2>; rel=\"next\", \
+             </items?page=2&per_page=2>; rel=\"last\""
+        );
+
+        let last = ApiResponse::paginated(vec![3, 4], 2, 2, 4).pagination_links(&req);
+        assert!(!last.headers["Link"].contains("rel=\"next\""));
+        assert!(last.headers["Link"].contains("</items?page=1&per_page=2>; rel=\"prev\""));
+    }
+
+    #[tokio::test]
+    async fn prev_from_past_the_end_points_at_the_last_page() {
+        let req = request("/items?page=9").await;
+        let response = ApiResponse::paginated(Vec::<u32>::new(), 9, 2, 4).pagination_links(&req);
+
+        assert_eq!(response.data["data"], json!([]));
+        assert!(response.headers["Link"].contains("</items?page=2&per_page=2>; rel=\"prev\""));
+    }
+}
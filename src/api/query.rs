@@ -0,0 +1,235 @@
+use super::pagination::Pagination;
+use crate::Request;
+use serde::Serialize;
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+pub const DEFAULT_PAGE: usize = 1;
+pub const DEFAULT_PER_PAGE: usize = 20;
+pub const MAX_PER_PAGE: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+// Parsed `?page=&per_page=&sort=&order=&q=` parameters for list endpoints.
+// Invalid or missing values fall back to defaults instead of erroring.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListQuery {
+    pub page: usize,
+    pub per_page: usize,
+    pub sort: Option<String>,
+    pub order: SortOrder,
+    pub q: Option<String>,
+}
+
+impl Default for ListQuery {
+    fn default() -> Self {
+        ListQuery {
+            page: DEFAULT_PAGE,
+            per_page: DEFAULT_PER_PAGE,
+            sort: None,
+            order: SortOrder::Asc,
+            q: None,
+        }
+    }
+}
+
+impl ListQuery {
+    pub fn from_request(req: &Request) -> Self {
+        Self::from_query(&req.query)
+    }
+
+    pub fn from_query(query: &HashMap<String, String>) -> Self {
+        Self::from_query_with_max(query, MAX_PER_PAGE)
+    }
+
+    pub fn from_query_with_max(query: &HashMap<String, String>, max_per_page: usize) -> Self {
+        let Pagination { page, per_page } = Pagination::from_query_with_max(query, max_per_page);
+
+        let sort = query.get("sort")
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty());
+
+        let order = match query.get("order").map(|v| v.trim().to_ascii_lowercase()) {
+            Some(ref o) if o == "desc" => SortOrder::Desc,
+            _ => SortOrder::Asc,
+        };
+
+        let q = query.get("q")
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty());
+
+        ListQuery { page, per_page, sort, order, q }
+    }
+
+    pub fn pagination(&self) -> Pagination {
+        Pagination { page: self.page, per_page: self.per_page }
+    }
+
+    pub fn offset(&self) -> usize {
+        self.pagination().offset()
+    }
+
+    // Keeps items where any top-level string or number field contains `q` (case-insensitive).
+    pub fn filter<T: Serialize + Clone>(&self, items: &[T]) -> Vec<T> {
+        let needle = match &self.q {
+            Some(q) => q.to_lowercase(),
+            None => return items.to_vec(),
+        };
+
+        items.iter()
+            .filter(|item| {
+                match serde_json::to_value(item) {
+                    Ok(Value::Object(map)) => map.values().any(|v| value_contains(v, &needle)),
+                    Ok(other) => value_contains(&other, &needle),
+                    Err(_) => false,
+                }
+            })
+            .cloned()
+            .collect()
+    }
+
+    // Sorts items in place by the top-level field named in `sort`; unknown fields leave the order untouched.
+    pub fn sort<T: Serialize>(&self, items: &mut [T]) {
+        let field = match &self.sort {
+            Some(field) => field,
+            None => return,
+        };
+
+        let mut keyed: Vec<(Value, usize)> = items.iter()
+            .enumerate()
+            .map(|(i, item)| {
+                let key = serde_json::to_value(item)
+                    .ok()
+                    .and_then(|v| v.get(field).cloned())
+                    .unwrap_or(Value::Null);
+                (key, i)
+            })
+            .collect();
+
+        keyed.sort_by(|(a, _), (b, _)| {
+            let ordering = compare_values(a, b);
+            match self.order {
+                SortOrder::Asc => ordering,
+                SortOrder::Desc => ordering.reverse(),
+            }
+        });
+
+        let order: Vec<usize> = keyed.into_iter().map(|(_, i)| i).collect();
+        apply_permutation(items, order);
+    }
+
+    pub fn paginate<T: Clone>(&self, items: &[T]) -> Vec<T> {
+        items.iter()
+            .skip(self.offset())
+            .take(self.per_page)
+            .cloned()
+            .collect()
+    }
+
+    // Filters, sorts and paginates, returning the page alongside the total number of matches.
+    pub fn apply<T: Serialize + Clone>(&self, items: &[T]) -> (Vec<T>, usize) {
+        let mut matched = self.filter(items);
+        self.sort(&mut matched);
+        let total = matched.len();
+        (self.paginate(&matched), total)
+    }
+}
+
+fn value_contains(value: &Value, needle: &str) -> bool {
+    match value {
+        Value::String(s) => s.to_lowercase().contains(needle),
+        Value::Number(n) => n.to_string().contains(needle),
+        _ => false,
+    }
+}
+
+fn compare_values(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => {
+            let x = x.as_f64().unwrap_or(0.0);
+            let y = y.as_f64().unwrap_or(0.0);
+            x.partial_cmp(&y).unwrap_or(Ordering::Equal)
+        }
+        (Value::String(x), Value::String(y)) => x.to_lowercase().cmp(&y.to_lowercase()),
+        (Value::Bool(x), Value::Bool(y)) => x.cmp(y),
+        (Value::Null, Value::Null) => Ordering::Equal,
+        // Missing values sort last in ascending order
+        (Value::Null, _) => Ordering::Greater,
+        (_, Value::Null) => Ordering::Less,
+        _ => a.to_string().cmp(&b.to_string()),
+    }
+}
+
+fn apply_permutation<T>(items: &mut [T], mut order: Vec<usize>) {
+    // order[i] is the index of the element that should end up at position i
+    for i in 0..order.len() {
+        let mut current = i;
+        while order[current] != i {
+            let next = order[current];
+            items.swap(current, next);
+            order[current] = current;
+            current = next;
+        }
+        order[current] = current;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn query(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    fn products() -> Vec<Value> {
+        vec![
+            json!({"name": "Widget", "price": 30}),
+            json!({"name": "gadget", "price": 10}),
+            json!({"name": "Gizmo", "price": 20}),
+            json!({"name": "Doohickey"}),
+        ]
+    }
+
+    fn names(items: &[Value]) -> Vec<&str> {
+        items.iter().map(|item| item["name"].as_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn invalid_values_fall_back_to_defaults() {
+        let parsed = ListQuery::from_query(&query(&[("page", "0"), ("per_page", "abc"), ("order", "sideways"), ("q", "  ")]));
+        assert_eq!(parsed, ListQuery::default());
+
+        let parsed = ListQuery::from_query(&query(&[("page", "3"), ("per_page", "500"), ("sort", " price "), ("order", "DESC")]));
+        assert_eq!(parsed.page, 3);
+        assert_eq!(parsed.per_page, MAX_PER_PAGE);
+        assert_eq!(parsed.sort.as_deref(), Some("price"));
+        assert_eq!(parsed.order, SortOrder::Desc);
+        assert_eq!(parsed.offset(), 2 * MAX_PER_PAGE);
+    }
+
+    #[test]
+    fn sorts_numbers_and_strings_with_missing_values_last() {
+        let mut items = products();
+        ListQuery::from_query(&query(&[("sort", "price")])).sort(&mut items);
+        assert_eq!(names(&items), ["gadget", "Gizmo", "Widget", "Doohickey"]);
+
+        ListQuery::from_query(&query(&[("sort", "name"), ("order", "desc")])).sort(&mut items);
+        assert_eq!(names(&items), ["Widget", "Gizmo", "gadget", "Doohickey"]);
+    }
+
+    #[test]
+    fn apply_filters_sorts_and_paginates() {
+        let list = ListQuery::from_query(&query(&[("q", "DG"), ("sort", "price"), ("per_page", "1"), ("page", "2")]));
+        let (page, total) = list.apply(&products());
+
+        assert_eq!(total, 2);
+        assert_eq!(names(&page), ["Widget"]);
+    }
+}
@@ -0,0 +1,157 @@
+use super::{ApiError, ApiHandler, ApiResponse, OperationMeta};
+use crate::forms::ValidationErrors;
+use crate::Request;
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+
+// An ApiHandler that receives its request body already parsed, e.g.
+//
+//     #[derive(Deserialize, Serialize)]
+//     struct NewTodo { task: String }
+//
+//     #[async_trait]
+//     impl TypedApiHandler for CreateTodoHandler {
+//         type Body = NewTodo;
+//
+//         fn validate(&self, body: &NewTodo) -> Result<(), ValidationErrors> {
+//             validate_request(body, &Rules::new().field("task", &[ValidationRule::Required]))
+//         }
+//
+//         async fn handle(&self, req: Request, body: NewTodo) -> Result<ApiResponse, ApiError> { .. }
+//     }
+//
+// Every TypedApiHandler is an ApiHandler, so it registers with `api_route!` as usual.
+#[async_trait]
+pub trait TypedApiHandler: Send + Sync {
+    type Body: DeserializeOwned + Send;
+
+    async fn handle(&self, req: Request, body: Self::Body) -> Result<ApiResponse, ApiError>;
+
+    // Runs after parsing; errors are answered with 422 and the field messages
+    fn validate(&self, _body: &Self::Body) -> Result<(), ValidationErrors> {
+        Ok(())
+    }
+
+    fn describe(&self) -> OperationMeta {
+        OperationMeta::default()
+    }
+}
+
+#[async_trait]
+impl<T: TypedApiHandler> ApiHandler for T {
+    async fn handle(&self, mut req: Request) -> Result<ApiResponse, ApiError> {
+        let body = parse_body::<T::Body>(&mut req).await?;
+        if let Err(errors) = self.validate(&body) {
+            return Ok(errors.into_api_response());
+        }
+        TypedApiHandler::handle(self, req, body).await
+    }
+
+    fn describe(&self) -> OperationMeta {
+        TypedApiHandler::describe(self)
+    }
+}
+
+// Deserializes the body by its Content-Type: JSON (the default when there is none) or a
+// urlencoded form, whose values are parsed into numbers, booleans etc. as the fields require.
+// Anything unparseable is a 400, other content types a 415.
+pub async fn parse_body<T: DeserializeOwned>(req: &mut Request) -> Result<T, ApiError> {
+    let content_type = req.headers.get(hyper::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.split(';').next().unwrap_or("").trim().to_ascii_lowercase())
+        .unwrap_or_default();
+
+    if content_type == "application/x-www-form-urlencoded" {
+        let form = req.form().await
+            .map_err(|e| ApiError::bad_request(&format!("Failed to parse form data: {}", e)))?;
+        let encoded = url::form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(form.iter())
+            .finish();
+        serde_urlencoded::from_str(&encoded)
+            .map_err(|e| ApiError::bad_request(&format!("Invalid form data: {}", e)))
+    } else if content_type.is_empty() || content_type == "application/json" || content_type.ends_with("+json") {
+        let json = req.json().await
+            .map_err(|e| ApiError::bad_request(&format!("Failed to parse JSON body: {}", e)))?;
+        serde_json::from_value(json)
+            .map_err(|e| ApiError::bad_request(&format!("Invalid request body: {}", e)))
+    } else {
+        Err(ApiError::new(
+            hyper::StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            &format!("Unsupported content type {}; expected JSON or a urlencoded form", content_type),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::ApiRegistry;
+    use crate::Response;
+    use serde::Deserialize;
+    use serde_json::{json, Value};
+
+    #[derive(Deserialize)]
+    struct NewTodo {
+        task: String,
+        priority: u8,
+    }
+
+    // Answers with the body it was handed
+    struct CreateTodo;
+
+    #[async_trait]
+    impl TypedApiHandler for CreateTodo {
+        type Body = NewTodo;
+
+        async fn handle(&self, _req: Request, body: NewTodo) -> Result<ApiResponse, ApiError> {
+            Ok(ApiResponse::created(json!({"task": body.task, "priority": body.priority})))
+        }
+    }
+
+    async fn post(content_type: &str, body: &str) -> Response {
+        let mut registry = ApiRegistry::new();
+        registry.add_route(hyper::Method::POST, "/api/todos", CreateTodo);
+        let req = hyper::Request::builder()
+            .method("POST")
+            .uri("/api/todos")
+            .header("content-type", content_type)
+            .body(hyper::Body::from(body.to_string()))
+            .unwrap();
+        registry.handle_request(Request::from_hyper(req).await.unwrap()).await.unwrap()
+    }
+
+    async fn body_json(response: Response) -> Value {
+        serde_json::from_slice(&hyper::body::to_bytes(response.body).await.unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn malformed_bodies_get_400_json() {
+        for body in [r#"{"task": "#, r#"{"task": "milk"}"#, r#"{"task": "milk", "priority": "high"}"#] {
+            let response = post("application/json", body).await;
+            assert_eq!(response.status, hyper::StatusCode::BAD_REQUEST, "{}", body);
+            assert_eq!(response.headers.get("content-type").unwrap(), "application/json");
+            assert!(body_json(response).await["error"].is_string(), "{}", body);
+        }
+
+        let response = post("application/x-www-form-urlencoded", "task=milk&priority=high").await;
+        assert_eq!(response.status, hyper::StatusCode::BAD_REQUEST);
+        assert!(body_json(response).await["error"].as_str().unwrap().starts_with("Invalid form data"));
+    }
+
+    #[tokio::test]
+    async fn valid_bodies_reach_the_handler_deserialized() {
+        let response = post("application/json", r#"{"task": "milk", "priority": 2}"#).await;
+        assert_eq!(response.status, hyper::StatusCode::CREATED);
+        assert_eq!(body_json(response).await, json!({"task": "milk", "priority": 2}));
+
+        let response = post("application/x-www-form-urlencoded", "task=bread&priority=1").await;
+        assert_eq!(response.status, hyper::StatusCode::CREATED);
+        assert_eq!(body_json(response).await, json!({"task": "bread", "priority": 1}));
+    }
+
+    #[tokio::test]
+    async fn other_content_types_get_415() {
+        let response = post("text/plain", "milk").await;
+        assert_eq!(response.status, hyper::StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+}
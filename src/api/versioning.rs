@@ -0,0 +1,199 @@
+use super::{ApiHandler, ApiRegistry, ApiRoute};
+use crate::Request;
+use once_cell::sync::OnceCell;
+use regex::Regex;
+use std::borrow::Cow;
+
+// How the registry tells which API version a request wants
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionStrategy {
+    // `/api/v2/projects` with prefix `/api` resolves to v2 and matches routes registered as
+    // `/api/projects`; a path without a version segment gets the default version
+    PathPrefix(String),
+    // `X-Api-Version: 2` (or `v2`), or `Accept: application/vnd.<vendor>.v2+json`
+    Header { vendor: String },
+}
+
+fn vendor_regex() -> &'static Regex {
+    static VENDOR: OnceCell<Regex> = OnceCell::new();
+    VENDOR.get_or_init(|| Regex::new(r"application/vnd\.([A-Za-z0-9_-]+)\.(v\d+)\+json").unwrap())
+}
+
+fn looks_like_version(segment: &str) -> bool {
+    segment.len() > 1
+        && segment.starts_with('v')
+        && segment[1..].chars().all(|c| c.is_ascii_digit())
+}
+
+impl ApiRoute {
+    pub fn versioned<H>(version: &str, method: hyper::Method, path: &str, handler: H) -> Self
+    where
+        H: ApiHandler + 'static,
+    {
+        let mut route = ApiRoute::new(method, path, handler);
+        route.version = Some(version.to_string());
+        route
+    }
+}
+
+impl ApiRegistry {
+    pub fn add_versioned_route<H>(&mut self, version: &str, method: hyper::Method, path: &str, handler: H)
+    where
+        H: ApiHandler + 'static,
+    {
+        self.push_route(ApiRoute::versioned(version, method, path, handler));
+    }
+
+    // Without a strategy every request resolves to the default version
+    pub fn set_version_strategy(&mut self, strategy: VersionStrategy) {
+        self.version_strategy = Some(strategy);
+    }
+
+    // The version of requests that don't ask for one, and of routes added with `add_route`
+    pub fn set_default_version(&mut self, version: &str) {
+        self.default_version = version.to_string();
+    }
+
+    pub fn versions(&self) -> Vec<String> {
+        let mut versions = vec![self.default_version.clone()];
+        for route in &self.routes {
+            let version = self.route_version(route);
+            if !versions.iter().any(|v| v == version) {
+                versions.push(version.to_string());
+            }
+        }
+        versions.sort();
+        versions
+    }
+
+    pub(crate) fn has_version(&self, version: &str) -> bool {
+        version == self.default_version || self.routes.iter().any(|route| route.version.as_deref() == Some(version))
+    }
+
+    pub(crate) fn route_version<'a>(&'a self, route: &'a ApiRoute) -> &'a str {
+        route.version.as_deref().unwrap_or(&self.default_version)
+    }
+
+    // The requested version and the path to match routes against
+    pub(crate) fn resolve_version<'a>(&'a self, req: &'a Request) -> (Cow<'a, str>, Cow<'a, str>) {
+        let path = req.uri.path();
+        match &self.version_strategy {
+            Some(VersionStrategy::PathPrefix(prefix)) => {
+                let prefix = prefix.trim_end_matches('/');
+                if let Some(rest) = path.strip_prefix(prefix).and_then(|rest| rest.strip_prefix('/')) {
+                    let (segment, remainder) = rest.split_once('/').map_or((rest, ""), |(s, r)| (s, r));
+                    if looks_like_version(segment) {
+                        let stripped = if remainder.is_empty() {
+                            Cow::Borrowed(prefix)
+                        } else {
+                            Cow::Owned(format!("{}/{}", prefix, remainder))
+                        };
+                        return (Cow::Borrowed(segment), stripped);
+                    }
+                }
+                (Cow::Borrowed(&self.default_version), Cow::Borrowed(path))
+            }
+            Some(VersionStrategy::Header { vendor }) => {
+                let header = |name: &str| req.headers.get(name).and_then(|v| v.to_str().ok());
+                let from_header = header("x-api-version")
+                    .map(|v| v.trim())
+                    .filter(|v| !v.is_empty())
+                    .map(|v| if v.starts_with('v') { Cow::Borrowed(v) } else { Cow::Owned(format!("v{}", v)) });
+                let from_accept = || {
+                    vendor_regex().captures_iter(header("accept")?)
+                        .find(|captures| captures[1].eq_ignore_ascii_case(vendor))
+                        .and_then(|captures| Some(Cow::Borrowed(captures.get(2)?.as_str())))
+                };
+                let version = from_header.or_else(from_accept).unwrap_or(Cow::Borrowed(&self.default_version));
+                (version, Cow::Borrowed(path))
+            }
+            None => (Cow::Borrowed(&self.default_version), Cow::Borrowed(path)),
+        }
+    }
+
+    // The public path of a route, e.g. `/api/v2/projects` under a path prefix strategy
+    pub(crate) fn public_path(&self, route: &ApiRoute) -> String {
+        match (&self.version_strategy, &route.version) {
+            (Some(VersionStrategy::PathPrefix(prefix)), Some(version)) if *version != self.default_version => {
+                let prefix = prefix.trim_end_matches('/');
+                match route.path.strip_prefix(prefix) {
+                    Some(rest) => format!("{}/{}{}", prefix, version, rest),
+                    None => route.path.clone(),
+                }
+            }
+            _ => route.path.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{ApiError, ApiResponse};
+    use crate::Response;
+    use async_trait::async_trait;
+    use serde_json::{json, Value};
+
+    // Answers with the version it was registered for
+    struct Version(&'static str);
+
+    #[async_trait]
+    impl ApiHandler for Version {
+        async fn handle(&self, _req: Request) -> Result<ApiResponse, ApiError> {
+            Ok(ApiResponse::ok(json!({"version": self.0})))
+        }
+    }
+
+    fn registry(strategy: VersionStrategy) -> ApiRegistry {
+        let mut registry = ApiRegistry::new();
+        registry.set_version_strategy(strategy);
+        registry.add_route(hyper::Method::GET, "/api/projects", Version("v1"));
+        registry.add_versioned_route("v2", hyper::Method::GET, "/api/projects", Version("v2"));
+        registry
+    }
+
+    async fn send(registry: &ApiRegistry, uri: &str, headers: &[(&str, &str)]) -> Response {
+        let mut builder = hyper::Request::builder().uri(uri);
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        let req = Request::from_hyper(builder.body(hyper::Body::empty()).unwrap()).await.unwrap();
+        registry.handle_request(req).await.expect("a response")
+    }
+
+    async fn body_json(response: Response) -> Value {
+        serde_json::from_slice(&hyper::body::to_bytes(response.body).await.unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn path_prefix_versions() {
+        let registry = registry(VersionStrategy::PathPrefix("/api".to_string()));
+
+        assert_eq!(body_json(send(&registry, "/api/projects", &[]).await).await["version"], "v1");
+        assert_eq!(body_json(send(&registry, "/api/v1/projects", &[]).await).await["version"], "v1");
+        assert_eq!(body_json(send(&registry, "/api/v2/projects", &[]).await).await["version"], "v2");
+
+        let unknown = send(&registry, "/api/v9/projects", &[]).await;
+        assert_eq!(unknown.status, hyper::StatusCode::NOT_FOUND);
+        assert_eq!(body_json(unknown).await["available_versions"], json!(["v1", "v2"]));
+    }
+
+    #[tokio::test]
+    async fn header_versions() {
+        let registry = registry(VersionStrategy::Header { vendor: "myapp".to_string() });
+
+        assert_eq!(body_json(send(&registry, "/api/projects", &[]).await).await["version"], "v1");
+        assert_eq!(body_json(send(&registry, "/api/projects", &[("x-api-version", "2")]).await).await["version"], "v2");
+        let accept = [("accept", "application/vnd.myapp.v2+json")];
+        assert_eq!(body_json(send(&registry, "/api/projects", &accept).await).await["version"], "v2");
+        let other_vendor = [("accept", "application/vnd.other.v2+json")];
+        assert_eq!(body_json(send(&registry, "/api/projects", &other_vendor).await).await["version"], "v1");
+    }
+
+    #[test]
+    fn public_paths_include_non_default_versions() {
+        let registry = registry(VersionStrategy::PathPrefix("/api".to_string()));
+        let paths: Vec<String> = registry.routes.iter().map(|route| registry.public_path(route)).collect();
+        assert_eq!(paths, ["/api/projects", "/api/v2/projects"]);
+    }
+}
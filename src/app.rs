@@ -1,4 +1,6 @@
-use crate::{Router, Request, Response, Handler, static_files::StaticFiles, template::TemplateEngine, error::{AppError, IntoResponse}};
+use crate::{Router, Request, Response, Handler, static_files::StaticFiles, template::TemplateEngine, error::{AppError, ErrorContext, ErrorHandler, ErrorPages}};
+use crate::site::{Favicon, RobotsConfig};
+use crate::tasks::TaskScheduler;
 use async_trait::async_trait;
 use std::sync::Arc; // Ensure Arc is imported
 
@@ -6,8 +8,16 @@ pub struct App {
     router: Router,
     static_handler: Option<Arc<StaticFiles>>,
     template_engine: Option<Arc<TemplateEngine>>,
-    // This field type is correct, it stores an Arc to the error handler trait object
-    error_handler: Arc<dyn Fn(AppError) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> + Send + Sync>,
+    error_handler: ErrorHandler,
+    error_pages: Option<Arc<ErrorPages>>,
+    // Errors under this path prefix are answered with JSON whatever the `Accept` header says
+    api_prefix: Option<String>,
+    // Taken over by `Server`, which runs it alongside the server
+    tasks: TaskScheduler,
+    // Served at /favicon.ico, /robots.txt and /sitemap.xml unless the router has a route there
+    favicon: Option<Favicon>,
+    robots: Option<String>,
+    sitemap: Option<String>,
 }
 
 impl App {
@@ -16,8 +26,13 @@ impl App {
             router: Router::new(),
             static_handler: None,
             template_engine: None,
-            // Default error handler is also an Arc
-            error_handler: Arc::new(|err: AppError| err.into_response()),
+            error_handler: ErrorHandler::default(),
+            error_pages: None,
+            api_prefix: Some("/api".to_string()),
+            tasks: TaskScheduler::new(),
+            favicon: None,
+            robots: None,
+            sitemap: None,
         }
     }
 
@@ -31,34 +46,300 @@ impl App {
         self
     }
 
+    // Like `static_files`, for a configured handler, e.g.
+    // `StaticFiles::new("public", "/static").with_directory_listing(true)`
+    pub fn static_handler(mut self, files: StaticFiles) -> Self {
+        self.static_handler = Some(Arc::new(files));
+        self
+    }
+
     pub fn templates(mut self, engine: TemplateEngine) -> Self {
         self.template_engine = Some(Arc::new(engine));
         self
     }
 
-    // Modified: Now accepts an Arc<dyn Fn(...)> directly
-    pub fn error_handler(mut self, handler: Arc<dyn Fn(AppError) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> + Send + Sync + 'static>) -> Self
-    {
-        self.error_handler = handler; // Directly assign the Arc
+    // Background tasks started by `Server::run` and stopped when it shuts down
+    pub fn tasks(mut self, scheduler: TaskScheduler) -> Self {
+        self.tasks = scheduler;
+        self
+    }
+
+    // The App's scheduler plus whatever its middleware registers (see `Middleware::tasks`)
+    pub(crate) fn take_tasks(&mut self) -> TaskScheduler {
+        self.router.middleware_tasks(std::mem::take(&mut self.tasks))
+    }
+
+    // `ErrorHandler::new(|ctx, err| ..)`, or an `Arc::new(|err: AppError| ..)` closure
+    pub fn error_handler<H: Into<ErrorHandler>>(mut self, handler: H) -> Self {
+        self.error_handler = handler.into();
+        self
+    }
+
+    // Consulted before the error handler, which still handles statuses without a page
+    pub fn error_pages(mut self, pages: ErrorPages) -> Self {
+        self.error_pages = Some(Arc::new(pages));
+        self
+    }
+
+    // A file path or bytes, served at `/favicon.ico` as ICO, PNG or SVG by its content
+    pub fn favicon<F: Into<Favicon>>(mut self, favicon: F) -> Self {
+        self.favicon = Some(favicon.into());
+        self
+    }
+
+    pub fn robots(mut self, robots: RobotsConfig) -> Self {
+        self.robots = Some(robots.to_string());
+        self
+    }
+
+    // Served at `/sitemap.xml`, e.g. from `sitemap_from_pages`
+    pub fn sitemap(mut self, xml: String) -> Self {
+        self.sitemap = Some(xml);
+        self
+    }
+
+    // Defaults to `/api`; an empty prefix leaves JSON errors to the `Accept` header alone
+    pub fn api_prefix(mut self, prefix: &str) -> Self {
+        let prefix = prefix.trim_end_matches('/');
+        self.api_prefix = (!prefix.is_empty()).then(|| prefix.to_string());
         self
     }
+
+    // Only when the router doesn't answer the path itself
+    async fn site_file(&self, req: &Request) -> Option<Result<Response, Box<dyn std::error::Error + Send + Sync>>> {
+        let path = req.uri.path();
+        if !matches!(req.method, hyper::Method::GET | hyper::Method::HEAD)
+            || !matches!(path, "/favicon.ico" | "/robots.txt" | "/sitemap.xml")
+            || self.router.has_route(&req.method, path)
+        {
+            return None;
+        }
+        Some(match path {
+            // A missing file is left to the router's 404
+            "/favicon.ico" => match self.favicon.as_ref()?.response().await {
+                Ok(response) => Ok(response),
+                Err(e) => {
+                    log::warn!("Failed to serve favicon: {}", e);
+                    return None;
+                }
+            },
+            "/robots.txt" => Ok(Response::new().text(self.robots.as_ref()?)),
+            _ => Ok(Response::new()
+                .header(hyper::header::CONTENT_TYPE, "application/xml; charset=utf-8")
+                .body(hyper::Body::from(self.sitemap.clone()?))),
+        })
+    }
+
+    fn is_api_path(&self, path: &str) -> bool {
+        self.api_prefix.as_deref().is_some_and(|prefix| {
+            path.strip_prefix(prefix).is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        })
+    }
+}
+
+impl Default for App {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[async_trait]
 impl Handler for App {
     async fn handle(&self, req: Request) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+        if let Some(response) = self.site_file(&req).await {
+            return response;
+        }
         if let Some(static_handler) = &self.static_handler {
-            if req.uri.path().starts_with("/static") {
+            // With an SPA fallback the static prefix is often `/`, so let routes below it
+            // (e.g. `/api/...`) through rather than answering them with the fallback
+            let routed = static_handler.has_spa_fallback()
+                && self.router.has_route(&req.method, req.uri.path());
+            if req.uri.path().starts_with(static_handler.prefix()) && !routed {
                 return static_handler.handle(req).await;
             }
         }
 
+        // The original request is consumed by routing
+        let mut ctx = ErrorContext::new(&req);
+        ctx.wants_json |= self.is_api_path(&ctx.path);
+        // What an error page handler gets; pages are HTML, so JSON clients skip them
+        let page_request = match &self.error_pages {
+            Some(_) if !ctx.wants_json => Some(bodyless_copy(&req).await?),
+            _ => None,
+        };
+
         match self.router.handle_request(req).await {
             Ok(response) => Ok(response),
             Err(e) => {
                 let app_error: AppError = e.into();
-                (self.error_handler)(app_error)
+                if let (Some(pages), Some(page_request)) = (&self.error_pages, page_request) {
+                    if let Some(response) = pages.render(&app_error, page_request).await {
+                        return Ok(response);
+                    }
+                }
+                self.error_handler.call(&ctx, app_error)
             }
         }
     }
 }
+
+async fn bodyless_copy(req: &Request) -> Result<Request, Box<dyn std::error::Error + Send + Sync>> {
+    let mut builder = hyper::Request::builder().method(req.method.clone()).uri(req.uri.clone());
+    if let Some(headers) = builder.headers_mut() {
+        *headers = req.headers.clone();
+    }
+    let mut copy = Request::from_hyper(builder.body(hyper::Body::empty())?).await?;
+    copy.request_id = req.request_id.clone();
+    copy.locale = req.locale.clone();
+    copy.remote_addr = req.remote_addr;
+    Ok(copy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn get(app: &App, uri: &str, accept: &str) -> Response {
+        let req = hyper::Request::get(uri).header("accept", accept).body(hyper::Body::empty()).unwrap();
+        app.handle(Request::from_hyper(req).await.unwrap()).await.unwrap()
+    }
+
+    async fn body_text(response: Response) -> String {
+        let bytes = hyper::body::to_bytes(response.body).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn routes_below_an_spa_prefix_take_precedence() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("index.html"), "<p>shell</p>").unwrap();
+        let router = Router::new().get("/api/status", |_req: Request| async {
+            Ok::<_, Box<dyn std::error::Error + Send + Sync>>(Response::new().text("ok"))
+        });
+        let app = App::new()
+            .router(router)
+            .static_handler(StaticFiles::new(dir.path().to_str().unwrap(), "/").with_spa_fallback("index.html"));
+
+        assert_eq!(body_text(get(&app, "/api/status", "text/html").await).await, "ok");
+        assert_eq!(body_text(get(&app, "/settings/profile", "text/html").await).await, "<p>shell</p>");
+    }
+
+    fn failing_router() -> Router {
+        Router::new()
+            .get("/posts/missing", |_req: Request| async {
+                Err::<Response, Box<dyn std::error::Error + Send + Sync>>(Box::new(AppError::NotFound("no such post".to_string())))
+            })
+            .get("/api/boom", |_req: Request| async {
+                Err::<Response, Box<dyn std::error::Error + Send + Sync>>("database unreachable".into())
+            })
+    }
+
+    #[tokio::test]
+    async fn errors_follow_the_accept_header() {
+        let app = App::new().router(failing_router());
+
+        let response = get(&app, "/posts/missing", "application/json").await;
+        assert_eq!(response.status, hyper::StatusCode::NOT_FOUND);
+        let body: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+        assert_eq!(body, serde_json::json!({"error": "no such post", "status": 404}));
+
+        let response = get(&app, "/posts/missing", "text/html").await;
+        assert_eq!(response.status, hyper::StatusCode::NOT_FOUND);
+        let html = body_text(response).await;
+        assert!(html.contains("Error 404: Not Found"));
+        assert!(html.contains("no such post"));
+    }
+
+    #[tokio::test]
+    async fn errors_under_the_api_prefix_are_json() {
+        let response = get(&App::new().router(failing_router()), "/api/boom", "text/html").await;
+        assert_eq!(response.status, hyper::StatusCode::INTERNAL_SERVER_ERROR);
+        let body: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+        assert_eq!(body["error"], "Internal Server Error");
+
+        let app = App::new().router(failing_router()).api_prefix("");
+        let response = get(&app, "/api/boom", "text/html").await;
+        assert!(body_text(response).await.contains("Error 500"));
+    }
+
+    #[tokio::test]
+    async fn error_handler_gets_the_request_context() {
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = seen.clone();
+        let app = App::new().router(failing_router()).error_handler(ErrorHandler::new(move |ctx: &ErrorContext, err: AppError| {
+            log.lock().unwrap().push(format!("{} {} json={} {}", ctx.method, ctx.path, ctx.wants_json, err.status().as_u16()));
+            Ok(Response::new().status(err.status()).text("handled"))
+        }));
+
+        assert_eq!(body_text(get(&app, "/posts/missing", "text/html").await).await, "handled");
+        assert_eq!(body_text(get(&app, "/api/boom", "text/html").await).await, "handled");
+        assert_eq!(*seen.lock().unwrap(), ["GET /posts/missing json=false 404", "GET /api/boom json=true 500"]);
+    }
+
+    #[tokio::test]
+    async fn context_free_handlers_only_see_html_clients() {
+        let handler = Arc::new(|err: AppError| Ok(Response::new().status(err.status()).text("legacy")));
+        let app = App::new().router(failing_router()).error_handler(handler);
+
+        assert_eq!(body_text(get(&app, "/posts/missing", "text/html").await).await, "legacy");
+        let response = get(&app, "/posts/missing", "application/json").await;
+        assert!(body_text(response).await.contains("\"status\":404"));
+    }
+
+    #[tokio::test]
+    async fn error_pages_are_for_html_clients() {
+        let page = |req: Request| async move {
+            Ok::<_, Box<dyn std::error::Error + Send + Sync>>(Response::new().html(&format!("<p>lost at {}</p>", req.uri.path())))
+        };
+        let app = App::new()
+            .router(failing_router())
+            .error_pages(ErrorPages::new().handler(hyper::StatusCode::NOT_FOUND, page));
+
+        let response = get(&app, "/posts/missing", "text/html").await;
+        assert_eq!(response.status, hyper::StatusCode::NOT_FOUND);
+        assert_eq!(body_text(response).await, "<p>lost at /posts/missing</p>");
+
+        let response = get(&app, "/posts/missing", "application/json").await;
+        assert!(body_text(response).await.contains("no such post"));
+        // No page for 500s
+        let response = get(&app, "/api/boom", "text/html").await;
+        assert_eq!(response.status, hyper::StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[tokio::test]
+    async fn serves_favicon_robots_and_sitemap() {
+        let app = App::new()
+            .favicon(b"\x89PNG\r\n\x1a\n")
+            .robots(RobotsConfig::new().disallow("/admin"))
+            .sitemap("<urlset/>".to_string());
+
+        let response = get(&app, "/favicon.ico", "*/*").await;
+        assert_eq!(response.headers["content-type"], "image/png");
+        let response = get(&app, "/robots.txt", "*/*").await;
+        assert_eq!(body_text(response).await, "User-agent: *\nDisallow: /admin\n");
+        let response = get(&app, "/sitemap.xml", "*/*").await;
+        assert_eq!(response.headers["content-type"], "application/xml; charset=utf-8");
+        assert_eq!(body_text(response).await, "<urlset/>");
+
+        // Only for GET and HEAD
+        let req = hyper::Request::post("/robots.txt").body(hyper::Body::empty()).unwrap();
+        let response = app.handle(Request::from_hyper(req).await.unwrap()).await.unwrap();
+        assert_eq!(response.status, hyper::StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn router_routes_and_missing_files_fall_through() {
+        let router = Router::new().get("/robots.txt", |_req: Request| async {
+            Ok::<_, Box<dyn std::error::Error + Send + Sync>>(Response::new().text("from the router"))
+        });
+        let app = App::new()
+            .router(router)
+            .robots(RobotsConfig::new())
+            .favicon("/nonexistent/favicon.ico");
+
+        assert_eq!(body_text(get(&app, "/robots.txt", "*/*").await).await, "from the router");
+        assert_eq!(get(&app, "/favicon.ico", "*/*").await.status, hyper::StatusCode::NOT_FOUND);
+        // Not configured
+        assert_eq!(get(&app, "/sitemap.xml", "*/*").await.status, hyper::StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,174 @@
+use crate::api::negotiation::parse_accept;
+use crate::{AppError, Request};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::{self, PngEncoder};
+use image::codecs::webp::WebPEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat};
+use serde::Deserialize;
+use std::path::Path;
+
+// Larger `w` or `h` values are rejected with a 400
+pub const MAX_DIMENSION: u32 = 4096;
+
+// How an image is fitted to both `w` and `h`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Fit {
+    // Scaled to fit inside the box, keeping its aspect ratio
+    #[default]
+    Contain,
+    // Scaled to cover the box, then cropped to it around the center
+    Cover,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Webp,
+    #[serde(alias = "jpg")]
+    Jpeg,
+    Png,
+}
+
+impl OutputFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            OutputFormat::Webp => "image/webp",
+            OutputFormat::Jpeg => "image/jpeg",
+            OutputFormat::Png => "image/png",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Webp => "webp",
+            OutputFormat::Jpeg => "jpg",
+            OutputFormat::Png => "png",
+        }
+    }
+
+    fn image_format(self) -> ImageFormat {
+        match self {
+            OutputFormat::Webp => ImageFormat::WebP,
+            OutputFormat::Jpeg => ImageFormat::Jpeg,
+            OutputFormat::Png => ImageFormat::Png,
+        }
+    }
+
+    // JPEG at `quality` (1-100), PNG at its best compression, WebP lossless
+    fn encode(self, image: &DynamicImage, quality: u8) -> Result<Vec<u8>, image::ImageError> {
+        let mut encoded = Vec::new();
+        match self {
+            // JPEG has no alpha channel
+            OutputFormat::Jpeg => DynamicImage::ImageRgb8(image.to_rgb8())
+                .write_with_encoder(JpegEncoder::new_with_quality(&mut encoded, quality.clamp(1, 100)))?,
+            OutputFormat::Png => image
+                .write_with_encoder(PngEncoder::new_with_quality(&mut encoded, png::CompressionType::Best, png::FilterType::Adaptive))?,
+            // The WebP encoder only takes 8-bit RGB(A)
+            OutputFormat::Webp if image.color().has_alpha() => DynamicImage::ImageRgba8(image.to_rgba8())
+                .write_with_encoder(WebPEncoder::new_lossless(&mut encoded))?,
+            OutputFormat::Webp => DynamicImage::ImageRgb8(image.to_rgb8())
+                .write_with_encoder(WebPEncoder::new_lossless(&mut encoded))?,
+        }
+        Ok(encoded)
+    }
+
+    // The images AssetManager can process, by extension; anything else is served as is
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match ImageFormat::from_path(path).ok()? {
+            ImageFormat::WebP => Some(OutputFormat::Webp),
+            ImageFormat::Jpeg => Some(OutputFormat::Jpeg),
+            ImageFormat::Png => Some(OutputFormat::Png),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Query {
+    w: Option<u32>,
+    h: Option<u32>,
+    fit: Option<Fit>,
+    format: Option<OutputFormat>,
+}
+
+// What an image URL's query asks for, e.g. `/img/shoe.png?w=320&h=320&fit=cover&format=webp`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageParams {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub fit: Fit,
+    // None keeps the source format, or picks WebP for clients that accept it (see `negotiate`)
+    pub format: Option<OutputFormat>,
+}
+
+impl ImageParams {
+    // None when the query has none of `w`, `h`, `fit` or `format`; a 400 for a value that doesn't
+    // parse, or a dimension that's 0 or over MAX_DIMENSION
+    pub fn from_request(req: &Request) -> Result<Option<Self>, AppError> {
+        let query: Query = req.query_as()?;
+        if query.w.is_none() && query.h.is_none() && query.fit.is_none() && query.format.is_none() {
+            return Ok(None);
+        }
+        for dimension in [query.w, query.h].into_iter().flatten() {
+            if dimension == 0 || dimension > MAX_DIMENSION {
+                return Err(AppError::BadRequest(format!(
+                    "Image dimensions must be between 1 and {}, got {}", MAX_DIMENSION, dimension
+                )));
+            }
+        }
+        Ok(Some(ImageParams {
+            width: query.w,
+            height: query.h,
+            fit: query.fit.unwrap_or_default(),
+            format: query.format,
+        }))
+    }
+
+    // With no explicit `format`, WebP for an `Accept` that lists `image/webp`, the source format
+    // otherwise
+    pub fn negotiate(&self, accept: Option<&str>, source: OutputFormat) -> OutputFormat {
+        if let Some(format) = self.format {
+            return format;
+        }
+        let accepts_webp = accept.map(parse_accept).unwrap_or_default().iter()
+            .any(|range| range.main == "image" && range.sub == "webp" && range.q > 0.0);
+        if accepts_webp { OutputFormat::Webp } else { source }
+    }
+
+    // Every parameter, in a fixed order, for cache keys
+    pub fn cache_key(&self, output: OutputFormat) -> String {
+        let dimension = |d: Option<u32>| d.map_or_else(|| "auto".to_string(), |d| d.to_string());
+        format!(
+            "w={}&h={}&fit={}&format={}",
+            dimension(self.width),
+            dimension(self.height),
+            match self.fit { Fit::Contain => "contain", Fit::Cover => "cover" },
+            output.extension(),
+        )
+    }
+
+    // Decodes `content`, resizes it and encodes it as `output`, JPEG at `quality`. CPU-bound, so
+    // run it off the async workers.
+    pub fn apply(&self, content: &[u8], source: OutputFormat, output: OutputFormat, quality: u8) -> Result<Vec<u8>, image::ImageError> {
+        let image = image::load_from_memory_with_format(content, source.image_format())?;
+        let image = match (self.width, self.height) {
+            (None, None) => image,
+            (Some(width), None) => image.resize(width, u32::MAX, FilterType::Lanczos3),
+            (None, Some(height)) => image.resize(u32::MAX, height, FilterType::Lanczos3),
+            (Some(width), Some(height)) => match self.fit {
+                Fit::Contain => image.resize(width, height, FilterType::Lanczos3),
+                Fit::Cover => image.resize_to_fill(width, height, FilterType::Lanczos3),
+            },
+        };
+        output.encode(&image, quality)
+    }
+}
+
+// `content` re-encoded in its own format (see `OutputFormat::encode`), if that's smaller
+pub(crate) fn recompress(content: &[u8], format: OutputFormat, quality: u8) -> Result<Option<Vec<u8>>, image::ImageError> {
+    let image = image::load_from_memory_with_format(content, format.image_format())?;
+    let encoded = format.encode(&image, quality)?;
+    Ok((encoded.len() < content.len()).then_some(encoded))
+}
@@ -2,12 +2,136 @@ use crate::{Request, Response, Handler};
 use async_trait::async_trait;
 use std::path::{Path, PathBuf};
 use std::collections::HashMap;
+#[cfg(feature = "images")]
+use std::collections::BTreeMap;
+#[cfg(feature = "images")]
+use std::sync::{Arc, Mutex};
 use tokio::fs;
+use crate::static_files::{find_sidecars, pick_sidecar};
+
+#[cfg(feature = "images")]
+pub mod images;
+pub mod pipeline;
+
+#[cfg(feature = "images")]
+pub use images::{Fit, ImageParams, OutputFormat};
+pub use pipeline::{asset_url, AssetError, AssetPipeline, BuiltBundle};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum AssetKind {
+    Css,
+    Js,
+    Other,
+}
+
+impl AssetKind {
+    pub(crate) fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("css") => AssetKind::Css,
+            Some("js") => AssetKind::Js,
+            _ => AssetKind::Other,
+        }
+    }
+}
+
+// The lines `minify` keeps: (line index, column of the first non-blank character, trimmed text).
+// Blank lines go, and so do lines starting a comment: `/*` in CSS, `//` in JS.
+pub(crate) fn minified_lines(content: &str, kind: AssetKind) -> impl Iterator<Item = (usize, usize, &str)> {
+    let comment = match kind {
+        AssetKind::Css => Some("/*"),
+        AssetKind::Js => Some("//"),
+        AssetKind::Other => None,
+    };
+    content.lines().enumerate().filter_map(move |(index, line)| {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || comment.is_some_and(|comment| trimmed.starts_with(comment)) {
+            return None;
+        }
+        Some((index, line.len() - line.trim_start().len(), trimmed))
+    })
+}
+
+// Simple minification: the kept lines, trimmed and joined with spaces
+pub(crate) fn minify(content: &str, kind: AssetKind) -> String {
+    minified_lines(content, kind).map(|(_, _, line)| line).collect::<Vec<_>>().join(" ")
+}
 
 pub struct AssetManager {
     pub root_dir: PathBuf,
     pub cache: HashMap<String, CachedAsset>,
     pub optimization: AssetOptimization,
+    // Serve `file.css.br`/`file.css.gz` siblings as they are to clients that accept them
+    pub precompressed: bool,
+    // Where resized images are kept between requests, named by source content and parameters.
+    // Only written with `image_sizes` set, which bounds how many variants there can be.
+    #[cfg(feature = "images")]
+    pub image_cache_dir: Option<PathBuf>,
+    // The only `w`/`h` values accepted, if not empty; others get a 400
+    #[cfg(feature = "images")]
+    pub image_sizes: Vec<u32>,
+    // Resized images, shared between clones
+    #[cfg(feature = "images")]
+    images: Arc<Mutex<ImageCache>>,
+}
+
+// Least-recently-used eviction once either bound is exceeded, so clients asking for every size
+// from 1 to MAX_DIMENSION can't grow it without limit
+#[cfg(feature = "images")]
+struct ImageCache {
+    // key -> (image, position in `order`)
+    entries: HashMap<String, (CachedAsset, u64)>,
+    // tick -> key, oldest first
+    order: BTreeMap<u64, String>,
+    next_tick: u64,
+    bytes: usize,
+    max_entries: usize,
+    max_bytes: usize,
+}
+
+#[cfg(feature = "images")]
+impl ImageCache {
+    fn new(max_entries: usize, max_bytes: usize) -> Self {
+        ImageCache {
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            next_tick: 0,
+            bytes: 0,
+            max_entries,
+            max_bytes,
+        }
+    }
+
+    fn get(&mut self, key: &str) -> Option<CachedAsset> {
+        let tick = self.next_tick;
+        let (asset, entry_tick) = self.entries.get_mut(key)?;
+        self.order.remove(entry_tick);
+        *entry_tick = tick;
+        self.order.insert(tick, key.to_string());
+        self.next_tick += 1;
+        Some(asset.clone())
+    }
+
+    fn insert(&mut self, key: &str, asset: CachedAsset) {
+        let size = asset.content.len();
+        if size > self.max_bytes || self.max_entries == 0 {
+            return;
+        }
+        if let Some((old, tick)) = self.entries.remove(key) {
+            self.order.remove(&tick);
+            self.bytes -= old.content.len();
+        }
+        while self.entries.len() >= self.max_entries || self.bytes + size > self.max_bytes {
+            let Some((_, oldest)) = self.order.pop_first() else { break };
+            if let Some((old, _)) = self.entries.remove(&oldest) {
+                self.bytes -= old.content.len();
+            }
+        }
+        let tick = self.next_tick;
+        self.next_tick += 1;
+        self.order.insert(tick, key.to_string());
+        self.bytes += size;
+        self.entries.insert(key.to_string(), (asset, tick));
+    }
 }
 
 #[derive(Clone)]
@@ -21,7 +145,13 @@ pub struct CachedAsset {
 pub struct AssetOptimization {
     pub minify_css: bool,
     pub minify_js: bool,
+    // Re-encode PNG and JPEG files, keeping the result only if it's smaller; needs the `images`
+    // feature
     pub compress_images: bool,
+    // JPEG quality (1-100) for compressed and resized images
+    pub image_quality: u8,
+    // Larger images are served as they are, so a first request isn't slowed down by them
+    pub max_compressed_image_size: usize,
     pub cache_duration: u64,
 }
 
@@ -31,6 +161,8 @@ impl Default for AssetOptimization {
             minify_css: true,
             minify_js: true,
             compress_images: true,
+            image_quality: 80,
+            max_compressed_image_size: 2 * 1024 * 1024,
             cache_duration: 3600, // 1 hour
         }
     }
@@ -42,36 +174,196 @@ impl AssetManager {
             root_dir: root_dir.as_ref().to_path_buf(),
             cache: HashMap::new(),
             optimization: AssetOptimization::default(),
+            precompressed: true,
+            #[cfg(feature = "images")]
+            image_cache_dir: Some(std::env::temp_dir().join("rustnext-images")),
+            #[cfg(feature = "images")]
+            image_sizes: Vec::new(),
+            #[cfg(feature = "images")]
+            images: Arc::new(Mutex::new(ImageCache::new(256, 64 * 1024 * 1024))),
         }
     }
 
-    pub async fn serve_asset(&mut self, path: &str) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+    pub fn precompressed(mut self, enabled: bool) -> Self {
+        self.precompressed = enabled;
+        self
+    }
+
+    #[cfg(feature = "images")]
+    pub fn image_cache_dir(mut self, dir: Option<PathBuf>) -> Self {
+        self.image_cache_dir = dir;
+        self
+    }
+
+    // Restricts `w` and `h` to these values, e.g. the widths of a `srcset`
+    #[cfg(feature = "images")]
+    pub fn image_sizes(mut self, sizes: &[u32]) -> Self {
+        self.image_sizes = sizes.to_vec();
+        self
+    }
+
+    // Bounds for resized images kept in memory (default 256 images, 64 MiB)
+    #[cfg(feature = "images")]
+    pub fn image_cache_limits(mut self, max_entries: usize, max_bytes: usize) -> Self {
+        self.images = Arc::new(Mutex::new(ImageCache::new(max_entries, max_bytes)));
+        self
+    }
+
+    // The file under root_dir for a request path, or the 404/403 to answer with instead
+    async fn locate(&self, path: &str) -> Result<Result<PathBuf, Response>, std::io::Error> {
         let file_path = self.root_dir.join(path.trim_start_matches('/'));
-        
+
         // Security check: prevent directory traversal
         let canonical_root = fs::canonicalize(&self.root_dir).await?;
         let canonical_file = match fs::canonicalize(&file_path).await {
             Ok(path) => path,
             Err(_) => {
-                return Ok(Response::new()
+                return Ok(Err(Response::new()
                     .status(hyper::StatusCode::NOT_FOUND)
-                    .text("Asset not found"));
+                    .text("Asset not found")));
             }
         };
-        
+
         if !canonical_file.starts_with(&canonical_root) {
-            return Ok(Response::new()
+            return Ok(Err(Response::new()
                 .status(hyper::StatusCode::FORBIDDEN)
-                .text("Forbidden"));
+                .text("Forbidden")));
+        }
+        Ok(Ok(file_path))
+    }
+
+    // `serve_asset` for the request's path, or its precompressed sibling (see `precompressed`).
+    // With the `images` feature, PNG, JPEG and WebP images are resized and transcoded as their
+    // query asks (see ImageParams); other files ignore it.
+    pub async fn serve_request(&mut self, req: &Request) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+        let path = req.uri.path();
+        #[cfg(feature = "images")]
+        if let Some(source) = images::OutputFormat::from_path(Path::new(path)) {
+            if let Some(params) = ImageParams::from_request(req)? {
+                let accept = req.headers.get(hyper::header::ACCEPT).and_then(|v| v.to_str().ok());
+                return self.serve_image(path, &params, source, accept).await;
+            }
+        }
+        let accept_encoding = req.headers.get(hyper::header::ACCEPT_ENCODING).and_then(|v| v.to_str().ok());
+        self.serve_file(path, accept_encoding).await
+    }
+
+    #[cfg(feature = "images")]
+    async fn serve_image(
+        &mut self,
+        path: &str,
+        params: &ImageParams,
+        source: OutputFormat,
+        accept: Option<&str>,
+    ) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+        if !self.image_sizes.is_empty() {
+            for size in [params.width, params.height].into_iter().flatten() {
+                if !self.image_sizes.contains(&size) {
+                    return Err(crate::AppError::BadRequest(format!("Image size {} is not available", size)).into());
+                }
+            }
+        }
+        let file_path = match self.locate(path).await? {
+            Ok(file_path) => file_path,
+            Err(response) => return Ok(response),
+        };
+        let output = params.negotiate(accept, source);
+        let cache_key = format!("{}?{}", path, params.cache_key(output));
+        // Without an explicit format the body depends on Accept
+        let vary = params.format.is_none();
+
+        let cached = self.images.lock().unwrap_or_else(|e| e.into_inner()).get(&cache_key);
+        let cached = match cached {
+            Some(cached) => cached,
+            None => {
+                let content = fs::read(&file_path).await?;
+                let disk_dir = self.image_cache_dir.as_ref().filter(|_| !self.image_sizes.is_empty());
+                let disk_path = disk_dir.map(|dir| {
+                    let key = md5::compute(format!("{:x}{}&q={}", md5::compute(&content), params.cache_key(output), self.optimization.image_quality));
+                    dir.join(format!("{:x}.{}", key, output.extension()))
+                });
+                let processed = match &disk_path {
+                    Some(disk_path) => fs::read(disk_path).await.ok(),
+                    None => None,
+                };
+                let processed = match processed {
+                    Some(processed) => processed,
+                    None => {
+                        let params = params.clone();
+                        let quality = self.optimization.image_quality;
+                        let processed = tokio::task::spawn_blocking(move || params.apply(&content, source, output, quality))
+                            .await?
+                            .map_err(|e| crate::AppError::BadRequest(format!("Can't process image {}: {}", path, e)))?;
+                        if let Some(disk_path) = &disk_path {
+                            // A failed write only costs a resize next time
+                            let written = match disk_path.parent() {
+                                Some(dir) => fs::create_dir_all(dir).await.and(fs::write(disk_path, &processed).await),
+                                None => fs::write(disk_path, &processed).await,
+                            };
+                            if let Err(e) = written {
+                                log::warn!("Failed to cache resized image {}: {}", disk_path.display(), e);
+                            }
+                        }
+                        processed
+                    }
+                };
+                let cached = CachedAsset {
+                    etag: format!("\"{:x}\"", md5::compute(&processed)),
+                    content: processed,
+                    content_type: output.content_type().to_string(),
+                    last_modified: chrono::Utc::now().format("%a, %d %b %Y %H:%M:%S GMT").to_string(),
+                };
+                self.images.lock().unwrap_or_else(|e| e.into_inner()).insert(&cache_key, cached.clone());
+                cached
+            }
+        };
+
+        let response = Response::new()
+            .header("Content-Type", &cached.content_type)
+            .header("ETag", &cached.etag)
+            .header("Cache-Control", format!("public, max-age={}", self.optimization.cache_duration));
+        let response = if vary { response.header("Vary", "Accept") } else { response };
+        Ok(response.body(hyper::Body::from(cached.content)))
+    }
+
+    // Always uncompressed; `serve_request` also serves precompressed siblings
+    pub async fn serve_asset(&mut self, path: &str) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+        self.serve_file(path, None).await
+    }
+
+    async fn serve_file(&mut self, path: &str, accept_encoding: Option<&str>) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+        let file_path = match self.locate(path).await? {
+            Ok(file_path) => file_path,
+            Err(response) => return Ok(response),
+        };
+
+        let sidecars = if self.precompressed {
+            find_sidecars(&fs::canonicalize(&self.root_dir).await?, &file_path)
+        } else {
+            Vec::new()
+        };
+        // The body depends on Accept-Encoding whenever there's a variant to choose
+        let vary = !sidecars.is_empty();
+        if let Some((coding, sidecar)) = pick_sidecar(&sidecars, accept_encoding) {
+            // Compressed at build time, so already minified if it's going to be
+            let content = fs::read(sidecar).await?;
+            return Ok(Response::new()
+                .header("Content-Type", self.get_content_type(&file_path))
+                .header("Content-Encoding", coding)
+                .header("Vary", "Accept-Encoding")
+                .header("ETag", format!("\"{:x}\"", md5::compute(&content)))
+                .header("Cache-Control", format!("public, max-age={}", self.optimization.cache_duration))
+                .body(hyper::Body::from(content)));
         }
 
         // Check cache first
         if let Some(cached) = self.cache.get(path) {
-            return Ok(Response::new()
+            let response = Response::new()
                 .header("Content-Type", &cached.content_type)
                 .header("ETag", &cached.etag)
-                .header("Cache-Control", &format!("public, max-age={}", self.optimization.cache_duration))
-                .body(hyper::Body::from(cached.content.clone())));
+                .header("Cache-Control", format!("public, max-age={}", self.optimization.cache_duration));
+            let response = if vary { response.header("Vary", "Accept-Encoding") } else { response };
+            return Ok(response.body(hyper::Body::from(cached.content.clone())));
         }
 
         // Read and process file
@@ -80,7 +372,7 @@ impl AssetManager {
         let processed_content = self.optimize_content(&content, &content_type).await?;
         
         // Generate ETag using a simple hash
-        let etag = format!("\"{}\"", format!("{:x}", md5::compute(&processed_content)));
+        let etag = format!("\"{:x}\"", md5::compute(&processed_content));
         
         // Cache the asset
         let cached_asset = CachedAsset {
@@ -91,36 +383,41 @@ impl AssetManager {
         };
         self.cache.insert(path.to_string(), cached_asset);
 
-        Ok(Response::new()
+        let response = Response::new()
             .header("Content-Type", &content_type)
             .header("ETag", &etag)
-            .header("Cache-Control", &format!("public, max-age={}", self.optimization.cache_duration))
-            .body(hyper::Body::from(processed_content)))
+            .header("Cache-Control", format!("public, max-age={}", self.optimization.cache_duration));
+        let response = if vary { response.header("Vary", "Accept-Encoding") } else { response };
+        Ok(response.body(hyper::Body::from(processed_content)))
     }
 
     async fn optimize_content(&self, content: &[u8], content_type: &str) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
         match content_type {
             "text/css" if self.optimization.minify_css => {
-                // Simple CSS minification (remove comments and extra whitespace)
                 let css_content = String::from_utf8_lossy(content);
-                let minified = css_content
-                    .lines()
-                    .map(|line| line.trim())
-                    .filter(|line| !line.starts_with("/*") && !line.is_empty())
-                    .collect::<Vec<_>>()
-                    .join(" ");
-                Ok(minified.into_bytes())
+                Ok(minify(&css_content, AssetKind::Css).into_bytes())
             }
             "application/javascript" | "text/javascript" if self.optimization.minify_js => {
-                // Simple JS minification (remove comments and extra whitespace)
                 let js_content = String::from_utf8_lossy(content);
-                let minified = js_content
-                    .lines()
-                    .map(|line| line.trim())
-                    .filter(|line| !line.trim_start().starts_with("//") && !line.is_empty())
-                    .collect::<Vec<_>>()
-                    .join(" ");
-                Ok(minified.into_bytes())
+                Ok(minify(&js_content, AssetKind::Js).into_bytes())
+            }
+            #[cfg(feature = "images")]
+            "image/png" | "image/jpeg"
+                if self.optimization.compress_images && content.len() <= self.optimization.max_compressed_image_size =>
+            {
+                let format = if content_type == "image/png" { OutputFormat::Png } else { OutputFormat::Jpeg };
+                let quality = self.optimization.image_quality;
+                let original = content.to_vec();
+                let compressed = tokio::task::spawn_blocking(move || images::recompress(&original, format, quality)).await?;
+                match compressed {
+                    Ok(Some(compressed)) => Ok(compressed),
+                    Ok(None) => Ok(content.to_vec()),
+                    // Served as it is; the browser may still manage
+                    Err(e) => {
+                        log::warn!("Failed to compress image: {}", e);
+                        Ok(content.to_vec())
+                    }
+                }
             }
             _ => Ok(content.to_vec()),
         }
@@ -138,6 +435,7 @@ impl AssetManager {
             Some("woff2") => "font/woff2".to_string(),
             Some("ttf") => "font/ttf".to_string(),
             Some("ico") => "image/x-icon".to_string(),
+            Some("webp") => "image/webp".to_string(),
             _ => "application/octet-stream".to_string(),
         }
     }
@@ -146,9 +444,8 @@ impl AssetManager {
 #[async_trait]
 impl Handler for AssetManager {
     async fn handle(&self, req: Request) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
-        let path = req.uri.path();
         let mut manager = self.clone();
-        manager.serve_asset(path).await
+        manager.serve_request(&req).await
     }
 }
 
@@ -161,8 +458,101 @@ impl Clone for AssetManager {
                 minify_css: self.optimization.minify_css,
                 minify_js: self.optimization.minify_js,
                 compress_images: self.optimization.compress_images,
+                image_quality: self.optimization.image_quality,
+                max_compressed_image_size: self.optimization.max_compressed_image_size,
                 cache_duration: self.optimization.cache_duration,
             },
+            precompressed: self.precompressed,
+            #[cfg(feature = "images")]
+            image_cache_dir: self.image_cache_dir.clone(),
+            #[cfg(feature = "images")]
+            image_sizes: self.image_sizes.clone(),
+            #[cfg(feature = "images")]
+            images: self.images.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn get(manager: &mut AssetManager, uri: &str, accept: Option<&str>) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+        let mut builder = hyper::Request::builder().uri(uri);
+        if let Some(accept) = accept {
+            builder = builder.header("Accept", accept);
+        }
+        let req = Request::from_hyper(builder.body(hyper::Body::empty()).unwrap()).await.unwrap();
+        manager.serve_request(&req).await
+    }
+
+    async fn body(response: Response) -> Vec<u8> {
+        hyper::body::to_bytes(response.body).await.unwrap().to_vec()
+    }
+
+    #[tokio::test]
+    async fn minifies_css_and_serves_other_files_unchanged() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("site.css"), "/* theme */\nbody {\n  color: red;\n}\n").unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "a\n\nb").unwrap();
+        let mut manager = AssetManager::new(dir.path());
+
+        let css = get(&mut manager, "/site.css", None).await.unwrap();
+        assert_eq!(css.headers.get("Content-Type").unwrap(), "text/css");
+        assert_eq!(body(css).await, b"body { color: red; }");
+        // Resize parameters mean nothing to a non-image
+        assert_eq!(body(get(&mut manager, "/notes.txt?w=10", None).await.unwrap()).await, b"a\n\nb");
+    }
+
+    #[cfg(feature = "images")]
+    fn png_fixture(width: u32, height: u32) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        image::RgbImage::from_pixel(width, height, image::Rgb([200, 40, 40]))
+            .save(dir.path().join("pic.png"))
+            .unwrap();
+        dir
+    }
+
+    #[cfg(feature = "images")]
+    #[tokio::test]
+    async fn resizes_and_negotiates_the_output_format() {
+        let dir = png_fixture(64, 32);
+        let mut manager = AssetManager::new(dir.path()).image_cache_dir(None);
+
+        let png = get(&mut manager, "/pic.png?w=16", None).await.unwrap();
+        assert_eq!(png.headers.get("Content-Type").unwrap(), "image/png");
+        let decoded = image::load_from_memory(&body(png).await).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (16, 8));
+
+        let webp = get(&mut manager, "/pic.png?w=16&h=16&fit=cover", Some("image/webp,*/*")).await.unwrap();
+        assert_eq!(webp.headers.get("Content-Type").unwrap(), "image/webp");
+        let decoded = image::load_from_memory(&body(webp).await).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (16, 16));
+    }
+
+    #[cfg(feature = "images")]
+    #[tokio::test]
+    async fn rejects_oversized_and_unlisted_dimensions() {
+        let dir = png_fixture(8, 8);
+        let mut manager = AssetManager::new(dir.path()).image_cache_dir(None);
+        assert!(get(&mut manager, "/pic.png?w=5000", None).await.is_err());
+
+        let mut manager = manager.image_sizes(&[4]);
+        assert!(get(&mut manager, "/pic.png?w=4", None).await.is_ok());
+        assert!(get(&mut manager, "/pic.png?w=5", None).await.is_err());
+    }
+
+    #[cfg(feature = "images")]
+    #[tokio::test]
+    async fn resized_images_are_evicted_past_the_cache_limits() {
+        let dir = png_fixture(32, 32);
+        let mut manager = AssetManager::new(dir.path()).image_cache_dir(None).image_cache_limits(2, usize::MAX);
+
+        for width in 1..=5 {
+            get(&mut manager, &format!("/pic.png?w={}", width), None).await.unwrap();
         }
+        let images = manager.images.lock().unwrap();
+        assert_eq!(images.entries.len(), 2);
+        assert!(images.entries.contains_key("/pic.png?w=5&h=auto&fit=contain&format=png"));
     }
 }
@@ -0,0 +1,532 @@
+use super::{minified_lines, AssetKind};
+use log::info;
+use once_cell::sync::OnceCell;
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+// Bundle name => public URL of its latest build
+static MANIFEST: OnceCell<RwLock<HashMap<String, String>>> = OnceCell::new();
+
+fn manifest() -> &'static RwLock<HashMap<String, String>> {
+    MANIFEST.get_or_init(|| RwLock::new(HashMap::new()))
+}
+
+// The URL of a bundle built by an AssetPipeline, e.g. `asset_url("app.js")` is
+// `/assets/app.1a2b3c4d.js`. Names that aren't bundles come back unchanged.
+pub fn asset_url(name: &str) -> String {
+    manifest().read().unwrap().get(name).cloned().unwrap_or_else(|| name.to_string())
+}
+
+// Concatenates JS and CSS files into fingerprinted bundles with source maps, e.g.
+//
+//     AssetPipeline::new("assets", "public/assets", "/assets")
+//         .bundle("vendor.js", &["js/htmx.js"])
+//         .bundle("app.js", &["vendor.js", "js/a.js", "js/b.js"])
+//         .bundle("app.css", &["css/reset.css", "css/site.css"])
+//         .build()
+//         .await?;
+//
+// Inputs are paths under the source directory, or the names of other bundles, which are built
+// first. Each bundle is minified (see `assets::minify`), written to the output directory as
+// `<name>.<hash>.<ext>` next to a `.map` source map of the original files, and registered for
+// `asset_url`. Serve the output directory at the URL prefix, e.g. with `App::static_files`.
+// Earlier builds are left in place, so pages already loaded keep working.
+#[derive(Debug, Clone)]
+pub struct AssetPipeline {
+    source_dir: PathBuf,
+    output_dir: PathBuf,
+    url_prefix: String,
+    bundles: Vec<(String, Vec<String>)>,
+    minify: bool,
+}
+
+// A bundle written by `AssetPipeline::build`
+#[derive(Debug, Clone)]
+pub struct BuiltBundle {
+    pub name: String,
+    pub url: String,
+    pub path: PathBuf,
+    pub map_path: PathBuf,
+    // The files it was built from, in order
+    pub sources: Vec<String>,
+}
+
+// Why the pipeline couldn't build
+#[derive(Debug)]
+pub enum AssetError {
+    Read { path: String, source: std::io::Error },
+    Write { path: String, source: std::io::Error },
+    // A problem in an input file, at a byte offset into it
+    Invalid { path: String, offset: usize, message: String },
+    // A bundle that includes itself, directly or through other bundles
+    Cycle(String),
+}
+
+impl fmt::Display for AssetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssetError::Read { path, source } => write!(f, "Failed to read asset {}: {}", path, source),
+            AssetError::Write { path, source } => write!(f, "Failed to write asset {}: {}", path, source),
+            AssetError::Invalid { path, offset, message } => write!(f, "{} at byte {}: {}", path, offset, message),
+            AssetError::Cycle(name) => write!(f, "Bundle {} includes itself", name),
+        }
+    }
+}
+
+impl std::error::Error for AssetError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AssetError::Read { source, .. } | AssetError::Write { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+struct SourceFile {
+    path: String,
+    content: String,
+}
+
+impl AssetPipeline {
+    pub fn new<P: AsRef<Path>, Q: AsRef<Path>>(source_dir: P, output_dir: Q, url_prefix: &str) -> Self {
+        AssetPipeline {
+            source_dir: source_dir.as_ref().to_path_buf(),
+            output_dir: output_dir.as_ref().to_path_buf(),
+            url_prefix: url_prefix.trim_end_matches('/').to_string(),
+            bundles: Vec::new(),
+            minify: true,
+        }
+    }
+
+    // Declaring a bundle again replaces it
+    pub fn bundle(mut self, name: &str, inputs: &[&str]) -> Self {
+        let inputs = inputs.iter().map(|input| input.to_string()).collect();
+        match self.bundles.iter_mut().find(|(existing, _)| existing == name) {
+            Some(bundle) => bundle.1 = inputs,
+            None => self.bundles.push((name.to_string(), inputs)),
+        }
+        self
+    }
+
+    // On by default
+    pub fn minify(mut self, minify: bool) -> Self {
+        self.minify = minify;
+        self
+    }
+
+    // Reads every input once, concurrently, then builds the bundles in dependency order. Nothing
+    // is written or registered unless every input is valid.
+    pub async fn build(&self) -> Result<Vec<BuiltBundle>, AssetError> {
+        let order = self.build_order()?;
+
+        let files: HashSet<&str> = self.bundles.iter()
+            .flat_map(|(_, inputs)| inputs.iter().map(String::as_str))
+            .filter(|input| !self.is_bundle(input))
+            .collect();
+        let files: HashMap<&str, Arc<SourceFile>> = futures::future::try_join_all(files.into_iter().map(|path| async move {
+            Ok::<_, AssetError>((path, Arc::new(self.read_source(path).await?)))
+        }))
+        .await?
+        .into_iter()
+        .collect();
+
+        // Each bundle's files, with those of the bundles it includes, each file once
+        let mut sources: HashMap<&str, Vec<Arc<SourceFile>>> = HashMap::new();
+        let mut outputs = Vec::new();
+        for &index in &order {
+            let (name, inputs) = &self.bundles[index];
+            let mut bundle_sources: Vec<Arc<SourceFile>> = Vec::new();
+            for input in inputs {
+                let included = match files.get(input.as_str()) {
+                    Some(file) => vec![file.clone()],
+                    None => sources[input.as_str()].clone(),
+                };
+                for file in included {
+                    if !bundle_sources.iter().any(|existing| existing.path == file.path) {
+                        bundle_sources.push(file);
+                    }
+                }
+            }
+            outputs.push(self.render(name, &bundle_sources));
+            sources.insert(name, bundle_sources);
+        }
+
+        let mut built = Vec::new();
+        for output in outputs {
+            built.push(self.write(output).await?);
+        }
+        let mut manifest = manifest().write().unwrap();
+        for bundle in &built {
+            manifest.insert(bundle.name.clone(), bundle.url.clone());
+        }
+        info!("Built {} asset bundle(s) into {}", built.len(), self.output_dir.display());
+        Ok(built)
+    }
+
+    fn is_bundle(&self, name: &str) -> bool {
+        self.bundles.iter().any(|(existing, _)| existing == name)
+    }
+
+    // Positions in `bundles`, each after the bundles it includes
+    fn build_order(&self) -> Result<Vec<usize>, AssetError> {
+        fn visit(pipeline: &AssetPipeline, index: usize, visiting: &mut Vec<usize>, order: &mut Vec<usize>) -> Result<(), AssetError> {
+            if order.contains(&index) {
+                return Ok(());
+            }
+            if visiting.contains(&index) {
+                return Err(AssetError::Cycle(pipeline.bundles[index].0.clone()));
+            }
+            visiting.push(index);
+            for input in &pipeline.bundles[index].1 {
+                if let Some(included) = pipeline.bundles.iter().position(|(name, _)| name == input) {
+                    visit(pipeline, included, visiting, order)?;
+                }
+            }
+            visiting.pop();
+            order.push(index);
+            Ok(())
+        }
+
+        let mut order = Vec::new();
+        for index in 0..self.bundles.len() {
+            visit(self, index, &mut Vec::new(), &mut order)?;
+        }
+        Ok(order)
+    }
+
+    async fn read_source(&self, path: &str) -> Result<SourceFile, AssetError> {
+        let bytes = tokio::fs::read(self.source_dir.join(path)).await
+            .map_err(|source| AssetError::Read { path: path.to_string(), source })?;
+        let content = String::from_utf8(bytes).map_err(|e| AssetError::Invalid {
+            path: path.to_string(),
+            offset: e.utf8_error().valid_up_to(),
+            message: "invalid UTF-8".to_string(),
+        })?;
+        // The minifier drops comment lines, so a block comment must end on a line of its own
+        if AssetKind::from_path(Path::new(path)) == AssetKind::Css {
+            if let Some(offset) = unterminated_comment(&content) {
+                return Err(AssetError::Invalid {
+                    path: path.to_string(),
+                    offset,
+                    message: "unterminated comment".to_string(),
+                });
+            }
+        }
+        Ok(SourceFile { path: path.to_string(), content })
+    }
+
+    // The bundle's content and source map. Files are separated by a line break; minified, each
+    // file is one line, with a mapping per original line, otherwise lines map one to one.
+    fn render(&self, name: &str, sources: &[Arc<SourceFile>]) -> (String, String, SourceMap) {
+        let kind = AssetKind::from_path(Path::new(name));
+        let mut content = String::new();
+        let mut map = SourceMap::default();
+        for (source, file) in sources.iter().enumerate() {
+            if source > 0 {
+                content.push('\n');
+                map.next_line();
+            }
+            if self.minify && kind != AssetKind::Other {
+                let mut column = 0;
+                for (n, (line, start, text)) in minified_lines(&file.content, kind).enumerate() {
+                    if n > 0 {
+                        content.push(' ');
+                        column += 1;
+                    }
+                    map.add(column, source, line, start);
+                    content.push_str(text);
+                    column += text.encode_utf16().count();
+                }
+            } else {
+                for (line, text) in file.content.lines().enumerate() {
+                    if line > 0 {
+                        content.push('\n');
+                        map.next_line();
+                    }
+                    if !text.is_empty() {
+                        map.add(0, source, line, 0);
+                    }
+                    content.push_str(text);
+                }
+            }
+        }
+        map.sources = sources.iter().map(|file| (file.path.clone(), file.content.clone())).collect();
+        (name.to_string(), content, map)
+    }
+
+    async fn write(&self, (name, mut content, map): (String, String, SourceMap)) -> Result<BuiltBundle, AssetError> {
+        let hash = format!("{:x}", md5::compute(&content));
+        let file_name = fingerprinted(&name, &hash[..8]);
+        let base_name = Path::new(&file_name).file_name().and_then(|n| n.to_str()).unwrap_or(&file_name).to_string();
+        match AssetKind::from_path(Path::new(&name)) {
+            AssetKind::Js => content.push_str(&format!("\n//# sourceMappingURL={}.map\n", base_name)),
+            AssetKind::Css => content.push_str(&format!("\n/*# sourceMappingURL={}.map */\n", base_name)),
+            AssetKind::Other => {}
+        }
+
+        let path = self.output_dir.join(&file_name);
+        let map_path = self.output_dir.join(format!("{}.map", file_name));
+        let write_error = |path: &Path| {
+            let path = path.display().to_string();
+            move |source| AssetError::Write { path, source }
+        };
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await.map_err(write_error(parent))?;
+        }
+        tokio::fs::write(&path, content).await.map_err(write_error(&path))?;
+        tokio::fs::write(&map_path, map.to_json(&base_name)).await.map_err(write_error(&map_path))?;
+
+        Ok(BuiltBundle {
+            url: format!("{}/{}", self.url_prefix, file_name),
+            name,
+            path,
+            map_path,
+            sources: map.sources.into_iter().map(|(path, _)| path).collect(),
+        })
+    }
+
+    // Rebuilds whenever a file under the source directory changes. A failed rebuild is logged
+    // and the previous bundles stay registered. Must be called inside the Tokio runtime;
+    // watching stops when the returned value is dropped.
+    #[cfg(feature = "dev")]
+    pub fn watch(self) -> Result<AssetWatcher, notify::Error> {
+        use log::error;
+        use notify::{RecursiveMode, Watcher};
+        use std::time::Duration;
+
+        // Writing the bundles mustn't trigger another build when the output is under the source
+        let output_dir = self.output_dir.canonicalize().unwrap_or_else(|_| self.output_dir.clone());
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |res: Result<notify::Event, notify::Error>| {
+            if let Ok(event) = res {
+                if !matches!(event.kind, notify::EventKind::Access(_))
+                    && event.paths.iter().any(|path| !path.starts_with(&output_dir))
+                {
+                    let _ = tx.send(());
+                }
+            }
+        })?;
+        watcher.watch(&self.source_dir, RecursiveMode::Recursive)?;
+
+        info!("Watching {} for asset changes", self.source_dir.display());
+        let task = tokio::spawn(async move {
+            while rx.recv().await.is_some() {
+                // A save is often several events; rebuild once it settles
+                while let Ok(Some(())) = tokio::time::timeout(Duration::from_millis(200), rx.recv()).await {}
+                if let Err(e) = self.build().await {
+                    error!("Keeping the previous asset bundles: {}", e);
+                }
+            }
+        });
+
+        Ok(AssetWatcher { _watcher: watcher, task })
+    }
+}
+
+#[cfg(feature = "dev")]
+pub struct AssetWatcher {
+    _watcher: notify::RecommendedWatcher,
+    task: tokio::task::JoinHandle<()>,
+}
+
+#[cfg(feature = "dev")]
+impl Drop for AssetWatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+// "js/app.js" with hash "1a2b3c4d" is "js/app.1a2b3c4d.js"
+fn fingerprinted(name: &str, hash: &str) -> String {
+    let (dir, file) = name.rsplit_once('/').map_or(("", name), |(dir, file)| (dir, file));
+    let file = match file.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => format!("{}.{}.{}", stem, hash, extension),
+        _ => format!("{}.{}", file, hash),
+    };
+    if dir.is_empty() { file } else { format!("{}/{}", dir, file) }
+}
+
+// Where a `/*` has no closing `*/`
+fn unterminated_comment(css: &str) -> Option<usize> {
+    let mut rest = 0;
+    while let Some(start) = css[rest..].find("/*").map(|i| rest + i) {
+        match css[start + 2..].find("*/") {
+            Some(end) => rest = start + 2 + end + 2,
+            None => return Some(start),
+        }
+    }
+    None
+}
+
+// A version 3 source map (https://sourcemaps.info/spec.html); lines and columns are zero-based
+#[derive(Default)]
+struct SourceMap {
+    sources: Vec<(String, String)>,
+    mappings: String,
+    // Segments are relative to the previous one; the column only within a line
+    line_has_segment: bool,
+    previous: [i64; 4],
+}
+
+impl SourceMap {
+    fn next_line(&mut self) {
+        self.mappings.push(';');
+        self.line_has_segment = false;
+        self.previous[0] = 0;
+    }
+
+    fn add(&mut self, column: usize, source: usize, line: usize, source_column: usize) {
+        if self.line_has_segment {
+            self.mappings.push(',');
+        }
+        self.line_has_segment = true;
+        let segment = [column as i64, source as i64, line as i64, source_column as i64];
+        for (value, previous) in segment.iter().zip(self.previous.iter_mut()) {
+            push_vlq(&mut self.mappings, value - *previous);
+            *previous = *value;
+        }
+    }
+
+    fn to_json(&self, file: &str) -> String {
+        json!({
+            "version": 3,
+            "file": file,
+            "sources": self.sources.iter().map(|(path, _)| path).collect::<Vec<_>>(),
+            "sourcesContent": self.sources.iter().map(|(_, content)| content).collect::<Vec<_>>(),
+            "names": [],
+            "mappings": self.mappings,
+        })
+        .to_string()
+    }
+}
+
+// Base64 VLQ: five bits per digit, least significant first, the sign in the lowest bit
+fn push_vlq(out: &mut String, value: i64) {
+    const DIGITS: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut vlq = if value < 0 { ((-value as u64) << 1) | 1 } else { (value as u64) << 1 };
+    loop {
+        let mut digit = (vlq & 31) as usize;
+        vlq >>= 5;
+        if vlq > 0 {
+            digit |= 32;
+        }
+        out.push(DIGITS[digit] as char);
+        if vlq == 0 {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sources(files: &[(&str, &str)]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for (path, content) in files {
+            let path = dir.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        dir
+    }
+
+    fn read_map(bundle: &BuiltBundle) -> serde_json::Value {
+        serde_json::from_str(&std::fs::read_to_string(&bundle.map_path).unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn builds_fingerprinted_bundles_in_dependency_order() {
+        let src = sources(&[
+            ("js/vendor.js", "// vendored\nwindow.v = 1;\n"),
+            ("js/a.js", "let a = 1;\n// c\n  let b = 2;\n"),
+            ("js/b.js", "x();\n"),
+        ]);
+        let out = tempfile::tempdir().unwrap();
+        let built = AssetPipeline::new(src.path(), out.path(), "/assets/")
+            .bundle("pipeline-test/app.js", &["pipeline-test/vendor.js", "js/a.js", "js/b.js", "js/a.js"])
+            .bundle("pipeline-test/vendor.js", &["js/vendor.js"])
+            .build()
+            .await
+            .unwrap();
+
+        // The included bundle first, whatever the declaration order
+        assert_eq!(built.iter().map(|b| b.name.as_str()).collect::<Vec<_>>(), ["pipeline-test/vendor.js", "pipeline-test/app.js"]);
+        let app = &built[1];
+        assert_eq!(app.sources, ["js/vendor.js", "js/a.js", "js/b.js"]);
+
+        let file_name = app.path.file_name().unwrap().to_str().unwrap();
+        assert!(file_name.starts_with("app.") && file_name.ends_with(".js") && file_name.len() == "app..js".len() + 8);
+        assert_eq!(app.url, format!("/assets/pipeline-test/{}", file_name));
+        assert_eq!(asset_url("pipeline-test/app.js"), app.url);
+        assert_eq!(asset_url("not-a-bundle.js"), "not-a-bundle.js");
+
+        let content = std::fs::read_to_string(&app.path).unwrap();
+        assert_eq!(content, format!("window.v = 1;\nlet a = 1; let b = 2;\nx();\n//# sourceMappingURL={}.map\n", file_name));
+
+        let map = read_map(app);
+        assert_eq!(map["file"], file_name);
+        assert_eq!(map["sources"], json!(["js/vendor.js", "js/a.js", "js/b.js"]));
+        assert_eq!(map["sourcesContent"][2], "x();\n");
+        // vendor.js line 1; a.js lines 0 and 2 (column 2); b.js line 0
+        assert_eq!(map["mappings"], "AACA;ACDA,WAEE;ACFF");
+    }
+
+    #[tokio::test]
+    async fn unminified_css_maps_lines_one_to_one() {
+        let src = sources(&[("a.css", "body {\n\n  margin: 0;\n}"), ("b.css", "p{}")]);
+        let out = tempfile::tempdir().unwrap();
+        let built = AssetPipeline::new(src.path(), out.path(), "/assets")
+            .minify(false)
+            .bundle("pipeline-test/site.css", &["a.css", "b.css"])
+            .build()
+            .await
+            .unwrap();
+
+        let content = std::fs::read_to_string(&built[0].path).unwrap();
+        assert!(content.starts_with("body {\n\n  margin: 0;\n}\np{}\n/*# sourceMappingURL=site."));
+        // The blank line has no segment
+        assert_eq!(read_map(&built[0])["mappings"], "AAAA;;AAEA;AACA;ACHA");
+    }
+
+    #[tokio::test]
+    async fn invalid_inputs_stop_the_build_before_anything_is_written() {
+        let src = sources(&[("ok.css", "p{}"), ("broken.css", "p{}\n/* never closed\n")]);
+        std::fs::write(src.path().join("bad.js"), b"ok\xff").unwrap();
+        let out = tempfile::tempdir().unwrap();
+        let pipeline = |bundles: &[(&str, &[&str])]| {
+            bundles.iter().fold(AssetPipeline::new(src.path(), out.path(), "/assets"), |pipeline, (name, inputs)| pipeline.bundle(name, inputs))
+        };
+
+        let err = pipeline(&[("a.css", &["ok.css"]), ("b.css", &["broken.css"])]).build().await.unwrap_err();
+        assert!(matches!(err, AssetError::Invalid { ref path, offset: 4, .. } if path == "broken.css"));
+        assert_eq!(std::fs::read_dir(out.path()).unwrap().count(), 0);
+
+        let err = pipeline(&[("a.js", &["bad.js"])]).build().await.unwrap_err();
+        assert_eq!(err.to_string(), "bad.js at byte 2: invalid UTF-8");
+
+        let err = pipeline(&[("a.css", &["missing.css"])]).build().await.unwrap_err();
+        assert!(matches!(err, AssetError::Read { ref path, .. } if path == "missing.css"));
+
+        let err = pipeline(&[("a.js", &["b.js"]), ("b.js", &["c.js"]), ("c.js", &["a.js"])]).build().await.unwrap_err();
+        assert!(matches!(err, AssetError::Cycle(_)));
+    }
+
+    #[test]
+    fn fingerprints_and_vlq() {
+        assert_eq!(fingerprinted("js/app.js", "1a2b3c4d"), "js/app.1a2b3c4d.js");
+        assert_eq!(fingerprinted("app.min.css", "1a2b3c4d"), "app.min.1a2b3c4d.css");
+        assert_eq!(fingerprinted(".env", "1a2b3c4d"), ".env.1a2b3c4d");
+        assert_eq!(fingerprinted("LICENSE", "1a2b3c4d"), "LICENSE.1a2b3c4d");
+
+        let vlq = |value| {
+            let mut out = String::new();
+            push_vlq(&mut out, value);
+            out
+        };
+        assert_eq!([vlq(0), vlq(1), vlq(-1), vlq(16), vlq(123)], ["A", "C", "D", "gB", "2H"]);
+    }
+}
@@ -0,0 +1,456 @@
+// Ready-made login/logout API handlers. Opt-in: register them like any other `ApiHandler`,
+// e.g. `api_route!(Method::POST, "/api/login", LoginHandler::jwt(users, jwt))`.
+use super::{hash_password, verify_password, AuthError, JwtAuth, PasswordError};
+use crate::api::{ApiError, ApiHandler, ApiResponse};
+use crate::session::SessionStore;
+use crate::Request;
+use async_trait::async_trait;
+use once_cell::sync::OnceCell;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, RwLock};
+
+#[derive(Debug, Clone)]
+pub struct UserRecord {
+    pub id: String,
+    pub password_hash: String,
+    pub roles: Vec<String>,
+}
+
+#[async_trait]
+pub trait UserStore: Send + Sync {
+    async fn find_by_username(&self, username: &str) -> Option<UserRecord>;
+}
+
+// Users kept in memory; meant for examples and tests
+pub struct MemoryUserStore {
+    users: RwLock<HashMap<String, UserRecord>>,
+}
+
+impl MemoryUserStore {
+    pub fn new() -> Self {
+        MemoryUserStore {
+            users: RwLock::new(HashMap::new()),
+        }
+    }
+
+    // Hashes `password` and stores the user; the username doubles as the user id
+    pub async fn add_user(&self, username: &str, password: &str, roles: Vec<String>) -> Result<(), PasswordError> {
+        let password = password.to_string();
+        let record = UserRecord {
+            id: username.to_string(),
+            password_hash: blocking(move || hash_password(&password)).await?,
+            roles,
+        };
+        self.insert(username, record).await;
+        Ok(())
+    }
+
+    pub async fn insert(&self, username: &str, record: UserRecord) {
+        self.users.write().await.insert(username.to_string(), record);
+    }
+}
+
+impl Default for MemoryUserStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl UserStore for MemoryUserStore {
+    async fn find_by_username(&self, username: &str) -> Option<UserRecord> {
+        self.users.read().await.get(username).cloned()
+    }
+}
+
+// Where a successful login puts the identity
+#[derive(Clone)]
+pub enum LoginMode {
+    // Writes the user id and roles into the current session (requires SessionMiddleware)
+    Session(Arc<dyn SessionStore>),
+    // Returns an access/refresh token pair in the response body
+    Jwt(Arc<JwtAuth>),
+}
+
+// Failures per username. Entries older than `window` are swept out at most once per window, so
+// attempts spread over many made-up usernames don't grow the map without bound.
+struct FailedAttempts {
+    max_attempts: u32,
+    window: Duration,
+    state: Mutex<AttemptsState>,
+}
+
+struct AttemptsState {
+    counts: HashMap<String, (u32, Instant)>,
+    last_sweep: Instant,
+}
+
+impl FailedAttempts {
+    fn new(max_attempts: u32, window: Duration) -> Self {
+        FailedAttempts {
+            max_attempts,
+            window,
+            state: Mutex::new(AttemptsState {
+                counts: HashMap::new(),
+                last_sweep: Instant::now(),
+            }),
+        }
+    }
+
+    async fn is_locked(&self, username: &str) -> bool {
+        let counts = &mut self.state.lock().await.counts;
+        match counts.get(username) {
+            Some((_, since)) if since.elapsed() >= self.window => {
+                counts.remove(username);
+                false
+            }
+            Some((count, _)) => *count >= self.max_attempts,
+            None => false,
+        }
+    }
+
+    async fn record_failure(&self, username: &str) {
+        let now = Instant::now();
+        let mut state = self.state.lock().await;
+        if now.duration_since(state.last_sweep) >= self.window {
+            let window = self.window;
+            state.counts.retain(|_, (_, since)| now.duration_since(*since) < window);
+            state.last_sweep = now;
+        }
+
+        let entry = state.counts.entry(username.to_string()).or_insert((0, now));
+        if now.duration_since(entry.1) >= self.window {
+            *entry = (0, now);
+        }
+        entry.0 += 1;
+    }
+
+    async fn reset(&self, username: &str) {
+        self.state.lock().await.counts.remove(username);
+    }
+}
+
+pub struct LoginHandler {
+    users: Arc<dyn UserStore>,
+    mode: LoginMode,
+    user_key: String,
+    roles_key: String,
+    failed_attempts: Option<FailedAttempts>,
+}
+
+impl LoginHandler {
+    pub fn new(users: Arc<dyn UserStore>, mode: LoginMode) -> Self {
+        LoginHandler {
+            users,
+            mode,
+            // Same keys AuthGuard::from_session reads
+            user_key: "user_id".to_string(),
+            roles_key: "roles".to_string(),
+            failed_attempts: None,
+        }
+    }
+
+    pub fn session(users: Arc<dyn UserStore>, store: Arc<dyn SessionStore>) -> Self {
+        Self::new(users, LoginMode::Session(store))
+    }
+
+    pub fn jwt(users: Arc<dyn UserStore>, jwt: Arc<JwtAuth>) -> Self {
+        Self::new(users, LoginMode::Jwt(jwt))
+    }
+
+    pub fn session_keys(mut self, user_key: &str, roles_key: &str) -> Self {
+        self.user_key = user_key.to_string();
+        self.roles_key = roles_key.to_string();
+        self
+    }
+
+    // After `max_attempts` failed logins for a username within `window`, further attempts get 429
+    pub fn max_failed_attempts(mut self, max_attempts: u32, window: Duration) -> Self {
+        self.failed_attempts = Some(FailedAttempts::new(max_attempts, window));
+        self
+    }
+
+    async fn authenticate(&self, username: &str, password: &str) -> Option<UserRecord> {
+        let user = self.users.find_by_username(username).await;
+        let hash = user.as_ref().map(|user| user.password_hash.clone());
+        let password = password.to_string();
+        let verified = blocking(move || match hash {
+            Some(hash) => verify_password(&password, &hash).unwrap_or(false),
+            None => {
+                // Burn the same hashing work as a real check so unknown usernames can't be told apart by timing
+                let _ = verify_password(&password, dummy_hash());
+                false
+            }
+        }).await;
+        user.filter(|_| verified)
+    }
+}
+
+// bcrypt and argon2 are slow on purpose, tens to hundreds of milliseconds per call; run them on
+// the blocking pool rather than holding up an async worker for every login
+async fn blocking<T: Send + 'static>(work: impl FnOnce() -> T + Send + 'static) -> T {
+    match tokio::task::spawn_blocking(work).await {
+        Ok(result) => result,
+        Err(e) => std::panic::resume_unwind(e.into_panic()),
+    }
+}
+
+fn dummy_hash() -> &'static str {
+    static DUMMY_HASH: OnceCell<String> = OnceCell::new();
+    DUMMY_HASH.get_or_init(|| hash_password("rustnext-dummy-password").unwrap_or_default())
+}
+
+fn is_form_request(req: &Request) -> bool {
+    req.headers
+        .get(hyper::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.starts_with("application/x-www-form-urlencoded"))
+        .unwrap_or(false)
+}
+
+// Accepts `username`/`password` as a form post or a JSON object
+async fn read_credentials(req: &mut Request) -> Result<(String, String), ApiError> {
+    let (username, password) = if is_form_request(req) {
+        let form = req.form().await.map_err(|_| ApiError::bad_request("Invalid form data"))?;
+        (form.get("username").cloned(), form.get("password").cloned())
+    } else {
+        let json = req.json().await.map_err(|_| ApiError::bad_request("Invalid JSON body"))?;
+        (
+            json.get("username").and_then(|v| v.as_str()).map(str::to_string),
+            json.get("password").and_then(|v| v.as_str()).map(str::to_string),
+        )
+    };
+
+    match (username, password) {
+        (Some(username), Some(password)) if !username.trim().is_empty() && !password.is_empty() => {
+            Ok((username.trim().to_string(), password))
+        }
+        _ => Err(ApiError::bad_request("username and password are required")),
+    }
+}
+
+#[async_trait]
+impl ApiHandler for LoginHandler {
+    async fn handle(&self, mut req: Request) -> Result<ApiResponse, ApiError> {
+        let (username, password) = read_credentials(&mut req).await?;
+
+        if let Some(attempts) = &self.failed_attempts {
+            if attempts.is_locked(&username).await {
+                return Err(ApiError::new(
+                    hyper::StatusCode::TOO_MANY_REQUESTS,
+                    "Too many failed login attempts, try again later",
+                ));
+            }
+        }
+
+        let user = match self.authenticate(&username, &password).await {
+            Some(user) => user,
+            None => {
+                if let Some(attempts) = &self.failed_attempts {
+                    attempts.record_failure(&username).await;
+                }
+                return Err(ApiError::new(hyper::StatusCode::UNAUTHORIZED, "Invalid username or password"));
+            }
+        };
+
+        if let Some(attempts) = &self.failed_attempts {
+            attempts.reset(&username).await;
+        }
+
+        match &self.mode {
+            LoginMode::Session(store) => {
+                // A new ID on login, so a session ID fixed before authentication is worthless
+                req.regenerate_session();
+                let mut session = req.session.take()
+                    .ok_or_else(|| ApiError::internal_error("Session login requires SessionMiddleware"))?;
+                session.set(&self.user_key, &user.id)
+                    .and_then(|_| session.set(&self.roles_key, &user.roles))
+                    .map_err(|e| ApiError::internal_error(&e.to_string()))?;
+                store.set(session).await.map_err(|e| ApiError::internal_error(&e.to_string()))?;
+
+                Ok(ApiResponse::ok(serde_json::json!({
+                    "user_id": user.id,
+                    "roles": user.roles,
+                })))
+            }
+            LoginMode::Jwt(jwt) => {
+                let tokens = jwt.generate_token_pair(&user.id, user.roles.clone())
+                    .map_err(|e| ApiError::internal_error(&e.to_string()))?;
+
+                Ok(ApiResponse::ok(serde_json::json!({
+                    "user_id": user.id,
+                    "roles": user.roles,
+                    "access_token": tokens.access_token,
+                    "refresh_token": tokens.refresh_token,
+                    "token_type": tokens.token_type,
+                    "expires_in": tokens.expires_in,
+                })))
+            }
+        }
+    }
+}
+
+// Exchanges `refresh_token` (JSON or form body) for a new token pair; the old refresh
+// token is revoked. Access tokens are rejected.
+pub struct RefreshHandler {
+    jwt: Arc<JwtAuth>,
+}
+
+impl RefreshHandler {
+    pub fn new(jwt: Arc<JwtAuth>) -> Self {
+        RefreshHandler { jwt }
+    }
+}
+
+#[async_trait]
+impl ApiHandler for RefreshHandler {
+    async fn handle(&self, mut req: Request) -> Result<ApiResponse, ApiError> {
+        let refresh_token = if is_form_request(&req) {
+            let form = req.form().await.map_err(|_| ApiError::bad_request("Invalid form data"))?;
+            form.get("refresh_token").cloned()
+        } else {
+            let json = req.json().await.map_err(|_| ApiError::bad_request("Invalid JSON body"))?;
+            json.get("refresh_token").and_then(|v| v.as_str()).map(str::to_string)
+        };
+        let refresh_token = refresh_token
+            .filter(|t| !t.is_empty())
+            .ok_or_else(|| ApiError::bad_request("refresh_token is required"))?;
+
+        let tokens = self.jwt.refresh(&refresh_token).await.map_err(|e| match e {
+            AuthError::Store(_) | AuthError::Key(_) => ApiError::internal_error(&e.to_string()),
+            _ => ApiError::new(hyper::StatusCode::UNAUTHORIZED, &e.to_string()),
+        })?;
+
+        Ok(ApiResponse::ok(serde_json::json!({
+            "access_token": tokens.access_token,
+            "refresh_token": tokens.refresh_token,
+            "token_type": tokens.token_type,
+            "expires_in": tokens.expires_in,
+        })))
+    }
+}
+
+pub struct LogoutHandler {
+    mode: LoginMode,
+}
+
+impl LogoutHandler {
+    pub fn new(mode: LoginMode) -> Self {
+        LogoutHandler { mode }
+    }
+
+    // Deletes the current session from the store
+    pub fn session(store: Arc<dyn SessionStore>) -> Self {
+        Self::new(LoginMode::Session(store))
+    }
+
+    // Revokes the bearer token and tells the client to discard its tokens
+    pub fn jwt(jwt: Arc<JwtAuth>) -> Self {
+        Self::new(LoginMode::Jwt(jwt))
+    }
+}
+
+#[async_trait]
+impl ApiHandler for LogoutHandler {
+    async fn handle(&self, req: Request) -> Result<ApiResponse, ApiError> {
+        match &self.mode {
+            LoginMode::Session(store) => {
+                if let Some(session) = &req.session {
+                    store.delete(&session.id).await.map_err(|e| ApiError::internal_error(&e.to_string()))?;
+                }
+                Ok(ApiResponse::ok(serde_json::json!({"logged_out": true})))
+            }
+            LoginMode::Jwt(jwt) => {
+                let token = req.headers
+                    .get("authorization")
+                    .and_then(|auth| auth.to_str().ok())
+                    .and_then(|auth| auth.strip_prefix("Bearer "));
+                // An invalid or already expired token needs no revoking
+                if let Some(token) = token {
+                    let _ = jwt.revoke(token).await;
+                }
+                Ok(ApiResponse::ok(serde_json::json!({
+                    "logged_out": true,
+                    "discard_tokens": true,
+                })))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn login(handler: &LoginHandler, body: &str) -> Result<ApiResponse, ApiError> {
+        let req = Request::from_hyper(
+            hyper::Request::builder()
+                .method("POST")
+                .uri("/api/login")
+                .header("content-type", "application/json")
+                .body(hyper::Body::from(body.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+        handler.handle(req).await
+    }
+
+    async fn handler() -> (LoginHandler, Arc<JwtAuth>) {
+        let users = MemoryUserStore::new();
+        users.add_user("alice", "s3cret", vec!["admin".to_string()]).await.unwrap();
+        let jwt = Arc::new(JwtAuth::new("test-secret"));
+        (LoginHandler::jwt(Arc::new(users), jwt.clone()), jwt)
+    }
+
+    #[tokio::test]
+    async fn jwt_login_issues_a_verifiable_token() {
+        let (handler, jwt) = handler().await;
+        let response = login(&handler, r#"{"username": "alice", "password": "s3cret"}"#).await.unwrap();
+
+        let token = response.data["access_token"].as_str().unwrap();
+        let claims = jwt.verify_token(token).await.unwrap();
+        assert_eq!(claims.sub, "alice");
+        assert_eq!(claims.roles, vec!["admin".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn wrong_password_and_unknown_user_are_rejected_alike() {
+        let (handler, _) = handler().await;
+        let wrong = login(&handler, r#"{"username": "alice", "password": "nope"}"#).await.unwrap_err();
+        let unknown = login(&handler, r#"{"username": "bob", "password": "s3cret"}"#).await.unwrap_err();
+
+        assert_eq!(wrong.status, hyper::StatusCode::UNAUTHORIZED);
+        assert_eq!(unknown.status, wrong.status);
+        assert_eq!(unknown.message, wrong.message);
+    }
+
+    #[tokio::test]
+    async fn locks_out_after_too_many_failures() {
+        let (handler, _) = handler().await;
+        let handler = handler.max_failed_attempts(2, Duration::from_secs(60));
+        for _ in 0..2 {
+            login(&handler, r#"{"username": "alice", "password": "nope"}"#).await.unwrap_err();
+        }
+
+        let locked = login(&handler, r#"{"username": "alice", "password": "s3cret"}"#).await.unwrap_err();
+        assert_eq!(locked.status, hyper::StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[tokio::test]
+    async fn failures_for_other_usernames_are_swept_after_the_window() {
+        let attempts = FailedAttempts::new(3, Duration::from_millis(50));
+        for i in 0..100 {
+            attempts.record_failure(&format!("user{}", i)).await;
+        }
+        assert_eq!(attempts.state.lock().await.counts.len(), 100);
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        attempts.record_failure("alice").await;
+        let state = attempts.state.lock().await;
+        assert_eq!(state.counts.len(), 1);
+        assert_eq!(state.counts["alice"].0, 1);
+    }
+}
@@ -0,0 +1,817 @@
+use crate::{Request, Response, Handler};
+use crate::config::{get_config, AuthConfig};
+use crate::middleware::Middleware; // Corrected import path for Middleware
+use async_trait::async_trait;
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+pub use jsonwebtoken::Algorithm;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+pub mod handlers;
+pub mod password;
+
+pub use handlers::{LoginHandler, LoginMode, LogoutHandler, MemoryUserStore, RefreshHandler, UserRecord, UserStore};
+pub use password::{
+    hash_password, hash_password_argon2, hash_password_with_cost, needs_rehash, verify_password,
+    PasswordAlgorithm, PasswordError,
+};
+
+pub const ACCESS_TOKEN: &str = "access";
+pub const REFRESH_TOKEN: &str = "refresh";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Claims {
+    pub sub: String,
+    pub exp: usize,
+    pub iat: usize,
+    pub roles: Vec<String>,
+    // Unique token id, used for revocation
+    #[serde(default)]
+    pub jti: String,
+    // Token type: "access" or "refresh"
+    #[serde(default = "default_token_type")]
+    pub typ: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aud: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+}
+
+fn default_token_type() -> String {
+    ACCESS_TOKEN.to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenPair {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub token_type: String,
+    // Access token lifetime in seconds
+    pub expires_in: i64,
+}
+
+#[derive(Debug)]
+pub enum AuthError {
+    Jwt(jsonwebtoken::errors::Error),
+    Revoked,
+    WrongTokenType { expected: String, found: String },
+    Store(String),
+    Key(String),
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthError::Jwt(e) => write!(f, "Invalid token: {}", e),
+            AuthError::Revoked => write!(f, "Token has been revoked"),
+            AuthError::WrongTokenType { expected, found } => {
+                write!(f, "Wrong token type: expected {} token, got {}", expected, found)
+            }
+            AuthError::Store(msg) => write!(f, "Token revocation store error: {}", msg),
+            AuthError::Key(msg) => write!(f, "JWT key error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
+
+impl From<jsonwebtoken::errors::Error> for AuthError {
+    fn from(err: jsonwebtoken::errors::Error) -> Self {
+        AuthError::Jwt(err)
+    }
+}
+
+// Keeps track of revoked token ids (`jti`) until the tokens would have expired anyway
+#[async_trait]
+pub trait TokenRevocationStore: Send + Sync {
+    // Revokes `jti` as a single check-and-set: true if this call revoked it, false if it already
+    // was. Refresh token rotation relies on only one caller ever getting true.
+    async fn revoke(&self, jti: &str, expires_at: usize) -> Result<bool, Box<dyn std::error::Error + Send + Sync>>;
+    async fn is_revoked(&self, jti: &str) -> Result<bool, Box<dyn std::error::Error + Send + Sync>>;
+}
+
+pub struct MemoryRevocationStore {
+    revoked: tokio::sync::RwLock<HashMap<String, usize>>,
+}
+
+impl MemoryRevocationStore {
+    pub fn new() -> Self {
+        MemoryRevocationStore {
+            revoked: tokio::sync::RwLock::new(HashMap::new()),
+        }
+    }
+}
+
+impl Default for MemoryRevocationStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl TokenRevocationStore for MemoryRevocationStore {
+    async fn revoke(&self, jti: &str, expires_at: usize) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        let now = chrono::Utc::now().timestamp() as usize;
+        let mut revoked = self.revoked.write().await;
+        // Drop entries whose tokens have expired on their own
+        revoked.retain(|_, exp| *exp > now);
+        Ok(revoked.insert(jti.to_string(), expires_at).is_none())
+    }
+
+    async fn is_revoked(&self, jti: &str) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(self.revoked.read().await.contains_key(jti))
+    }
+}
+
+#[cfg(feature = "cache")]
+pub struct RedisRevocationStore {
+    client: redis::Client,
+    prefix: String,
+}
+
+#[cfg(feature = "cache")]
+impl RedisRevocationStore {
+    pub fn new(redis_url: &str) -> Result<Self, redis::RedisError> {
+        Ok(RedisRevocationStore {
+            client: redis::Client::open(redis_url)?,
+            prefix: "rustnext:revoked:".to_string(),
+        })
+    }
+}
+
+#[cfg(feature = "cache")]
+#[async_trait]
+impl TokenRevocationStore for RedisRevocationStore {
+    // `SET NX`, so of two concurrent revocations only one sees the key created
+    async fn revoke(&self, jti: &str, expires_at: usize) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        let ttl = expires_at.saturating_sub(chrono::Utc::now().timestamp() as usize).max(1);
+        let mut conn = self.client.get_async_connection().await?;
+        let created: Option<String> = redis::cmd("SET")
+            .arg(format!("{}{}", self.prefix, jti))
+            .arg(1)
+            .arg("NX")
+            .arg("EX")
+            .arg(ttl)
+            .query_async(&mut conn)
+            .await?;
+        Ok(created.is_some())
+    }
+
+    async fn is_revoked(&self, jti: &str) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        use redis::AsyncCommands;
+        let mut conn = self.client.get_async_connection().await?;
+        Ok(conn.exists(format!("{}{}", self.prefix, jti)).await?)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum KeyFamily {
+    Hmac,
+    Rsa,
+    Ec,
+}
+
+fn key_family(algorithm: Algorithm) -> Result<KeyFamily, AuthError> {
+    match algorithm {
+        Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => Ok(KeyFamily::Hmac),
+        Algorithm::RS256 | Algorithm::RS384 | Algorithm::RS512
+        | Algorithm::PS256 | Algorithm::PS384 | Algorithm::PS512 => Ok(KeyFamily::Rsa),
+        Algorithm::ES256 | Algorithm::ES384 => Ok(KeyFamily::Ec),
+        other => Err(AuthError::Key(format!("unsupported JWT algorithm {:?}", other))),
+    }
+}
+
+// Parses names like "HS256" or "rs256" from config
+pub fn parse_algorithm(name: &str) -> Result<Algorithm, AuthError> {
+    let algorithm = name.trim().to_ascii_uppercase().parse::<Algorithm>()
+        .map_err(|_| AuthError::Key(format!("unknown JWT algorithm {:?}", name)))?;
+    key_family(algorithm)?;
+    Ok(algorithm)
+}
+
+fn read_key_file(path: &str, what: &str) -> Result<Vec<u8>, AuthError> {
+    std::fs::read(path).map_err(|e| AuthError::Key(format!("failed to read {} from {}: {}", what, path, e)))
+}
+
+pub struct JwtAuth {
+    algorithm: Algorithm,
+    // None for verify-only instances (public key without private key)
+    encoding_key: Option<EncodingKey>,
+    decoding_key: DecodingKey,
+    audience: Option<String>,
+    issuer: Option<String>,
+    leeway: u64,
+    access_ttl: chrono::Duration,
+    refresh_ttl: chrono::Duration,
+    revocation_store: Arc<dyn TokenRevocationStore>,
+}
+
+impl JwtAuth {
+    // HS256 with a shared secret; token lifetimes come from the global `[auth]` config
+    pub fn new(secret: &str) -> Self {
+        Self::with_keys(
+            Algorithm::HS256,
+            Some(EncodingKey::from_secret(secret.as_bytes())),
+            DecodingKey::from_secret(secret.as_bytes()),
+            &get_config().auth,
+        )
+    }
+
+    // Shared-secret constructor for HS256/HS384/HS512
+    pub fn hmac(secret: &str, algorithm: Algorithm) -> Result<Self, AuthError> {
+        if key_family(algorithm)? != KeyFamily::Hmac {
+            return Err(AuthError::Key(format!(
+                "{:?} is an asymmetric algorithm; use JwtAuth::rsa_pem/ec_pem instead of a shared secret",
+                algorithm
+            )));
+        }
+        Ok(Self::with_keys(
+            algorithm,
+            Some(EncodingKey::from_secret(secret.as_bytes())),
+            DecodingKey::from_secret(secret.as_bytes()),
+            &get_config().auth,
+        ))
+    }
+
+    // RS*/PS* keys in PEM format. Without a private key the instance can only verify tokens.
+    pub fn rsa_pem(algorithm: Algorithm, private_key: Option<&[u8]>, public_key: &[u8]) -> Result<Self, AuthError> {
+        Self::from_pem(algorithm, KeyFamily::Rsa, private_key, public_key)
+    }
+
+    // ES256/ES384 keys in PEM format. Without a private key the instance can only verify tokens.
+    pub fn ec_pem(algorithm: Algorithm, private_key: Option<&[u8]>, public_key: &[u8]) -> Result<Self, AuthError> {
+        Self::from_pem(algorithm, KeyFamily::Ec, private_key, public_key)
+    }
+
+    pub fn from_pem_files(algorithm: Algorithm, private_key_path: Option<&str>, public_key_path: &str) -> Result<Self, AuthError> {
+        let private_key = private_key_path
+            .map(|path| read_key_file(path, "private key"))
+            .transpose()?;
+        let public_key = read_key_file(public_key_path, "public key")?;
+
+        match key_family(algorithm)? {
+            KeyFamily::Rsa => Self::rsa_pem(algorithm, private_key.as_deref(), &public_key),
+            KeyFamily::Ec => Self::ec_pem(algorithm, private_key.as_deref(), &public_key),
+            KeyFamily::Hmac => Err(AuthError::Key(format!(
+                "{:?} uses a shared secret, not PEM key files",
+                algorithm
+            ))),
+        }
+    }
+
+    // Builds from `[auth]`: `jwt_algorithm` selects the algorithm, HMAC uses `jwt_secret`,
+    // RSA/EC load `jwt_public_key_path` and (optionally) `jwt_private_key_path`.
+    pub fn from_config(config: &AuthConfig) -> Result<Self, AuthError> {
+        let algorithm = parse_algorithm(&config.jwt_algorithm)?;
+
+        let auth = match key_family(algorithm)? {
+            KeyFamily::Hmac => Self::with_keys(
+                algorithm,
+                Some(EncodingKey::from_secret(config.jwt_secret.as_bytes())),
+                DecodingKey::from_secret(config.jwt_secret.as_bytes()),
+                config,
+            ),
+            _ => {
+                let public_key_path = config.jwt_public_key_path.as_deref().ok_or_else(|| {
+                    AuthError::Key(format!("auth.jwt_public_key_path is required for {:?}", algorithm))
+                })?;
+                let mut auth = Self::from_pem_files(algorithm, config.jwt_private_key_path.as_deref(), public_key_path)?;
+                auth.access_ttl = chrono::Duration::seconds(config.access_token_ttl as i64);
+                auth.refresh_ttl = chrono::Duration::seconds(config.refresh_token_ttl as i64);
+                auth
+            }
+        };
+        Ok(auth)
+    }
+
+    fn from_pem(algorithm: Algorithm, family: KeyFamily, private_key: Option<&[u8]>, public_key: &[u8]) -> Result<Self, AuthError> {
+        let actual = key_family(algorithm)?;
+        if actual != family {
+            return Err(AuthError::Key(format!(
+                "algorithm {:?} does not match the supplied {} key",
+                algorithm,
+                if family == KeyFamily::Rsa { "RSA" } else { "EC" }
+            )));
+        }
+
+        let kind = if family == KeyFamily::Rsa { "RSA" } else { "EC" };
+        let load_private = |pem: &[u8]| match family {
+            KeyFamily::Rsa => EncodingKey::from_rsa_pem(pem),
+            _ => EncodingKey::from_ec_pem(pem),
+        };
+        let load_public = |pem: &[u8]| match family {
+            KeyFamily::Rsa => DecodingKey::from_rsa_pem(pem),
+            _ => DecodingKey::from_ec_pem(pem),
+        };
+
+        let encoding_key = private_key
+            .map(|pem| load_private(pem).map_err(|e| {
+                AuthError::Key(format!("private key is not a valid {} PEM key for {:?}: {}", kind, algorithm, e))
+            }))
+            .transpose()?;
+        let decoding_key = load_public(public_key).map_err(|e| {
+            AuthError::Key(format!("public key is not a valid {} PEM key for {:?}: {}", kind, algorithm, e))
+        })?;
+
+        Ok(Self::with_keys(algorithm, encoding_key, decoding_key, &get_config().auth))
+    }
+
+    fn with_keys(algorithm: Algorithm, encoding_key: Option<EncodingKey>, decoding_key: DecodingKey, config: &AuthConfig) -> Self {
+        JwtAuth {
+            algorithm,
+            encoding_key,
+            decoding_key,
+            audience: None,
+            issuer: None,
+            leeway: 60,
+            access_ttl: chrono::Duration::seconds(config.access_token_ttl as i64),
+            refresh_ttl: chrono::Duration::seconds(config.refresh_token_ttl as i64),
+            revocation_store: Arc::new(MemoryRevocationStore::new()),
+        }
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    // Issued tokens carry `aud` and verification requires it to match
+    pub fn audience(mut self, audience: &str) -> Self {
+        self.audience = Some(audience.to_string());
+        self
+    }
+
+    // Issued tokens carry `iss` and verification requires it to match
+    pub fn issuer(mut self, issuer: &str) -> Self {
+        self.issuer = Some(issuer.to_string());
+        self
+    }
+
+    // Allowed clock skew in seconds when checking `exp`
+    pub fn leeway(mut self, seconds: u64) -> Self {
+        self.leeway = seconds;
+        self
+    }
+
+    // Override the lifetimes taken from `[auth]`
+    pub fn access_token_ttl(mut self, ttl: std::time::Duration) -> Self {
+        self.access_ttl = chrono::Duration::from_std(ttl).unwrap_or(self.access_ttl);
+        self
+    }
+
+    pub fn refresh_token_ttl(mut self, ttl: std::time::Duration) -> Self {
+        self.refresh_ttl = chrono::Duration::from_std(ttl).unwrap_or(self.refresh_ttl);
+        self
+    }
+
+    pub fn revocation_store(mut self, store: Arc<dyn TokenRevocationStore>) -> Self {
+        self.revocation_store = store;
+        self
+    }
+
+    pub fn generate_token(&self, user_id: &str, roles: Vec<String>) -> Result<String, AuthError> {
+        self.issue(user_id, roles, ACCESS_TOKEN, self.access_ttl)
+    }
+
+    // A standalone refresh token (`typ: "refresh"`, refresh TTL) without roles; access tokens
+    // minted from it by `refresh` carry no roles either. Use `generate_token_pair` to keep them.
+    pub fn generate_refresh_token(&self, user_id: &str) -> Result<String, AuthError> {
+        self.issue(user_id, Vec::new(), REFRESH_TOKEN, self.refresh_ttl)
+    }
+
+    pub fn generate_token_pair(&self, user_id: &str, roles: Vec<String>) -> Result<TokenPair, AuthError> {
+        Ok(TokenPair {
+            access_token: self.issue(user_id, roles.clone(), ACCESS_TOKEN, self.access_ttl)?,
+            refresh_token: self.issue(user_id, roles, REFRESH_TOKEN, self.refresh_ttl)?,
+            token_type: "Bearer".to_string(),
+            expires_in: self.access_ttl.num_seconds(),
+        })
+    }
+
+    fn issue(&self, user_id: &str, roles: Vec<String>, typ: &str, ttl: chrono::Duration) -> Result<String, AuthError> {
+        let key = self.encoding_key.as_ref().ok_or_else(|| {
+            AuthError::Key(format!("no private key configured for {:?}; this instance can only verify tokens", self.algorithm))
+        })?;
+
+        let now = chrono::Utc::now();
+        let exp = now + ttl;
+
+        let claims = Claims {
+            sub: user_id.to_string(),
+            exp: exp.timestamp() as usize,
+            iat: now.timestamp() as usize,
+            roles,
+            jti: uuid::Uuid::new_v4().to_string(),
+            typ: typ.to_string(),
+            aud: self.audience.clone(),
+            iss: self.issuer.clone(),
+        };
+
+        Ok(encode(&Header::new(self.algorithm), &claims, key)?)
+    }
+
+    // Verifies an access token, including its revocation status
+    pub async fn verify_token(&self, token: &str) -> Result<Claims, AuthError> {
+        self.verify_typed(token, ACCESS_TOKEN).await
+    }
+
+    pub async fn verify_refresh_token(&self, token: &str) -> Result<Claims, AuthError> {
+        self.verify_typed(token, REFRESH_TOKEN).await
+    }
+
+    async fn verify_typed(&self, token: &str, expected: &str) -> Result<Claims, AuthError> {
+        let claims = self.decode_claims(token)?;
+        if claims.typ != expected {
+            return Err(AuthError::WrongTokenType {
+                expected: expected.to_string(),
+                found: claims.typ,
+            });
+        }
+        if !claims.jti.is_empty() && self.is_revoked(&claims.jti).await? {
+            return Err(AuthError::Revoked);
+        }
+        Ok(claims)
+    }
+
+    // Only the configured algorithm is accepted, so a token can't pick its own (alg-swap)
+    fn decode_claims(&self, token: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
+        let mut validation = Validation::new(self.algorithm);
+        validation.leeway = self.leeway;
+        if let Some(audience) = &self.audience {
+            validation.set_audience(&[audience]);
+        }
+        if let Some(issuer) = &self.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+
+        decode::<Claims>(token, &self.decoding_key, &validation).map(|data| data.claims)
+    }
+
+    async fn is_revoked(&self, jti: &str) -> Result<bool, AuthError> {
+        self.revocation_store.is_revoked(jti).await.map_err(|e| AuthError::Store(e.to_string()))
+    }
+
+    // Exchanges a refresh token for a new pair; the old refresh token is revoked (rotation).
+    // Revoking is what claims the token, so of concurrent refreshes with the same token only
+    // one gets a pair, whatever the earlier revocation check saw.
+    pub async fn refresh(&self, refresh_token: &str) -> Result<TokenPair, AuthError> {
+        let claims = self.verify_refresh_token(refresh_token).await?;
+        if claims.jti.is_empty() {
+            return Err(AuthError::Store("refresh token has no jti and cannot be rotated".to_string()));
+        }
+        let claimed = self.revocation_store.revoke(&claims.jti, claims.exp).await
+            .map_err(|e| AuthError::Store(e.to_string()))?;
+        if !claimed {
+            return Err(AuthError::Revoked);
+        }
+        self.generate_token_pair(&claims.sub, claims.roles)
+    }
+
+    // Revokes any valid token (access or refresh) until its natural expiry
+    pub async fn revoke(&self, token: &str) -> Result<(), AuthError> {
+        let claims = self.decode_claims(token)?;
+        if claims.jti.is_empty() {
+            return Err(AuthError::Store("token has no jti and cannot be revoked".to_string()));
+        }
+        // Already revoked is fine too
+        self.revocation_store.revoke(&claims.jti, claims.exp).await
+            .map(|_| ())
+            .map_err(|e| AuthError::Store(e.to_string()))
+    }
+}
+
+pub struct AuthMiddleware {
+    jwt: Arc<JwtAuth>,
+    skip_paths: Vec<String>,
+}
+
+impl AuthMiddleware {
+    pub fn new(jwt: Arc<JwtAuth>) -> Self {
+        AuthMiddleware {
+            jwt,
+            skip_paths: vec!["/login".to_string(), "/register".to_string()],
+        }
+    }
+
+    pub fn skip_path(mut self, path: &str) -> Self {
+        self.skip_paths.push(path.to_string());
+        self
+    }
+}
+
+#[async_trait]
+impl Middleware for AuthMiddleware {
+    async fn handle(
+        &self,
+        mut req: Request,
+        next: Arc<dyn Handler>,
+    ) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+        // Skip authentication for certain paths
+        if self.skip_paths.contains(&req.uri.path().to_string()) {
+            return next.handle(req).await;
+        }
+
+        // Extract JWT token from Authorization header
+        let token = req.headers
+            .get("authorization")
+            .and_then(|auth| auth.to_str().ok())
+            .and_then(|auth| auth.strip_prefix("Bearer "));
+
+        if let Some(token) = token {
+            match self.jwt.verify_token(token).await {
+                Ok(claims) => {
+                    req.user_id = Some(claims.sub);
+                    req.user_roles = claims.roles;
+                    next.handle(req).await
+                }
+                Err(e) => rejected_token(&e),
+            }
+        } else {
+            Ok(Response::new()
+                .status(hyper::StatusCode::UNAUTHORIZED)
+                .json(&serde_json::json!({"error": "Missing token"}))?)
+        }
+    }
+}
+
+// The 401 for a bearer token that failed verification
+fn rejected_token(e: &AuthError) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+    let message = match e {
+        AuthError::Revoked => "Token has been revoked",
+        _ => "Invalid token",
+    };
+    Ok(Response::new()
+        .status(hyper::StatusCode::UNAUTHORIZED)
+        .json(&serde_json::json!({"error": message}))?)
+}
+
+// Like `AuthMiddleware`, but lets requests without a token through anonymously: a valid bearer
+// token sets `req.user_id` and `req.user_roles`, no token leaves them unset. A token that is sent
+// but fails verification (invalid, expired, revoked) is still rejected with 401.
+pub struct OptionalAuth {
+    jwt: Arc<JwtAuth>,
+}
+
+impl OptionalAuth {
+    pub fn new(jwt: Arc<JwtAuth>) -> Self {
+        OptionalAuth { jwt }
+    }
+}
+
+#[async_trait]
+impl Middleware for OptionalAuth {
+    async fn handle(
+        &self,
+        mut req: Request,
+        next: Arc<dyn Handler>,
+    ) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+        let token = req.headers
+            .get("authorization")
+            .and_then(|auth| auth.to_str().ok())
+            .and_then(|auth| auth.strip_prefix("Bearer "))
+            .map(|token| token.to_string());
+
+        if let Some(token) = token {
+            match self.jwt.verify_token(&token).await {
+                Ok(claims) => {
+                    req.user_id = Some(claims.sub);
+                    req.user_roles = claims.roles;
+                }
+                Err(e) => return rejected_token(&e),
+            }
+        }
+
+        next.handle(req).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roles() -> Vec<String> {
+        vec!["admin".to_string()]
+    }
+
+    #[tokio::test]
+    async fn refresh_rotates_and_invalidates_the_old_refresh_token() {
+        let jwt = JwtAuth::new("test-secret");
+        let pair = jwt.generate_token_pair("alice", roles()).unwrap();
+
+        let rotated = jwt.refresh(&pair.refresh_token).await.unwrap();
+        assert_ne!(rotated.refresh_token, pair.refresh_token);
+        assert_eq!(jwt.verify_token(&rotated.access_token).await.unwrap().roles, roles());
+
+        assert!(matches!(jwt.refresh(&pair.refresh_token).await, Err(AuthError::Revoked)));
+        assert!(jwt.refresh(&rotated.refresh_token).await.is_ok());
+    }
+
+    // Holds every revocation check until two are waiting, so both refreshes below pass the check
+    // before either revokes
+    struct RacingChecks {
+        inner: MemoryRevocationStore,
+        barrier: tokio::sync::Barrier,
+    }
+
+    #[async_trait]
+    impl TokenRevocationStore for RacingChecks {
+        async fn revoke(&self, jti: &str, expires_at: usize) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+            self.inner.revoke(jti, expires_at).await
+        }
+
+        async fn is_revoked(&self, jti: &str) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+            let revoked = self.inner.is_revoked(jti).await;
+            self.barrier.wait().await;
+            revoked
+        }
+    }
+
+    #[tokio::test]
+    async fn concurrent_refreshes_with_one_token_yield_one_pair() {
+        let store = RacingChecks { inner: MemoryRevocationStore::new(), barrier: tokio::sync::Barrier::new(2) };
+        let jwt = JwtAuth::new("test-secret").revocation_store(Arc::new(store));
+        let token = jwt.generate_token_pair("alice", roles()).unwrap().refresh_token;
+
+        let (first, second) = tokio::join!(jwt.refresh(&token), jwt.refresh(&token));
+        assert_eq!([&first, &second].iter().filter(|r| r.is_ok()).count(), 1);
+        assert!(matches!(first, Err(AuthError::Revoked)) || matches!(second, Err(AuthError::Revoked)));
+    }
+
+    #[tokio::test]
+    async fn memory_revocation_reports_whether_it_was_new() {
+        let store = MemoryRevocationStore::new();
+        let exp = chrono::Utc::now().timestamp() as usize + 60;
+        assert!(store.revoke("a", exp).await.unwrap());
+        assert!(!store.revoke("a", exp).await.unwrap());
+        assert!(store.is_revoked("a").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn access_and_refresh_tokens_are_not_interchangeable() {
+        let jwt = JwtAuth::new("test-secret");
+        let pair = jwt.generate_token_pair("alice", roles()).unwrap();
+
+        assert!(matches!(jwt.refresh(&pair.access_token).await, Err(AuthError::WrongTokenType { .. })));
+        assert!(matches!(jwt.verify_token(&pair.refresh_token).await, Err(AuthError::WrongTokenType { .. })));
+    }
+
+    #[tokio::test]
+    async fn auth_middleware_rejects_revoked_tokens_with_their_own_message() {
+        let jwt = Arc::new(JwtAuth::new("test-secret"));
+        let token = jwt.generate_token("alice", roles()).unwrap();
+        jwt.revoke(&token).await.unwrap();
+        assert!(matches!(jwt.verify_token(&token).await, Err(AuthError::Revoked)));
+
+        let req = Request::from_hyper(
+            hyper::Request::builder()
+                .uri("/account")
+                .header("authorization", format!("Bearer {}", token))
+                .body(hyper::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+        let next: Arc<dyn Handler> = Arc::new(|_req: Request| async {
+            Ok::<_, Box<dyn std::error::Error + Send + Sync>>(Response::new().text("secret"))
+        });
+
+        let response = AuthMiddleware::new(jwt).handle(req, next).await.unwrap();
+        assert_eq!(response.status, hyper::StatusCode::UNAUTHORIZED);
+        let body = hyper::body::to_bytes(response.body).await.unwrap();
+        assert!(String::from_utf8_lossy(&body).contains("revoked"));
+    }
+
+    // Answers with who the request was authenticated as, e.g. `alice admin` or `anonymous`
+    async fn through_optional_auth(jwt: Arc<JwtAuth>, token: Option<&str>) -> Response {
+        let mut builder = hyper::Request::builder().uri("/");
+        if let Some(token) = token {
+            builder = builder.header("authorization", format!("Bearer {}", token));
+        }
+        let req = Request::from_hyper(builder.body(hyper::Body::empty()).unwrap()).await.unwrap();
+        let next: Arc<dyn Handler> = Arc::new(|req: Request| async move {
+            let who = match &req.user_id {
+                Some(user) => format!("{} {}", user, req.user_roles.join(",")),
+                None => "anonymous".to_string(),
+            };
+            Ok::<_, Box<dyn std::error::Error + Send + Sync>>(Response::new().text(&who))
+        });
+        OptionalAuth::new(jwt).handle(req, next).await.unwrap()
+    }
+
+    async fn body_text(response: Response) -> String {
+        String::from_utf8(hyper::body::to_bytes(response.body).await.unwrap().to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn optional_auth_lets_requests_without_a_token_through() {
+        let jwt = Arc::new(JwtAuth::new("test-secret"));
+        let response = through_optional_auth(jwt, None).await;
+        assert_eq!(response.status, hyper::StatusCode::OK);
+        assert_eq!(body_text(response).await, "anonymous");
+    }
+
+    #[tokio::test]
+    async fn optional_auth_attaches_the_claims_of_a_valid_token() {
+        let jwt = Arc::new(JwtAuth::new("test-secret"));
+        let token = jwt.generate_token("alice", roles()).unwrap();
+        let response = through_optional_auth(jwt, Some(&token)).await;
+        assert_eq!(response.status, hyper::StatusCode::OK);
+        assert_eq!(body_text(response).await, "alice admin");
+    }
+
+    #[tokio::test]
+    async fn optional_auth_rejects_invalid_and_revoked_tokens() {
+        let jwt = Arc::new(JwtAuth::new("test-secret"));
+        let forged = JwtAuth::new("other-secret").generate_token("alice", roles()).unwrap();
+        let response = through_optional_auth(jwt.clone(), Some(&forged)).await;
+        assert_eq!(response.status, hyper::StatusCode::UNAUTHORIZED);
+        assert!(body_text(response).await.contains("Invalid token"));
+
+        let revoked = jwt.generate_token("alice", roles()).unwrap();
+        jwt.revoke(&revoked).await.unwrap();
+        let response = through_optional_auth(jwt, Some(&revoked)).await;
+        assert_eq!(response.status, hyper::StatusCode::UNAUTHORIZED);
+        assert!(body_text(response).await.contains("revoked"));
+    }
+
+    const RSA_PRIVATE: &[u8] = include_bytes!("../../tests/fixtures/jwt/rsa_private.pem");
+    const RSA_PUBLIC: &[u8] = include_bytes!("../../tests/fixtures/jwt/rsa_public.pem");
+    const EC_PRIVATE: &[u8] = include_bytes!("../../tests/fixtures/jwt/ec_private.pem");
+    const EC_PUBLIC: &[u8] = include_bytes!("../../tests/fixtures/jwt/ec_public.pem");
+
+    #[tokio::test]
+    async fn hs256_round_trip() {
+        let jwt = JwtAuth::hmac("test-secret", Algorithm::HS256).unwrap();
+        let token = jwt.generate_token("alice", roles()).unwrap();
+        assert_eq!(jwt.verify_token(&token).await.unwrap().sub, "alice");
+    }
+
+    #[tokio::test]
+    async fn rs256_round_trip_and_verify_only_instances() {
+        let signer = JwtAuth::rsa_pem(Algorithm::RS256, Some(RSA_PRIVATE), RSA_PUBLIC).unwrap();
+        let verifier = JwtAuth::rsa_pem(Algorithm::RS256, None, RSA_PUBLIC).unwrap();
+        let token = signer.generate_token("alice", roles()).unwrap();
+
+        assert_eq!(verifier.verify_token(&token).await.unwrap().sub, "alice");
+        assert!(matches!(verifier.generate_token("alice", roles()), Err(AuthError::Key(_))));
+    }
+
+    #[tokio::test]
+    async fn es256_round_trip() {
+        let jwt = JwtAuth::ec_pem(Algorithm::ES256, Some(EC_PRIVATE), EC_PUBLIC).unwrap();
+        let token = jwt.generate_token("alice", roles()).unwrap();
+        assert_eq!(jwt.verify_token(&token).await.unwrap().sub, "alice");
+    }
+
+    #[tokio::test]
+    async fn an_rs256_verifier_rejects_hs256_tokens() {
+        // Signed with the public key as the HMAC secret, the classic alg-swap
+        let forger = JwtAuth::hmac(std::str::from_utf8(RSA_PUBLIC).unwrap(), Algorithm::HS256).unwrap();
+        let forged = forger.generate_token("mallory", roles()).unwrap();
+
+        let verifier = JwtAuth::rsa_pem(Algorithm::RS256, None, RSA_PUBLIC).unwrap();
+        assert!(matches!(verifier.verify_token(&forged).await, Err(AuthError::Jwt(_))));
+    }
+
+    #[test]
+    fn mismatched_keys_and_algorithms_are_described() {
+        let error = JwtAuth::hmac("secret", Algorithm::RS256).err().unwrap();
+        assert!(error.to_string().contains("asymmetric"), "{}", error);
+
+        let error = JwtAuth::rsa_pem(Algorithm::ES256, None, EC_PUBLIC).err().unwrap();
+        assert!(error.to_string().contains("does not match"), "{}", error);
+
+        let error = JwtAuth::rsa_pem(Algorithm::RS256, None, EC_PUBLIC).err().unwrap();
+        assert!(error.to_string().contains("not a valid RSA PEM key"), "{}", error);
+
+        assert!(parse_algorithm("rs256").is_ok());
+        assert!(parse_algorithm("none").is_err());
+    }
+
+    #[tokio::test]
+    async fn audience_and_issuer_must_match() {
+        let issuer = JwtAuth::new("test-secret").audience("api").issuer("auth.example.com");
+        let token = issuer.generate_token("alice", roles()).unwrap();
+
+        assert!(issuer.verify_token(&token).await.is_ok());
+        let other_audience = JwtAuth::new("test-secret").audience("admin").issuer("auth.example.com");
+        assert!(other_audience.verify_token(&token).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn from_config_loads_pem_files() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/jwt");
+        let config = AuthConfig {
+            jwt_algorithm: "RS256".to_string(),
+            jwt_private_key_path: Some(format!("{}/rsa_private.pem", dir)),
+            jwt_public_key_path: Some(format!("{}/rsa_public.pem", dir)),
+            ..get_config().auth.clone()
+        };
+        let jwt = JwtAuth::from_config(&config).unwrap();
+        assert_eq!(jwt.algorithm(), Algorithm::RS256);
+
+        let token = jwt.generate_token("alice", roles()).unwrap();
+        assert!(jwt.verify_token(&token).await.is_ok());
+    }
+}
@@ -0,0 +1,175 @@
+// Password hashing. The algorithm and bcrypt cost come from `[auth]` in the global config;
+// verification picks the algorithm from the hash itself, so stored bcrypt hashes keep
+// working after switching to argon2 (and vice versa).
+use crate::config::get_config;
+use argon2::password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::{Argon2, Params};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasswordAlgorithm {
+    Bcrypt,
+    Argon2,
+}
+
+impl PasswordAlgorithm {
+    // Unknown names fall back to bcrypt (Config::validate reports them)
+    pub fn from_config() -> Self {
+        match get_config().auth.password_algorithm.trim().to_ascii_lowercase().as_str() {
+            "argon2" => PasswordAlgorithm::Argon2,
+            _ => PasswordAlgorithm::Bcrypt,
+        }
+    }
+
+    // Detects the algorithm from the PHC / modular crypt prefix of a stored hash
+    pub fn of_hash(hash: &str) -> Option<Self> {
+        if hash.starts_with("$argon2") {
+            Some(PasswordAlgorithm::Argon2)
+        } else if hash.starts_with("$2a$") || hash.starts_with("$2b$") || hash.starts_with("$2x$") || hash.starts_with("$2y$") {
+            Some(PasswordAlgorithm::Bcrypt)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum PasswordError {
+    Bcrypt(bcrypt::BcryptError),
+    Argon2(argon2::password_hash::Error),
+    UnknownFormat,
+}
+
+impl fmt::Display for PasswordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PasswordError::Bcrypt(e) => write!(f, "bcrypt error: {}", e),
+            PasswordError::Argon2(e) => write!(f, "argon2 error: {}", e),
+            PasswordError::UnknownFormat => write!(f, "unrecognized password hash format"),
+        }
+    }
+}
+
+impl std::error::Error for PasswordError {}
+
+impl From<bcrypt::BcryptError> for PasswordError {
+    fn from(err: bcrypt::BcryptError) -> Self {
+        PasswordError::Bcrypt(err)
+    }
+}
+
+impl From<argon2::password_hash::Error> for PasswordError {
+    fn from(err: argon2::password_hash::Error) -> Self {
+        PasswordError::Argon2(err)
+    }
+}
+
+// Hashes with the configured algorithm (`auth.password_algorithm`, `auth.bcrypt_cost`)
+pub fn hash_password(password: &str) -> Result<String, PasswordError> {
+    match PasswordAlgorithm::from_config() {
+        PasswordAlgorithm::Argon2 => hash_password_argon2(password),
+        PasswordAlgorithm::Bcrypt => hash_password_with_cost(password, get_config().auth.bcrypt_cost),
+    }
+}
+
+pub fn hash_password_with_cost(password: &str, cost: u32) -> Result<String, PasswordError> {
+    Ok(bcrypt::hash(password, cost)?)
+}
+
+// Argon2id with the crate defaults (19 MiB memory, 2 iterations, 1 lane)
+pub fn hash_password_argon2(password: &str) -> Result<String, PasswordError> {
+    let salt = SaltString::generate(&mut OsRng);
+    Ok(Argon2::default().hash_password(password.as_bytes(), &salt)?.to_string())
+}
+
+pub fn verify_password(password: &str, hash: &str) -> Result<bool, PasswordError> {
+    match PasswordAlgorithm::of_hash(hash) {
+        Some(PasswordAlgorithm::Bcrypt) => Ok(bcrypt::verify(password, hash)?),
+        Some(PasswordAlgorithm::Argon2) => {
+            let parsed = PasswordHash::new(hash)?;
+            match Argon2::default().verify_password(password.as_bytes(), &parsed) {
+                Ok(()) => Ok(true),
+                Err(argon2::password_hash::Error::Password) => Ok(false),
+                Err(e) => Err(e.into()),
+            }
+        }
+        None => Err(PasswordError::UnknownFormat),
+    }
+}
+
+// True when `hash` wasn't produced with the current settings: a different algorithm,
+// a different bcrypt cost, or non-default argon2 parameters. Check after a successful
+// login and store a fresh `hash_password` result if it returns true.
+pub fn needs_rehash(hash: &str) -> bool {
+    needs_rehash_for(hash, PasswordAlgorithm::from_config(), get_config().auth.bcrypt_cost)
+}
+
+fn needs_rehash_for(hash: &str, configured: PasswordAlgorithm, bcrypt_cost: u32) -> bool {
+    match PasswordAlgorithm::of_hash(hash) {
+        Some(algorithm) if algorithm != configured => true,
+        Some(PasswordAlgorithm::Bcrypt) => {
+            let cost = hash.split('$').nth(2).and_then(|c| c.parse::<u32>().ok());
+            cost != Some(bcrypt_cost)
+        }
+        Some(PasswordAlgorithm::Argon2) => {
+            let parsed = match PasswordHash::new(hash) {
+                Ok(parsed) => parsed,
+                Err(_) => return true,
+            };
+            let params = match Params::try_from(&parsed) {
+                Ok(params) => params,
+                Err(_) => return true,
+            };
+            let defaults = Params::default();
+            parsed.algorithm != argon2::Algorithm::Argon2id.ident()
+                || params.m_cost() != defaults.m_cost()
+                || params.t_cost() != defaults.t_cost()
+                || params.p_cost() != defaults.p_cost()
+        }
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The lowest cost bcrypt accepts, to keep the tests fast
+    const COST: u32 = 4;
+
+    #[test]
+    fn bcrypt_round_trip() {
+        let hash = hash_password_with_cost("hunter2", COST).unwrap();
+        assert_eq!(PasswordAlgorithm::of_hash(&hash), Some(PasswordAlgorithm::Bcrypt));
+        assert!(verify_password("hunter2", &hash).unwrap());
+        assert!(!verify_password("hunter3", &hash).unwrap());
+    }
+
+    #[test]
+    fn argon2_round_trip() {
+        let hash = hash_password_argon2("hunter2").unwrap();
+        assert!(hash.starts_with("$argon2id$"));
+        assert!(verify_password("hunter2", &hash).unwrap());
+        assert!(!verify_password("hunter3", &hash).unwrap());
+    }
+
+    #[test]
+    fn both_formats_verify_whatever_is_configured() {
+        let bcrypt = hash_password_with_cost("hunter2", COST).unwrap();
+        let argon2 = hash_password_argon2("hunter2").unwrap();
+        assert!(verify_password("hunter2", &bcrypt).unwrap() && verify_password("hunter2", &argon2).unwrap());
+        assert!(matches!(verify_password("hunter2", "plaintext"), Err(PasswordError::UnknownFormat)));
+    }
+
+    #[test]
+    fn bcrypt_hashes_need_a_rehash_when_argon2_is_configured() {
+        let bcrypt = hash_password_with_cost("hunter2", COST).unwrap();
+        let argon2 = hash_password_argon2("hunter2").unwrap();
+
+        assert!(needs_rehash_for(&bcrypt, PasswordAlgorithm::Argon2, COST));
+        assert!(!needs_rehash_for(&argon2, PasswordAlgorithm::Argon2, COST));
+        assert!(!needs_rehash_for(&bcrypt, PasswordAlgorithm::Bcrypt, COST));
+        assert!(needs_rehash_for(&bcrypt, PasswordAlgorithm::Bcrypt, COST + 1));
+        assert!(needs_rehash_for(&argon2, PasswordAlgorithm::Bcrypt, COST));
+    }
+}
@@ -1,26 +1,155 @@
 #[cfg(feature = "cache")] // Conditional compilation
 use redis::{AsyncCommands, Client};
-use std::time::Duration;
+use crate::middleware::Middleware;
+use crate::{Handler, Request, Response};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use once_cell::sync::OnceCell;
 use log::{info, warn}; // New import for logging
+use tokio::sync::RwLock;
+
+// In-process backend: JSON-serialized values with an expiry, so it behaves like Redis
+pub struct MemoryCache {
+    entries: RwLock<HashMap<String, (String, Instant)>>,
+    // tag -> keys stored with that tag
+    tags: RwLock<HashMap<String, HashSet<String>>>,
+    inflight: Inflight,
+    // Expired entries nobody reads again are dropped by a sweep on write, at most once per interval
+    last_sweep: Mutex<Instant>,
+    sweep_interval: Duration,
+}
+
+impl MemoryCache {
+    pub fn new() -> Self {
+        MemoryCache {
+            entries: RwLock::new(HashMap::new()),
+            tags: RwLock::new(HashMap::new()),
+            inflight: Inflight::default(),
+            last_sweep: Mutex::new(Instant::now()),
+            sweep_interval: Duration::from_secs(60),
+        }
+    }
+
+    pub async fn get<T: for<'de> serde::Deserialize<'de>>(&self, key: &str) -> Result<Option<T>, Box<dyn std::error::Error + Send + Sync>> {
+        let now = Instant::now();
+        {
+            let entries = self.entries.read().await;
+            match entries.get(key) {
+                Some((value, expires_at)) if *expires_at > now => return Ok(Some(serde_json::from_str(value)?)),
+                Some(_) => {}
+                None => return Ok(None),
+            }
+        }
+
+        // Expired: drop it so the map doesn't grow with dead entries
+        let mut entries = self.entries.write().await;
+        if entries.get(key).is_some_and(|(_, expires_at)| *expires_at <= now) {
+            entries.remove(key);
+        }
+        Ok(None)
+    }
+
+    pub async fn set<T: serde::Serialize>(&self, key: &str, value: &T, ttl: Duration) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let serialized = serde_json::to_string(value)?;
+        let now = Instant::now();
+        let mut entries = self.entries.write().await;
+        if self.sweep_due(now) {
+            entries.retain(|_, (_, expires_at)| *expires_at > now);
+        }
+        entries.insert(key.to_string(), (serialized, now + ttl));
+        Ok(())
+    }
+
+    pub async fn delete(&self, key: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.entries.write().await.remove(key);
+        Ok(())
+    }
+
+    pub async fn increment(&self, key: &str, ttl: Duration) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        let now = Instant::now();
+        let mut entries = self.entries.write().await;
+        let (count, expires_at) = match entries.get(key) {
+            Some((value, expires_at)) if *expires_at > now => (serde_json::from_str::<u64>(value)? + 1, *expires_at),
+            _ => (1, now + ttl),
+        };
+        entries.insert(key.to_string(), (count.to_string(), expires_at));
+        Ok(count)
+    }
+
+    pub async fn increment_and_get(&self, key: &str, ttl: Duration, other: &str) -> Result<(u64, Option<u64>), Box<dyn std::error::Error + Send + Sync>> {
+        let count = self.increment(key, ttl).await?;
+        Ok((count, self.get(other).await?))
+    }
+
+    pub async fn ttl(&self, key: &str) -> Result<Option<Duration>, Box<dyn std::error::Error + Send + Sync>> {
+        let now = Instant::now();
+        Ok(self.entries.read().await
+            .get(key)
+            .and_then(|(_, expires_at)| expires_at.checked_duration_since(now))
+            .filter(|remaining| !remaining.is_zero()))
+    }
+
+    pub async fn set_tagged<T: serde::Serialize>(&self, key: &str, value: &T, ttl: Duration, tags: &[&str]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.set(key, value, ttl).await?;
+        let entries = self.entries.read().await;
+        let mut index = self.tags.write().await;
+        for tag in tags {
+            let keys = index.entry(tag.to_string()).or_default();
+            // Forget keys that expired since they were tagged
+            keys.retain(|k| entries.contains_key(k));
+            keys.insert(key.to_string());
+        }
+        Ok(())
+    }
+
+    fn sweep_due(&self, now: Instant) -> bool {
+        let mut last_sweep = self.last_sweep.lock().unwrap_or_else(|e| e.into_inner());
+        if now.duration_since(*last_sweep) < self.sweep_interval {
+            return false;
+        }
+        *last_sweep = now;
+        true
+    }
+
+    // Deletes every key stored with `tag`, returning how many were removed
+    pub async fn invalidate_tag(&self, tag: &str) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+        let keys = match self.tags.write().await.remove(tag) {
+            Some(keys) => keys,
+            None => return Ok(0),
+        };
+        let mut entries = self.entries.write().await;
+        Ok(keys.iter().filter(|key| entries.remove(*key).is_some()).count())
+    }
+}
+
+impl Default for MemoryCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 #[cfg(feature = "cache")]
 #[derive(Clone)]
-pub struct Cache {
+pub struct RedisCache {
     client: Client,
+    inflight: Inflight,
 }
 
 #[cfg(feature = "cache")]
-impl Cache {
+impl RedisCache {
     pub async fn new(redis_url: &str) -> Result<Self, redis::RedisError> {
         let client = Client::open(redis_url)?;
-        Ok(Cache { client })
+        Ok(RedisCache { client, inflight: Inflight::default() })
     }
 
     pub async fn get<T: for<'de> serde::Deserialize<'de>>(&self, key: &str) -> Result<Option<T>, Box<dyn std::error::Error + Send + Sync>> {
         let mut conn = self.client.get_async_connection().await?;
         let value: Option<String> = conn.get(key).await?;
-        
+
         match value {
             Some(v) => Ok(Some(serde_json::from_str(&v)?)),
             None => Ok(None),
@@ -30,7 +159,7 @@ impl Cache {
     pub async fn set<T: serde::Serialize>(&self, key: &str, value: &T, ttl: Duration) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         let mut conn = self.client.get_async_connection().await?;
         let serialized = serde_json::to_string(value)?;
-        conn.set_ex::<_, _, ()>(key, serialized, ttl.as_secs().try_into().unwrap()).await?;
+        conn.set_ex::<_, _, ()>(key, serialized, ttl.as_secs().try_into()?).await?;
         Ok(())
     }
 
@@ -39,42 +168,824 @@ impl Cache {
         conn.del::<_, ()>(key).await?;
         Ok(())
     }
+
+    // INCR and the first EXPIRE in one script, so no key is left without an expiry
+    pub async fn increment(&self, key: &str, ttl: Duration) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        let mut conn = self.client.get_async_connection().await?;
+        let script = redis::Script::new(
+            "local count = redis.call('INCR', KEYS[1]) \
+             if count == 1 then redis.call('PEXPIRE', KEYS[1], ARGV[1]) end \
+             return count",
+        );
+        let count: u64 = script.key(key).arg(ttl.as_millis().max(1) as u64).invoke_async(&mut conn).await?;
+        Ok(count)
+    }
+
+    // `increment`, plus a GET of `other`, in the same script: one connection and round trip
+    pub async fn increment_and_get(&self, key: &str, ttl: Duration, other: &str) -> Result<(u64, Option<u64>), Box<dyn std::error::Error + Send + Sync>> {
+        let mut conn = self.client.get_async_connection().await?;
+        let script = redis::Script::new(
+            "local count = redis.call('INCR', KEYS[1]) \
+             if count == 1 then redis.call('PEXPIRE', KEYS[1], ARGV[1]) end \
+             return {count, redis.call('GET', KEYS[2])}",
+        );
+        let (count, other): (u64, Option<String>) = script
+            .key(key)
+            .key(other)
+            .arg(ttl.as_millis().max(1) as u64)
+            .invoke_async(&mut conn)
+            .await?;
+        Ok((count, other.map(|v| serde_json::from_str(&v)).transpose()?))
+    }
+
+    // `None` when the key is missing or has no expiry
+    pub async fn ttl(&self, key: &str) -> Result<Option<Duration>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut conn = self.client.get_async_connection().await?;
+        let seconds: i64 = conn.ttl(key).await?;
+        Ok(if seconds > 0 { Some(Duration::from_secs(seconds as u64)) } else { None })
+    }
+
+    // Tag membership lives in a Redis set per tag, kept alive at least as long as its longest entry
+    pub async fn set_tagged<T: serde::Serialize>(&self, key: &str, value: &T, ttl: Duration, tags: &[&str]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.set(key, value, ttl).await?;
+        let mut conn = self.client.get_async_connection().await?;
+        let ttl_secs = ttl.as_secs() as i64;
+        for tag in tags {
+            let tag_key = Self::tag_key(tag);
+            conn.sadd::<_, _, ()>(&tag_key, key).await?;
+            let current: i64 = conn.ttl(&tag_key).await?;
+            if current < ttl_secs {
+                conn.expire::<_, ()>(&tag_key, ttl_secs.try_into()?).await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn invalidate_tag(&self, tag: &str) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+        let mut conn = self.client.get_async_connection().await?;
+        let tag_key = Self::tag_key(tag);
+        let keys: Vec<String> = conn.smembers(&tag_key).await?;
+        let removed: usize = if keys.is_empty() { 0 } else { conn.del(&keys).await? };
+        conn.del::<_, ()>(&tag_key).await?;
+        Ok(removed)
+    }
+
+    fn tag_key(tag: &str) -> String {
+        format!("rustnext:tag:{}", tag)
+    }
+}
+
+// The in-memory backend is always available; Redis is the optional distributed one
+pub enum Cache {
+    Memory(MemoryCache),
+    #[cfg(feature = "cache")]
+    Redis(RedisCache),
+}
+
+impl Cache {
+    pub fn memory() -> Self {
+        Cache::Memory(MemoryCache::new())
+    }
+
+    #[cfg(feature = "cache")]
+    pub async fn redis(redis_url: &str) -> Result<Self, redis::RedisError> {
+        Ok(Cache::Redis(RedisCache::new(redis_url).await?))
+    }
+
+    pub async fn get<T: for<'de> serde::Deserialize<'de>>(&self, key: &str) -> Result<Option<T>, Box<dyn std::error::Error + Send + Sync>> {
+        match self {
+            Cache::Memory(cache) => cache.get(key).await,
+            #[cfg(feature = "cache")]
+            Cache::Redis(cache) => cache.get(key).await,
+        }
+    }
+
+    pub async fn set<T: serde::Serialize>(&self, key: &str, value: &T, ttl: Duration) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        match self {
+            Cache::Memory(cache) => cache.set(key, value, ttl).await,
+            #[cfg(feature = "cache")]
+            Cache::Redis(cache) => cache.set(key, value, ttl).await,
+        }
+    }
+
+    pub async fn delete(&self, key: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        match self {
+            Cache::Memory(cache) => cache.delete(key).await,
+            #[cfg(feature = "cache")]
+            Cache::Redis(cache) => cache.delete(key).await,
+        }
+    }
+
+    // Atomically adds 1 to the counter at `key` and returns the new count. A missing (or expired)
+    // key starts at 1 and expires `ttl` later; later increments keep that expiry. The count is
+    // stored as a number, so `get::<u64>` reads it too.
+    pub async fn increment(&self, key: &str, ttl: Duration) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        match self {
+            Cache::Memory(cache) => cache.increment(key, ttl).await,
+            #[cfg(feature = "cache")]
+            Cache::Redis(cache) => cache.increment(key, ttl).await,
+        }
+    }
+
+    // `increment(key, ttl)` together with `get::<u64>(other)`, e.g. a counter and its
+    // neighbour. On Redis this takes a single round trip instead of two.
+    pub async fn increment_and_get(&self, key: &str, ttl: Duration, other: &str) -> Result<(u64, Option<u64>), Box<dyn std::error::Error + Send + Sync>> {
+        match self {
+            Cache::Memory(cache) => cache.increment_and_get(key, ttl, other).await,
+            #[cfg(feature = "cache")]
+            Cache::Redis(cache) => cache.increment_and_get(key, ttl, other).await,
+        }
+    }
+
+    // Remaining lifetime of `key`, or `None` if it doesn't exist
+    pub async fn ttl(&self, key: &str) -> Result<Option<Duration>, Box<dyn std::error::Error + Send + Sync>> {
+        match self {
+            Cache::Memory(cache) => cache.ttl(key).await,
+            #[cfg(feature = "cache")]
+            Cache::Redis(cache) => cache.ttl(key).await,
+        }
+    }
+
+    // Like `set`, but also records the key under each tag for `invalidate_tag`, e.g.
+    // `cache.set_tagged("product:42", &product, ttl, &["products"])`
+    pub async fn set_tagged<T: serde::Serialize>(&self, key: &str, value: &T, ttl: Duration, tags: &[&str]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        match self {
+            Cache::Memory(cache) => cache.set_tagged(key, value, ttl, tags).await,
+            #[cfg(feature = "cache")]
+            Cache::Redis(cache) => cache.set_tagged(key, value, ttl, tags).await,
+        }
+    }
+
+    // Deletes all keys stored with `tag`; returns the number of keys removed
+    pub async fn invalidate_tag(&self, tag: &str) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+        match self {
+            Cache::Memory(cache) => cache.invalidate_tag(tag).await,
+            #[cfg(feature = "cache")]
+            Cache::Redis(cache) => cache.invalidate_tag(tag).await,
+        }
+    }
+
+    // Read-through caching: returns the cached value, or runs `compute`, stores its result for
+    // `ttl` and returns it. Concurrent callers for the same key in this process wait for the first
+    // computation instead of all running it; a failed computation isn't cached, so the next
+    // waiter tries again.
+    // `cache.get_or_set("product:42", ttl, || async { load_product(42).await }).await?`
+    pub async fn get_or_set<T, F, Fut>(&self, key: &str, ttl: Duration, compute: F) -> Result<T, Box<dyn std::error::Error + Send + Sync>>
+    where
+        T: Serialize + for<'de> Deserialize<'de>,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, Box<dyn std::error::Error + Send + Sync>>>,
+    {
+        if let Some(value) = self.get(key).await? {
+            return Ok(value);
+        }
+
+        let flight = self.inflight().join(key);
+        let _turn = flight.lock.lock().await;
+        // Filled by whoever went first
+        if let Some(value) = self.get(key).await? {
+            return Ok(value);
+        }
+        let value = compute().await?;
+        self.set(key, &value, ttl).await?;
+        Ok(value)
+    }
+
+    // A view that prefixes every key (and tag) with `prefix`, e.g.
+    // `get_cache().with_prefix("products:").invalidate_tag("all")`
+    pub fn with_prefix(&self, prefix: &str) -> CacheNamespace<'_> {
+        CacheNamespace {
+            cache: self,
+            prefix: prefix.to_string(),
+        }
+    }
+
+    fn inflight(&self) -> &Inflight {
+        match self {
+            Cache::Memory(cache) => &cache.inflight,
+            #[cfg(feature = "cache")]
+            Cache::Redis(cache) => &cache.inflight,
+        }
+    }
+}
+
+// Keys with a `get_or_set` computation under way, each with a lock callers queue on
+#[derive(Clone, Default)]
+struct Inflight {
+    keys: Arc<Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>>,
+}
+
+impl Inflight {
+    fn join(&self, key: &str) -> Flight {
+        let mut keys = self.keys.lock().unwrap_or_else(|e| e.into_inner());
+        let lock = keys.entry(key.to_string()).or_default().clone();
+        Flight {
+            inflight: self.clone(),
+            key: key.to_string(),
+            lock,
+        }
+    }
+}
+
+struct Flight {
+    inflight: Inflight,
+    key: String,
+    lock: Arc<tokio::sync::Mutex<()>>,
+}
+
+// The last caller out removes the key's entry
+impl Drop for Flight {
+    fn drop(&mut self) {
+        let mut keys = self.inflight.keys.lock().unwrap_or_else(|e| e.into_inner());
+        // Held by the map and by us only
+        if Arc::strong_count(&self.lock) == 2 {
+            keys.remove(&self.key);
+        }
+    }
+}
+
+// See `Cache::with_prefix`
+pub struct CacheNamespace<'a> {
+    cache: &'a Cache,
+    prefix: String,
+}
+
+impl CacheNamespace<'_> {
+    // The full key stored in the underlying cache
+    pub fn key(&self, key: &str) -> String {
+        format!("{}{}", self.prefix, key)
+    }
+
+    pub async fn get<T: for<'de> Deserialize<'de>>(&self, key: &str) -> Result<Option<T>, Box<dyn std::error::Error + Send + Sync>> {
+        self.cache.get(&self.key(key)).await
+    }
+
+    pub async fn set<T: Serialize>(&self, key: &str, value: &T, ttl: Duration) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.cache.set(&self.key(key), value, ttl).await
+    }
+
+    pub async fn delete(&self, key: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.cache.delete(&self.key(key)).await
+    }
+
+    pub async fn increment(&self, key: &str, ttl: Duration) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        self.cache.increment(&self.key(key), ttl).await
+    }
+
+    pub async fn increment_and_get(&self, key: &str, ttl: Duration, other: &str) -> Result<(u64, Option<u64>), Box<dyn std::error::Error + Send + Sync>> {
+        self.cache.increment_and_get(&self.key(key), ttl, &self.key(other)).await
+    }
+
+    pub async fn ttl(&self, key: &str) -> Result<Option<Duration>, Box<dyn std::error::Error + Send + Sync>> {
+        self.cache.ttl(&self.key(key)).await
+    }
+
+    pub async fn set_tagged<T: Serialize>(&self, key: &str, value: &T, ttl: Duration, tags: &[&str]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let tags: Vec<String> = tags.iter().map(|tag| self.key(tag)).collect();
+        let tags: Vec<&str> = tags.iter().map(String::as_str).collect();
+        self.cache.set_tagged(&self.key(key), value, ttl, &tags).await
+    }
+
+    pub async fn invalidate_tag(&self, tag: &str) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+        self.cache.invalidate_tag(&self.key(tag)).await
+    }
+
+    pub async fn get_or_set<T, F, Fut>(&self, key: &str, ttl: Duration, compute: F) -> Result<T, Box<dyn std::error::Error + Send + Sync>>
+    where
+        T: Serialize + for<'de> Deserialize<'de>,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, Box<dyn std::error::Error + Send + Sync>>>,
+    {
+        self.cache.get_or_set(&self.key(key), ttl, compute).await
+    }
 }
 
-#[cfg(feature = "cache")]
 static GLOBAL_CACHE: OnceCell<Cache> = OnceCell::new();
 
+// Must run before the first `get_cache()`, which otherwise settles on the in-memory backend
 #[cfg(feature = "cache")]
 pub async fn init_cache(redis_url: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let cache = Cache::new(redis_url).await?;
+    let cache = Cache::redis(redis_url).await?;
     if GLOBAL_CACHE.set(cache).is_err() {
         warn!("Cache already initialized, ignoring new initialization.");
     } else {
-        info!("Cache client initialized.");
+        info!("Redis cache client initialized.");
     }
     Ok(())
 }
 
-#[cfg(feature = "cache")]
-pub fn get_cache() -> Option<&'static Cache> {
-    GLOBAL_CACHE.get()
+#[cfg(not(feature = "cache"))]
+pub async fn init_cache(_redis_url: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    warn!("Attempted to initialize Redis cache, but 'cache' feature is not enabled; using the in-memory cache.");
+    Ok(())
 }
 
-// Dummy implementations if cache feature is not enabled
-#[cfg(not(feature = "cache"))]
-pub struct Cache;
-#[cfg(not(feature = "cache"))]
-impl Cache {
-    pub async fn new(_redis_url: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
-        Err("Cache feature not enabled".into())
+pub fn get_cache() -> &'static Cache {
+    GLOBAL_CACHE.get_or_init(|| {
+        info!("Using in-memory cache.");
+        Cache::memory()
+    })
+}
+
+// A response as stored by `CacheMiddleware`
+#[derive(Clone, Serialize, Deserialize)]
+struct CachedResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: CachedBody,
+}
+
+// Text stays readable in Redis; anything else is stored as a byte array
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+enum CachedBody {
+    Text(String),
+    Bytes(Vec<u8>),
+}
+
+impl CachedResponse {
+    fn size(&self) -> usize {
+        let body = match &self.body {
+            CachedBody::Text(text) => text.len(),
+            CachedBody::Bytes(bytes) => bytes.len(),
+        };
+        body + self.headers.iter().map(|(k, v)| k.len() + v.len()).sum::<usize>()
+    }
+
+    fn to_response(&self) -> Response {
+        let body = match &self.body {
+            CachedBody::Text(text) => hyper::Body::from(text.clone()),
+            CachedBody::Bytes(bytes) => hyper::Body::from(bytes.clone()),
+        };
+        let status = hyper::StatusCode::from_u16(self.status).unwrap_or(hyper::StatusCode::OK);
+        self.headers.iter()
+            .fold(Response::new().status(status), |response, (key, value)| response.append_header(key, value))
+            .body(body)
     }
 }
-#[cfg(not(feature = "cache"))]
-pub async fn init_cache(_redis_url: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    log::warn!("Attempted to initialize cache, but 'cache' feature is not enabled.");
-    Ok(())
+
+struct LruEntry {
+    response: Arc<CachedResponse>,
+    expires_at: Instant,
+    // Position in `LruStore::order`
+    tick: u64,
 }
-#[cfg(not(feature = "cache"))]
-pub fn get_cache() -> Option<&'static Cache> {
-    None
+
+// Least-recently-used eviction once either bound is exceeded
+struct LruStore {
+    entries: HashMap<String, LruEntry>,
+    // tick -> key, oldest first
+    order: BTreeMap<u64, String>,
+    next_tick: u64,
+    bytes: usize,
+    max_entries: usize,
+    max_bytes: usize,
+}
+
+impl LruStore {
+    fn get(&mut self, key: &str) -> Option<Arc<CachedResponse>> {
+        let tick = self.next_tick;
+        let entry = self.entries.get_mut(key)?;
+        if entry.expires_at <= Instant::now() {
+            self.remove(key);
+            return None;
+        }
+        self.order.remove(&entry.tick);
+        entry.tick = tick;
+        self.order.insert(tick, key.to_string());
+        self.next_tick += 1;
+        Some(entry.response.clone())
+    }
+
+    fn insert(&mut self, key: &str, response: CachedResponse, ttl: Duration) {
+        let size = response.size();
+        if size > self.max_bytes || self.max_entries == 0 {
+            return;
+        }
+        self.remove(key);
+        while self.entries.len() >= self.max_entries || self.bytes + size > self.max_bytes {
+            let Some((_, oldest)) = self.order.pop_first() else { break };
+            if let Some(entry) = self.entries.remove(&oldest) {
+                self.bytes -= entry.response.size();
+            }
+        }
+        let tick = self.next_tick;
+        self.next_tick += 1;
+        self.order.insert(tick, key.to_string());
+        self.bytes += size;
+        self.entries.insert(key.to_string(), LruEntry {
+            response: Arc::new(response),
+            expires_at: Instant::now() + ttl,
+            tick,
+        });
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.order.remove(&entry.tick);
+            self.bytes -= entry.response.size();
+        }
+    }
+}
+
+enum ResponseStore {
+    Memory(Mutex<LruStore>),
+    #[cfg(feature = "cache")]
+    Redis(RedisCache),
+}
+
+// Caches successful GET responses whole and replays them without calling the handler, marked
+// `X-Cache: HIT` (fresh ones get `X-Cache: MISS`). Keyed by path, sorted query string and
+// `Accept`. Only the paths given to `route` are cached unless `default_ttl` says otherwise, e.g.
+// `App::new().middleware(CacheMiddleware::memory(1000, 32 * 1024 * 1024).route("/blog", ttl))`.
+//
+// Only 200 responses with a cacheable content type are stored, and never ones that set a
+// cookie, carry `Vary`, or say `Cache-Control: private` / `no-store`. Requests with an
+// `Authorization` or `Cookie` header skip the cache (see `with_cookies`), and
+// `Cache-Control: no-cache` on a request refetches and refreshes the entry.
+pub struct CacheMiddleware {
+    store: ResponseStore,
+    default_ttl: Duration,
+    // Path prefix -> TTL, longest prefix wins
+    routes: Vec<(String, Duration)>,
+    content_types: Vec<String>,
+    cookies: bool,
+}
+
+impl CacheMiddleware {
+    // In-process LRU holding at most `max_entries` responses and `max_bytes` in total
+    pub fn memory(max_entries: usize, max_bytes: usize) -> Self {
+        Self::with_store(ResponseStore::Memory(Mutex::new(LruStore {
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            next_tick: 0,
+            bytes: 0,
+            max_entries,
+            max_bytes,
+        })))
+    }
+
+    // Shared between instances; Redis expiry replaces LRU eviction
+    #[cfg(feature = "cache")]
+    pub fn redis(cache: RedisCache) -> Self {
+        Self::with_store(ResponseStore::Redis(cache))
+    }
+
+    fn with_store(store: ResponseStore) -> Self {
+        CacheMiddleware {
+            store,
+            default_ttl: Duration::ZERO,
+            routes: Vec::new(),
+            content_types: vec!["text/html".to_string(), "application/json".to_string()],
+            cookies: false,
+        }
+    }
+
+    // TTL for paths without a `route` entry; the default, zero, caches only the listed routes
+    pub fn default_ttl(mut self, ttl: Duration) -> Self {
+        self.default_ttl = ttl;
+        self
+    }
+
+    // TTL for `prefix` and everything below it (`/blog` covers `/blog/post-1`, not `/blogs`).
+    // A zero TTL turns caching off for those paths.
+    pub fn route(mut self, prefix: &str, ttl: Duration) -> Self {
+        self.routes.push((prefix.trim_end_matches('/').to_string(), ttl));
+        self
+    }
+
+    // Replaces the cacheable content types (default `text/html` and `application/json`)
+    pub fn content_types(mut self, content_types: &[&str]) -> Self {
+        self.content_types = content_types.iter().map(|ct| ct.to_ascii_lowercase()).collect();
+        self
+    }
+
+    // Also serves requests that carry cookies from the cache. Only for routes whose responses
+    // don't depend on them: one user's session page would be replayed to everyone else.
+    pub fn with_cookies(mut self, cookies: bool) -> Self {
+        self.cookies = cookies;
+        self
+    }
+
+    fn ttl_for(&self, path: &str) -> Duration {
+        self.routes.iter()
+            .filter(|(prefix, _)| {
+                path.strip_prefix(prefix.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/') || prefix.is_empty())
+            })
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(self.default_ttl, |(_, ttl)| *ttl)
+    }
+
+    fn is_cacheable(&self, response: &Response) -> bool {
+        let header = |name: &str| {
+            response.headers.get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_ascii_lowercase)
+        };
+        let content_type = header("content-type").unwrap_or_default();
+        let content_type = content_type.split(';').next().unwrap_or("").trim();
+        let private = header("cache-control")
+            .is_some_and(|cc| cc.contains("private") || cc.contains("no-store"));
+
+        response.status == hyper::StatusCode::OK
+            && self.content_types.iter().any(|allowed| allowed == content_type)
+            && header("set-cookie").is_none()
+            && header("vary").is_none()
+            && !private
+    }
+
+    async fn lookup(&self, key: &str) -> Option<Arc<CachedResponse>> {
+        match &self.store {
+            ResponseStore::Memory(store) => store.lock().ok()?.get(key),
+            #[cfg(feature = "cache")]
+            ResponseStore::Redis(cache) => match cache.get::<CachedResponse>(key).await {
+                Ok(cached) => cached.map(Arc::new),
+                Err(e) => {
+                    warn!("Response cache lookup failed: {}", e);
+                    None
+                }
+            },
+        }
+    }
+
+    async fn store(&self, key: &str, response: CachedResponse, ttl: Duration) {
+        match &self.store {
+            ResponseStore::Memory(store) => {
+                if let Ok(mut store) = store.lock() {
+                    store.insert(key, response, ttl);
+                }
+            }
+            #[cfg(feature = "cache")]
+            ResponseStore::Redis(cache) => {
+                if let Err(e) = cache.set(key, &response, ttl).await {
+                    warn!("Response cache store failed: {}", e);
+                }
+            }
+        }
+    }
+}
+
+// `GET /blog?b=2&a=1` and `GET /blog?a=1&b=2` share an entry; different `Accept` headers don't,
+// since API responses are negotiated on it
+fn response_cache_key(req: &Request) -> String {
+    let mut pairs: Vec<&str> = req.uri.query()
+        .map(|query| query.split('&').filter(|pair| !pair.is_empty()).collect())
+        .unwrap_or_default();
+    pairs.sort_unstable();
+    let accept = req.headers.get(hyper::header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("");
+    format!("rustnext:response:{}:{}?{}:{}", req.method, req.uri.path(), pairs.join("&"), accept.trim())
+}
+
+#[async_trait]
+impl Middleware for CacheMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        next: Arc<dyn Handler>,
+    ) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+        let ttl = self.ttl_for(req.uri.path());
+        let personal = req.headers.contains_key(hyper::header::AUTHORIZATION)
+            || (!self.cookies && req.headers.contains_key(hyper::header::COOKIE));
+        if req.method != hyper::Method::GET || ttl.is_zero() || personal {
+            return next.handle(req).await;
+        }
+
+        let key = response_cache_key(&req);
+        let refresh = req.headers.get_all(hyper::header::CACHE_CONTROL)
+            .iter()
+            .chain(req.headers.get_all(hyper::header::PRAGMA).iter())
+            .filter_map(|value| value.to_str().ok())
+            .any(|value| value.to_ascii_lowercase().contains("no-cache"));
+        if !refresh {
+            if let Some(cached) = self.lookup(&key).await {
+                return Ok(cached.to_response().header("X-Cache", "HIT"));
+            }
+        }
+
+        let mut response = next.handle(req).await?;
+        if !self.is_cacheable(&response) {
+            return Ok(response);
+        }
+
+        let bytes = hyper::body::to_bytes(std::mem::take(&mut response.body)).await?;
+        let body = match std::str::from_utf8(&bytes) {
+            Ok(text) => CachedBody::Text(text.to_string()),
+            Err(_) => CachedBody::Bytes(bytes.to_vec()),
+        };
+        // Values that aren't valid UTF-8 are left out
+        let headers = response.headers.iter()
+            .filter(|(key, _)| key.as_str() != "x-cache")
+            .filter_map(|(key, value)| Some((key.to_string(), value.to_str().ok()?.to_string())))
+            .collect();
+        self.store(&key, CachedResponse { status: response.status.as_u16(), headers, body }, ttl).await;
+
+        response.body = hyper::Body::from(bytes);
+        Ok(response.header("X-Cache", "MISS"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    async fn request(method: &str, uri: &str, headers: &[(&str, &str)]) -> Request {
+        let mut builder = hyper::Request::builder().method(method).uri(uri);
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        Request::from_hyper(builder.body(hyper::Body::empty()).unwrap()).await.unwrap()
+    }
+
+    // A handler counting its calls
+    fn counting_handler() -> (Arc<dyn Handler>, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let handler = move |_req: Request| {
+            let count = counter.fetch_add(1, Ordering::SeqCst) + 1;
+            async move {
+                Ok::<_, Box<dyn std::error::Error + Send + Sync>>(Response::new().html(&format!("<p>{}</p>", count)))
+            }
+        };
+        (Arc::new(handler), calls)
+    }
+
+    async fn body_text(response: Response) -> String {
+        let bytes = hyper::body::to_bytes(response.body).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn memory_cache_expires_entries_and_sweeps_them_on_write() {
+        let mut store = MemoryCache::new();
+        store.set("short", &1, Duration::from_millis(10)).await.unwrap();
+        store.set("long", &2, Duration::from_secs(60)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        assert_eq!(store.get::<u32>("long").await.unwrap(), Some(2));
+        assert!(store.ttl("short").await.unwrap().is_none());
+        // Not read again, so only a sweep removes it
+        store.sweep_interval = Duration::ZERO;
+        store.set("other", &3, Duration::from_secs(60)).await.unwrap();
+        assert!(!store.entries.read().await.contains_key("short"));
+        assert_eq!(store.entries.read().await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn memory_cache_sweeps_at_most_once_per_interval() {
+        let store = MemoryCache::new();
+        store.set("short", &1, Duration::from_millis(1)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+
+        store.set("other", &2, Duration::from_secs(60)).await.unwrap();
+        assert!(store.entries.read().await.contains_key("short"));
+        assert_eq!(store.get::<u32>("short").await.unwrap(), None);
+    }
+
+    fn cache() -> CacheMiddleware {
+        CacheMiddleware::memory(100, 1024 * 1024).route("/blog", Duration::from_secs(60))
+    }
+
+    #[tokio::test]
+    async fn second_identical_get_skips_the_handler() {
+        let cache = cache();
+        let (handler, calls) = counting_handler();
+
+        let first = cache.handle(request("GET", "/blog?b=2&a=1", &[]).await, handler.clone()).await.unwrap();
+        assert_eq!(first.headers.get("X-Cache").unwrap(), "MISS");
+        let second = cache.handle(request("GET", "/blog?a=1&b=2", &[]).await, handler.clone()).await.unwrap();
+        assert_eq!(second.headers.get("X-Cache").unwrap(), "HIT");
+        assert_eq!(body_text(second).await, "<p>1</p>");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn post_is_never_cached() {
+        let cache = cache();
+        let (handler, calls) = counting_handler();
+
+        for _ in 0..2 {
+            let response = cache.handle(request("POST", "/blog", &[]).await, handler.clone()).await.unwrap();
+            assert!(response.headers.get("X-Cache").is_none());
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn only_listed_routes_are_cached_by_default() {
+        let cache = cache();
+        let (handler, calls) = counting_handler();
+
+        for _ in 0..2 {
+            cache.handle(request("GET", "/account", &[]).await, handler.clone()).await.unwrap();
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn requests_with_cookies_bypass_the_cache_unless_opted_in() {
+        let (handler, calls) = counting_handler();
+        let cookie = [("Cookie", "session_id=abc")];
+
+        let cache = cache();
+        for _ in 0..2 {
+            let response = cache.handle(request("GET", "/blog", &cookie).await, handler.clone()).await.unwrap();
+            assert!(response.headers.get("X-Cache").is_none());
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        let cache = cache.with_cookies(true);
+        for _ in 0..2 {
+            cache.handle(request("GET", "/blog", &cookie).await, handler.clone()).await.unwrap();
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn accept_header_is_part_of_the_key() {
+        let cache = cache();
+        let (handler, calls) = counting_handler();
+
+        cache.handle(request("GET", "/blog", &[("Accept", "text/html")]).await, handler.clone()).await.unwrap();
+        cache.handle(request("GET", "/blog", &[("Accept", "application/json")]).await, handler.clone()).await.unwrap();
+        cache.handle(request("GET", "/blog", &[("Accept", "text/html")]).await, handler.clone()).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn get_or_set_runs_one_computation_for_concurrent_callers() {
+        let cache = Cache::memory();
+        let calls = AtomicUsize::new(0);
+        let compute = || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            Ok::<_, Box<dyn std::error::Error + Send + Sync>>("value".to_string())
+        };
+
+        let results = futures::future::join_all(
+            (0..5).map(|_| cache.get_or_set("key", Duration::from_secs(60), compute)),
+        )
+        .await;
+        assert!(results.into_iter().all(|result| result.unwrap() == "value"));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(cache.get::<String>("key").await.unwrap().as_deref(), Some("value"));
+        // Nothing left behind once every caller is done
+        assert!(cache.inflight().keys.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn get_or_set_does_not_cache_failures() {
+        let cache = Cache::memory();
+        let failed = cache
+            .get_or_set::<u32, _, _>("key", Duration::from_secs(60), || async { Err("backend down".into()) })
+            .await;
+        assert_eq!(failed.unwrap_err().to_string(), "backend down");
+        assert_eq!(cache.get::<u32>("key").await.unwrap(), None);
+
+        let value = cache
+            .get_or_set("key", Duration::from_secs(60), || async { Ok::<_, Box<dyn std::error::Error + Send + Sync>>(7u32) })
+            .await
+            .unwrap();
+        assert_eq!(value, 7);
+        // Cached now, so this computation never runs
+        let value = cache
+            .get_or_set("key", Duration::from_secs(60), || async { Ok::<_, Box<dyn std::error::Error + Send + Sync>>(8u32) })
+            .await
+            .unwrap();
+        assert_eq!(value, 7);
+    }
+
+    #[tokio::test]
+    async fn namespaces_prefix_keys_and_tags() {
+        let cache = Cache::memory();
+        let products = cache.with_prefix("products:");
+        let users = cache.with_prefix("users:");
+        let ttl = Duration::from_secs(60);
+
+        products.set("1", &"lamp", ttl).await.unwrap();
+        users.set("1", &"ada", ttl).await.unwrap();
+        assert_eq!(products.key("1"), "products:1");
+        assert_eq!(cache.get::<String>("products:1").await.unwrap().as_deref(), Some("lamp"));
+        assert_eq!(users.get::<String>("1").await.unwrap().as_deref(), Some("ada"));
+
+        assert_eq!(products.increment("views", ttl).await.unwrap(), 1);
+        assert_eq!(users.increment("views", ttl).await.unwrap(), 1);
+        assert_eq!(products.increment("views", ttl).await.unwrap(), 2);
+        assert_eq!(products.increment_and_get("clicks", ttl, "views").await.unwrap(), (1, Some(2)));
+        assert_eq!(users.increment_and_get("clicks", ttl, "missing").await.unwrap(), (1, None));
+
+        // The same tag name in two namespaces is two different tags
+        products.set_tagged("2", &"desk", ttl, &["all"]).await.unwrap();
+        users.set_tagged("2", &"grace", ttl, &["all"]).await.unwrap();
+        assert_eq!(products.invalidate_tag("all").await.unwrap(), 1);
+        assert_eq!(products.get::<String>("2").await.unwrap(), None);
+        assert_eq!(users.get::<String>("2").await.unwrap().as_deref(), Some("grace"));
+
+        products.delete("1").await.unwrap();
+        assert_eq!(products.ttl("1").await.unwrap(), None);
+        assert!(users.ttl("1").await.unwrap().is_some());
+    }
 }
@@ -2,6 +2,7 @@ use crate::{Request, Response, Handler};
 use crate::middleware::Middleware; // Corrected import path for Middleware
 use async_trait::async_trait;
 use async_compression::tokio::write::{GzipEncoder, BrotliEncoder};
+use hyper::header::{HeaderValue, CONTENT_ENCODING, CONTENT_LENGTH};
 use tokio::io::AsyncWriteExt;
 use std::sync::Arc;
 
@@ -21,15 +22,12 @@ impl CompressionMiddleware {
         self
     }
 
-    async fn compress_response(&self, response: Response, encoding: &str) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
-        let body_bytes = hyper::body::to_bytes(response.body).await?;
+    async fn compress_response(&self, mut response: Response, encoding: &'static str) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+        let body_bytes = hyper::body::to_bytes(std::mem::take(&mut response.body)).await?;
         
         if body_bytes.len() < self.min_size {
-            return Ok(Response {
-                status: response.status,
-                headers: response.headers,
-                body: hyper::Body::from(body_bytes),
-            });
+            response.body = hyper::Body::from(body_bytes);
+            return Ok(response);
         }
 
         let compressed = match encoding {
@@ -45,22 +43,22 @@ impl CompressionMiddleware {
                 encoder.shutdown().await?;
                 encoder.into_inner()
             }
-            _ => return Ok(Response {
-                status: response.status,
-                headers: response.headers,
-                body: hyper::Body::from(body_bytes),
-            }),
+            _ => {
+                response.body = hyper::Body::from(body_bytes);
+                return Ok(response);
+            }
         };
 
-        let mut headers = response.headers;
-        headers.insert("Content-Encoding".to_string(), encoding.to_string());
-        headers.insert("Content-Length".to_string(), compressed.len().to_string());
+        response.headers.insert(CONTENT_ENCODING, HeaderValue::from_static(encoding));
+        response.headers.insert(CONTENT_LENGTH, compressed.len().into());
+        response.body = hyper::Body::from(compressed);
+        Ok(response)
+    }
+}
 
-        Ok(Response {
-            status: response.status,
-            headers,
-            body: hyper::Body::from(compressed),
-        })
+impl Default for CompressionMiddleware {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -79,6 +77,11 @@ impl Middleware for CompressionMiddleware {
 
         let response = next.handle(req).await?;
 
+        // Already encoded, e.g. a precompressed static file
+        if response.headers.contains_key(CONTENT_ENCODING) {
+            return Ok(response);
+        }
+
         // Choose compression method based on client support
         if accept_encoding.contains("br") {
             self.compress_response(response, "br").await
@@ -1,11 +1,19 @@
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::collections::HashMap;
 use std::env;
+use std::fmt;
 use std::fs;
+use std::path::Path;
+use std::str::FromStr;
 use toml;
 use log::{info, warn, error};
 use once_cell::sync::OnceCell;
 
+mod reload;
+pub use reload::{get_config_dyn, reload_config, subscribe_config};
+#[cfg(feature = "config-watch")]
+pub use reload::{watch, ConfigWatcher};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub server: ServerConfig,
@@ -14,6 +22,12 @@ pub struct Config {
     pub features: FeatureConfig,
     #[serde(default)]
     pub custom: HashMap<String, String>,
+    // From `RUSTNEXT_ENV`, e.g. "development" or "production"; picks the environment file
+    #[serde(default = "default_environment")]
+    pub environment: String,
+    // Any other top-level tables from the files (e.g. `[smtp]`), read with `section`/`get_path`
+    #[serde(flatten)]
+    pub sections: toml::Table,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -21,6 +35,13 @@ pub struct ServerConfig {
     pub host: String,
     pub port: u16,
     pub workers: usize,
+    // Largest request body Request::json/form/multipart will read, in bytes
+    #[serde(default = "default_max_body_size")]
+    pub max_body_size: usize,
+    // Whether `X-Forwarded-For`/`X-Real-IP` name the client (see `Request::client_ip`). Only
+    // behind a proxy that sets them; otherwise any client can claim any address.
+    #[serde(default)]
+    pub trust_proxy: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,6 +56,45 @@ pub struct AuthConfig {
     pub jwt_secret: String,
     pub session_timeout: u64,
     pub bcrypt_cost: u32,
+    // "bcrypt" or "argon2"; hashes in either format keep verifying after a switch
+    #[serde(default = "default_password_algorithm")]
+    pub password_algorithm: String,
+    // JWT lifetimes in seconds
+    #[serde(default = "default_access_token_ttl")]
+    pub access_token_ttl: u64,
+    #[serde(default = "default_refresh_token_ttl")]
+    pub refresh_token_ttl: u64,
+    // HS256/HS384/HS512 use `jwt_secret`; RS*/PS*/ES* load PEM keys from the paths below
+    #[serde(default = "default_jwt_algorithm")]
+    pub jwt_algorithm: String,
+    #[serde(default)]
+    pub jwt_private_key_path: Option<String>,
+    #[serde(default)]
+    pub jwt_public_key_path: Option<String>,
+}
+
+fn default_environment() -> String {
+    "development".to_string()
+}
+
+fn default_max_body_size() -> usize {
+    2 * 1024 * 1024
+}
+
+fn default_password_algorithm() -> String {
+    "bcrypt".to_string()
+}
+
+fn default_jwt_algorithm() -> String {
+    "HS256".to_string()
+}
+
+fn default_access_token_ttl() -> u64 {
+    15 * 60
+}
+
+fn default_refresh_token_ttl() -> u64 {
+    30 * 24 * 60 * 60
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -45,6 +105,8 @@ pub struct FeatureConfig {
     pub logging: bool,
 }
 
+const DEFAULT_JWT_SECRET: &str = "your-secret-key";
+
 impl Default for Config {
     fn default() -> Self {
         Config {
@@ -52,6 +114,8 @@ impl Default for Config {
                 host: "127.0.0.1".to_string(),
                 port: 3000,
                 workers: num_cpus::get(),
+                max_body_size: default_max_body_size(),
+                trust_proxy: false,
             },
             database: DatabaseConfig {
                 url: "postgresql://localhost/rustnext".to_string(),
@@ -59,9 +123,15 @@ impl Default for Config {
                 timeout: 30,
             },
             auth: AuthConfig {
-                jwt_secret: "your-secret-key".to_string(),
+                jwt_secret: DEFAULT_JWT_SECRET.to_string(),
                 session_timeout: 3600,
                 bcrypt_cost: 12,
+                password_algorithm: default_password_algorithm(),
+                access_token_ttl: default_access_token_ttl(),
+                refresh_token_ttl: default_refresh_token_ttl(),
+                jwt_algorithm: default_jwt_algorithm(),
+                jwt_private_key_path: None,
+                jwt_public_key_path: None,
             },
             features: FeatureConfig {
                 compression: true,
@@ -70,67 +140,268 @@ impl Default for Config {
                 logging: true,
             },
             custom: HashMap::new(),
+            environment: default_environment(),
+            sections: toml::Table::new(),
+        }
+    }
+}
+
+// Why a configuration couldn't be loaded
+#[derive(Debug)]
+pub enum ConfigError {
+    Read { path: String, source: std::io::Error },
+    // A file isn't valid TOML, or the merged result doesn't fit the Config fields
+    Parse { path: String, message: String },
+    // Every problem `Config::validate` found
+    Invalid(Vec<String>),
+    MissingSection(String),
+    // A section exists but doesn't fit the requested type
+    InvalidSection { name: String, message: String },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read { path, source } => write!(f, "Failed to read config file {}: {}", path, source),
+            ConfigError::Parse { path, message } => write!(f, "Failed to parse config {}: {}", path, message),
+            ConfigError::Invalid(problems) => write!(f, "Invalid configuration: {}", problems.join("; ")),
+            ConfigError::MissingSection(name) => write!(f, "Config section [{}] is not defined", name),
+            ConfigError::InvalidSection { name, message } => write!(f, "Config section [{}] is invalid: {}", name, message),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Read { source, .. } => Some(source),
+            _ => None,
         }
     }
 }
 
 impl Config {
-    pub fn load(file_path: Option<&str>) -> Self {
-        let mut config = Config::default();
+    // Layers, later ones overriding earlier ones field by field:
+    //   defaults < `file_path` < `config.{RUSTNEXT_ENV}.toml` next to it (if present)
+    //   < `RUSTNEXT__SECTION__FIELD` variables < the named variables in `apply_env_overrides`
+    // then validates. `RUSTNEXT_ENV` defaults to "development".
+    pub fn load(file_path: Option<&str>) -> Result<Self, ConfigError> {
+        let config = Self::build(file_path)?;
+        config.validate().map_err(ConfigError::Invalid)?;
+        Ok(config)
+    }
 
-        if let Some(path) = file_path {
-            match fs::read_to_string(path) {
-                Ok(contents) => {
-                    match toml::from_str(&contents) {
-                        Ok(file_config) => {
-                            info!("Configuration loaded from {}", path);
-                            config = file_config;
-                        },
-                        Err(e) => {
-                            error!("Failed to parse config file {}: {}", path, e);
-                            warn!("Using default configuration.");
-                        }
-                    }
-                },
-                Err(e) => {
-                    error!("Failed to read config file {}: {}", path, e);
-                    warn!("Using default configuration.");
+    // The old lenient behavior: a missing or malformed file is logged and the defaults (plus
+    // environment overrides) are used instead, and validation problems are only warned about.
+    pub fn load_or_default(file_path: Option<&str>) -> Self {
+        let config = Self::build(file_path).unwrap_or_else(|e| {
+            error!("{}", e);
+            warn!("Using default configuration.");
+            Self::build(None).unwrap_or_default()
+        });
+        if let Err(problems) = config.validate() {
+            for problem in &problems {
+                warn!("Invalid configuration: {}", problem);
+            }
+        }
+        config
+    }
+
+    // Like `load`; kept for callers that want the problems as strings
+    pub fn load_strict(file_path: Option<&str>) -> Result<Self, Vec<String>> {
+        Self::load(file_path).map_err(|e| match e {
+            ConfigError::Invalid(problems) => problems,
+            other => vec![other.to_string()],
+        })
+    }
+
+    fn build(file_path: Option<&str>) -> Result<Self, ConfigError> {
+        let environment = env::var(ENVIRONMENT_ENV).unwrap_or_else(|_| default_environment());
+        let mut merged = toml::Value::try_from(Config::default()).map_err(|e| ConfigError::Parse {
+            path: "defaults".to_string(),
+            message: e.to_string(),
+        })?;
+        let mut sources = vec!["defaults".to_string()];
+
+        match file_path {
+            Some(path) => {
+                merge_toml(&mut merged, read_toml(path)?);
+                sources.push(path.to_string());
+
+                let env_path = environment_file(path, &environment);
+                if Path::new(&env_path).exists() {
+                    merge_toml(&mut merged, read_toml(&env_path)?);
+                    sources.push(env_path);
+                }
+                info!("Configuration loaded from {}", sources[1..].join(" + "));
+            }
+            None => info!("No config file specified, using default configuration."),
+        }
+
+        for (name, raw) in env::vars() {
+            if let Some(path) = parse_env_path(&name) {
+                match set_toml_path(&mut merged, &path, &raw) {
+                    Ok(()) => info!("Overriding {} with {}", path.join("."), name),
+                    Err(e) => error!("Invalid value for {}: {}, keeping previous value", name, e),
+                }
+            }
+        }
+
+        let mut config: Config = merged.try_into().map_err(|e: toml::de::Error| ConfigError::Parse {
+            path: sources.join(" + "),
+            message: e.to_string(),
+        })?;
+        config.environment = environment;
+        config.apply_env_overrides();
+        Ok(config)
+    }
+
+    pub fn is_development(&self) -> bool {
+        matches!(self.environment.as_str(), "development" | "dev")
+    }
+
+    // Checks the configuration and returns every problem found, not just the first.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut problems = Vec::new();
+
+        if self.server.host.trim().is_empty() {
+            problems.push("server.host must not be empty".to_string());
+        }
+        if self.server.port == 0 {
+            problems.push("server.port must be between 1 and 65535".to_string());
+        }
+        if self.server.workers == 0 {
+            problems.push("server.workers must be at least 1".to_string());
+        }
+        if self.server.max_body_size == 0 {
+            problems.push("server.max_body_size must be at least 1 byte".to_string());
+        }
+
+        if self.database.url.trim().is_empty() {
+            problems.push("database.url must not be empty".to_string());
+        }
+        if self.database.max_connections == 0 {
+            problems.push("database.max_connections must be at least 1".to_string());
+        }
+        if self.database.timeout == 0 {
+            problems.push("database.timeout must be at least 1 second".to_string());
+        }
+
+        let algorithm = self.auth.jwt_algorithm.trim().to_ascii_uppercase();
+        match algorithm.get(..2) {
+            // Development may run with the placeholder secret
+            Some("HS") if !self.is_development() => {
+                if self.auth.jwt_secret.trim().is_empty() {
+                    problems.push("auth.jwt_secret must not be empty".to_string());
+                } else if self.auth.jwt_secret == DEFAULT_JWT_SECRET {
+                    problems.push(format!("auth.jwt_secret is the insecure default {:?}; set a real secret for production", DEFAULT_JWT_SECRET));
+                }
+            }
+            Some("HS") => {}
+            Some("RS") | Some("PS") | Some("ES") => {
+                if self.auth.jwt_public_key_path.as_deref().is_none_or(|p| p.trim().is_empty()) {
+                    problems.push(format!("auth.jwt_public_key_path is required for {}", algorithm));
                 }
             }
+            _ => problems.push(format!("auth.jwt_algorithm {:?} is not supported", self.auth.jwt_algorithm)),
+        }
+        if self.auth.access_token_ttl == 0 || self.auth.refresh_token_ttl == 0 {
+            problems.push("auth.access_token_ttl and auth.refresh_token_ttl must be at least 1 second".to_string());
+        }
+        if !(4..=31).contains(&self.auth.bcrypt_cost) {
+            problems.push(format!("auth.bcrypt_cost must be between 4 and 31 (got {})", self.auth.bcrypt_cost));
+        }
+        if !matches!(self.auth.password_algorithm.trim().to_ascii_lowercase().as_str(), "bcrypt" | "argon2") {
+            problems.push(format!("auth.password_algorithm must be \"bcrypt\" or \"argon2\" (got {:?})", self.auth.password_algorithm));
+        }
+
+        if problems.is_empty() {
+            Ok(())
         } else {
-            info!("No config file specified, using default configuration.");
+            Err(problems)
         }
-        
-        // Override with environment variables
+    }
+
+    // Applied last by `load`, so these win over `RUSTNEXT__...` paths.
+    //
+    // Supported variables:
+    //   RUSTNEXT_HOST, RUSTNEXT_PORT, RUSTNEXT_WORKERS, RUSTNEXT_MAX_BODY_SIZE, RUSTNEXT_TRUST_PROXY
+    //   DATABASE_URL, RUSTNEXT_DB_MAX_CONNECTIONS, RUSTNEXT_DB_TIMEOUT
+    //   JWT_SECRET
+    //   ENABLE_COMPRESSION, ENABLE_METRICS, ENABLE_HOT_RELOAD, ENABLE_LOGGING
+    //   RUSTNEXT_CUSTOM_<KEY>=value  -> custom["<key>"] (key is lowercased)
+    pub fn apply_env_overrides(&mut self) {
         if let Ok(host) = env::var("RUSTNEXT_HOST") {
             info!("Overriding server host with RUSTNEXT_HOST={}", host);
-            config.server.host = host;
-        }
-        if let Ok(port) = env::var("RUSTNEXT_PORT") {
-            if let Ok(port_num) = port.parse() {
-                info!("Overriding server port with RUSTNEXT_PORT={}", port_num);
-                config.server.port = port_num;
-            } else {
-                warn!("Invalid RUSTNEXT_PORT value: {}", port);
-            }
+            self.server.host = host;
         }
-        
+        env_override("RUSTNEXT_PORT", &mut self.server.port);
+        env_override("RUSTNEXT_WORKERS", &mut self.server.workers);
+        env_override("RUSTNEXT_MAX_BODY_SIZE", &mut self.server.max_body_size);
+        env_override_bool("RUSTNEXT_TRUST_PROXY", &mut self.server.trust_proxy);
+
         if let Ok(db_url) = env::var("DATABASE_URL") {
             info!("Overriding database URL with DATABASE_URL");
-            config.database.url = db_url;
+            self.database.url = db_url;
         }
-        
+        env_override("RUSTNEXT_DB_MAX_CONNECTIONS", &mut self.database.max_connections);
+        env_override("RUSTNEXT_DB_TIMEOUT", &mut self.database.timeout);
+
         if let Ok(jwt_secret) = env::var("JWT_SECRET") {
             info!("Overriding JWT secret with JWT_SECRET");
-            config.auth.jwt_secret = jwt_secret;
+            self.auth.jwt_secret = jwt_secret;
         }
-        
-        config.features.compression = env::var("ENABLE_COMPRESSION").map_or(config.features.compression, |s| s == "true");
-        config.features.metrics = env::var("ENABLE_METRICS").map_or(config.features.metrics, |s| s == "true");
-        config.features.hot_reload = env::var("ENABLE_HOT_RELOAD").map_or(config.features.hot_reload, |s| s == "true");
-        config.features.logging = env::var("ENABLE_LOGGING").map_or(config.features.logging, |s| s == "true");
 
-        config
+        env_override_bool("ENABLE_COMPRESSION", &mut self.features.compression);
+        env_override_bool("ENABLE_METRICS", &mut self.features.metrics);
+        env_override_bool("ENABLE_HOT_RELOAD", &mut self.features.hot_reload);
+        env_override_bool("ENABLE_LOGGING", &mut self.features.logging);
+
+        for (name, value) in env::vars() {
+            if let Some(key) = name.strip_prefix(CUSTOM_ENV_PREFIX) {
+                if key.is_empty() {
+                    continue;
+                }
+                let key = key.to_lowercase();
+                info!("Overriding custom.{} with {}", key, name);
+                self.custom.insert(key, value);
+            }
+        }
+    }
+
+    // A table deserialized into the app's own type, e.g. `config.section::<SmtpConfig>("smtp")`.
+    // Built-in sections like "server" work too.
+    pub fn section<T: DeserializeOwned>(&self, name: &str) -> Result<T, ConfigError> {
+        let value = self.to_toml()?
+            .remove(name)
+            .ok_or_else(|| ConfigError::MissingSection(name.to_string()))?;
+        value.try_into().map_err(|e: toml::de::Error| ConfigError::InvalidSection {
+            name: name.to_string(),
+            message: e.message().to_string(),
+        })
+    }
+
+    // A value by dotted path, e.g. `config.get_path("smtp.port")`; numbers and booleans are
+    // formatted, tables and arrays give `None`
+    pub fn get_path(&self, path: &str) -> Option<String> {
+        let table = self.to_toml().ok()?;
+        let (first, rest) = path.split_once('.').unwrap_or((path, ""));
+        let mut value = table.get(first)?;
+        for part in rest.split('.').filter(|part| !part.is_empty()) {
+            value = value.get(part)?;
+        }
+        match value {
+            toml::Value::String(s) => Some(s.clone()),
+            toml::Value::Table(_) | toml::Value::Array(_) => None,
+            other => Some(other.to_string()),
+        }
+    }
+
+    fn to_toml(&self) -> Result<toml::Table, ConfigError> {
+        toml::Table::try_from(self).map_err(|e| ConfigError::Parse {
+            path: "config".to_string(),
+            message: e.to_string(),
+        })
     }
 
     pub fn get(&self, key: &str) -> Option<&String> {
@@ -142,16 +413,288 @@ impl Config {
     }
 }
 
+const CUSTOM_ENV_PREFIX: &str = "RUSTNEXT_CUSTOM_";
+const ENVIRONMENT_ENV: &str = "RUSTNEXT_ENV";
+const PATH_ENV_PREFIX: &str = "RUSTNEXT__";
+
+fn read_toml(path: &str) -> Result<toml::Value, ConfigError> {
+    let contents = fs::read_to_string(path).map_err(|source| ConfigError::Read { path: path.to_string(), source })?;
+    toml::from_str(&contents).map_err(|e: toml::de::Error| ConfigError::Parse { path: path.to_string(), message: e.to_string() })
+}
+
+// `config.toml` -> `config.production.toml`, in the same directory
+fn environment_file(path: &str, environment: &str) -> String {
+    let path = Path::new(path);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("config");
+    let name = match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) => format!("{}.{}.{}", stem, environment, ext),
+        None => format!("{}.{}", stem, environment),
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
+// Tables merge key by key; anything else in `overlay` replaces what's in `base`
+fn merge_toml(base: &mut toml::Value, overlay: toml::Value) {
+    match (base, overlay) {
+        (toml::Value::Table(base), toml::Value::Table(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_toml(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+// `RUSTNEXT__SERVER__PORT` -> ["server", "port"]
+fn parse_env_path(name: &str) -> Option<Vec<String>> {
+    let rest = name.strip_prefix(PATH_ENV_PREFIX)?;
+    let path: Vec<String> = rest.split("__").map(|part| part.to_ascii_lowercase()).collect();
+    if path.iter().any(|part| part.is_empty()) {
+        return None;
+    }
+    Some(path)
+}
+
+// Parses `raw` as the type of the value already at `path` (a string if there is none)
+fn set_toml_path(root: &mut toml::Value, path: &[String], raw: &str) -> Result<(), String> {
+    let (last, parents) = path.split_last().ok_or("empty path")?;
+    let mut table = root.as_table_mut().ok_or("config is not a table")?;
+    for part in parents {
+        table = table
+            .entry(part.clone())
+            .or_insert_with(|| toml::Value::Table(toml::value::Table::new()))
+            .as_table_mut()
+            .ok_or_else(|| format!("{} is not a section", part))?;
+    }
+
+    let value = match table.get(last) {
+        Some(toml::Value::Integer(_)) => raw.trim().parse::<i64>().map(toml::Value::Integer).map_err(|e| format!("{:?} ({})", raw, e))?,
+        Some(toml::Value::Float(_)) => raw.trim().parse::<f64>().map(toml::Value::Float).map_err(|e| format!("{:?} ({})", raw, e))?,
+        Some(toml::Value::Boolean(_)) => parse_bool(raw)
+            .map(toml::Value::Boolean)
+            .ok_or_else(|| format!("{:?} (expected true/false, 1/0, yes/no, on/off)", raw))?,
+        Some(toml::Value::Table(_)) => return Err(format!("{} is a section, not a value", last)),
+        _ => toml::Value::String(raw.to_string()),
+    };
+    table.insert(last.clone(), value);
+    Ok(())
+}
+
+fn env_override<T: FromStr>(name: &str, target: &mut T)
+where
+    T::Err: fmt::Display,
+{
+    if let Ok(raw) = env::var(name) {
+        match raw.trim().parse::<T>() {
+            Ok(value) => {
+                info!("Overriding config with {}={}", name, raw);
+                *target = value;
+            }
+            Err(e) => error!("Invalid value for {}: {:?} ({}), keeping previous value", name, raw, e),
+        }
+    }
+}
+
+fn env_override_bool(name: &str, target: &mut bool) {
+    if let Ok(raw) = env::var(name) {
+        match parse_bool(&raw) {
+            Some(value) => *target = value,
+            None => error!("Invalid boolean for {}: {:?} (expected true/false, 1/0, yes/no, on/off)", name, raw),
+        }
+    }
+}
+
+fn parse_bool(raw: &str) -> Option<bool> {
+    match raw.trim().to_ascii_lowercase().as_str() {
+        "true" | "1" | "yes" | "on" => Some(true),
+        "false" | "0" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
 static GLOBAL_CONFIG: OnceCell<Config> = OnceCell::new();
 
 pub fn get_config() -> &'static Config {
     GLOBAL_CONFIG.get_or_init(|| {
-        Config::load(None)
+        Config::load_or_default(None)
     })
 }
 
+// The global config if it has been set or loaded, without loading the defaults otherwise
+pub fn try_get_config() -> Option<&'static Config> {
+    GLOBAL_CONFIG.get()
+}
+
 pub fn init_config(config: Config) {
     if GLOBAL_CONFIG.set(config).is_err() {
         warn!("Config already initialized, ignoring new initialization.");
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    // Loading reads process-wide variables, so tests that load or set them take turns
+    pub(super) static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+    pub(super) fn env_lock() -> std::sync::MutexGuard<'static, ()> {
+        ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(super) fn write(dir: &tempfile::TempDir, name: &str, contents: &str) -> String {
+        let path = dir.path().join(name);
+        fs::write(&path, contents).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn layers_override_each_other_field_by_field() {
+        let _env = env_lock();
+        let dir = tempfile::tempdir().unwrap();
+        let path = write(&dir, "config.toml", "[server]\nhost = \"0.0.0.0\"\nport = 8000\n\n[database]\ntimeout = 5\n");
+        write(&dir, "config.staging.toml", "[server]\nport = 9000\n\n[auth]\njwt_secret = \"staging\"\n");
+
+        env::set_var(ENVIRONMENT_ENV, "staging");
+        env::set_var("RUSTNEXT__DATABASE__TIMEOUT", "7");
+        env::set_var("RUSTNEXT__SERVER__WORKERS", "3");
+        env::set_var("RUSTNEXT_WORKERS", "4");
+        let config = Config::load(Some(&path));
+        for name in [ENVIRONMENT_ENV, "RUSTNEXT__DATABASE__TIMEOUT", "RUSTNEXT__SERVER__WORKERS", "RUSTNEXT_WORKERS"] {
+            env::remove_var(name);
+        }
+        let config = config.unwrap();
+
+        assert_eq!(config.environment, "staging");
+        // The file
+        assert_eq!(config.server.host, "0.0.0.0");
+        // The environment file over the file
+        assert_eq!(config.server.port, 9000);
+        // A path variable over both
+        assert_eq!(config.database.timeout, 7);
+        // A named variable over a path variable
+        assert_eq!(config.server.workers, 4);
+        // Defaults for everything else
+        assert_eq!(config.database.max_connections, 10);
+        assert_eq!(config.auth.jwt_algorithm, "HS256");
+    }
+
+    #[test]
+    fn load_reports_why_a_file_was_rejected() {
+        let _env = env_lock();
+        let dir = tempfile::tempdir().unwrap();
+
+        let missing = dir.path().join("missing.toml");
+        let err = Config::load(missing.to_str()).unwrap_err();
+        assert!(matches!(err, ConfigError::Read { .. }));
+        assert!(std::error::Error::source(&err).is_some());
+
+        let malformed = write(&dir, "malformed.toml", "[server\nport = 1");
+        assert!(matches!(Config::load(Some(&malformed)), Err(ConfigError::Parse { path, .. }) if path == malformed));
+
+        let wrong_type = write(&dir, "wrong_type.toml", "[server]\nport = \"eighty\"\n");
+        let err = Config::load(Some(&wrong_type)).unwrap_err();
+        assert!(matches!(err, ConfigError::Parse { ref path, .. } if path.starts_with("defaults + ")));
+
+        let invalid = write(&dir, "invalid.toml", "[server]\nport = 0\nworkers = 0\n");
+        match Config::load(Some(&invalid)).unwrap_err() {
+            ConfigError::Invalid(problems) => assert_eq!(problems.len(), 2),
+            other => panic!("unexpected error: {}", other),
+        }
+
+        // The lenient loader falls back instead
+        let config = Config::load_or_default(Some(&malformed));
+        assert_eq!(config.server.port, 3000);
+        assert!(Config::load_strict(Some(&invalid)).unwrap_err()[0].contains("server.port"));
+    }
+
+    #[test]
+    fn validate_requires_a_real_secret_outside_development() {
+        let mut config = Config::default();
+        assert!(config.validate().is_ok());
+
+        config.environment = "production".to_string();
+        let problems = config.validate().unwrap_err();
+        assert!(problems[0].contains("insecure default"));
+
+        config.auth.jwt_secret = "s3cret".to_string();
+        assert!(config.validate().is_ok());
+
+        config.auth.jwt_algorithm = "RS256".to_string();
+        config.auth.bcrypt_cost = 2;
+        config.auth.password_algorithm = "md5".to_string();
+        let problems = config.validate().unwrap_err();
+        assert_eq!(problems.len(), 3);
+        assert!(problems[0].contains("jwt_public_key_path"));
+    }
+
+    #[test]
+    fn env_paths_keep_the_existing_type() {
+        let mut root = toml::Value::try_from(Config::default()).unwrap();
+        let path = parse_env_path("RUSTNEXT__SERVER__PORT").unwrap();
+        assert_eq!(path, ["server", "port"]);
+        set_toml_path(&mut root, &path, "8080").unwrap();
+        assert_eq!(root["server"]["port"].as_integer(), Some(8080));
+
+        assert!(set_toml_path(&mut root, &path, "eighty").is_err());
+        assert!(set_toml_path(&mut root, &["features".to_string(), "metrics".to_string()], "maybe").is_err());
+        set_toml_path(&mut root, &["features".to_string(), "metrics".to_string()], "on").unwrap();
+        assert_eq!(root["features"]["metrics"].as_bool(), Some(true));
+        assert!(set_toml_path(&mut root, &["server".to_string()], "x").is_err());
+
+        assert_eq!(parse_env_path("RUSTNEXT__SERVER__"), None);
+        assert_eq!(parse_env_path("RUSTNEXT_PORT"), None);
+        assert_eq!(environment_file("conf/app.toml", "production"), "conf/app.production.toml");
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct SmtpConfig {
+        host: String,
+        port: u16,
+        #[serde(default)]
+        tls: bool,
+    }
+
+    fn with_smtp() -> Config {
+        let _env = env_lock();
+        let dir = tempfile::tempdir().unwrap();
+        let path = write(&dir, "config.toml", "[smtp]\nhost = \"mail.local\"\nport = 2525\n\n[smtp.auth]\nuser = \"app\"\n");
+        Config::load(Some(&path)).unwrap()
+    }
+
+    #[test]
+    fn section_deserializes_custom_and_built_in_tables() {
+        let config = with_smtp();
+        let smtp: SmtpConfig = config.section("smtp").unwrap();
+        assert_eq!(smtp, SmtpConfig { host: "mail.local".to_string(), port: 2525, tls: false });
+
+        let server: ServerConfig = config.section("server").unwrap();
+        assert_eq!(server.port, 3000);
+
+        assert!(matches!(config.section::<SmtpConfig>("queue"), Err(ConfigError::MissingSection(name)) if name == "queue"));
+        let err = config.section::<ServerConfig>("smtp").unwrap_err();
+        assert!(matches!(err, ConfigError::InvalidSection { ref name, .. } if name == "smtp"));
+        assert!(err.to_string().starts_with("Config section [smtp] is invalid"));
+    }
+
+    #[test]
+    fn get_path_reads_dotted_values() {
+        let config = with_smtp();
+        assert_eq!(config.get_path("smtp.host").as_deref(), Some("mail.local"));
+        assert_eq!(config.get_path("smtp.port").as_deref(), Some("2525"));
+        assert_eq!(config.get_path("smtp.auth.user").as_deref(), Some("app"));
+        assert_eq!(config.get_path("features.compression").as_deref(), Some("true"));
+        assert_eq!(config.get_path("environment").as_deref(), Some("development"));
+
+        // Tables and missing paths have no value
+        assert_eq!(config.get_path("smtp"), None);
+        assert_eq!(config.get_path("smtp.auth"), None);
+        assert_eq!(config.get_path("smtp.password"), None);
+        assert_eq!(config.get_path("smtp.host.name"), None);
+    }
+}
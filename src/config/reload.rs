@@ -0,0 +1,161 @@
+use super::{get_config, Config, ConfigError};
+use log::info;
+use once_cell::sync::OnceCell;
+use std::sync::Arc;
+use tokio::sync::watch;
+
+// Holds the current reloadable config; receivers are notified on every change
+static DYNAMIC_CONFIG: OnceCell<watch::Sender<Arc<Config>>> = OnceCell::new();
+
+fn dynamic() -> &'static watch::Sender<Arc<Config>> {
+    DYNAMIC_CONFIG.get_or_init(|| watch::channel(Arc::new(get_config().clone())).0)
+}
+
+// The latest config, including reloads. Starts out as `get_config()`, which itself stays the
+// snapshot taken at first access.
+pub fn get_config_dyn() -> Arc<Config> {
+    dynamic().borrow().clone()
+}
+
+// Fires whenever a reload changes the config, e.g.
+// `let mut changes = subscribe_config(); while changes.changed().await.is_ok() { .. }`
+pub fn subscribe_config() -> watch::Receiver<Arc<Config>> {
+    dynamic().subscribe()
+}
+
+// Loads `path` like `Config::load` and, if it's valid and differs from the current config,
+// publishes it. On error the current config stays in place.
+pub fn reload_config(path: &str) -> Result<Arc<Config>, ConfigError> {
+    let config = Arc::new(Config::load(Some(path))?);
+    let changed = dynamic().send_if_modified(|current| {
+        // Config has no PartialEq; compare what would be written out
+        if toml::to_string(&**current).ok() == toml::to_string(&*config).ok() {
+            return false;
+        }
+        *current = config.clone();
+        true
+    });
+    if changed {
+        info!("Configuration reloaded from {}", path);
+    }
+    Ok(config)
+}
+
+// Watches `path` (and its `config.{RUSTNEXT_ENV}.toml`) and calls `reload_config` when either
+// changes. Invalid files are logged and ignored. Must be called inside the Tokio runtime;
+// watching stops when the returned value is dropped.
+#[cfg(feature = "config-watch")]
+pub fn watch(path: &str) -> Result<ConfigWatcher, notify::Error> {
+    use super::environment_file;
+    use log::error;
+    use notify::{RecursiveMode, Watcher};
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
+
+    let file = PathBuf::from(path);
+    let environment = get_config_dyn().environment.clone();
+    let names: Vec<std::ffi::OsString> = [file.clone(), PathBuf::from(environment_file(path, &environment))]
+        .iter()
+        .filter_map(|p| p.file_name().map(|n| n.to_os_string()))
+        .collect();
+    // The directory rather than the file: editors often save by replacing the file
+    let dir = match file.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |res: Result<notify::Event, notify::Error>| {
+        if let Ok(event) = res {
+            let relevant = !matches!(event.kind, notify::EventKind::Access(_))
+                && event.paths.iter().any(|p| p.file_name().is_some_and(|n| names.iter().any(|name| name == n)));
+            if relevant {
+                let _ = tx.send(());
+            }
+        }
+    })?;
+    watcher.watch(Path::new(&dir), RecursiveMode::NonRecursive)?;
+
+    info!("Watching {} for configuration changes", path);
+    let path = path.to_string();
+    let task = tokio::spawn(async move {
+        while rx.recv().await.is_some() {
+            // A save is often several events; reload once it settles
+            while let Ok(Some(())) = tokio::time::timeout(Duration::from_millis(200), rx.recv()).await {}
+            if let Err(e) = reload_config(&path) {
+                error!("Keeping the current configuration: {}", e);
+            }
+        }
+    });
+
+    Ok(ConfigWatcher { _watcher: watcher, task })
+}
+
+#[cfg(feature = "config-watch")]
+pub struct ConfigWatcher {
+    _watcher: notify::RecommendedWatcher,
+    task: tokio::task::JoinHandle<()>,
+}
+
+#[cfg(feature = "config-watch")]
+impl Drop for ConfigWatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::tests::{env_lock, write};
+
+    // The dynamic config is process-wide
+    static RELOAD_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+    fn reload(path: &str) -> Result<Arc<Config>, ConfigError> {
+        let _env = env_lock();
+        reload_config(path)
+    }
+
+    #[tokio::test]
+    async fn reload_publishes_only_valid_changes() {
+        let _reload = RELOAD_LOCK.lock().await;
+        let dir = tempfile::tempdir().unwrap();
+        let path = write(&dir, "config.toml", "[server]\nport = 4001\n");
+        let mut changes = subscribe_config();
+        changes.borrow_and_update();
+
+        reload(&path).unwrap();
+        assert!(changes.has_changed().unwrap());
+        assert_eq!(changes.borrow_and_update().server.port, 4001);
+        assert_eq!(get_config_dyn().server.port, 4001);
+
+        // Same contents: nothing to announce
+        reload(&path).unwrap();
+        assert!(!changes.has_changed().unwrap());
+
+        write(&dir, "config.toml", "[server]\nport = 0\n");
+        assert!(matches!(reload(&path), Err(ConfigError::Invalid(_))));
+        assert!(!changes.has_changed().unwrap());
+        assert_eq!(get_config_dyn().server.port, 4001);
+    }
+
+    #[cfg(feature = "config-watch")]
+    #[tokio::test]
+    async fn watch_reloads_when_the_file_changes() {
+        let _reload = RELOAD_LOCK.lock().await;
+        let dir = tempfile::tempdir().unwrap();
+        let path = write(&dir, "config.toml", "[server]\nport = 4101\n");
+        reload(&path).unwrap();
+        let mut changes = subscribe_config();
+        changes.borrow_and_update();
+
+        let _watcher = watch(&path).unwrap();
+        write(&dir, "config.toml", "[server]\nport = 4102\n");
+        tokio::time::timeout(std::time::Duration::from_secs(5), changes.changed())
+            .await
+            .expect("no reload within 5s")
+            .unwrap();
+        assert_eq!(get_config_dyn().server.port, 4102);
+    }
+}
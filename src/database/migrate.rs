@@ -0,0 +1,530 @@
+use super::{Database, QueryParams, Row};
+use log::{info, warn};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+const MIGRATIONS_TABLE: &str = "_rustnext_migrations";
+
+// One SQL file. The version is the file name's numeric prefix (`0001_init.sql` is version 1).
+#[derive(Debug, Clone)]
+pub struct Migration {
+    pub version: i64,
+    pub name: String,
+    pub sql: String,
+    pub checksum: String,
+}
+
+impl Migration {
+    pub fn new(name: &str, sql: &str) -> Result<Self, MigrationError> {
+        let version = name
+            .split(|c: char| !c.is_ascii_digit())
+            .next()
+            .filter(|digits| !digits.is_empty())
+            .and_then(|digits| digits.parse().ok())
+            .ok_or_else(|| MigrationError::InvalidName(name.to_string()))?;
+        // Line endings don't count, so a checkout with CRLF conversion doesn't look like an edit
+        let sql = sql.replace("\r\n", "\n");
+        Ok(Migration {
+            version,
+            name: name.to_string(),
+            checksum: format!("{:x}", md5::compute(sql.as_bytes())),
+            sql,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum MigrationError {
+    Io { path: PathBuf, source: std::io::Error },
+    // File names must start with a version number, e.g. `0001_init.sql`
+    InvalidName(String),
+    DuplicateVersion { version: i64, first: String, second: String },
+    // An applied migration's file was edited afterwards
+    ChecksumMismatch { name: String, applied: String, current: String },
+    // A statement in `name` failed; `line` is where it starts in the file
+    Statement { name: String, line: usize, statement: String, source: sqlx::Error },
+    Database(sqlx::Error),
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationError::Io { path, source } => write!(f, "Failed to read migrations from {}: {}", path.display(), source),
+            MigrationError::InvalidName(name) => {
+                write!(f, "Migration file '{}' must start with a version number, e.g. 0001_init.sql", name)
+            }
+            MigrationError::DuplicateVersion { version, first, second } => {
+                write!(f, "Migrations '{}' and '{}' both have version {}", first, second, version)
+            }
+            MigrationError::ChecksumMismatch { name, applied, current } => write!(
+                f,
+                "Migration '{}' was changed after it was applied (checksum {} applied, {} now); add a new migration instead",
+                name, applied, current
+            ),
+            MigrationError::Statement { name, line, statement, source } => {
+                write!(f, "Migration '{}' failed at line {}: {}\n  in: {}", name, line, source, abbreviate(statement))
+            }
+            MigrationError::Database(e) => write!(f, "Migration bookkeeping failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for MigrationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MigrationError::Io { source, .. } => Some(source),
+            MigrationError::Statement { source, .. } | MigrationError::Database(source) => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<sqlx::Error> for MigrationError {
+    fn from(e: sqlx::Error) -> Self {
+        MigrationError::Database(e)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrationInfo {
+    pub version: i64,
+    pub name: String,
+    // RFC 3339; `None` for pending migrations
+    pub applied_at: Option<String>,
+}
+
+// What `Migrator::status` found, e.g. for a health endpoint. `modified` lists applied migrations
+// whose file has changed since; `run` refuses to proceed while it's non-empty.
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrationStatus {
+    pub applied: Vec<MigrationInfo>,
+    pub pending: Vec<MigrationInfo>,
+    pub modified: Vec<String>,
+}
+
+impl MigrationStatus {
+    pub fn is_up_to_date(&self) -> bool {
+        self.pending.is_empty() && self.modified.is_empty()
+    }
+}
+
+// Applies numbered SQL files in order, each in its own transaction, recording them in
+// `_rustnext_migrations`. Load them with `Migrator::from_dir("migrations")` or compile them in
+// with `embed_migrations!`, then call `run(&db)` at startup.
+//
+// Files are split into statements on `;`, outside quotes, comments and Postgres `$$` bodies.
+// A SQLite `CREATE TRIGGER` runs until its `END;`.
+#[derive(Debug, Clone, Default)]
+pub struct Migrator {
+    migrations: Vec<Migration>,
+}
+
+impl Migrator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Every `*.sql` file in `dir`; other files are ignored
+    pub fn from_dir(dir: impl AsRef<Path>) -> Result<Self, MigrationError> {
+        let dir = dir.as_ref();
+        let io_error = |source| MigrationError::Io { path: dir.to_path_buf(), source };
+        let mut migrator = Migrator::new();
+        for entry in std::fs::read_dir(dir).map_err(io_error)? {
+            let path = entry.map_err(io_error)?.path();
+            if !path.is_file() || path.extension().and_then(|ext| ext.to_str()) != Some("sql") {
+                continue;
+            }
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default().to_string();
+            let sql = std::fs::read_to_string(&path).map_err(|source| MigrationError::Io { path: path.clone(), source })?;
+            migrator.push(Migration::new(&name, &sql)?)?;
+        }
+        Ok(migrator)
+    }
+
+    // (file name, contents) pairs; see `embed_migrations!`
+    pub fn embedded(files: &[(&str, &str)]) -> Result<Self, MigrationError> {
+        let mut migrator = Migrator::new();
+        for (name, sql) in files {
+            migrator.push(Migration::new(name, sql)?)?;
+        }
+        Ok(migrator)
+    }
+
+    // Keeps migrations ordered by version whatever order they're added in
+    pub fn push(&mut self, migration: Migration) -> Result<(), MigrationError> {
+        if let Some(existing) = self.migrations.iter().find(|m| m.version == migration.version) {
+            return Err(MigrationError::DuplicateVersion {
+                version: migration.version,
+                first: existing.name.clone(),
+                second: migration.name,
+            });
+        }
+        let index = self.migrations.partition_point(|m| m.version < migration.version);
+        self.migrations.insert(index, migration);
+        Ok(())
+    }
+
+    pub fn migrations(&self) -> &[Migration] {
+        &self.migrations
+    }
+
+    // Applies every pending migration and returns their names. Fails before applying anything
+    // if an applied file has been edited; a failing migration is rolled back and stops the run.
+    pub async fn run(&self, db: &Database) -> Result<Vec<String>, MigrationError> {
+        let applied = self.applied(db).await?;
+        if let Some(err) = self.checksum_mismatches(&applied).into_iter().next() {
+            return Err(err);
+        }
+
+        let mut newly_applied = Vec::new();
+        for migration in self.migrations.iter().filter(|m| !applied.contains_key(&m.version)) {
+            apply(db, migration).await?;
+            info!("Applied migration {}", migration.name);
+            newly_applied.push(migration.name.clone());
+        }
+        Ok(newly_applied)
+    }
+
+    pub async fn status(&self, db: &Database) -> Result<MigrationStatus, MigrationError> {
+        let applied = self.applied(db).await?;
+        let mut applied_list: Vec<MigrationInfo> = applied
+            .values()
+            .map(|record| MigrationInfo {
+                version: record.version,
+                name: record.name.clone(),
+                applied_at: Some(record.applied_at.clone()),
+            })
+            .collect();
+        applied_list.sort_by_key(|info| info.version);
+
+        let pending = self.migrations.iter()
+            .filter(|m| !applied.contains_key(&m.version))
+            .map(|m| MigrationInfo { version: m.version, name: m.name.clone(), applied_at: None })
+            .collect();
+        let modified = self.checksum_mismatches(&applied)
+            .into_iter()
+            .filter_map(|err| match err {
+                MigrationError::ChecksumMismatch { name, .. } => Some(name),
+                _ => None,
+            })
+            .collect();
+
+        Ok(MigrationStatus { applied: applied_list, pending, modified })
+    }
+
+    async fn applied(&self, db: &Database) -> Result<HashMap<i64, AppliedRecord>, MigrationError> {
+        db.execute(&format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                version BIGINT PRIMARY KEY,
+                name TEXT NOT NULL,
+                checksum TEXT NOT NULL,
+                applied_at TEXT NOT NULL
+            )",
+            MIGRATIONS_TABLE
+        ))
+        .await?;
+
+        let rows = db
+            .fetch_rows(&format!("SELECT version, name, checksum, applied_at FROM {}", MIGRATIONS_TABLE), QueryParams::new())
+            .await?;
+        let records = rows.iter().filter_map(AppliedRecord::from_row).map(|record| (record.version, record)).collect::<HashMap<_, _>>();
+
+        for record in records.values() {
+            if !self.migrations.iter().any(|m| m.version == record.version) {
+                warn!("Applied migration {} ({}) has no matching file", record.version, record.name);
+            }
+        }
+        Ok(records)
+    }
+
+    fn checksum_mismatches(&self, applied: &HashMap<i64, AppliedRecord>) -> Vec<MigrationError> {
+        self.migrations.iter()
+            .filter_map(|m| {
+                let record = applied.get(&m.version)?;
+                (record.checksum != m.checksum).then(|| MigrationError::ChecksumMismatch {
+                    name: m.name.clone(),
+                    applied: record.checksum.clone(),
+                    current: m.checksum.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+struct AppliedRecord {
+    version: i64,
+    name: String,
+    checksum: String,
+    applied_at: String,
+}
+
+impl AppliedRecord {
+    fn from_row(row: &Row) -> Option<Self> {
+        let text = |column: &str| row.get(column).and_then(|v| v.as_str()).map(str::to_string);
+        Some(AppliedRecord {
+            version: row.get("version")?.as_i64()?,
+            name: text("name")?,
+            checksum: text("checksum")?,
+            applied_at: text("applied_at")?,
+        })
+    }
+}
+
+async fn apply(db: &Database, migration: &Migration) -> Result<(), MigrationError> {
+    let statements = split_statements(&migration.sql);
+    let version = migration.version;
+    let name = migration.name.clone();
+    let checksum = migration.checksum.clone();
+
+    db.transaction(move |tx| {
+        Box::pin(async move {
+            for (line, statement) in statements {
+                if let Err(source) = tx.execute(&statement).await {
+                    return Err(MigrationError::Statement { name, line, statement, source });
+                }
+            }
+            // Also guards against two instances migrating at once: the second insert conflicts
+            // and its transaction rolls back
+            let record = format!(
+                "INSERT INTO {} (version, name, checksum, applied_at) VALUES ($1, $2, $3, $4)",
+                MIGRATIONS_TABLE
+            );
+            let params = QueryParams::new()
+                .bind(version)
+                .bind(name.clone())
+                .bind(checksum)
+                .bind(chrono::Utc::now().to_rfc3339());
+            tx.execute_with(&record, params).await?;
+            Ok(())
+        })
+    })
+    .await
+}
+
+// Each statement with the line it starts on (comments before it are dropped)
+fn split_statements(sql: &str) -> Vec<(usize, String)> {
+    let bytes = sql.as_bytes();
+    let mut statements = Vec::new();
+    // Byte offset and line of the current statement's first code, if any yet
+    let mut current: Option<(usize, usize)> = None;
+    let mut line = 1;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let end = match bytes[i] {
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                sql[i..].find('\n').map_or(sql.len(), |n| i + n)
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                sql[i + 2..].find("*/").map_or(sql.len(), |n| i + 2 + n + 2)
+            }
+            quote @ (b'\'' | b'"' | b'`') => {
+                current.get_or_insert((i, line));
+                // A doubled quote inside a string reads as closing and reopening, which is harmless
+                sql[i + 1..].find(quote as char).map_or(sql.len(), |n| i + 1 + n + 1)
+            }
+            b'$' => {
+                current.get_or_insert((i, line));
+                match dollar_tag(&sql[i..]) {
+                    Some(tag) => sql[i + tag.len()..].find(tag).map_or(sql.len(), |n| i + tag.len() + n + tag.len()),
+                    None => i + 1,
+                }
+            }
+            b';' => {
+                if let Some((start, start_line)) = current {
+                    let text = sql[start..i].trim_end();
+                    if is_trigger(text) && !ends_with_word(text, "END") {
+                        i += 1;
+                        continue;
+                    }
+                    statements.push((start_line, text.to_string()));
+                }
+                current = None;
+                i + 1
+            }
+            b if b.is_ascii_whitespace() => i + 1,
+            _ => {
+                current.get_or_insert((i, line));
+                i + 1
+            }
+        };
+        line += sql[i..end].matches('\n').count();
+        i = end;
+    }
+
+    if let Some((start, start_line)) = current {
+        statements.push((start_line, sql[start..].trim_end().to_string()));
+    }
+    statements
+}
+
+// `$$` or `$tag$` opening a Postgres dollar-quoted string (`$1` is a placeholder, not a tag)
+fn dollar_tag(s: &str) -> Option<&str> {
+    let rest = &s[1..];
+    let len = rest.find('$')?;
+    let tag = &rest[..len];
+    let valid = !tag.starts_with(|c: char| c.is_ascii_digit())
+        && tag.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    valid.then(|| &s[..len + 2])
+}
+
+fn is_trigger(statement: &str) -> bool {
+    let mut words = statement.split_whitespace().map(|w| w.to_ascii_uppercase());
+    if words.next().as_deref() != Some("CREATE") {
+        return false;
+    }
+    match words.next().as_deref() {
+        Some("TEMP") | Some("TEMPORARY") => words.next().as_deref() == Some("TRIGGER"),
+        Some("TRIGGER") => true,
+        _ => false,
+    }
+}
+
+fn ends_with_word(text: &str, word: &str) -> bool {
+    text.rsplit(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .next()
+        .is_some_and(|last| last.eq_ignore_ascii_case(word))
+}
+
+fn abbreviate(statement: &str) -> String {
+    let flat = statement.split_whitespace().collect::<Vec<_>>().join(" ");
+    match flat.char_indices().nth(120) {
+        Some((cut, _)) => format!("{}...", &flat[..cut]),
+        None => flat,
+    }
+}
+
+// Compiles migration files into the binary, paths relative to the calling source file:
+// `let migrator = rustnext::embed_migrations!("../migrations", "0001_init.sql", "0002_posts.sql")?;`
+#[macro_export]
+macro_rules! embed_migrations {
+    ($dir:literal, $($file:literal),+ $(,)?) => {
+        $crate::database::Migrator::embedded(&[
+            $(($file, include_str!(concat!($dir, "/", $file)))),+
+        ])
+    };
+}
+
+#[cfg(all(test, feature = "database-sqlite"))]
+mod tests {
+    use super::*;
+
+    const INIT: (&str, &str) = (
+        "0001_init.sql",
+        "-- users; first table\nCREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT NOT NULL);\n\
+         INSERT INTO users (name) VALUES ('a;b');",
+    );
+    const POSTS: (&str, &str) = ("0002_posts.sql", "CREATE TABLE posts (id INTEGER PRIMARY KEY, user_id INTEGER);");
+
+    async fn tables(db: &Database) -> Vec<String> {
+        let rows = db
+            .fetch_rows("SELECT name FROM sqlite_master WHERE type = 'table' ORDER BY name", QueryParams::new())
+            .await
+            .unwrap();
+        rows.iter().filter_map(|row| row["name"].as_str().map(str::to_string)).collect()
+    }
+
+    #[tokio::test]
+    async fn fresh_database_applies_everything_in_order() {
+        let db = Database::new("sqlite::memory:").await.unwrap();
+        // Added out of order on purpose
+        let migrator = Migrator::embedded(&[POSTS, INIT]).unwrap();
+
+        let applied = migrator.run(&db).await.unwrap();
+        assert_eq!(applied, ["0001_init.sql", "0002_posts.sql"]);
+        assert_eq!(tables(&db).await, [MIGRATIONS_TABLE, "posts", "users"]);
+
+        let users = db.fetch_rows("SELECT name FROM users", QueryParams::new()).await.unwrap();
+        assert_eq!(users[0]["name"], "a;b");
+
+        let status = migrator.status(&db).await.unwrap();
+        assert!(status.is_up_to_date());
+        assert_eq!(status.applied.iter().map(|m| m.version).collect::<Vec<_>>(), [1, 2]);
+        assert!(status.applied.iter().all(|m| m.applied_at.is_some()));
+    }
+
+    #[tokio::test]
+    async fn running_again_is_a_no_op() {
+        let db = Database::new("sqlite::memory:").await.unwrap();
+        Migrator::embedded(&[INIT]).unwrap().run(&db).await.unwrap();
+
+        let migrator = Migrator::embedded(&[INIT, POSTS]).unwrap();
+        let status = migrator.status(&db).await.unwrap();
+        assert!(!status.is_up_to_date());
+        assert_eq!(status.pending.len(), 1);
+        assert_eq!(status.pending[0].name, "0002_posts.sql");
+
+        assert_eq!(migrator.run(&db).await.unwrap(), ["0002_posts.sql"]);
+        assert!(migrator.run(&db).await.unwrap().is_empty());
+        let users = db.fetch_rows("SELECT name FROM users", QueryParams::new()).await.unwrap();
+        assert_eq!(users.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn edited_migrations_are_refused() {
+        let db = Database::new("sqlite::memory:").await.unwrap();
+        Migrator::embedded(&[INIT]).unwrap().run(&db).await.unwrap();
+
+        let edited = (INIT.0, "CREATE TABLE users (id INTEGER PRIMARY KEY);");
+        let migrator = Migrator::embedded(&[edited, POSTS]).unwrap();
+
+        let status = migrator.status(&db).await.unwrap();
+        assert_eq!(status.modified, ["0001_init.sql"]);
+        assert!(!status.is_up_to_date());
+
+        let err = migrator.run(&db).await.unwrap_err();
+        assert!(matches!(err, MigrationError::ChecksumMismatch { ref name, .. } if name == "0001_init.sql"));
+        // Nothing was applied, not even the new migration
+        assert!(!tables(&db).await.contains(&"posts".to_string()));
+
+        // CRLF line endings are not an edit
+        let crlf = (INIT.0, &*INIT.1.replace('\n', "\r\n"));
+        assert!(Migrator::embedded(&[crlf]).unwrap().status(&db).await.unwrap().modified.is_empty());
+    }
+
+    #[tokio::test]
+    async fn failing_migration_is_rolled_back_and_reports_its_line() {
+        let db = Database::new("sqlite::memory:").await.unwrap();
+        let broken = ("0002_broken.sql", "CREATE TABLE tags (id INTEGER);\n\nINSERT INTO nowhere VALUES (1);");
+        let migrator = Migrator::embedded(&[INIT, broken]).unwrap();
+
+        match migrator.run(&db).await.unwrap_err() {
+            MigrationError::Statement { name, line, statement, .. } => {
+                assert_eq!(name, "0002_broken.sql");
+                assert_eq!(line, 3);
+                assert_eq!(statement, "INSERT INTO nowhere VALUES (1)");
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+        let tables = tables(&db).await;
+        assert!(tables.contains(&"users".to_string()));
+        assert!(!tables.contains(&"tags".to_string()));
+        assert_eq!(migrator.status(&db).await.unwrap().pending.len(), 1);
+    }
+
+    #[test]
+    fn names_need_a_version_and_versions_are_unique() {
+        assert_eq!(Migration::new("0042_add_index.sql", "").unwrap().version, 42);
+        assert!(matches!(Migration::new("init.sql", ""), Err(MigrationError::InvalidName(_))));
+
+        let err = Migrator::embedded(&[INIT, ("001_other.sql", "")]).unwrap_err();
+        assert!(matches!(err, MigrationError::DuplicateVersion { version: 1, .. }));
+    }
+
+    #[test]
+    fn statements_split_outside_quotes_comments_and_bodies() {
+        let sql = "CREATE TABLE t (note TEXT DEFAULT 'x;y'); -- trailing; comment\n\
+                   /* block; */ CREATE FUNCTION f() RETURNS trigger AS $$ BEGIN; END; $$ LANGUAGE plpgsql;\n\
+                   CREATE TRIGGER tr AFTER INSERT ON t BEGIN UPDATE t SET note = 'z'; END;\n\
+                   SELECT $1";
+        let statements = split_statements(sql);
+        let lines: Vec<usize> = statements.iter().map(|(line, _)| *line).collect();
+        assert_eq!(lines, [1, 2, 3, 4]);
+        assert_eq!(statements[0].1, "CREATE TABLE t (note TEXT DEFAULT 'x;y')");
+        assert!(statements[1].1.ends_with("$$ LANGUAGE plpgsql"));
+        assert!(statements[2].1.ends_with("SET note = 'z'; END"));
+        assert_eq!(statements[3].1, "SELECT $1");
+    }
+}
@@ -0,0 +1,619 @@
+#[cfg(feature = "database-postgres")] // Conditional compilation
+use sqlx::{postgres::{PgArguments, PgPool, PgPoolOptions, PgRow}, Postgres};
+#[cfg(feature = "database-sqlite")]
+use sqlx::{sqlite::{SqliteArguments, SqliteConnectOptions, SqlitePool, SqlitePoolOptions, SqliteRow}, Sqlite};
+#[cfg(any(feature = "database-postgres", feature = "database-sqlite"))]
+use sqlx::{Column, Row as _, Transaction, TypeInfo, ValueRef};
+#[cfg(any(feature = "database-postgres", feature = "database-sqlite"))]
+use futures::future::BoxFuture;
+#[cfg(any(feature = "database-postgres", feature = "database-sqlite"))]
+use crate::config::{get_config, DatabaseConfig};
+#[cfg(any(feature = "database-postgres", feature = "database-sqlite"))]
+use serde_json::{Map, Value};
+#[cfg(any(feature = "database-postgres", feature = "database-sqlite"))]
+use std::marker::PhantomData;
+#[cfg(any(feature = "database-postgres", feature = "database-sqlite"))]
+use std::time::Duration;
+use once_cell::sync::OnceCell;
+use log::{info, warn}; // New import for logging
+
+#[cfg(any(feature = "database-postgres", feature = "database-sqlite"))]
+mod migrate;
+#[cfg(any(feature = "database-postgres", feature = "database-sqlite"))]
+pub use migrate::{Migration, MigrationError, MigrationInfo, MigrationStatus, Migrator};
+
+// Which driver a `Database` talks to, picked from the URL scheme:
+// `postgres://` / `postgresql://`, or `sqlite:` (e.g. `sqlite://app.db`, `sqlite::memory:`)
+#[cfg(any(feature = "database-postgres", feature = "database-sqlite"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Postgres,
+    Sqlite,
+}
+
+#[cfg(any(feature = "database-postgres", feature = "database-sqlite"))]
+impl Backend {
+    pub fn from_url(url: &str) -> Option<Self> {
+        if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+            Some(Backend::Postgres)
+        } else if url.starts_with("sqlite:") {
+            Some(Backend::Sqlite)
+        } else {
+            None
+        }
+    }
+}
+
+// The driver pool behind a `Database`, see `Database::pool`
+#[cfg(any(feature = "database-postgres", feature = "database-sqlite"))]
+#[derive(Clone)]
+pub enum Pool {
+    #[cfg(feature = "database-postgres")]
+    Postgres(PgPool),
+    #[cfg(feature = "database-sqlite")]
+    Sqlite(SqlitePool),
+}
+
+// A row as column name -> JSON value, for code that shouldn't depend on the driver
+#[cfg(any(feature = "database-postgres", feature = "database-sqlite"))]
+pub type Row = Map<String, Value>;
+
+// Rows `fetch_*_as` can map into: anything deriving `sqlx::FromRow` for every enabled backend
+#[cfg(all(feature = "database-postgres", feature = "database-sqlite"))]
+pub trait FromDbRow: for<'r> sqlx::FromRow<'r, PgRow> + for<'r> sqlx::FromRow<'r, SqliteRow> + Send + Unpin {}
+#[cfg(all(feature = "database-postgres", feature = "database-sqlite"))]
+impl<T> FromDbRow for T where T: for<'r> sqlx::FromRow<'r, PgRow> + for<'r> sqlx::FromRow<'r, SqliteRow> + Send + Unpin {}
+
+#[cfg(all(feature = "database-postgres", not(feature = "database-sqlite")))]
+pub trait FromDbRow: for<'r> sqlx::FromRow<'r, PgRow> + Send + Unpin {}
+#[cfg(all(feature = "database-postgres", not(feature = "database-sqlite")))]
+impl<T> FromDbRow for T where T: for<'r> sqlx::FromRow<'r, PgRow> + Send + Unpin {}
+
+#[cfg(all(feature = "database-sqlite", not(feature = "database-postgres")))]
+pub trait FromDbRow: for<'r> sqlx::FromRow<'r, SqliteRow> + Send + Unpin {}
+#[cfg(all(feature = "database-sqlite", not(feature = "database-postgres")))]
+impl<T> FromDbRow for T where T: for<'r> sqlx::FromRow<'r, SqliteRow> + Send + Unpin {}
+
+#[cfg(any(feature = "database-postgres", feature = "database-sqlite"))]
+#[derive(Clone)]
+pub struct Database {
+    pool: Pool,
+}
+
+#[cfg(any(feature = "database-postgres", feature = "database-sqlite"))]
+impl Database {
+    // Uses the pool size and timeout from the global config
+    pub async fn new(database_url: &str) -> Result<Self, sqlx::Error> {
+        let config = DatabaseConfig {
+            url: database_url.to_string(),
+            ..get_config().database.clone()
+        };
+        Self::from_config(&config).await
+    }
+
+    pub async fn from_config(config: &DatabaseConfig) -> Result<Self, sqlx::Error> {
+        let timeout = Duration::from_secs(config.timeout);
+        let connect = async {
+            #[allow(unreachable_patterns)]
+            match Backend::from_url(&config.url) {
+                #[cfg(feature = "database-postgres")]
+                Some(Backend::Postgres) => Self::connect_postgres(config, timeout).await,
+                #[cfg(feature = "database-sqlite")]
+                Some(Backend::Sqlite) => Self::connect_sqlite(config, timeout).await,
+                Some(backend) => Err(sqlx::Error::Configuration(
+                    format!("{:?} support is not enabled (see the database-* features)", backend).into(),
+                )),
+                None => Err(sqlx::Error::Configuration(
+                    format!("Unsupported database URL scheme: {}", config.url).into(),
+                )),
+            }
+        };
+
+        // Bound the initial connection attempt by the same timeout
+        let pool = tokio::time::timeout(timeout, connect)
+            .await
+            .map_err(|_| sqlx::Error::PoolTimedOut)??;
+
+        Ok(Database { pool })
+    }
+
+    #[cfg(feature = "database-postgres")]
+    async fn connect_postgres(config: &DatabaseConfig, timeout: Duration) -> Result<Pool, sqlx::Error> {
+        let pool = PgPoolOptions::new()
+            .max_connections(config.max_connections)
+            .acquire_timeout(timeout)
+            .connect(&config.url)
+            .await?;
+        Ok(Pool::Postgres(pool))
+    }
+
+    #[cfg(feature = "database-sqlite")]
+    async fn connect_sqlite(config: &DatabaseConfig, timeout: Duration) -> Result<Pool, sqlx::Error> {
+        let options: SqliteConnectOptions = config.url.parse()?;
+        let options = options.create_if_missing(true);
+        let mut pool_options = SqlitePoolOptions::new().acquire_timeout(timeout);
+        // Every connection to `:memory:` is a separate, empty database, so keep exactly one open
+        if config.url.contains(":memory:") || config.url.contains("mode=memory") {
+            pool_options = pool_options
+                .max_connections(1)
+                .idle_timeout(None)
+                .max_lifetime(None);
+        } else {
+            pool_options = pool_options.max_connections(config.max_connections);
+        }
+        Ok(Pool::Sqlite(pool_options.connect_with(options).await?))
+    }
+
+    pub fn backend(&self) -> Backend {
+        match &self.pool {
+            #[cfg(feature = "database-postgres")]
+            Pool::Postgres(_) => Backend::Postgres,
+            #[cfg(feature = "database-sqlite")]
+            Pool::Sqlite(_) => Backend::Sqlite,
+        }
+    }
+
+    // The driver pool, for anything the helpers below don't cover (e.g. `sqlx::query_as!` or
+    // streaming with `fetch`). `postgres_pool`/`sqlite_pool` when only one backend is expected.
+    pub fn pool(&self) -> &Pool {
+        &self.pool
+    }
+
+    #[cfg(feature = "database-postgres")]
+    pub fn postgres_pool(&self) -> Option<&PgPool> {
+        #[allow(unreachable_patterns)]
+        match &self.pool {
+            Pool::Postgres(pool) => Some(pool),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "database-sqlite")]
+    pub fn sqlite_pool(&self) -> Option<&SqlitePool> {
+        #[allow(unreachable_patterns)]
+        match &self.pool {
+            Pool::Sqlite(pool) => Some(pool),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "database-postgres")]
+    fn require_postgres(&self) -> Result<&PgPool, sqlx::Error> {
+        self.postgres_pool().ok_or_else(|| {
+            sqlx::Error::Configuration("PgRow methods need a Postgres database; use fetch_rows or fetch_*_as".into())
+        })
+    }
+
+    // The raw methods below are for static SQL only. Never format user input
+    // into `query`; use the `*_with` variants and bind values instead.
+    pub async fn execute(&self, query: &str) -> Result<u64, sqlx::Error> {
+        self.execute_with(query, QueryParams::new()).await
+    }
+
+    #[cfg(feature = "database-postgres")]
+    pub async fn fetch_one(&self, query: &str) -> Result<PgRow, sqlx::Error> {
+        sqlx::query(query).fetch_one(self.require_postgres()?).await
+    }
+
+    #[cfg(feature = "database-postgres")]
+    pub async fn fetch_all(&self, query: &str) -> Result<Vec<PgRow>, sqlx::Error> {
+        sqlx::query(query).fetch_all(self.require_postgres()?).await
+    }
+
+    // Parameterized variants: placeholders ($1, $2, ...) are bound from `params`. SQLite
+    // understands the same `$N` placeholders, so these queries work on either backend.
+    pub async fn execute_with<'q>(&self, query: &'q str, params: QueryParams<'q>) -> Result<u64, sqlx::Error> {
+        match &self.pool {
+            #[cfg(feature = "database-postgres")]
+            Pool::Postgres(pool) => Ok(sqlx::query_with(query, params.pg).execute(pool).await?.rows_affected()),
+            #[cfg(feature = "database-sqlite")]
+            Pool::Sqlite(pool) => Ok(sqlx::query_with(query, params.sqlite).execute(pool).await?.rows_affected()),
+        }
+    }
+
+    #[cfg(feature = "database-postgres")]
+    pub async fn fetch_one_with<'q>(&self, query: &'q str, params: QueryParams<'q>) -> Result<PgRow, sqlx::Error> {
+        sqlx::query_with(query, params.pg).fetch_one(self.require_postgres()?).await
+    }
+
+    #[cfg(feature = "database-postgres")]
+    pub async fn fetch_all_with<'q>(&self, query: &'q str, params: QueryParams<'q>) -> Result<Vec<PgRow>, sqlx::Error> {
+        sqlx::query_with(query, params.pg).fetch_all(self.require_postgres()?).await
+    }
+
+    // Backend-neutral rows as JSON maps (see `Row`)
+    pub async fn fetch_rows<'q>(&self, query: &'q str, params: QueryParams<'q>) -> Result<Vec<Row>, sqlx::Error> {
+        match &self.pool {
+            #[cfg(feature = "database-postgres")]
+            Pool::Postgres(pool) => {
+                let rows = sqlx::query_with(query, params.pg).fetch_all(pool).await?;
+                Ok(rows.iter().map(pg_row_to_json).collect())
+            }
+            #[cfg(feature = "database-sqlite")]
+            Pool::Sqlite(pool) => {
+                let rows = sqlx::query_with(query, params.sqlite).fetch_all(pool).await?;
+                Ok(rows.iter().map(sqlite_row_to_json).collect())
+            }
+        }
+    }
+
+    pub async fn fetch_row<'q>(&self, query: &'q str, params: QueryParams<'q>) -> Result<Option<Row>, sqlx::Error> {
+        match &self.pool {
+            #[cfg(feature = "database-postgres")]
+            Pool::Postgres(pool) => {
+                let row = sqlx::query_with(query, params.pg).fetch_optional(pool).await?;
+                Ok(row.as_ref().map(pg_row_to_json))
+            }
+            #[cfg(feature = "database-sqlite")]
+            Pool::Sqlite(pool) => {
+                let row = sqlx::query_with(query, params.sqlite).fetch_optional(pool).await?;
+                Ok(row.as_ref().map(sqlite_row_to_json))
+            }
+        }
+    }
+
+    // Typed variants mapping rows into a struct deriving `sqlx::FromRow`
+    pub async fn fetch_one_as<T: FromDbRow>(&self, query: &str) -> Result<T, sqlx::Error> {
+        self.fetch_one_as_with(query, QueryParams::new()).await
+    }
+
+    pub async fn fetch_all_as<T: FromDbRow>(&self, query: &str) -> Result<Vec<T>, sqlx::Error> {
+        self.fetch_all_as_with(query, QueryParams::new()).await
+    }
+
+    pub async fn fetch_one_as_with<'q, T: FromDbRow>(&self, query: &'q str, params: QueryParams<'q>) -> Result<T, sqlx::Error> {
+        match &self.pool {
+            #[cfg(feature = "database-postgres")]
+            Pool::Postgres(pool) => sqlx::query_as_with::<_, T, _>(query, params.pg).fetch_one(pool).await,
+            #[cfg(feature = "database-sqlite")]
+            Pool::Sqlite(pool) => sqlx::query_as_with::<_, T, _>(query, params.sqlite).fetch_one(pool).await,
+        }
+    }
+
+    // `None` instead of `RowNotFound` when nothing matches, e.g. for lookups by id
+    pub async fn fetch_optional_as_with<'q, T: FromDbRow>(&self, query: &'q str, params: QueryParams<'q>) -> Result<Option<T>, sqlx::Error> {
+        match &self.pool {
+            #[cfg(feature = "database-postgres")]
+            Pool::Postgres(pool) => sqlx::query_as_with::<_, T, _>(query, params.pg).fetch_optional(pool).await,
+            #[cfg(feature = "database-sqlite")]
+            Pool::Sqlite(pool) => sqlx::query_as_with::<_, T, _>(query, params.sqlite).fetch_optional(pool).await,
+        }
+    }
+
+    pub async fn fetch_all_as_with<'q, T: FromDbRow>(&self, query: &'q str, params: QueryParams<'q>) -> Result<Vec<T>, sqlx::Error> {
+        match &self.pool {
+            #[cfg(feature = "database-postgres")]
+            Pool::Postgres(pool) => sqlx::query_as_with::<_, T, _>(query, params.pg).fetch_all(pool).await,
+            #[cfg(feature = "database-sqlite")]
+            Pool::Sqlite(pool) => sqlx::query_as_with::<_, T, _>(query, params.sqlite).fetch_all(pool).await,
+        }
+    }
+
+    // Runs `f` inside a transaction: commits if it returns `Ok`, rolls back on `Err`.
+    // Statements must run on the transaction, not on the pool, e.g.
+    // `db.transaction(|tx| Box::pin(async move { tx.execute_with("...", params).await?; Ok(()) }))`
+    pub async fn transaction<F, R, E>(&self, f: F) -> Result<R, E>
+    where
+        F: for<'c> FnOnce(&'c mut DbTransaction) -> BoxFuture<'c, Result<R, E>> + Send,
+        R: Send,
+        E: From<sqlx::Error> + Send,
+    {
+        let mut tx = match &self.pool {
+            #[cfg(feature = "database-postgres")]
+            Pool::Postgres(pool) => DbTransaction::Postgres(pool.begin().await?),
+            #[cfg(feature = "database-sqlite")]
+            Pool::Sqlite(pool) => DbTransaction::Sqlite(pool.begin().await?),
+        };
+        match f(&mut tx).await {
+            Ok(value) => {
+                tx.commit().await?;
+                Ok(value)
+            }
+            Err(err) => {
+                // A failed rollback still leaves the transaction aborted when the connection is dropped
+                if let Err(rollback_err) = tx.rollback().await {
+                    warn!("Transaction rollback failed: {}", rollback_err);
+                }
+                Err(err)
+            }
+        }
+    }
+}
+
+// An open transaction. Match on it for driver-specific work, e.g.
+// `sqlx::query("...").execute(&mut **tx)` on the inner `sqlx::Transaction`.
+#[cfg(any(feature = "database-postgres", feature = "database-sqlite"))]
+#[allow(clippy::large_enum_variant)] // One lives per transaction call; boxing would only complicate matching
+pub enum DbTransaction {
+    #[cfg(feature = "database-postgres")]
+    Postgres(Transaction<'static, Postgres>),
+    #[cfg(feature = "database-sqlite")]
+    Sqlite(Transaction<'static, Sqlite>),
+}
+
+#[cfg(any(feature = "database-postgres", feature = "database-sqlite"))]
+impl DbTransaction {
+    pub async fn execute(&mut self, query: &str) -> Result<u64, sqlx::Error> {
+        self.execute_with(query, QueryParams::new()).await
+    }
+
+    pub async fn execute_with<'q>(&mut self, query: &'q str, params: QueryParams<'q>) -> Result<u64, sqlx::Error> {
+        match self {
+            #[cfg(feature = "database-postgres")]
+            DbTransaction::Postgres(tx) => Ok(sqlx::query_with(query, params.pg).execute(&mut **tx).await?.rows_affected()),
+            #[cfg(feature = "database-sqlite")]
+            DbTransaction::Sqlite(tx) => Ok(sqlx::query_with(query, params.sqlite).execute(&mut **tx).await?.rows_affected()),
+        }
+    }
+
+    pub async fn fetch_rows<'q>(&mut self, query: &'q str, params: QueryParams<'q>) -> Result<Vec<Row>, sqlx::Error> {
+        match self {
+            #[cfg(feature = "database-postgres")]
+            DbTransaction::Postgres(tx) => {
+                let rows = sqlx::query_with(query, params.pg).fetch_all(&mut **tx).await?;
+                Ok(rows.iter().map(pg_row_to_json).collect())
+            }
+            #[cfg(feature = "database-sqlite")]
+            DbTransaction::Sqlite(tx) => {
+                let rows = sqlx::query_with(query, params.sqlite).fetch_all(&mut **tx).await?;
+                Ok(rows.iter().map(sqlite_row_to_json).collect())
+            }
+        }
+    }
+
+    pub async fn fetch_all_as_with<'q, T: FromDbRow>(&mut self, query: &'q str, params: QueryParams<'q>) -> Result<Vec<T>, sqlx::Error> {
+        match self {
+            #[cfg(feature = "database-postgres")]
+            DbTransaction::Postgres(tx) => sqlx::query_as_with::<_, T, _>(query, params.pg).fetch_all(&mut **tx).await,
+            #[cfg(feature = "database-sqlite")]
+            DbTransaction::Sqlite(tx) => sqlx::query_as_with::<_, T, _>(query, params.sqlite).fetch_all(&mut **tx).await,
+        }
+    }
+
+    async fn commit(self) -> Result<(), sqlx::Error> {
+        match self {
+            #[cfg(feature = "database-postgres")]
+            DbTransaction::Postgres(tx) => tx.commit().await,
+            #[cfg(feature = "database-sqlite")]
+            DbTransaction::Sqlite(tx) => tx.commit().await,
+        }
+    }
+
+    async fn rollback(self) -> Result<(), sqlx::Error> {
+        match self {
+            #[cfg(feature = "database-postgres")]
+            DbTransaction::Postgres(tx) => tx.rollback().await,
+            #[cfg(feature = "database-sqlite")]
+            DbTransaction::Sqlite(tx) => tx.rollback().await,
+        }
+    }
+}
+
+#[cfg(feature = "database-postgres")]
+fn pg_row_to_json(row: &PgRow) -> Row {
+    let mut map = Map::new();
+    for column in row.columns() {
+        let i = column.ordinal();
+        let is_null = row.try_get_raw(i).map(|raw| raw.is_null()).unwrap_or(true);
+        let value = if is_null {
+            None
+        } else {
+            match column.type_info().name() {
+                "BOOL" => row.try_get::<bool, _>(i).ok().map(Value::from),
+                "INT2" => row.try_get::<i16, _>(i).ok().map(Value::from),
+                "INT4" => row.try_get::<i32, _>(i).ok().map(Value::from),
+                "INT8" => row.try_get::<i64, _>(i).ok().map(Value::from),
+                "FLOAT4" => row.try_get::<f32, _>(i).ok().map(Value::from),
+                "FLOAT8" => row.try_get::<f64, _>(i).ok().map(Value::from),
+                "JSON" | "JSONB" => row.try_get::<Value, _>(i).ok(),
+                "TIMESTAMPTZ" => row.try_get::<chrono::DateTime<chrono::Utc>, _>(i).ok().map(|t| Value::from(t.to_rfc3339())),
+                "TIMESTAMP" => row.try_get::<chrono::NaiveDateTime, _>(i).ok().map(|t| Value::from(t.to_string())),
+                "DATE" => row.try_get::<chrono::NaiveDate, _>(i).ok().map(|d| Value::from(d.to_string())),
+                "BYTEA" => row.try_get::<Vec<u8>, _>(i).ok().map(Value::from),
+                // Text types; anything else that doesn't decode as text comes out as null
+                _ => row.try_get::<String, _>(i).ok().map(Value::from),
+            }
+        };
+        map.insert(column.name().to_string(), value.unwrap_or(Value::Null));
+    }
+    map
+}
+
+// SQLite types are per value, so the stored type decides, except for columns declared BOOLEAN
+#[cfg(feature = "database-sqlite")]
+fn sqlite_row_to_json(row: &SqliteRow) -> Row {
+    let mut map = Map::new();
+    for column in row.columns() {
+        let i = column.ordinal();
+        let stored = match row.try_get_raw(i) {
+            Ok(raw) if !raw.is_null() => Some(raw.type_info().name().to_string()),
+            _ => None,
+        };
+        let value = match (column.type_info().name(), stored.as_deref()) {
+            (_, None) => None,
+            ("BOOLEAN", _) => row.try_get_unchecked::<bool, _>(i).ok().map(Value::from),
+            (_, Some("INTEGER")) => row.try_get_unchecked::<i64, _>(i).ok().map(Value::from),
+            (_, Some("REAL")) => row.try_get_unchecked::<f64, _>(i).ok().map(Value::from),
+            (_, Some("BLOB")) => row.try_get_unchecked::<Vec<u8>, _>(i).ok().map(Value::from),
+            _ => row.try_get_unchecked::<String, _>(i).ok().map(Value::from),
+        };
+        map.insert(column.name().to_string(), value.unwrap_or(Value::Null));
+    }
+    map
+}
+
+// Values `QueryParams::bind` accepts: encodable for every enabled backend
+#[cfg(all(feature = "database-postgres", feature = "database-sqlite"))]
+pub trait BindValue<'q>:
+    sqlx::Encode<'q, Postgres> + sqlx::Type<Postgres> + sqlx::Encode<'q, Sqlite> + sqlx::Type<Sqlite> + Clone + Send + 'q
+{
+}
+#[cfg(all(feature = "database-postgres", feature = "database-sqlite"))]
+impl<'q, T> BindValue<'q> for T where
+    T: sqlx::Encode<'q, Postgres> + sqlx::Type<Postgres> + sqlx::Encode<'q, Sqlite> + sqlx::Type<Sqlite> + Clone + Send + 'q
+{
+}
+
+#[cfg(all(feature = "database-postgres", not(feature = "database-sqlite")))]
+pub trait BindValue<'q>: sqlx::Encode<'q, Postgres> + sqlx::Type<Postgres> + Send + 'q {}
+#[cfg(all(feature = "database-postgres", not(feature = "database-sqlite")))]
+impl<'q, T> BindValue<'q> for T where T: sqlx::Encode<'q, Postgres> + sqlx::Type<Postgres> + Send + 'q {}
+
+#[cfg(all(feature = "database-sqlite", not(feature = "database-postgres")))]
+pub trait BindValue<'q>: sqlx::Encode<'q, Sqlite> + sqlx::Type<Sqlite> + Send + 'q {}
+#[cfg(all(feature = "database-sqlite", not(feature = "database-postgres")))]
+impl<'q, T> BindValue<'q> for T where T: sqlx::Encode<'q, Sqlite> + sqlx::Type<Sqlite> + Send + 'q {}
+
+// Bind parameters for the `*_with` query methods, e.g.
+// `db.fetch_rows("SELECT * FROM posts WHERE author = $1", QueryParams::new().bind(author))`.
+// Values are encoded for every enabled backend, so the same params work on either.
+#[cfg(any(feature = "database-postgres", feature = "database-sqlite"))]
+#[derive(Default)]
+pub struct QueryParams<'q> {
+    #[cfg(feature = "database-postgres")]
+    pg: PgArguments,
+    #[cfg(feature = "database-sqlite")]
+    sqlite: SqliteArguments<'q>,
+    // Only SQLite arguments borrow
+    _values: PhantomData<&'q ()>,
+}
+
+#[cfg(any(feature = "database-postgres", feature = "database-sqlite"))]
+impl<'q> QueryParams<'q> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn bind<T: BindValue<'q>>(mut self, value: T) -> Self {
+        #[cfg(all(feature = "database-postgres", feature = "database-sqlite"))]
+        sqlx::Arguments::add(&mut self.pg, value.clone());
+        #[cfg(all(feature = "database-postgres", not(feature = "database-sqlite")))]
+        sqlx::Arguments::add(&mut self.pg, value);
+        #[cfg(feature = "database-sqlite")]
+        sqlx::Arguments::add(&mut self.sqlite, value);
+        self
+    }
+}
+
+#[cfg(any(feature = "database-postgres", feature = "database-sqlite"))]
+static GLOBAL_DATABASE: OnceCell<Database> = OnceCell::new();
+
+// Falls back to `database.url` from the global config when no URL is given; the URL scheme
+// picks the backend
+#[cfg(any(feature = "database-postgres", feature = "database-sqlite"))]
+pub async fn init_database(database_url: Option<&str>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut config = get_config().database.clone();
+    if let Some(url) = database_url {
+        config.url = url.to_string();
+    }
+    let db = Database::from_config(&config).await?;
+    let backend = db.backend();
+    if GLOBAL_DATABASE.set(db).is_err() {
+        warn!("Database already initialized, ignoring new initialization.");
+    } else {
+        info!("Database pool initialized ({:?}).", backend);
+    }
+    Ok(())
+}
+
+#[cfg(any(feature = "database-postgres", feature = "database-sqlite"))]
+pub fn get_database() -> Option<&'static Database> {
+    GLOBAL_DATABASE.get()
+}
+
+// Dummy implementations if no database backend is enabled
+#[cfg(not(any(feature = "database-postgres", feature = "database-sqlite")))]
+pub struct Database;
+#[cfg(not(any(feature = "database-postgres", feature = "database-sqlite")))]
+impl Database {
+    pub async fn new(_database_url: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Err("Database feature not enabled".into())
+    }
+}
+#[cfg(not(any(feature = "database-postgres", feature = "database-sqlite")))]
+pub async fn init_database(_database_url: Option<&str>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    log::warn!("Attempted to initialize database, but 'database' feature is not enabled.");
+    Ok(())
+}
+#[cfg(not(any(feature = "database-postgres", feature = "database-sqlite")))]
+pub fn get_database() -> Option<&'static Database> {
+    None
+}
+
+#[cfg(all(test, feature = "database-sqlite"))]
+mod tests {
+    use super::*;
+
+    async fn memory_db() -> Database {
+        let db = Database::new("sqlite::memory:").await.unwrap();
+        db.execute("CREATE TABLE notes (id INTEGER PRIMARY KEY, title TEXT NOT NULL, done BOOLEAN NOT NULL)")
+            .await
+            .unwrap();
+        db
+    }
+
+    #[test]
+    fn backend_follows_the_url_scheme() {
+        assert_eq!(Backend::from_url("postgres://localhost/app"), Some(Backend::Postgres));
+        assert_eq!(Backend::from_url("postgresql://localhost/app"), Some(Backend::Postgres));
+        assert_eq!(Backend::from_url("sqlite::memory:"), Some(Backend::Sqlite));
+        assert_eq!(Backend::from_url("mysql://localhost/app"), None);
+    }
+
+    #[tokio::test]
+    async fn sqlite_round_trip() {
+        let db = memory_db().await;
+        assert_eq!(db.backend(), Backend::Sqlite);
+        assert!(matches!(db.pool(), Pool::Sqlite(_)));
+
+        let inserted = db
+            .execute_with(
+                "INSERT INTO notes (title, done) VALUES ($1, $2)",
+                QueryParams::new().bind("write tests").bind(true),
+            )
+            .await
+            .unwrap();
+        assert_eq!(inserted, 1);
+
+        let row = db
+            .fetch_row("SELECT id, title, done FROM notes WHERE title = $1", QueryParams::new().bind("write tests"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(row["id"], 1);
+        assert_eq!(row["title"], "write tests");
+        assert_eq!(row["done"], true);
+    }
+
+    #[tokio::test]
+    async fn failed_transactions_roll_back() {
+        let db = memory_db().await;
+        let result: Result<(), sqlx::Error> = db
+            .transaction(|tx| Box::pin(async move {
+                tx.execute("INSERT INTO notes (title, done) VALUES ('kept?', false)").await?;
+                tx.execute("INSERT INTO missing (x) VALUES (1)").await?;
+                Ok(())
+            }))
+            .await;
+
+        assert!(result.is_err());
+        let rows = db.fetch_rows("SELECT * FROM notes", QueryParams::new()).await.unwrap();
+        assert!(rows.is_empty());
+    }
+
+    #[tokio::test]
+    async fn successful_transactions_commit_and_return_the_value() {
+        let db = memory_db().await;
+        let inserted: Result<u64, sqlx::Error> = db
+            .transaction(|tx| Box::pin(async move {
+                let first = tx.execute_with("INSERT INTO notes (title, done) VALUES ($1, false)", QueryParams::new().bind("a")).await?;
+                let second = tx.execute_with("INSERT INTO notes (title, done) VALUES ($1, true)", QueryParams::new().bind("b")).await?;
+                Ok(first + second)
+            }))
+            .await;
+
+        assert_eq!(inserted.unwrap(), 2);
+        let rows = db.fetch_rows("SELECT title FROM notes ORDER BY id", QueryParams::new()).await.unwrap();
+        assert_eq!(rows.iter().map(|row| row["title"].as_str().unwrap()).collect::<Vec<_>>(), ["a", "b"]);
+    }
+}
@@ -1,21 +1,133 @@
 #[cfg(feature = "dev")]
-use crate::{App, Server};
+use crate::{App, Handler, Middleware, Request, Response, Server};
+#[cfg(feature = "dev")]
+use async_trait::async_trait;
 #[cfg(feature = "dev")]
 use notify::{Watcher, RecursiveMode, RecommendedWatcher, Event};
 #[cfg(feature = "dev")]
 use std::net::SocketAddr;
 #[cfg(feature = "dev")]
-use std::path::Path;
+use std::path::{Path, PathBuf};
 #[cfg(feature = "dev")]
-use std::sync::mpsc::channel;
+use std::sync::Arc;
 #[cfg(feature = "dev")]
 use std::time::Duration;
+#[cfg(feature = "dev")]
+use tokio::sync::{broadcast, mpsc};
+
+// Server-sent events endpoint the injected script listens on
+#[cfg(feature = "dev")]
+pub const LIVE_RELOAD_PATH: &str = "/__rustnext/livereload";
+
+// Reloads on a `reload` event, and also after the connection drops and comes back (the server restarted)
+#[cfg(feature = "dev")]
+const LIVE_RELOAD_SCRIPT: &str = r#"<script>(function(){var dropped=false;var es=new EventSource("/__rustnext/livereload");es.addEventListener("reload",function(){location.reload();});es.onerror=function(){dropped=true;};es.onopen=function(){if(dropped){location.reload();}};})();</script>"#;
+
+#[cfg(feature = "dev")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Signal {
+    Reload,
+    // Ends the event streams so graceful shutdown isn't held up by open browser tabs
+    Shutdown,
+}
+
+// Serves the reload event stream and injects the client script into HTML responses.
+// `DevServer` sets this up; `trigger` can also be called directly.
+#[cfg(feature = "dev")]
+#[derive(Clone)]
+pub struct LiveReload {
+    signals: broadcast::Sender<Signal>,
+}
+
+#[cfg(feature = "dev")]
+impl LiveReload {
+    pub fn new() -> Self {
+        let (signals, _) = broadcast::channel(16);
+        LiveReload { signals }
+    }
+
+    // Tells every connected page to reload
+    pub fn trigger(&self) {
+        let _ = self.signals.send(Signal::Reload);
+    }
+
+    fn shutdown(&self) {
+        let _ = self.signals.send(Signal::Shutdown);
+    }
+
+    fn event_stream(&self) -> Response {
+        let first = futures::stream::once(async { Ok::<_, std::io::Error>(": connected\n\n".to_string()) });
+        let events = futures::stream::unfold(self.signals.subscribe(), |mut rx| async move {
+            loop {
+                match rx.recv().await {
+                    Ok(Signal::Reload) => return Some((Ok("event: reload\ndata: {}\n\n".to_string()), rx)),
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Ok(Signal::Shutdown) | Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        });
+
+        Response::new()
+            .header("Content-Type", "text/event-stream")
+            .header("Cache-Control", "no-cache")
+            .body(hyper::Body::wrap_stream(futures::StreamExt::chain(first, events)))
+    }
+}
+
+#[cfg(feature = "dev")]
+impl Default for LiveReload {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "dev")]
+#[async_trait]
+impl Middleware for LiveReload {
+    async fn handle(
+        &self,
+        req: Request,
+        next: Arc<dyn Handler>,
+    ) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+        if req.uri.path() == LIVE_RELOAD_PATH {
+            return Ok(self.event_stream());
+        }
+
+        let mut response = next.handle(req).await?;
+        let is_html = response.headers.get(hyper::header::CONTENT_TYPE)
+            .and_then(|ct| ct.to_str().ok())
+            .is_some_and(|ct| ct.starts_with("text/html"));
+        if !is_html || response.headers.contains_key(hyper::header::CONTENT_ENCODING) {
+            return Ok(response);
+        }
+
+        let body = hyper::body::to_bytes(std::mem::take(&mut response.body)).await?;
+        let mut html = String::from_utf8_lossy(&body).into_owned();
+        match html.to_ascii_lowercase().rfind("</body>") {
+            Some(index) => html.insert_str(index, LIVE_RELOAD_SCRIPT),
+            None => html.push_str(LIVE_RELOAD_SCRIPT),
+        }
+        response.headers.remove(hyper::header::CONTENT_LENGTH);
+        response.body = hyper::Body::from(html);
+        Ok(response)
+    }
+}
 
 #[cfg(feature = "dev")]
 pub struct DevServer {
     app: App,
     addr: SocketAddr,
     watch_dir: String,
+    // Changes to other files are ignored
+    extensions: Vec<String>,
+    // Glob patterns for paths to skip even when the extension matches
+    ignore: Vec<String>,
+    // Quiet period after the last change before reloading, so one save is one reload
+    debounce: Duration,
+    // Program and arguments run when a source file changes, e.g. `cargo build`
+    rebuild_command: Option<Vec<String>>,
+    // Changes to these need a rebuild; anything else (css, js, html...) only reloads the page
+    rebuild_extensions: Vec<String>,
 }
 
 #[cfg(feature = "dev")]
@@ -25,40 +137,231 @@ impl DevServer {
             app,
             addr,
             watch_dir: watch_dir.to_string(),
+            extensions: ["html", "css", "js", "json", "toml", "md", "svg", "png", "jpg", "rs"]
+                .iter()
+                .map(|ext| ext.to_string())
+                .collect(),
+            ignore: ["target/", ".git/", "node_modules/", "*.swp", "*~", ".#*"]
+                .iter()
+                .map(|pattern| pattern.to_string())
+                .collect(),
+            debounce: Duration::from_millis(200),
+            rebuild_command: None,
+            rebuild_extensions: vec!["rs".to_string(), "toml".to_string()],
         }
     }
 
+    pub fn extensions(mut self, extensions: &[&str]) -> Self {
+        self.extensions = extensions.iter().map(|ext| ext.trim_start_matches('.').to_string()).collect();
+        self
+    }
+
+    // Replaces the default ignore list. A pattern ending in `/` matches a directory anywhere under
+    // the watched one (`target/`), one containing `/` matches the path relative to it
+    // (`assets/generated/*`), and any other matches the file name (`*.swp`). `*` and `?` are
+    // the only wildcards.
+    pub fn ignore(mut self, patterns: &[&str]) -> Self {
+        self.ignore = patterns.iter().map(|pattern| pattern.to_string()).collect();
+        self
+    }
+
+    pub fn debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    // Runs `command` (split on whitespace, no shell) when a source file changes. If it succeeds
+    // the server shuts down and restarts into the rebuilt binary, and open pages reload once it's
+    // back; if it fails the old server keeps running. Without one, source changes just reload.
+    pub fn rebuild_command(mut self, command: &str) -> Self {
+        let parts: Vec<String> = command.split_whitespace().map(|part| part.to_string()).collect();
+        self.rebuild_command = (!parts.is_empty()).then_some(parts);
+        self
+    }
+
+    // Which changes count as source changes (default `rs` and `toml`)
+    pub fn rebuild_extensions(mut self, extensions: &[&str]) -> Self {
+        self.rebuild_extensions = extensions.iter().map(|ext| ext.trim_start_matches('.').to_string()).collect();
+        self
+    }
+
     pub async fn run_with_reload(self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let (tx, rx) = channel();
-        
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
         // Create watcher with proper event handler
         let mut watcher = RecommendedWatcher::new(
             move |res: Result<Event, notify::Error>| {
                 match res {
                     Ok(event) => {
-                        if let Err(e) = tx.send(event) {
-                            eprintln!("Watch error: {}", e);
-                        }
+                        let _ = tx.send(event);
                     }
                     Err(e) => eprintln!("Watch error: {:?}", e),
                 }
             },
             notify::Config::default()
         )?;
-        
+
         // Convert String to Path and watch
         watcher.watch(Path::new(&self.watch_dir), RecursiveMode::Recursive)?;
 
         println!("Development server starting with file watching on {}", self.watch_dir);
-        
-        let server = Server::new(self.app, self.addr);
-        
+
+        let live_reload = LiveReload::new();
+        let debounce = self.debounce;
+        let root = Path::new(&self.watch_dir).canonicalize().unwrap_or_else(|_| PathBuf::from(&self.watch_dir));
+        let extensions = self.extensions.clone();
+        let ignore = self.ignore.clone();
+        let is_relevant = move |event: &Event| {
+            !matches!(event.kind, notify::EventKind::Access(_))
+                && event.paths.iter().any(|path| is_watched(path.strip_prefix(&root).unwrap_or(path), &extensions, &ignore))
+        };
+
+        // Captured now: once the binary is rebuilt, the running one's path may read as deleted
+        let executable = std::env::current_exe()?;
+        let (restart_tx, mut restart_rx) = mpsc::channel::<()>(1);
+        let rebuild_command = self.rebuild_command.clone();
+        let rebuild_extensions = self.rebuild_extensions.clone();
+
+        let reloader = live_reload.clone();
         tokio::spawn(async move {
-            while let Ok(event) = rx.recv() {
-                println!("File changed: {:?}", event);
+            while let Some(event) = rx.recv().await {
+                if !is_relevant(&event) {
+                    continue;
+                }
+                let mut changed = event.paths;
+                // Swallow the rest of the burst (editors often write, rename and touch in one save)
+                while let Ok(Some(event)) = tokio::time::timeout(debounce, rx.recv()).await {
+                    if is_relevant(&event) {
+                        changed.extend(event.paths);
+                    }
+                }
+                changed.dedup();
+
+                let needs_rebuild = changed.iter().any(|path| {
+                    path.extension()
+                        .and_then(|ext| ext.to_str())
+                        .is_some_and(|ext| rebuild_extensions.iter().any(|r| r.eq_ignore_ascii_case(ext)))
+                });
+                match &rebuild_command {
+                    Some(command) if needs_rebuild => {
+                        println!("Source changed: {:?}; running `{}`", changed, command.join(" "));
+                        if rebuild(command).await {
+                            let _ = restart_tx.send(()).await;
+                            return;
+                        }
+                        println!("Rebuild failed; keeping the running server");
+                    }
+                    _ => {
+                        println!("File changed: {:?}; reloading", changed);
+                        reloader.trigger();
+                    }
+                }
             }
         });
 
-        server.run().await
+        let shutdown = live_reload.clone();
+        let restarting = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let restart_flag = restarting.clone();
+        let server = Server::new(self.app, self.addr)
+            .wrap(live_reload)
+            .with_shutdown_signal(async move {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    Some(()) = restart_rx.recv() => restart_flag.store(true, std::sync::atomic::Ordering::SeqCst),
+                }
+                shutdown.shutdown();
+            });
+
+        // Keep the watcher alive for as long as the server runs
+        let result = server.run().await;
+        drop(watcher);
+        if restarting.load(std::sync::atomic::Ordering::SeqCst) {
+            println!("Restarting {}", executable.display());
+            return Err(restart(&executable).into());
+        }
+        result
+    }
+}
+
+// Inherits stdout and stderr, so compiler output shows up in the dev console
+#[cfg(feature = "dev")]
+async fn rebuild(command: &[String]) -> bool {
+    match tokio::process::Command::new(&command[0]).args(&command[1..]).status().await {
+        Ok(status) => status.success(),
+        Err(e) => {
+            eprintln!("Could not run `{}`: {}", command.join(" "), e);
+            false
+        }
+    }
+}
+
+// Replaces this process with a fresh run of `executable` and the same arguments; only returns
+// if that fails
+#[cfg(all(feature = "dev", unix))]
+fn restart(executable: &Path) -> std::io::Error {
+    use std::os::unix::process::CommandExt;
+    std::process::Command::new(executable).args(std::env::args_os().skip(1)).exec()
+}
+
+#[cfg(all(feature = "dev", not(unix)))]
+fn restart(executable: &Path) -> std::io::Error {
+    match std::process::Command::new(executable).args(std::env::args_os().skip(1)).status() {
+        Ok(status) => std::process::exit(status.code().unwrap_or(1)),
+        Err(e) => e,
+    }
+}
+
+// `path` is relative to the watched directory
+#[cfg(feature = "dev")]
+fn is_watched(path: &Path, extensions: &[String], ignore: &[String]) -> bool {
+    let extension_matches = path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| extensions.iter().any(|allowed| allowed.eq_ignore_ascii_case(ext)));
+    extension_matches && !ignore.iter().any(|pattern| is_ignored(path, pattern))
+}
+
+#[cfg(feature = "dev")]
+fn is_ignored(path: &Path, pattern: &str) -> bool {
+    let relative = path.to_string_lossy().replace('\\', "/");
+    if let Some(dir) = pattern.strip_suffix('/') {
+        // Directories only, so the file name itself is skipped
+        let parent = relative.rsplit_once('/').map_or("", |(parent, _)| parent);
+        parent.split('/').any(|component| glob_match(dir, component))
+    } else if pattern.contains('/') {
+        glob_match(pattern.trim_start_matches('/'), &relative)
+    } else {
+        let name = relative.rsplit('/').next().unwrap_or(&relative);
+        glob_match(pattern, name)
+    }
+}
+
+// `*` matches any run of characters (including `/`), `?` any single one
+#[cfg(feature = "dev")]
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Where the last `*` was and how much text it has taken so far, to backtrack to
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
     }
+    pattern[p..].iter().all(|&c| c == '*')
 }
@@ -1,6 +1,9 @@
-use crate::{Response, ui::{div, h1, p, text, get_renderer}};
-use hyper::StatusCode;
+use crate::{Handler, Request, Response, ui::{div, h1, p, text, get_component_registry, get_renderer}};
+use hyper::{HeaderMap, Method, StatusCode};
+use serde_json::Value;
+use std::collections::HashMap;
 use std::fmt;
+use std::sync::Arc;
 use std::error::Error as StdError; // Alias for clarity
 
 #[derive(Debug, Clone)] // Added Clone derive
@@ -30,13 +33,78 @@ impl fmt::Display for AppError {
 
 impl StdError for AppError {}
 
+impl AppError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::Custom(status, _) => *status,
+        }
+    }
+
+    // Prefixes the message with `context`, keeping the variant (and so the status)
+    pub fn context<C: fmt::Display>(self, context: C) -> Self {
+        let wrap = |msg: String| format!("{}: {}", context, msg);
+        match self {
+            AppError::NotFound(msg) => AppError::NotFound(wrap(msg)),
+            AppError::Internal(msg) => AppError::Internal(wrap(msg)),
+            AppError::BadRequest(msg) => AppError::BadRequest(wrap(msg)),
+            AppError::Unauthorized(msg) => AppError::Unauthorized(wrap(msg)),
+            AppError::Forbidden(msg) => AppError::Forbidden(wrap(msg)),
+            AppError::Custom(status, msg) => AppError::Custom(status, wrap(msg)),
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            AppError::NotFound(msg)
+            | AppError::Internal(msg)
+            | AppError::BadRequest(msg)
+            | AppError::Unauthorized(msg)
+            | AppError::Forbidden(msg)
+            | AppError::Custom(_, msg) => msg,
+        }
+    }
+
+    // What the client is shown. An `Internal` error's message is a detail for the logs (a
+    // database error, a panic, ..), so it's logged here and replaced with a generic one.
+    fn public_message(&self) -> &str {
+        match self {
+            AppError::Internal(msg) => {
+                log::error!("Internal Server Error: {}", msg);
+                "Internal Server Error"
+            }
+            other => other.message(),
+        }
+    }
+}
+
 // Convert generic Box<dyn Error> to AppError
 impl From<Box<dyn StdError + Send + Sync>> for AppError {
     fn from(err: Box<dyn StdError + Send + Sync>) -> Self {
+        AppError::from_ref(err.as_ref())
+    }
+}
+
+impl AppError {
+    // What a handler error will be reported as, without taking ownership of it
+    pub(crate) fn from_ref(err: &(dyn StdError + Send + Sync + 'static)) -> Self {
         // Attempt to downcast to specific AppError variants if possible,
         // otherwise wrap in Internal.
         if let Some(app_err) = err.downcast_ref::<AppError>() {
             app_err.clone() // Now AppError implements Clone
+        } else if let Some(too_large) = err.downcast_ref::<crate::request::BodyTooLarge>() {
+            too_large.clone().into()
+        } else if let Some(multer::Error::StreamSizeExceeded { limit }) = err.downcast_ref::<multer::Error>() {
+            multer::Error::StreamSizeExceeded { limit: *limit }.into()
+        } else if let Some(upload_err) = err.downcast_ref::<crate::file_upload::UploadError>() {
+            upload_err.clone().into()
+        } else if let Some(multipart_err) = err.downcast_ref::<multer::Error>() {
+            // Malformed multipart bodies are the client's fault
+            AppError::BadRequest(format!("Multipart parsing error: {}", multipart_err))
         } else {
             AppError::Internal(err.to_string())
         }
@@ -61,9 +129,62 @@ impl From<std::io::Error> for AppError {
     }
 }
 
+impl From<crate::request::BodyTooLarge> for AppError {
+    fn from(err: crate::request::BodyTooLarge) -> Self {
+        AppError::Custom(StatusCode::PAYLOAD_TOO_LARGE, err.to_string())
+    }
+}
+
+impl From<crate::file_upload::UploadError> for AppError {
+    fn from(err: crate::file_upload::UploadError) -> Self {
+        use crate::file_upload::UploadError;
+        match err {
+            UploadError::TooLarge { .. } => AppError::Custom(StatusCode::PAYLOAD_TOO_LARGE, err.to_string()),
+            UploadError::DisallowedType { .. } => AppError::Custom(StatusCode::UNSUPPORTED_MEDIA_TYPE, err.to_string()),
+            UploadError::InvalidFilename(_) => AppError::BadRequest(err.to_string()),
+        }
+    }
+}
+
 impl From<multer::Error> for AppError {
     fn from(err: multer::Error) -> Self {
-        AppError::BadRequest(format!("Multipart parsing error: {}", err))
+        match err {
+            multer::Error::StreamSizeExceeded { limit } => {
+                AppError::Custom(StatusCode::PAYLOAD_TOO_LARGE, format!("Request body exceeds the limit of {} bytes", limit))
+            }
+            err => AppError::BadRequest(format!("Multipart parsing error: {}", err)),
+        }
+    }
+}
+
+// An `AppError` inside the chain keeps its status; anything else is Internal with the full
+// chain of context messages
+impl From<anyhow::Error> for AppError {
+    fn from(err: anyhow::Error) -> Self {
+        match err.downcast_ref::<AppError>() {
+            Some(app_err) => app_err.clone(),
+            None => AppError::Internal(format!("{:#}", err)),
+        }
+    }
+}
+
+// `.context(..)` for any result whose error converts into `AppError`, e.g.
+// `let post = load_post(id).await.context("loading the post")?;` gives
+// "loading the post: <original error>" with the original error's status
+pub trait ResultExt<T> {
+    fn context<C: fmt::Display>(self, context: C) -> Result<T, AppError>;
+
+    // Builds the message only on error
+    fn with_context<C: fmt::Display, F: FnOnce() -> C>(self, f: F) -> Result<T, AppError>;
+}
+
+impl<T, E: Into<AppError>> ResultExt<T> for Result<T, E> {
+    fn context<C: fmt::Display>(self, context: C) -> Result<T, AppError> {
+        self.map_err(|err| err.into().context(context))
+    }
+
+    fn with_context<C: fmt::Display, F: FnOnce() -> C>(self, f: F) -> Result<T, AppError> {
+        self.map_err(|err| err.into().context(f()))
     }
 }
 
@@ -73,29 +194,245 @@ impl From<url::ParseError> for AppError {
     }
 }
 
-// Trait for converting AppError to Response
+// Anything a handler can return: `Response`, `Json(value)`, `(StatusCode, body)`, a `String` or
+// `&str`, an `AppError`, or a `Result` of one of these whose error converts into a boxed error
 pub trait IntoResponse {
-    fn into_response(&self) -> Result<Response, Box<dyn StdError + Send + Sync>>;
+    fn into_response(self) -> Result<Response, Box<dyn StdError + Send + Sync>>;
 }
 
 impl IntoResponse for AppError {
-    fn into_response(&self) -> Result<Response, Box<dyn StdError + Send + Sync>> {
-        let (status, message) = match self {
-            AppError::NotFound(msg) => (StatusCode::NOT_FOUND, msg.clone()),
-            AppError::Internal(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg.clone()),
-            AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg.clone()),
-            AppError::Unauthorized(msg) => (StatusCode::UNAUTHORIZED, msg.clone()),
-            AppError::Forbidden(msg) => (StatusCode::FORBIDDEN, msg.clone()),
-            AppError::Custom(s, msg) => (*s, msg.clone()),
-        };
+    fn into_response(self) -> Result<Response, Box<dyn StdError + Send + Sync>> {
+        (&self).into_response()
+    }
+}
+
+// The generic HTML error page
+impl IntoResponse for &AppError {
+    fn into_response(self) -> Result<Response, Box<dyn StdError + Send + Sync>> {
+        let (status, message) = (self.status(), self.public_message());
 
         let error_page = div()
             .class("container")
             .child(h1().child(text(&format!("Error {}: {}", status.as_u16(), status.canonical_reason().unwrap_or("Unknown Error")))))
-            .child(p().child(text(&message)));
+            .child(p().child(text(message)));
 
         get_renderer().render_to_response(&error_page)
             .map(|res| res.status(status))
-            .map_err(|e| e) // Changed this line: 'e' is already the correct Box<dyn StdError> type
+    }
+}
+
+impl AppError {
+    // `{"error": <message>, "status": <code>}` with the error's status; `Internal` errors only
+    // say "Internal Server Error"
+    pub fn json_response(&self) -> Result<Response, Box<dyn StdError + Send + Sync>> {
+        let status = self.status();
+        let body = serde_json::json!({"error": self.public_message(), "status": status.as_u16()});
+        Ok(Response::new().status(status).json(&body)?)
+    }
+
+    // JSON for clients that want it, the HTML error page otherwise
+    pub fn respond_to(&self, ctx: &ErrorContext) -> Result<Response, Box<dyn StdError + Send + Sync>> {
+        if ctx.wants_json {
+            self.json_response()
+        } else {
+            self.into_response()
+        }
+    }
+}
+
+// The request an error came from, as seen by the App's error handler
+#[derive(Debug, Clone)]
+pub struct ErrorContext {
+    pub method: Method,
+    pub path: String,
+    pub headers: HeaderMap,
+    pub request_id: Option<String>,
+    // The client prefers JSON (`Accept`) or the path is under the App's API prefix
+    pub wants_json: bool,
+}
+
+impl ErrorContext {
+    pub fn new(req: &Request) -> Self {
+        let accept = req.headers.get(hyper::header::ACCEPT).and_then(|v| v.to_str().ok());
+        let wants_json = accept.is_some()
+            && crate::api::negotiation::negotiate(accept, &["text/html", "application/json"]) == Some("application/json");
+        ErrorContext {
+            method: req.method.clone(),
+            path: req.uri.path().to_string(),
+            headers: req.headers.clone(),
+            request_id: req.request_id.clone(),
+            wants_json,
+        }
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).and_then(|v| v.to_str().ok())
+    }
+}
+
+type ErrorHandlerFn = dyn Fn(&ErrorContext, AppError) -> Result<Response, Box<dyn StdError + Send + Sync>> + Send + Sync;
+
+// Turns errors that no error page handled into responses, e.g.
+// `ErrorHandler::new(|ctx: &ErrorContext, err: AppError| { error!("{} {}: {}", ctx.method, ctx.path, err); err.respond_to(ctx) })`
+#[derive(Clone)]
+pub struct ErrorHandler(Arc<ErrorHandlerFn>);
+
+impl ErrorHandler {
+    pub fn new<F>(handler: F) -> Self
+    where
+        F: Fn(&ErrorContext, AppError) -> Result<Response, Box<dyn StdError + Send + Sync>> + Send + Sync + 'static,
+    {
+        ErrorHandler(Arc::new(handler))
+    }
+
+    pub fn call(&self, ctx: &ErrorContext, err: AppError) -> Result<Response, Box<dyn StdError + Send + Sync>> {
+        (self.0)(ctx, err)
+    }
+}
+
+// JSON for JSON clients and the generic HTML page for everyone else
+impl Default for ErrorHandler {
+    fn default() -> Self {
+        ErrorHandler::new(|ctx: &ErrorContext, err: AppError| err.respond_to(ctx))
+    }
+}
+
+// Handlers written before `ErrorContext` (`Arc::new(|err: AppError| ...)`). They only see
+// errors for HTML clients; JSON clients get `AppError::json_response`.
+impl<F> From<Arc<F>> for ErrorHandler
+where
+    F: Fn(AppError) -> Result<Response, Box<dyn StdError + Send + Sync>> + Send + Sync + ?Sized + 'static,
+{
+    fn from(handler: Arc<F>) -> Self {
+        ErrorHandler::new(move |ctx: &ErrorContext, err: AppError| {
+            if ctx.wants_json {
+                err.json_response()
+            } else {
+                handler(err)
+            }
+        })
+    }
+}
+
+pub enum ErrorPage {
+    // Called with a body-less copy of the failed request
+    Handler(Arc<dyn Handler>),
+    // Rendered from the component registry with `status`, `reason`, `message` and `path` props
+    Component(String),
+}
+
+// Per-status error pages, e.g.
+// `App::new().error_pages(ErrorPages::new().component(StatusCode::NOT_FOUND, "not_found"))`.
+// Errors whose status has no page, or whose page fails to render, go to the App's error handler,
+// as do errors for JSON clients.
+#[derive(Default)]
+pub struct ErrorPages {
+    pages: HashMap<StatusCode, ErrorPage>,
+}
+
+impl ErrorPages {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn handler<H: Handler>(mut self, status: StatusCode, handler: H) -> Self {
+        self.pages.insert(status, ErrorPage::Handler(Arc::new(handler)));
+        self
+    }
+
+    pub fn component(mut self, status: StatusCode, name: &str) -> Self {
+        self.pages.insert(status, ErrorPage::Component(name.to_string()));
+        self
+    }
+
+    pub fn has_page(&self, status: StatusCode) -> bool {
+        self.pages.contains_key(&status)
+    }
+
+    // The page for `err`'s status, always sent with that status
+    pub async fn render(&self, err: &AppError, req: Request) -> Option<Response> {
+        let status = err.status();
+        let response = match self.pages.get(&status)? {
+            ErrorPage::Handler(handler) => handler.handle(req).await,
+            ErrorPage::Component(name) => {
+                let props = HashMap::from([
+                    ("status".to_string(), Value::from(status.as_u16())),
+                    ("reason".to_string(), Value::from(status.canonical_reason().unwrap_or("Error"))),
+                    ("message".to_string(), Value::from(err.message())),
+                    ("path".to_string(), Value::from(req.uri.path())),
+                ]);
+                match get_component_registry().lock().await.render(name, &props).await {
+                    Ok(element) => get_renderer().render_to_response(&element),
+                    Err(e) => Err(e.into()),
+                }
+            }
+        };
+        match response {
+            Ok(response) => Some(response.status(status)),
+            Err(e) => {
+                log::error!("Error page for {} failed: {}", status, e);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::Element;
+    use async_trait::async_trait;
+
+    crate::component!(StatusPage, props => {
+        let line = format!("{} {}: {} at {}", props["status"], props["reason"], props["message"], props["path"]);
+        Element::new("main").child(text(&line))
+    });
+
+    async fn request(uri: &str) -> Request {
+        Request::from_hyper(hyper::Request::builder().uri(uri).body(hyper::Body::empty()).unwrap()).await.unwrap()
+    }
+
+    async fn body_text(response: Response) -> String {
+        let bytes = hyper::body::to_bytes(response.body).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn component_pages_render_with_the_error_status() {
+        get_component_registry().lock().await.register("error_tests_status_page", StatusPage);
+        let pages = ErrorPages::new().component(StatusCode::NOT_FOUND, "error_tests_status_page");
+        let err = AppError::NotFound("No such post".to_string());
+
+        let response = pages.render(&err, request("/posts/7").await).await.unwrap();
+        assert_eq!(response.status, StatusCode::NOT_FOUND);
+        assert!(body_text(response).await.contains("<main>404 \"Not Found\": \"No such post\" at \"/posts/7\"</main>"));
+
+        let other = AppError::Internal("boom".to_string());
+        assert!(pages.render(&other, request("/posts/7").await).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn unregistered_component_pages_fall_through() {
+        let pages = ErrorPages::new().component(StatusCode::NOT_FOUND, "error_tests_missing_page");
+        let err = AppError::NotFound("No such post".to_string());
+        assert!(pages.has_page(StatusCode::NOT_FOUND));
+        assert!(pages.render(&err, request("/posts/7").await).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn internal_details_stay_out_of_responses() {
+        let err = AppError::Internal("password=hunter2 rejected by db".to_string());
+
+        let response = err.json_response().unwrap();
+        assert_eq!(response.status, StatusCode::INTERNAL_SERVER_ERROR);
+        let body: Value = serde_json::from_str(&body_text(response).await).unwrap();
+        assert_eq!(body, serde_json::json!({"error": "Internal Server Error", "status": 500}));
+
+        let html = body_text(err.into_response().unwrap()).await;
+        assert!(html.contains("Error 500"));
+        assert!(!html.contains("hunter2"));
+
+        let body = body_text(AppError::BadRequest("bad id".to_string()).json_response().unwrap()).await;
+        assert!(body.contains("bad id"));
     }
 }
@@ -0,0 +1,129 @@
+// HTML escaping shared by the UI Renderer, the TemplateEngine, Forms (through the Renderer) and the
+// built-in HTML responses, so the rules live in one place. Pick the function for where the value
+// ends up:
+//
+//     <p>{escape_text}</p>
+//     <input value="{escape_attribute}">
+//     <a href="{escape_url}">
+use std::borrow::Cow;
+
+// Element content: `&`, `<` and `>`. Not enough inside an attribute.
+pub fn escape_text(value: &str) -> Cow<'_, str> {
+    html_escape::encode_text(value)
+}
+
+// A single- or double-quoted attribute value; also safe as element content, which is why the
+// TemplateEngine, not knowing where a `{{ }}` sits, uses this
+pub fn escape_attribute(value: &str) -> Cow<'_, str> {
+    html_escape::encode_quoted_attribute(value)
+}
+
+// A quoted URL attribute (`href`, `src`, `action`, ..): `escape_attribute`, after prefixing
+// script-running URLs (`javascript:`, `vbscript:`, and `data:` other than raster images) with
+// `unsafe:`, which leaves them inert but visible when debugging. Relative URLs and other schemes
+// pass through.
+pub fn escape_url(url: &str) -> Cow<'_, str> {
+    if is_safe_url(url) {
+        escape_attribute(url)
+    } else {
+        Cow::Owned(escape_attribute(&format!("unsafe:{}", url)).into_owned())
+    }
+}
+
+// Attributes `Renderer` passes through `escape_url`
+pub fn is_url_attribute(name: &str) -> bool {
+    matches!(
+        name.to_ascii_lowercase().as_str(),
+        "href" | "src" | "action" | "formaction" | "poster" | "cite" | "background" | "xlink:href"
+    )
+}
+
+const SAFE_DATA_URL_TYPES: &[&str] = &["image/png", "image/gif", "image/jpeg", "image/webp", "image/avif"];
+
+fn is_safe_url(url: &str) -> bool {
+    // Browsers drop surrounding whitespace and control characters, and tabs and newlines
+    // anywhere, before looking at the scheme, so `java\tscript:` must be caught too
+    let url: String = url
+        .trim_matches(|c: char| c <= ' ')
+        .chars()
+        .filter(|c| !matches!(c, '\t' | '\n' | '\r'))
+        .collect();
+    let scheme = match url.split_once(':') {
+        Some((scheme, _)) if is_scheme(scheme) => scheme.to_ascii_lowercase(),
+        // No scheme: a relative URL, or a `:` in its path, query or fragment
+        _ => return true,
+    };
+    match scheme.as_str() {
+        "javascript" | "vbscript" => false,
+        "data" => {
+            let media_type = url["data:".len()..].split([';', ',']).next().unwrap_or("").trim();
+            SAFE_DATA_URL_TYPES.iter().any(|safe| media_type.eq_ignore_ascii_case(safe))
+        }
+        _ => true,
+    }
+}
+
+// RFC 3986: a letter, then letters, digits, `+`, `-` or `.`
+fn is_scheme(value: &str) -> bool {
+    let mut chars = value.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn script_schemes_are_blocked_however_they_are_spelled() {
+        for url in [
+            "javascript:alert(1)",
+            "JavaScript:alert(1)",
+            "java\tscript:alert(1)",
+            "java\nscript:alert(1)",
+            " \x01javascript:alert(1)",
+            "VBScript:msgbox(1)",
+        ] {
+            assert!(!is_safe_url(url), "{:?}", url);
+        }
+        assert_eq!(escape_url("JavaScript:alert(1)"), "unsafe:JavaScript:alert(1)");
+    }
+
+    #[test]
+    fn data_urls_are_limited_to_raster_images() {
+        assert!(!is_safe_url("data:text/html,<script>alert(1)</script>"));
+        assert!(!is_safe_url("data:text/html;base64,PHNjcmlwdD4="));
+        assert!(!is_safe_url("data:image/svg+xml,<svg onload=alert(1)>"));
+        assert!(!is_safe_url("data:,alert(1)"));
+        assert!(is_safe_url("data:image/png;base64,iVBORw0KGgo="));
+        assert!(is_safe_url("DATA:Image/JPEG;base64,/9j/4AAQ"));
+    }
+
+    #[test]
+    fn relative_urls_and_other_schemes_pass_through() {
+        for url in [
+            "/search?q=a:b",
+            "page?next=javascript:alert(1)",
+            "#section:2",
+            "../up",
+            "https://example.com/a:b",
+            "mailto:ann@example.com",
+        ] {
+            assert!(is_safe_url(url), "{:?}", url);
+        }
+        assert_eq!(escape_url("/search?q=a&b=\"c\""), "/search?q=a&amp;b=&quot;c&quot;");
+    }
+
+    #[test]
+    fn attributes_escape_both_quote_styles() {
+        assert_eq!(escape_attribute(r#"a "b" 'c' <d> & e"#), "a &quot;b&quot; &#x27;c&#x27; &lt;d&gt; &amp; e");
+        assert_eq!(escape_text(r#"<b> & "q""#), r#"&lt;b&gt; &amp; "q""#);
+    }
+
+    #[test]
+    fn url_attributes_are_matched_case_insensitively() {
+        assert!(is_url_attribute("HREF"));
+        assert!(is_url_attribute("xlink:href"));
+        assert!(!is_url_attribute("title"));
+    }
+}
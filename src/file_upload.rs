@@ -1,44 +1,597 @@
-use std::path::PathBuf;
+use crate::Request;
+use multer::Multipart;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use tokio::fs;
 use tokio::io::AsyncWriteExt;
 
 pub struct FileUpload {
+    // Name of the form field the file was sent in
+    pub field_name: String,
     pub filename: String,
     pub content_type: String,
     pub size: usize,
     pub data: Vec<u8>,
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum UploadError {
+    TooLarge { size: usize, limit: usize },
+    DisallowedType { filename: String, content_type: String },
+    // Nothing usable left after sanitizing, e.g. `..` or an empty name
+    InvalidFilename(String),
+}
+
+impl std::fmt::Display for UploadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UploadError::TooLarge { size, limit } => {
+                write!(f, "Uploaded file is {} bytes, over the limit of {} bytes", size, limit)
+            }
+            UploadError::DisallowedType { filename, content_type } => {
+                write!(f, "File type not allowed: {} ({})", filename, content_type)
+            }
+            UploadError::InvalidFilename(name) => write!(f, "Invalid upload filename: {:?}", name),
+        }
+    }
+}
+
+impl std::error::Error for UploadError {}
+
+// Reduces a client-supplied filename to a bare name: any directory part (either slash style) is
+// dropped, as are control characters and leading dots, so the result can't leave the target
+// directory or turn into a hidden file. None if nothing is left.
+pub fn sanitize_filename(filename: &str) -> Option<String> {
+    let name = filename.rsplit(['/', '\\']).next().unwrap_or("");
+    let name: String = name.chars().filter(|c| !c.is_control()).collect();
+    let name = name.trim().trim_start_matches('.');
+    if name.is_empty() {
+        None
+    } else {
+        Some(name.to_string())
+    }
+}
+
+// What `FileUpload::save_with` accepts. Empty allowlists allow everything; when both are set, a
+// file has to pass both.
+#[derive(Debug, Clone, Default)]
+pub struct UploadPolicy {
+    max_size: Option<usize>,
+    // Lowercase, without the dot
+    allowed_extensions: Vec<String>,
+    // Exact types or `type/*`
+    allowed_content_types: Vec<String>,
+    temp_dir: Option<PathBuf>,
+}
+
+impl UploadPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn max_size(mut self, bytes: usize) -> Self {
+        self.max_size = Some(bytes);
+        self
+    }
+
+    pub fn allow_extension(mut self, extension: &str) -> Self {
+        self.allowed_extensions.push(extension.trim_start_matches('.').to_ascii_lowercase());
+        self
+    }
+
+    pub fn allow_extensions(self, extensions: &[&str]) -> Self {
+        extensions.iter().fold(self, |policy, extension| policy.allow_extension(extension))
+    }
+
+    pub fn allow_content_type(mut self, content_type: &str) -> Self {
+        self.allowed_content_types.push(content_type.to_ascii_lowercase());
+        self
+    }
+
+    // Where `Request::multipart_to_disk` writes incoming files; the system temp dir by default.
+    // Keep it on the same filesystem as the final destination so `persist_to` is a rename.
+    pub fn temp_dir(mut self, directory: &str) -> Self {
+        self.temp_dir = Some(PathBuf::from(directory));
+        self
+    }
+
+    pub fn check(&self, upload: &FileUpload) -> Result<(), UploadError> {
+        // `data` is what gets written, whatever `size` claims
+        self.check_size(upload.size.max(upload.data.len()))?;
+        self.check_type(&upload.filename, &upload.content_type)
+    }
+
+    fn check_size(&self, size: usize) -> Result<(), UploadError> {
+        match self.max_size {
+            Some(limit) if size > limit => Err(UploadError::TooLarge { size, limit }),
+            _ => Ok(()),
+        }
+    }
+
+    // Extension and content type, judged from the sanitized name
+    fn check_type(&self, filename: &str, content_type: &str) -> Result<(), UploadError> {
+        let filename = sanitize_filename(filename).ok_or_else(|| UploadError::InvalidFilename(filename.to_string()))?;
+        let disallowed = || UploadError::DisallowedType {
+            filename: filename.clone(),
+            content_type: content_type.to_string(),
+        };
+
+        if !self.allowed_extensions.is_empty() {
+            let extension = std::path::Path::new(&filename)
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_ascii_lowercase());
+            if !extension.is_some_and(|ext| self.allowed_extensions.contains(&ext)) {
+                return Err(disallowed());
+            }
+        }
+
+        if !self.allowed_content_types.is_empty() {
+            // Ignore parameters such as `; charset=utf-8`
+            let content_type = content_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+            let allowed = self.allowed_content_types.iter().any(|allowed| match allowed.strip_suffix("/*") {
+                Some(main) => content_type.split_once('/').is_some_and(|(m, _)| m == main),
+                None => *allowed == content_type,
+            });
+            if !allowed {
+                return Err(disallowed());
+            }
+        }
+
+        Ok(())
+    }
+}
+
 impl FileUpload {
+    // The client's filename, reduced by `sanitize_filename`
+    pub fn safe_filename(&self) -> Result<String, UploadError> {
+        sanitize_filename(&self.filename).ok_or_else(|| UploadError::InvalidFilename(self.filename.clone()))
+    }
+
+    // Writes the file into `directory` under its sanitized name. Size and type aren't checked
+    // here; use `save_with` for untrusted uploads.
     pub async fn save_to(&self, directory: &str) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
-        let path = PathBuf::from(directory).join(&self.filename);
-        
+        let path = PathBuf::from(directory).join(self.safe_filename()?);
+
         // Create directory if it doesn't exist
-        if let Some(parent) = path.parent() {
-            fs::create_dir_all(parent).await?;
-        }
-        
+        fs::create_dir_all(directory).await?;
+
         let mut file = fs::File::create(&path).await?;
         file.write_all(&self.data).await?;
-        
+        // tokio finishes writes in the background; make sure this one landed before returning
+        file.flush().await?;
+
         Ok(path)
     }
+
+    // `save_to` after checking the upload against `policy`; nothing is written if it fails
+    pub async fn save_with(
+        &self,
+        directory: &str,
+        policy: &UploadPolicy,
+    ) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
+        policy.check(self)?;
+        self.save_to(directory).await
+    }
 }
 
+// A parsed multipart/form-data body: parts with a filename are files, the rest plain fields
+#[derive(Default)]
+pub struct MultipartForm {
+    pub files: Vec<FileUpload>,
+    // Repeated names keep the last value, like `Request::form`
+    pub fields: HashMap<String, String>,
+}
+
+impl MultipartForm {
+    pub fn file(&self, field_name: &str) -> Option<&FileUpload> {
+        self.files.iter().find(|f| f.field_name == field_name)
+    }
+
+    pub fn field(&self, name: &str) -> Option<&String> {
+        self.fields.get(name)
+    }
+}
+
+// Reads every part of `multipart` into memory
+pub async fn collect_multipart(mut multipart: Multipart<'_>) -> Result<MultipartForm, Box<dyn std::error::Error + Send + Sync>> {
+    let mut form = MultipartForm::default();
+
+    while let Some(field) = multipart.next_field().await? {
+        let field_name = field.name().unwrap_or_default().to_string();
+        match field.file_name().map(|name| name.to_string()) {
+            Some(filename) => {
+                let content_type = field.content_type()
+                    .map(|mime| mime.to_string())
+                    .unwrap_or_else(|| "application/octet-stream".to_string());
+                let data = field.bytes().await?.to_vec();
+                form.files.push(FileUpload {
+                    field_name,
+                    filename,
+                    content_type,
+                    size: data.len(),
+                    data,
+                });
+            }
+            None => {
+                let value = field.text().await?;
+                form.fields.insert(field_name, value);
+            }
+        }
+    }
+
+    Ok(form)
+}
+
+// Parses a raw multipart body; `content_type` is the request's Content-Type header, which
+// carries the boundary. Prefer `Request::multipart_form`, which also applies the body size limit.
+pub async fn parse_multipart(
+    body: hyper::Body,
+    content_type: &str,
+) -> Result<MultipartForm, Box<dyn std::error::Error + Send + Sync>> {
+    let boundary = multer::parse_boundary(content_type)?;
+    collect_multipart(Multipart::new(body, boundary)).await
+}
+
+// The uploaded files only; see `parse_multipart` for the other fields
 pub async fn parse_form_data(
     body: hyper::Body,
+    content_type: &str,
 ) -> Result<Vec<FileUpload>, Box<dyn std::error::Error + Send + Sync>> {
-    let body_bytes = hyper::body::to_bytes(body).await?;
-    
-    // Simple form parsing - in a real implementation you'd use a proper multipart parser
-    let uploads = vec![
+    Ok(parse_multipart(body, content_type).await?.files)
+}
+
+// A file part written to a temporary file as it arrived, instead of being held in memory. The
+// temp file is removed when this is dropped, unless it was moved out with `persist_to`.
+pub struct StreamedUpload {
+    pub field_name: String,
+    pub filename: String,
+    pub content_type: String,
+    pub size: usize,
+    // None once persisted
+    temp_path: Option<PathBuf>,
+}
+
+impl StreamedUpload {
+    // The temporary file holding the upload
+    pub fn path(&self) -> &Path {
+        self.temp_path.as_deref().unwrap_or_else(|| Path::new(""))
+    }
+
+    // The client's filename, reduced by `sanitize_filename`
+    pub fn safe_filename(&self) -> Result<String, UploadError> {
+        sanitize_filename(&self.filename).ok_or_else(|| UploadError::InvalidFilename(self.filename.clone()))
+    }
+
+    // Moves the file into `directory` under its sanitized name
+    pub async fn persist_to(mut self, directory: &str) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
+        let path = PathBuf::from(directory).join(self.safe_filename()?);
+        fs::create_dir_all(directory).await?;
+
+        let temp_path = self.temp_path.take().ok_or("Upload was already persisted")?;
+        if fs::rename(&temp_path, &path).await.is_err() {
+            // Different filesystem
+            let copied = fs::copy(&temp_path, &path).await;
+            let _ = fs::remove_file(&temp_path).await;
+            copied?;
+        }
+        Ok(path)
+    }
+}
+
+impl Drop for StreamedUpload {
+    fn drop(&mut self) {
+        if let Some(path) = self.temp_path.take() {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct StreamedForm {
+    pub files: Vec<StreamedUpload>,
+    pub fields: HashMap<String, String>,
+}
+
+impl StreamedForm {
+    pub fn file(&self, field_name: &str) -> Option<&StreamedUpload> {
+        self.files.iter().find(|f| f.field_name == field_name)
+    }
+
+    // Takes a file out of the form, e.g. to `persist_to` it
+    pub fn take_file(&mut self, field_name: &str) -> Option<StreamedUpload> {
+        let index = self.files.iter().position(|f| f.field_name == field_name)?;
+        Some(self.files.remove(index))
+    }
+
+    pub fn field(&self, name: &str) -> Option<&String> {
+        self.fields.get(name)
+    }
+}
+
+// Like `collect_multipart`, but file parts go chunk by chunk to temp files. Type checks happen
+// before anything is written and the size check as chunks arrive, so a part over the limit is
+// cut off early. On any error, everything written so far is removed.
+pub async fn stream_multipart(
+    mut multipart: Multipart<'_>,
+    policy: &UploadPolicy,
+) -> Result<StreamedForm, Box<dyn std::error::Error + Send + Sync>> {
+    let temp_dir = policy.temp_dir.clone().unwrap_or_else(std::env::temp_dir);
+    fs::create_dir_all(&temp_dir).await?;
+    let mut form = StreamedForm::default();
+
+    while let Some(mut field) = multipart.next_field().await? {
+        let field_name = field.name().unwrap_or_default().to_string();
+        let filename = match field.file_name() {
+            Some(filename) => filename.to_string(),
+            None => {
+                form.fields.insert(field_name, field.text().await?);
+                continue;
+            }
+        };
+        let content_type = field.content_type()
+            .map(|mime| mime.to_string())
+            .unwrap_or_else(|| "application/octet-stream".to_string());
+        policy.check_type(&filename, &content_type)?;
+
+        // Owned from here on, so an early return removes the partial file
+        let mut upload = StreamedUpload {
+            field_name,
+            filename,
+            content_type,
+            size: 0,
+            temp_path: Some(temp_dir.join(format!("rustnext-upload-{}", uuid::Uuid::new_v4()))),
+        };
+        let mut file = fs::File::create(upload.path()).await?;
+        while let Some(chunk) = field.chunk().await? {
+            upload.size += chunk.len();
+            policy.check_size(upload.size)?;
+            file.write_all(&chunk).await?;
+        }
+        file.flush().await?;
+        form.files.push(upload);
+    }
+
+    Ok(form)
+}
+
+impl Request {
+    pub async fn multipart_form(&mut self) -> Result<MultipartForm, Box<dyn std::error::Error + Send + Sync>> {
+        let multipart = self.multipart()?;
+        collect_multipart(multipart).await
+    }
+
+    // Streams file parts to disk (see `stream_multipart`). The whole body is still capped by
+    // `max_body_size`, so routes taking large uploads need a matching `BodyLimit`.
+    pub async fn multipart_to_disk(&mut self, policy: &UploadPolicy) -> Result<StreamedForm, Box<dyn std::error::Error + Send + Sync>> {
+        let multipart = self.multipart()?;
+        stream_multipart(multipart, policy).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+
+    fn upload(filename: &str, content_type: &str, data: &[u8]) -> FileUpload {
         FileUpload {
-            filename: "example.txt".to_string(),
-            content_type: "text/plain".to_string(),
-            size: body_bytes.len(),
-            data: body_bytes.to_vec(),
+            field_name: "file".to_string(),
+            filename: filename.to_string(),
+            content_type: content_type.to_string(),
+            size: data.len(),
+            data: data.to_vec(),
+        }
+    }
+
+    const BOUNDARY: &str = "X-BOUNDARY";
+
+    // A multipart body with one part per `(name, filename, content)`; parts without a filename
+    // are plain fields
+    fn multipart_body(parts: &[(&str, Option<&str>, &[u8])]) -> Vec<u8> {
+        let mut body = Vec::new();
+        for (name, filename, content) in parts {
+            body.extend_from_slice(format!("--{}\r\n", BOUNDARY).as_bytes());
+            match filename {
+                Some(filename) => body.extend_from_slice(
+                    format!(
+                        "Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\nContent-Type: text/plain\r\n\r\n",
+                        name, filename
+                    )
+                    .as_bytes(),
+                ),
+                None => body.extend_from_slice(format!("Content-Disposition: form-data; name=\"{}\"\r\n\r\n", name).as_bytes()),
+            }
+            body.extend_from_slice(content);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(format!("--{}--\r\n", BOUNDARY).as_bytes());
+        body
+    }
+
+    fn upload_files(dir: &Path) -> Vec<PathBuf> {
+        std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.file_name().unwrap().to_string_lossy().starts_with("rustnext-upload-"))
+            .collect()
+    }
+
+    #[test]
+    fn sanitize_strips_directories_and_leading_dots() {
+        assert_eq!(sanitize_filename("../../etc/passwd").as_deref(), Some("passwd"));
+        assert_eq!(sanitize_filename("..\\..\\x.exe").as_deref(), Some("x.exe"));
+        assert_eq!(sanitize_filename(".htaccess").as_deref(), Some("htaccess"));
+        assert_eq!(sanitize_filename("re\u{0}port\n.pdf").as_deref(), Some("report.pdf"));
+    }
+
+    #[test]
+    fn names_with_nothing_left_are_invalid() {
+        for name in ["", "..", "...", "uploads/..", " "] {
+            assert_eq!(sanitize_filename(name), None, "{:?}", name);
+            assert_eq!(
+                upload(name, "text/plain", b"x").safe_filename(),
+                Err(UploadError::InvalidFilename(name.to_string()))
+            );
         }
-    ];
+        assert_eq!(
+            UploadPolicy::new().check(&upload("..", "text/plain", b"x")),
+            Err(UploadError::InvalidFilename("..".to_string()))
+        );
+    }
+
+    #[test]
+    fn size_is_checked_before_type() {
+        let policy = UploadPolicy::new().max_size(4).allow_extension("png");
+
+        assert_eq!(
+            policy.check(&upload("a.exe", "application/octet-stream", b"12345")),
+            Err(UploadError::TooLarge { size: 5, limit: 4 })
+        );
+        assert_eq!(
+            policy.check(&upload("a.exe", "application/octet-stream", b"1234")),
+            Err(UploadError::DisallowedType {
+                filename: "a.exe".to_string(),
+                content_type: "application/octet-stream".to_string(),
+            })
+        );
+        assert_eq!(policy.check(&upload("a.png", "image/png", b"1234")), Ok(()));
+    }
+
+    #[test]
+    fn size_counts_the_data_not_the_claimed_size() {
+        let policy = UploadPolicy::new().max_size(4);
+        let mut lying = upload("a.txt", "text/plain", b"123456");
+        lying.size = 1;
+        assert_eq!(policy.check(&lying), Err(UploadError::TooLarge { size: 6, limit: 4 }));
+    }
+
+    #[test]
+    fn extensions_match_case_insensitively_on_the_sanitized_name() {
+        let policy = UploadPolicy::new().allow_extensions(&[".PNG", "jpg"]);
 
-    Ok(uploads)
+        assert_eq!(policy.check(&upload("photo.png", "image/png", b"x")), Ok(()));
+        assert_eq!(policy.check(&upload("../Photo.JPG", "image/jpeg", b"x")), Ok(()));
+        assert!(matches!(
+            policy.check(&upload("photo.png.exe", "image/png", b"x")),
+            Err(UploadError::DisallowedType { .. })
+        ));
+        // `.png` alone sanitizes to `png`, which has no extension
+        assert!(matches!(
+            policy.check(&upload(".png", "image/png", b"x")),
+            Err(UploadError::DisallowedType { .. })
+        ));
+    }
+
+    #[test]
+    fn content_types_match_exactly_or_by_wildcard() {
+        let policy = UploadPolicy::new().allow_content_type("image/*").allow_content_type("application/pdf");
+
+        assert_eq!(policy.check(&upload("a", "image/png", b"x")), Ok(()));
+        assert_eq!(policy.check(&upload("a", "IMAGE/WebP", b"x")), Ok(()));
+        assert_eq!(policy.check(&upload("a", "application/pdf; charset=binary", b"x")), Ok(()));
+        for content_type in ["text/html", "application/pdfx", "imagex/png", "image"] {
+            assert!(
+                matches!(policy.check(&upload("a", content_type, b"x")), Err(UploadError::DisallowedType { .. })),
+                "{}",
+                content_type
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn save_with_writes_nothing_when_the_check_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("uploads");
+        let policy = UploadPolicy::new().max_size(2);
+
+        let err = upload("a.txt", "text/plain", b"too big")
+            .save_with(target.to_str().unwrap(), &policy)
+            .await
+            .unwrap_err();
+        assert_eq!(err.downcast_ref::<UploadError>(), Some(&UploadError::TooLarge { size: 7, limit: 2 }));
+        assert!(!target.exists());
+
+        let path = upload("../../a.txt", "text/plain", b"ok")
+            .save_with(target.to_str().unwrap(), &policy)
+            .await
+            .unwrap();
+        assert_eq!(path, target.join("a.txt"));
+        assert_eq!(std::fs::read(&path).unwrap(), b"ok");
+    }
+
+    #[tokio::test]
+    async fn parse_multipart_splits_files_from_fields() {
+        let body = multipart_body(&[("avatar", Some("me.txt"), b"file body"), ("name", None, b"Ann")]);
+        let content_type = format!("multipart/form-data; boundary={}", BOUNDARY);
+
+        let form = parse_multipart(hyper::Body::from(body), &content_type).await.unwrap();
+        assert_eq!(form.files.len(), 1);
+        let file = form.file("avatar").unwrap();
+        assert_eq!(file.filename, "me.txt");
+        assert_eq!(file.content_type, "text/plain");
+        assert_eq!(file.size, 9);
+        assert_eq!(file.data, b"file body");
+        assert!(form.file("name").is_none());
+        assert_eq!(form.fields.len(), 1);
+        assert_eq!(form.field("name").map(String::as_str), Some("Ann"));
+    }
+
+    #[tokio::test]
+    async fn parse_multipart_needs_a_boundary() {
+        let body = || hyper::Body::from(multipart_body(&[("name", None, b"Ann")]));
+
+        assert!(parse_multipart(body(), "multipart/form-data").await.is_err());
+        assert!(parse_multipart(body(), "application/json").await.is_err());
+        // A boundary that isn't the one in the body
+        assert!(parse_multipart(body(), "multipart/form-data; boundary=OTHER").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn oversized_parts_are_cut_off_mid_stream_and_removed() {
+        let temp = tempfile::tempdir().unwrap();
+        let policy = UploadPolicy::new().max_size(8).temp_dir(temp.path().to_str().unwrap());
+
+        // The first chunk already goes over the limit and the rest of the body never arrives,
+        // so this only returns if the part is abandoned early
+        let mut head = multipart_body(&[("note", None, b"hi"), ("file", Some("big.txt"), &[b'x'; 64])]);
+        head.truncate(head.len() - 20);
+        let stream = futures::stream::iter([Ok::<_, std::io::Error>(hyper::body::Bytes::from(head))])
+            .chain(futures::stream::pending());
+
+        let err = stream_multipart(Multipart::new(stream, BOUNDARY), &policy).await.err().unwrap();
+        assert!(matches!(err.downcast_ref::<UploadError>(), Some(UploadError::TooLarge { limit: 8, .. })));
+        assert!(upload_files(temp.path()).is_empty());
+    }
+
+    #[tokio::test]
+    async fn dropped_uploads_delete_their_temp_file() {
+        let temp = tempfile::tempdir().unwrap();
+        let policy = UploadPolicy::new().temp_dir(temp.path().to_str().unwrap());
+        let body = multipart_body(&[("file", Some("a.txt"), b"hello"), ("name", None, b"Ann")]);
+
+        let form = stream_multipart(Multipart::new(hyper::Body::from(body), BOUNDARY), &policy).await.unwrap();
+        let file = form.file("file").unwrap();
+        assert_eq!(file.size, 5);
+        assert_eq!(std::fs::read(file.path()).unwrap(), b"hello");
+        assert_eq!(form.field("name").map(String::as_str), Some("Ann"));
+        assert_eq!(upload_files(temp.path()).len(), 1);
+
+        drop(form);
+        assert!(upload_files(temp.path()).is_empty());
+    }
+
+    #[tokio::test]
+    async fn persist_moves_the_file_under_its_sanitized_name() {
+        let temp = tempfile::tempdir().unwrap();
+        let target = tempfile::tempdir().unwrap();
+        let policy = UploadPolicy::new().temp_dir(temp.path().to_str().unwrap());
+        let body = multipart_body(&[("file", Some("../../.report.txt"), b"contents")]);
+
+        let mut form = stream_multipart(Multipart::new(hyper::Body::from(body), BOUNDARY), &policy).await.unwrap();
+        let path = form.take_file("file").unwrap().persist_to(target.path().to_str().unwrap()).await.unwrap();
+
+        assert_eq!(path, target.path().join("report.txt"));
+        assert_eq!(std::fs::read(&path).unwrap(), b"contents");
+        assert!(upload_files(temp.path()).is_empty());
+    }
 }
@@ -1,18 +1,59 @@
 use crate::Request;
 // Removed unused imports: Response, Deserialize, Serialize
+use regex::Regex;
 use std::collections::HashMap;
 
-// Removed pub mod validation;
-// Removed pub mod form_builder;
+pub mod render;
+pub mod validation;
+
+pub use validation::{validate_request, Rules, ValidationErrors};
 
 #[derive(Debug, Clone)]
 pub struct FormField {
     pub name: String,
+    // text, email, password, number, date, hidden, textarea, select, radio, checkbox, ...
     pub field_type: String,
     pub value: String,
     pub required: bool,
     pub validation_rules: Vec<ValidationRule>,
     pub errors: Vec<String>,
+    pub label: String,
+    pub placeholder: Option<String>,
+    // (value, label) pairs for select and radio fields
+    pub options: Vec<(String, String)>,
+    // Multi-selects only: every submitted value (`value` holds them comma-joined)
+    pub multiple: bool,
+    pub values: Vec<String>,
+}
+
+// Typed view of `FormField::field_type`; other HTML input types (email, url, ...) are `Text`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+    Text,
+    TextArea,
+    Select { multiple: bool },
+    Radio,
+    Checkbox,
+    Date,
+    Number,
+    Hidden,
+    Password,
+}
+
+impl FieldKind {
+    pub fn field_type(&self) -> &'static str {
+        match self {
+            FieldKind::Text => "text",
+            FieldKind::TextArea => "textarea",
+            FieldKind::Select { .. } => "select",
+            FieldKind::Radio => "radio",
+            FieldKind::Checkbox => "checkbox",
+            FieldKind::Date => "date",
+            FieldKind::Number => "number",
+            FieldKind::Hidden => "hidden",
+            FieldKind::Password => "password",
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -21,7 +62,15 @@ pub enum ValidationRule {
     MinLength(usize),
     MaxLength(usize),
     Email,
+    // http(s) URL with a host
+    Url,
     Numeric,
+    // Inclusive numeric bounds
+    Range(f64, f64),
+    Pattern(Regex),
+    OneOf(Vec<String>),
+    // Must equal the value of the named field, e.g. Matches("password".into())
+    Matches(String),
     Custom(fn(&str) -> Result<(), String>),
 }
 
@@ -30,6 +79,11 @@ pub struct Form {
     pub fields: HashMap<String, FormField>,
     pub is_valid: bool,
     pub errors: Vec<String>,
+    // Render order of `fields`
+    pub field_order: Vec<String>,
+    pub action: String,
+    pub method: String,
+    pub submit_label: String,
 }
 
 impl Form {
@@ -38,68 +92,80 @@ impl Form {
             fields: HashMap::new(),
             is_valid: true,
             errors: Vec::new(),
+            field_order: Vec::new(),
+            action: String::new(),
+            method: "POST".to_string(),
+            submit_label: "Submit".to_string(),
         }
     }
 
     pub fn add_field(&mut self, name: &str, field_type: &str, required: bool) -> &mut FormField {
-        let field = FormField {
-            name: name.to_string(),
-            field_type: field_type.to_string(),
-            value: String::new(),
-            required,
-            validation_rules: Vec::new(),
-            errors: Vec::new(),
-        };
-        
-        self.fields.insert(name.to_string(), field);
+        let mut field = FormField::new(name, field_type);
+        field.required = required;
+        self.insert_field(field);
         self.fields.get_mut(name).unwrap()
     }
 
+    // Builder-style counterpart of `add_field`, e.g.
+    // `Form::new().field(FormField::new("email", "email").label("Email").required().email())`
+    pub fn field(mut self, field: FormField) -> Self {
+        self.insert_field(field);
+        self
+    }
+
+    fn insert_field(&mut self, field: FormField) {
+        if !self.fields.contains_key(&field.name) {
+            self.field_order.push(field.name.clone());
+        }
+        self.fields.insert(field.name.clone(), field);
+    }
+
+    pub fn action(mut self, action: &str) -> Self {
+        self.action = action.to_string();
+        self
+    }
+
+    pub fn method(mut self, method: &str) -> Self {
+        self.method = method.to_string();
+        self
+    }
+
+    pub fn submit_label(mut self, label: &str) -> Self {
+        self.submit_label = label.to_string();
+        self
+    }
+
     pub fn validate(&mut self) -> bool {
         self.is_valid = true;
         self.errors.clear();
 
-        for (_, field) in &mut self.fields {
+        let values: HashMap<String, String> = self.fields.iter()
+            .map(|(name, field)| (name.clone(), field.value.clone()))
+            .collect();
+
+        for field in self.fields.values_mut() {
             field.errors.clear();
-            
-            for rule in &field.validation_rules {
-                match rule {
-                    ValidationRule::Required => {
-                        if field.value.trim().is_empty() {
-                            field.errors.push(format!("{} is required", field.name));
-                            self.is_valid = false;
-                        }
-                    }
-                    ValidationRule::MinLength(min) => {
-                        if field.value.len() < *min {
-                            field.errors.push(format!("{} must be at least {} characters", field.name, min));
-                            self.is_valid = false;
-                        }
-                    }
-                    ValidationRule::MaxLength(max) => {
-                        if field.value.len() > *max {
-                            field.errors.push(format!("{} must be no more than {} characters", field.name, max));
-                            self.is_valid = false;
-                        }
-                    }
-                    ValidationRule::Email => {
-                        if !field.value.contains('@') || !field.value.contains('.') {
-                            field.errors.push(format!("{} must be a valid email", field.name));
-                            self.is_valid = false;
-                        }
-                    }
-                    ValidationRule::Numeric => {
-                        if field.value.parse::<f64>().is_err() {
-                            field.errors.push(format!("{} must be a number", field.name));
-                            self.is_valid = false;
-                        }
-                    }
-                    ValidationRule::Custom(validator) => {
-                        if let Err(error) = validator(&field.value) {
-                            field.errors.push(error);
-                            self.is_valid = false;
-                        }
-                    }
+            // Rules see a checkbox as "on" or empty, so Required means "must be checked"
+            let value = if field.kind() == FieldKind::Checkbox {
+                if field.checked() { "on".to_string() } else { String::new() }
+            } else {
+                field.value.clone()
+            };
+
+            if let Some(error) = field.check_kind() {
+                field.errors.push(error);
+                self.is_valid = false;
+            }
+
+            // `required: true` without an explicit Required rule still counts
+            let implicit_required = (field.required
+                && !field.validation_rules.iter().any(|r| matches!(r, ValidationRule::Required)))
+                .then_some(ValidationRule::Required);
+
+            for rule in implicit_required.iter().chain(&field.validation_rules) {
+                if let Some(error) = rule.check(&field.label, &value, &values) {
+                    field.errors.push(error);
+                    self.is_valid = false;
                 }
             }
         }
@@ -107,20 +173,194 @@ impl Form {
         self.is_valid
     }
 
-    pub fn populate_from_request(&mut self, req: &Request) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        // This would parse form data from the request body
-        // For now, we'll use query parameters as a simple example
-        for (key, value) in &req.query {
-            if let Some(field) = self.fields.get_mut(key) {
-                field.value = value.clone();
+    // Field errors from the last `validate()` as `{ "field": ["msg", ...] }`
+    pub fn errors_as_json(&self) -> serde_json::Value {
+        self.validation_errors().to_json()
+    }
+
+    pub fn validation_errors(&self) -> ValidationErrors {
+        let mut errors = ValidationErrors::new();
+        for field in self.fields.values() {
+            for error in &field.errors {
+                errors.add(&field.name, error.clone());
             }
         }
+        errors
+    }
+
+    // Uses the parsed body (`req.form()`) when available, otherwise the query string. Every
+    // value of a repeated name is kept, so multi-selects and checkbox groups work.
+    pub fn populate_from_request(&mut self, req: &Request) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let values = req.form_values.as_ref().unwrap_or(&req.query_values);
+        let pairs: Vec<(String, String)> = values.iter()
+            .flat_map(|(name, values)| values.iter().map(move |value| (name.clone(), value.clone())))
+            .collect();
+        self.populate_from_pairs(&pairs);
         Ok(())
     }
+
+    // Takes the output of `req.form()`. Checkboxes missing from the data are unchecked.
+    // A HashMap keeps one value per name; use `populate_from_pairs` for multi-selects.
+    pub fn populate_from_form_data(&mut self, data: &HashMap<String, String>) {
+        for field in self.fields.values_mut() {
+            match data.get(&field.name) {
+                Some(value) => field.set_values(vec![value.clone()]),
+                None => field.clear_if_unsubmitted(),
+            }
+        }
+    }
+
+    // Takes raw name/value pairs, keeping repeated names, e.g.
+    // `url::form_urlencoded::parse(&body).into_owned().collect::<Vec<_>>()`
+    pub fn populate_from_pairs(&mut self, pairs: &[(String, String)]) {
+        for field in self.fields.values_mut() {
+            let submitted: Vec<String> = pairs.iter()
+                .filter(|(name, _)| *name == field.name)
+                .map(|(_, value)| value.clone())
+                .collect();
+            if submitted.is_empty() {
+                field.clear_if_unsubmitted();
+            } else {
+                field.set_values(submitted);
+            }
+        }
+    }
+
+    // Checkbox state; unknown fields count as unchecked
+    pub fn bool_value(&self, name: &str) -> bool {
+        self.fields.get(name).map(|f| f.checked()).unwrap_or(false)
+    }
+
+    // All selected values of a (multi-)select
+    pub fn values(&self, name: &str) -> Vec<String> {
+        self.fields.get(name).map(|f| f.selected_values()).unwrap_or_default()
+    }
+}
+
+impl Default for Form {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl FormField {
+    pub fn new(name: &str, field_type: &str) -> Self {
+        FormField {
+            name: name.to_string(),
+            field_type: field_type.to_string(),
+            value: String::new(),
+            required: false,
+            validation_rules: Vec::new(),
+            errors: Vec::new(),
+            label: name.to_string(),
+            placeholder: None,
+            options: Vec::new(),
+            multiple: false,
+            values: Vec::new(),
+        }
+    }
+
+    pub fn of_kind(name: &str, kind: FieldKind) -> Self {
+        let mut field = Self::new(name, kind.field_type());
+        field.multiple = matches!(kind, FieldKind::Select { multiple: true });
+        field
+    }
+
+    pub fn kind(&self) -> FieldKind {
+        match self.field_type.as_str() {
+            "textarea" => FieldKind::TextArea,
+            "select" => FieldKind::Select { multiple: self.multiple },
+            "radio" => FieldKind::Radio,
+            "checkbox" => FieldKind::Checkbox,
+            "date" => FieldKind::Date,
+            "number" => FieldKind::Number,
+            "hidden" => FieldKind::Hidden,
+            "password" => FieldKind::Password,
+            _ => FieldKind::Text,
+        }
+    }
+
+    pub fn checked(&self) -> bool {
+        matches!(self.value.trim().to_ascii_lowercase().as_str(), "on" | "true" | "1" | "yes")
+    }
+
+    pub fn selected_values(&self) -> Vec<String> {
+        if self.multiple {
+            self.values.clone()
+        } else if self.value.is_empty() {
+            Vec::new()
+        } else {
+            vec![self.value.clone()]
+        }
+    }
+
+    fn set_values(&mut self, mut submitted: Vec<String>) {
+        if self.multiple {
+            submitted.retain(|v| !v.is_empty());
+            self.value = submitted.join(",");
+            self.values = submitted;
+        } else if let Some(last) = submitted.pop() {
+            self.value = last;
+        }
+    }
+
+    // Browsers omit unchecked boxes and empty multi-selects entirely
+    fn clear_if_unsubmitted(&mut self) {
+        match self.kind() {
+            FieldKind::Checkbox => self.value.clear(),
+            FieldKind::Select { multiple: true } => {
+                self.value.clear();
+                self.values.clear();
+            }
+            _ => {}
+        }
+    }
+
+    // Kind-specific checks that apply before the explicit rules
+    fn check_kind(&self) -> Option<String> {
+        let known = |v: &String| self.options.iter().any(|(option, _)| option == v);
+        match self.kind() {
+            FieldKind::Select { .. } | FieldKind::Radio if !self.options.is_empty() => {
+                self.selected_values().iter().any(|v| !known(v))
+                    .then(|| format!("{} must be one of the available options", self.label))
+            }
+            FieldKind::Number if !self.value.trim().is_empty() => self.value.trim().parse::<f64>().is_err()
+                .then(|| format!("{} must be a number", self.label)),
+            FieldKind::Date if !self.value.trim().is_empty() => {
+                chrono::NaiveDate::parse_from_str(self.value.trim(), "%Y-%m-%d").is_err()
+                    .then(|| format!("{} must be a date (YYYY-MM-DD)", self.label))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn label(mut self, label: &str) -> Self {
+        self.label = label.to_string();
+        self
+    }
+
+    pub fn placeholder(mut self, placeholder: &str) -> Self {
+        self.placeholder = Some(placeholder.to_string());
+        self
+    }
+
+    pub fn value(mut self, value: &str) -> Self {
+        self.value = value.to_string();
+        self
+    }
+
+    pub fn multiple(mut self) -> Self {
+        self.multiple = true;
+        self
+    }
+
+    pub fn option(mut self, value: &str, label: &str) -> Self {
+        self.options.push((value.to_string(), label.to_string()));
+        self
+    }
+
     pub fn required(mut self) -> Self {
+        self.required = true;
         self.validation_rules.push(ValidationRule::Required);
         self
     }
@@ -144,4 +384,132 @@ impl FormField {
         self.validation_rules.push(ValidationRule::Numeric);
         self
     }
+
+    pub fn url(mut self) -> Self {
+        self.validation_rules.push(ValidationRule::Url);
+        self
+    }
+
+    pub fn range(mut self, min: f64, max: f64) -> Self {
+        self.validation_rules.push(ValidationRule::Range(min, max));
+        self
+    }
+
+    pub fn pattern(mut self, regex: Regex) -> Self {
+        self.validation_rules.push(ValidationRule::Pattern(regex));
+        self
+    }
+
+    pub fn one_of(mut self, options: &[&str]) -> Self {
+        self.validation_rules.push(ValidationRule::OneOf(options.iter().map(|o| o.to_string()).collect()));
+        self
+    }
+
+    pub fn matches(mut self, other_field: &str) -> Self {
+        self.validation_rules.push(ValidationRule::Matches(other_field.to_string()));
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    fn choices_form() -> Form {
+        let size = FormField::of_kind("size", FieldKind::Select { multiple: false })
+            .label("Size")
+            .option("s", "Small")
+            .option("m", "Medium");
+        let color = FormField::of_kind("color", FieldKind::Radio)
+            .label("Color")
+            .option("red", "Red")
+            .option("blue", "Blue");
+        let tags = FormField::of_kind("tags", FieldKind::Select { multiple: true })
+            .label("Tags")
+            .option("a", "A")
+            .option("b", "B")
+            .option("c", "C");
+        let terms = FormField::of_kind("terms", FieldKind::Checkbox).label("Terms").required();
+        Form::new().field(size).field(color).field(tags).field(terms)
+    }
+
+    async fn post(body: &str) -> Request {
+        let req = hyper::Request::post("/form")
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body(hyper::Body::from(body.to_string()))
+            .unwrap();
+        let mut req = Request::from_hyper(req).await.unwrap();
+        req.form().await.unwrap();
+        req
+    }
+
+    #[test]
+    fn known_options_validate() {
+        let mut form = choices_form();
+        form.populate_from_pairs(&pairs(&[("size", "m"), ("color", "blue"), ("tags", "a"), ("tags", "c"), ("terms", "on")]));
+
+        assert!(form.validate(), "{}", form.errors_as_json());
+        assert_eq!(form.values("size"), ["m"]);
+        assert_eq!(form.values("color"), ["blue"]);
+        assert_eq!(form.values("tags"), ["a", "c"]);
+        assert!(form.bool_value("terms"));
+    }
+
+    #[test]
+    fn unknown_options_are_rejected() {
+        let mut form = choices_form();
+        form.populate_from_pairs(&pairs(&[("size", "xl"), ("color", "green"), ("tags", "a"), ("tags", "z"), ("terms", "on")]));
+
+        assert!(!form.validate());
+        assert_eq!(form.errors_as_json(), serde_json::json!({
+            "color": ["Color must be one of the available options"],
+            "size": ["Size must be one of the available options"],
+            "tags": ["Tags must be one of the available options"],
+        }));
+    }
+
+    #[test]
+    fn unsubmitted_checkboxes_and_multi_selects_are_cleared() {
+        let mut form = choices_form();
+        form.populate_from_pairs(&pairs(&[("tags", "a"), ("terms", "on")]));
+        form.populate_from_pairs(&pairs(&[("size", "s")]));
+
+        assert!(form.values("tags").is_empty());
+        assert!(!form.bool_value("terms"));
+        assert!(!form.validate());
+        assert_eq!(form.errors_as_json()["terms"], serde_json::json!(["Terms is required"]));
+    }
+
+    #[tokio::test]
+    async fn requests_keep_every_value_of_a_multi_select() {
+        let mut form = choices_form();
+        form.populate_from_request(&post("tags=a&tags=b&size=s&terms=on").await).unwrap();
+        assert_eq!(form.values("tags"), ["a", "b"]);
+        assert_eq!(form.values("size"), ["s"]);
+        assert!(form.bool_value("terms"));
+
+        // Without a parsed body, the query string
+        let req = hyper::Request::get("/form?tags[]=b&tags[]=c").body(hyper::Body::empty()).unwrap();
+        let mut form = choices_form();
+        form.populate_from_request(&Request::from_hyper(req).await.unwrap()).unwrap();
+        assert_eq!(form.values("tags"), ["b", "c"]);
+    }
+
+    #[tokio::test]
+    async fn multi_selects_round_trip_through_rendering() {
+        let mut form = choices_form();
+        form.populate_from_request(&post("tags=a&tags=c").await).unwrap();
+
+        // form-group > [label, select > options]
+        let group = form.fields["tags"].to_element();
+        let selected: Vec<&str> = group.children[1].children.iter()
+            .filter(|option| option.props.contains_key("selected"))
+            .filter_map(|option| option.props["value"].as_str())
+            .collect();
+        assert_eq!(selected, ["a", "c"]);
+    }
 }
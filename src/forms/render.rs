@@ -0,0 +1,133 @@
+use super::{FieldKind, Form, FormField};
+use crate::ui::{button, div, form, input, label, text, Element};
+
+impl Form {
+    // Renders the fields in definition order with their current values and, after a failed
+    // `validate()`, each field's errors beneath it. Uses the built-in CSS classes.
+    pub fn to_element(&self) -> Element {
+        let mut element = form().prop("method", self.method.as_str());
+        if !self.action.is_empty() {
+            element = element.prop("action", self.action.as_str());
+        }
+
+        for error in &self.errors {
+            element = element.child(div().class("error-message").child(text(error)));
+        }
+
+        for name in &self.field_order {
+            if let Some(field) = self.fields.get(name) {
+                element = element.child(field.to_element());
+            }
+        }
+
+        element.child(
+            button()
+                .prop("type", "submit")
+                .class("btn mt-2")
+                .child(text(&self.submit_label)),
+        )
+    }
+}
+
+impl FormField {
+    // A `form-group` with the label, the control and any error messages
+    pub fn to_element(&self) -> Element {
+        let selected = self.selected_values();
+        let control = match self.kind() {
+            FieldKind::Hidden => {
+                return input()
+                    .prop("type", "hidden")
+                    .prop("name", self.name.as_str())
+                    .prop("value", self.value.as_str());
+            }
+            FieldKind::TextArea => self.base_control("textarea")
+                .prop("rows", "3")
+                .child(text(&self.value)),
+            FieldKind::Select { multiple } => {
+                let mut select = self.base_control("select");
+                if multiple {
+                    select = select.prop("multiple", "multiple");
+                } else if let Some(placeholder) = &self.placeholder {
+                    select = select.child(Element::new("option").prop("value", "").child(text(placeholder)));
+                }
+                for (value, option_label) in &self.options {
+                    let mut option = Element::new("option").prop("value", value.as_str());
+                    if selected.contains(value) {
+                        option = option.prop("selected", "selected");
+                    }
+                    select = select.child(option.child(text(option_label)));
+                }
+                select
+            }
+            FieldKind::Radio => {
+                let mut group = div().prop("role", "radiogroup");
+                for (i, (value, option_label)) in self.options.iter().enumerate() {
+                    let id = format!("{}_{}", self.name, i);
+                    let mut radio = input()
+                        .prop("type", "radio")
+                        .prop("id", id.as_str())
+                        .prop("name", self.name.as_str())
+                        .prop("value", value.as_str());
+                    if selected.contains(value) {
+                        radio = radio.prop("checked", "checked");
+                    }
+                    if self.required {
+                        radio = radio.prop("required", "required");
+                    }
+                    group = group.child(
+                        label().prop("for", id.as_str()).child(radio).child(text(option_label)),
+                    );
+                }
+                group
+            }
+            FieldKind::Checkbox => {
+                let mut checkbox = input()
+                    .prop("type", "checkbox")
+                    .prop("id", self.name.as_str())
+                    .prop("name", self.name.as_str())
+                    .prop("value", "on");
+                if self.checked() {
+                    checkbox = checkbox.prop("checked", "checked");
+                }
+                if self.required {
+                    checkbox = checkbox.prop("required", "required");
+                }
+                checkbox
+            }
+            kind => {
+                let mut field_input = self.base_control("input").prop("type", self.field_type.as_str());
+                // Never echo passwords back into the page
+                if kind != FieldKind::Password {
+                    field_input = field_input.prop("value", self.value.as_str());
+                }
+                field_input
+            }
+        };
+
+        let mut group = div()
+            .class("form-group")
+            .child(label().prop("for", self.name.as_str()).child(text(&self.label)))
+            .child(control);
+
+        for error in &self.errors {
+            group = group.child(div().class("error-message").child(text(error)));
+        }
+        group
+    }
+
+    fn base_control(&self, tag: &str) -> Element {
+        let mut control = Element::new(tag)
+            .class("form-control")
+            .prop("id", self.name.as_str())
+            .prop("name", self.name.as_str());
+        if let Some(placeholder) = &self.placeholder {
+            if tag != "select" {
+                control = control.prop("placeholder", placeholder.as_str());
+            }
+        }
+        if self.required {
+            control = control.prop("required", "required");
+        }
+        control
+    }
+}
@@ -0,0 +1,274 @@
+use super::ValidationRule;
+use crate::api::ApiResponse;
+use once_cell::sync::OnceCell;
+use regex::Regex;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+
+fn email_regex() -> &'static Regex {
+    static EMAIL: OnceCell<Regex> = OnceCell::new();
+    EMAIL.get_or_init(|| {
+        Regex::new(r"^[A-Za-z0-9.!#$%&'*+/=?^_`{|}~-]+@[A-Za-z0-9](?:[A-Za-z0-9-]{0,61}[A-Za-z0-9])?(?:\.[A-Za-z0-9](?:[A-Za-z0-9-]{0,61}[A-Za-z0-9])?)+$").unwrap()
+    })
+}
+
+impl ValidationRule {
+    // Returns the error message if `value` fails this rule. `values` holds every field of the
+    // form/struct so cross-field rules like `Matches` can look at their counterpart.
+    // Format rules accept empty values so optional fields can stay blank; `Matches` doesn't, so a
+    // blank confirmation of a filled-in field is still a mismatch.
+    pub fn check(&self, name: &str, value: &str, values: &HashMap<String, String>) -> Option<String> {
+        match self {
+            ValidationRule::Required => value.trim().is_empty()
+                .then(|| format!("{} is required", name)),
+            ValidationRule::Email
+            | ValidationRule::Url
+            | ValidationRule::Numeric
+            | ValidationRule::Range(..)
+            | ValidationRule::Pattern(_)
+            | ValidationRule::OneOf(_) if value.trim().is_empty() => None,
+            ValidationRule::MinLength(min) => (value.chars().count() < *min)
+                .then(|| format!("{} must be at least {} characters", name, min)),
+            ValidationRule::MaxLength(max) => (value.chars().count() > *max)
+                .then(|| format!("{} must be no more than {} characters", name, max)),
+            ValidationRule::Email => (!email_regex().is_match(value))
+                .then(|| format!("{} must be a valid email", name)),
+            ValidationRule::Url => {
+                let valid = url::Url::parse(value)
+                    .map(|u| matches!(u.scheme(), "http" | "https") && u.host().is_some())
+                    .unwrap_or(false);
+                (!valid).then(|| format!("{} must be a valid URL", name))
+            }
+            ValidationRule::Numeric => value.parse::<f64>().is_err()
+                .then(|| format!("{} must be a number", name)),
+            ValidationRule::Range(min, max) => match value.parse::<f64>() {
+                Ok(n) if n >= *min && n <= *max => None,
+                Ok(_) => Some(format!("{} must be between {} and {}", name, min, max)),
+                Err(_) => Some(format!("{} must be a number", name)),
+            },
+            ValidationRule::Pattern(regex) => (!regex.is_match(value))
+                .then(|| format!("{} has an invalid format", name)),
+            ValidationRule::OneOf(options) => (!options.iter().any(|o| o == value))
+                .then(|| format!("{} must be one of: {}", name, options.join(", "))),
+            ValidationRule::Matches(other) => (values.get(other).map_or("", String::as_str) != value)
+                .then(|| format!("{} must match {}", name, other)),
+            ValidationRule::Custom(validator) => validator(value).err(),
+        }
+    }
+}
+
+// Field name -> messages, serialized as `{ "field": ["msg", ...] }`
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct ValidationErrors {
+    pub fields: BTreeMap<String, Vec<String>>,
+}
+
+impl ValidationErrors {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, field: &str, message: String) {
+        self.fields.entry(field.to_string()).or_default().push(message);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    pub fn get(&self, field: &str) -> Option<&Vec<String>> {
+        self.fields.get(field)
+    }
+
+    pub fn to_json(&self) -> Value {
+        serde_json::to_value(self).unwrap_or(Value::Null)
+    }
+
+    // 422 with `{"error": "Validation failed", "errors": {...}}`
+    pub fn into_api_response(self) -> ApiResponse {
+        let mut response = ApiResponse::error(hyper::StatusCode::UNPROCESSABLE_ENTITY, "Validation failed");
+        response.data["errors"] = self.to_json();
+        response
+    }
+}
+
+impl std::fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let messages: Vec<&str> = self.fields.values().flatten().map(String::as_str).collect();
+        write!(f, "{}", messages.join("; "))
+    }
+}
+
+impl std::error::Error for ValidationErrors {}
+
+// A reusable rule set, e.g. `Rules::new().field("email", &[Required, Email])`
+#[derive(Debug, Clone, Default)]
+pub struct Rules {
+    fields: Vec<(String, Vec<ValidationRule>)>,
+}
+
+impl Rules {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn field(mut self, name: &str, rules: &[ValidationRule]) -> Self {
+        self.fields.push((name.to_string(), rules.to_vec()));
+        self
+    }
+
+    pub fn validate(&self, values: &HashMap<String, String>) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        for (name, rules) in &self.fields {
+            let value = values.get(name).map(String::as_str).unwrap_or("");
+            for rule in rules {
+                if let Some(message) = rule.check(name, value, values) {
+                    errors.add(name, message);
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+// Runs `rules` against the top-level fields of a deserialized request body.
+// Strings are checked as-is, numbers and booleans by their text form, null/missing as empty.
+pub fn validate_request<T: Serialize>(value: &T, rules: &Rules) -> Result<(), ValidationErrors> {
+    let values = match serde_json::to_value(value) {
+        Ok(Value::Object(map)) => map.into_iter()
+            .map(|(key, value)| {
+                let text = match value {
+                    Value::String(s) => s,
+                    Value::Null => String::new(),
+                    other => other.to_string(),
+                };
+                (key, text)
+            })
+            .collect(),
+        _ => HashMap::new(),
+    };
+    rules.validate(&values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn matches_rejects_a_blank_confirmation_of_a_filled_in_field() {
+        let rules = Rules::new()
+            .field("password_confirmation", &[ValidationRule::Matches("password".into())]);
+
+        let errors = rules.validate(&values(&[("password", "hunter22"), ("password_confirmation", "")])).unwrap_err();
+        assert_eq!(
+            errors.get("password_confirmation"),
+            Some(&vec!["password_confirmation must match password".to_string()])
+        );
+        assert!(rules.validate(&values(&[("password", ""), ("password_confirmation", "")])).is_ok());
+        assert!(rules.validate(&values(&[("password_confirmation", "")])).is_ok());
+    }
+
+    fn check(rule: ValidationRule, value: &str) -> Option<String> {
+        rule.check("field", value, &HashMap::new())
+    }
+
+    #[test]
+    fn email_needs_a_local_part_and_a_dotted_domain() {
+        assert_eq!(check(ValidationRule::Email, "ann@example.com"), None);
+        assert_eq!(check(ValidationRule::Email, "ann.o'neil+tag@mail.example.co.uk"), None);
+        for value in ["ann", "ann@", "@example.com", "ann@example", "ann@-example.com", "a b@example.com"] {
+            assert_eq!(check(ValidationRule::Email, value), Some("field must be a valid email".to_string()), "{}", value);
+        }
+    }
+
+    #[test]
+    fn url_needs_http_or_https_and_a_host() {
+        assert_eq!(check(ValidationRule::Url, "https://example.com/path?q=1"), None);
+        assert_eq!(check(ValidationRule::Url, "http://localhost:8080"), None);
+        for value in ["example.com", "ftp://example.com", "javascript:alert(1)", "http://"] {
+            assert_eq!(check(ValidationRule::Url, value), Some("field must be a valid URL".to_string()), "{}", value);
+        }
+    }
+
+    #[test]
+    fn pattern_range_and_one_of() {
+        let zip = ValidationRule::Pattern(regex::Regex::new(r"^\d{5}$").unwrap());
+        assert_eq!(check(zip.clone(), "12345"), None);
+        assert_eq!(check(zip, "1234"), Some("field has an invalid format".to_string()));
+
+        let age = ValidationRule::Range(18.0, 65.0);
+        assert_eq!(check(age.clone(), "18"), None);
+        assert_eq!(check(age.clone(), "65.0"), None);
+        assert_eq!(check(age.clone(), "65.5"), Some("field must be between 18 and 65".to_string()));
+        assert_eq!(check(age, "old"), Some("field must be a number".to_string()));
+
+        let size = ValidationRule::OneOf(vec!["s".into(), "m".into()]);
+        assert_eq!(check(size.clone(), "m"), None);
+        assert_eq!(check(size, "M"), Some("field must be one of: s, m".to_string()));
+    }
+
+    #[test]
+    fn format_rules_accept_blank_values() {
+        let rules = [
+            ValidationRule::Email,
+            ValidationRule::Url,
+            ValidationRule::Numeric,
+            ValidationRule::Range(1.0, 2.0),
+            ValidationRule::Pattern(regex::Regex::new("^x$").unwrap()),
+            ValidationRule::OneOf(vec!["a".into()]),
+        ];
+        for rule in rules {
+            assert_eq!(check(rule.clone(), "  "), None, "{:?}", rule);
+        }
+        assert_eq!(check(ValidationRule::Required, "  "), Some("field is required".to_string()));
+    }
+
+    #[test]
+    fn lengths_count_characters_not_bytes() {
+        // 4 characters, 8 bytes
+        assert_eq!(check(ValidationRule::MaxLength(4), "日本語!"), None);
+        assert_eq!(check(ValidationRule::MinLength(4), "日本語!"), None);
+        assert_eq!(check(ValidationRule::MinLength(5), "日本語!"), Some("field must be at least 5 characters".to_string()));
+        assert_eq!(check(ValidationRule::MaxLength(3), "日本語!"), Some("field must be no more than 3 characters".to_string()));
+    }
+
+    #[test]
+    fn errors_serialize_as_field_to_messages() {
+        let rules = Rules::new()
+            .field("email", &[ValidationRule::Required, ValidationRule::Email])
+            .field("name", &[ValidationRule::Required, ValidationRule::MinLength(2)]);
+
+        let errors = rules.validate(&values(&[("email", "nope"), ("name", "")])).unwrap_err();
+        assert_eq!(errors.to_json(), serde_json::json!({
+            "email": ["email must be a valid email"],
+            "name": ["name is required", "name must be at least 2 characters"],
+        }));
+        assert_eq!(errors.to_string(), "email must be a valid email; name is required; name must be at least 2 characters");
+
+        let response = errors.into_api_response();
+        assert_eq!(response.status, hyper::StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(response.data["error"], "Validation failed");
+        assert_eq!(response.data["errors"]["email"], serde_json::json!(["email must be a valid email"]));
+    }
+
+    #[test]
+    fn form_errors_as_json_use_field_names() {
+        let mut form = crate::forms::Form::new()
+            .field(crate::forms::FormField::new("email", "email").label("Email").required().email());
+        form.populate_from_form_data(&values(&[("email", "ann@")]));
+
+        assert!(!form.validate());
+        assert_eq!(form.errors_as_json(), serde_json::json!({"email": ["Email must be a valid email"]}));
+    }
+}
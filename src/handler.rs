@@ -1,4 +1,4 @@
-use crate::{Request, Response};
+use crate::{error::IntoResponse, Request, Response};
 use async_trait::async_trait;
 use std::future::Future;
 use std::pin::Pin;
@@ -10,13 +10,16 @@ pub trait Handler: Send + Sync + 'static {
     async fn handle(&self, req: Request) -> Result<Response, Box<dyn std::error::Error + Send + Sync>>;
 }
 
+// Async closures returning anything `IntoResponse`, e.g. `|req| async move { Ok(Json(load(&req).await?)) }`
+// or the usual `Result<Response, Box<dyn Error + Send + Sync>>`
 #[async_trait]
 impl<F, Fut> Handler for F
 where
     F: Fn(Request) -> Fut + Send + Sync + 'static,
-    Fut: Future<Output = Result<Response, Box<dyn std::error::Error + Send + Sync>>> + Send + 'static,
+    Fut: Future + Send + 'static,
+    Fut::Output: IntoResponse,
 {
     async fn handle(&self, req: Request) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
-        self(req).await
+        self(req).await.into_response()
     }
 }
@@ -0,0 +1,204 @@
+use crate::ui::{get_renderer, Element};
+use crate::{Request, Response};
+use hyper::header::VARY;
+use serde_json::{Map, Value};
+use std::future::Future;
+
+// Request headers htmx sends (https://htmx.org/reference/#request_headers)
+pub const HX_REQUEST: &str = "HX-Request";
+pub const HX_BOOSTED: &str = "HX-Boosted";
+pub const HX_HISTORY_RESTORE_REQUEST: &str = "HX-History-Restore-Request";
+pub const HX_TARGET: &str = "HX-Target";
+pub const HX_TRIGGER: &str = "HX-Trigger";
+pub const HX_TRIGGER_NAME: &str = "HX-Trigger-Name";
+
+// Response headers htmx acts on, besides `HX-Trigger` (see `Response::hx_trigger`)
+pub const HX_REDIRECT: &str = "HX-Redirect";
+pub const HX_PUSH_URL: &str = "HX-Push-Url";
+
+impl Request {
+    fn htmx_header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).and_then(|v| v.to_str().ok())
+    }
+
+    // Sent by htmx, rather than a plain browser navigation or form submit
+    pub fn is_htmx(&self) -> bool {
+        self.htmx_header(HX_REQUEST) == Some("true")
+    }
+
+    // From an `hx-boost`ed link or form, which swaps in the whole body
+    pub fn is_htmx_boosted(&self) -> bool {
+        self.htmx_header(HX_BOOSTED) == Some("true")
+    }
+
+    // The `id` of the element the response will be swapped into
+    pub fn htmx_target(&self) -> Option<&str> {
+        self.htmx_header(HX_TARGET)
+    }
+
+    // The `id` of the element that triggered the request
+    pub fn htmx_trigger(&self) -> Option<&str> {
+        self.htmx_header(HX_TRIGGER)
+    }
+
+    // The `name` of the element that triggered the request
+    pub fn htmx_trigger_name(&self) -> Option<&str> {
+        self.htmx_header(HX_TRIGGER_NAME)
+    }
+
+    // Whether a fragment will do: an htmx request, but not a boosted one or a history restore
+    // after a cache miss, which both need the whole page
+    pub fn wants_htmx_partial(&self) -> bool {
+        self.is_htmx()
+            && !self.is_htmx_boosted()
+            && self.htmx_header(HX_HISTORY_RESTORE_REQUEST) != Some("true")
+    }
+}
+
+impl Response {
+    // Has htmx do a full page load of `url`; unlike a 3xx, this reaches the browser rather than
+    // being followed inside the XHR
+    pub fn hx_redirect(self, url: &str) -> Self {
+        self.header(HX_REDIRECT, url)
+    }
+
+    // Has htmx put `url` in the address bar and history
+    pub fn hx_push_url(self, url: &str) -> Self {
+        self.header(HX_PUSH_URL, url)
+    }
+
+    // Has htmx fire `event` on the target once the response arrives, with `detail` as the event's
+    // detail (Value::Null for none). Calls merge into one `HX-Trigger` JSON object, e.g.
+    //
+    //     response.hx_trigger("todo-added", json!({"id": 7})).hx_trigger("flash", json!("Saved"))
+    //
+    // sends `HX-Trigger: {"todo-added":{"id":7},"flash":"Saved"}`. An event already set with the
+    // same name is replaced.
+    pub fn hx_trigger(self, event: &str, detail: Value) -> Self {
+        let mut events = self.headers.get(HX_TRIGGER)
+            .and_then(|v| v.to_str().ok())
+            .map(parse_trigger_header)
+            .unwrap_or_default();
+        events.insert(event.to_string(), detail);
+        let value = Value::Object(events).to_string();
+        self.header(HX_TRIGGER, value)
+    }
+}
+
+// An `HX-Trigger` value: a JSON object, or a comma-separated list of event names without detail
+fn parse_trigger_header(value: &str) -> Map<String, Value> {
+    match serde_json::from_str(value) {
+        Ok(Value::Object(events)) => events,
+        _ => value.split(',')
+            .map(str::trim)
+            .filter(|event| !event.is_empty())
+            .map(|event| (event.to_string(), Value::Null))
+            .collect(),
+    }
+}
+
+// `element` on its own, without the document Renderer::render_to_response wraps around pages
+pub fn render_fragment(element: &Element) -> Response {
+    Response::new().html(&get_renderer().render_to_html(element))
+}
+
+// Only `fragment` for htmx requests that can take a partial (see `Request::wants_htmx_partial`),
+// otherwise the whole page that `full` builds around it, e.g.
+//
+//     let list = todo_list().await;
+//     render_partial_or_full(&req, list, |list| home_page(&req, list)).await
+//
+// Either way the response varies on `HX-Request`, so caches keep the two apart.
+pub async fn render_partial_or_full<F, Fut>(
+    req: &Request,
+    fragment: Element,
+    full: F,
+) -> Result<Response, Box<dyn std::error::Error + Send + Sync>>
+where
+    F: FnOnce(Element) -> Fut,
+    Fut: Future<Output = Element>,
+{
+    let response = if req.wants_htmx_partial() {
+        render_fragment(&fragment)
+    } else {
+        get_renderer().render_to_response(&full(fragment).await)?
+    };
+    Ok(response.append_header(VARY, HX_REQUEST))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::{li, text, ul};
+    use serde_json::json;
+
+    async fn request(headers: &[(&str, &str)]) -> Request {
+        let mut builder = hyper::Request::builder().uri("/todos");
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        Request::from_hyper(builder.body(hyper::Body::empty()).unwrap()).await.unwrap()
+    }
+
+    async fn body_text(response: Response) -> String {
+        let bytes = hyper::body::to_bytes(response.body).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn reads_request_headers() {
+        let req = request(&[(HX_REQUEST, "true"), (HX_TARGET, "list"), (HX_TRIGGER, "add"), (HX_TRIGGER_NAME, "title")]).await;
+        assert!(req.is_htmx());
+        assert!(!req.is_htmx_boosted());
+        assert_eq!(req.htmx_target(), Some("list"));
+        assert_eq!(req.htmx_trigger(), Some("add"));
+        assert_eq!(req.htmx_trigger_name(), Some("title"));
+
+        let plain = request(&[(HX_REQUEST, "false")]).await;
+        assert!(!plain.is_htmx());
+        assert_eq!(plain.htmx_target(), None);
+    }
+
+    #[tokio::test]
+    async fn boosted_and_history_restores_want_the_whole_page() {
+        assert!(request(&[(HX_REQUEST, "true")]).await.wants_htmx_partial());
+        assert!(!request(&[]).await.wants_htmx_partial());
+        assert!(!request(&[(HX_REQUEST, "true"), (HX_BOOSTED, "true")]).await.wants_htmx_partial());
+        assert!(!request(&[(HX_REQUEST, "true"), (HX_HISTORY_RESTORE_REQUEST, "true")]).await.wants_htmx_partial());
+    }
+
+    #[test]
+    fn hx_trigger_merges_events() {
+        let response = Response::new()
+            .hx_trigger("todo-added", json!({"id": 7}))
+            .hx_trigger("flash", json!("Saved"))
+            .hx_trigger("todo-added", json!({"id": 8}));
+        assert_eq!(response.headers[HX_TRIGGER], r#"{"flash":"Saved","todo-added":{"id":8}}"#);
+
+        let response = Response::new().header(HX_TRIGGER, "refresh, closeModal").hx_trigger("flash", Value::Null);
+        assert_eq!(response.headers[HX_TRIGGER], r#"{"closeModal":null,"flash":null,"refresh":null}"#);
+    }
+
+    #[test]
+    fn redirect_and_push_url_headers() {
+        let response = Response::new().hx_redirect("/login").hx_push_url("/todos?page=2");
+        assert_eq!(response.headers[HX_REDIRECT], "/login");
+        assert_eq!(response.headers[HX_PUSH_URL], "/todos?page=2");
+    }
+
+    #[tokio::test]
+    async fn renders_the_fragment_for_htmx_and_the_page_otherwise() {
+        let list = || ul().id("list").child(li().child(text("Milk")));
+        let page = |fragment: Element| async move { crate::ui::main().child(fragment) };
+
+        let partial = render_partial_or_full(&request(&[(HX_REQUEST, "true")]).await, list(), page).await.unwrap();
+        assert_eq!(partial.headers[VARY], HX_REQUEST);
+        assert_eq!(body_text(partial).await, "<ul id=\"list\"><li>Milk</li></ul>");
+
+        let full = render_partial_or_full(&request(&[]).await, list(), page).await.unwrap();
+        assert_eq!(full.headers[VARY], HX_REQUEST);
+        let body = body_text(full).await;
+        assert!(body.starts_with("<!DOCTYPE html>"));
+        assert!(body.contains("<main><ul id=\"list\"><li>Milk</li></ul></main>"));
+    }
+}
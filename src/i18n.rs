@@ -0,0 +1,462 @@
+use crate::middleware::Middleware;
+use crate::{Handler, Request, Response};
+use async_trait::async_trait;
+use log::{info, warn};
+use once_cell::sync::OnceCell;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug)]
+pub enum I18nError {
+    Read { path: String, source: std::io::Error },
+    // Not valid TOML/JSON, or not a table of messages at the top
+    Parse { path: String, message: String },
+}
+
+impl fmt::Display for I18nError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            I18nError::Read { path, source } => write!(f, "Failed to read message catalog {}: {}", path, source),
+            I18nError::Parse { path, message } => write!(f, "Failed to parse message catalog {}: {}", path, message),
+        }
+    }
+}
+
+impl std::error::Error for I18nError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            I18nError::Read { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+// Message catalogs per locale, e.g. from `locales/en.toml`:
+//
+//     [cart]
+//     title = "Your cart"
+//     greeting = "Hello, {name}!"
+//     items = { zero = "Your cart is empty", one = "{count} item", other = "{count} items" }
+//
+// Nested keys are joined with dots (`cart.title`). A message whose table has `zero`, `one`
+// and/or `other` is plural: `t` picks the form by the `count` argument.
+pub struct Translator {
+    default_locale: String,
+    // By canonical locale (`de-AT`), flattened keys
+    catalogs: HashMap<String, HashMap<String, String>>,
+    // Missing keys already logged
+    reported: Mutex<HashSet<String>>,
+}
+
+impl Translator {
+    pub fn new(default_locale: &str) -> Self {
+        Translator {
+            default_locale: canonical_locale(default_locale),
+            catalogs: HashMap::new(),
+            reported: Mutex::new(HashSet::new()),
+        }
+    }
+
+    // Every `<locale>.toml` and `<locale>.json` in `dir`
+    pub fn load_dir<P: AsRef<Path>>(dir: P, default_locale: &str) -> Result<Self, I18nError> {
+        let dir = dir.as_ref();
+        let read_error = |source| I18nError::Read { path: dir.display().to_string(), source };
+        let mut paths: Vec<_> = std::fs::read_dir(dir).map_err(read_error)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<_, _>>()
+            .map_err(read_error)?;
+        // Later files add to earlier ones for the same locale, so keep that deterministic
+        paths.sort();
+
+        let mut translator = Translator::new(default_locale);
+        for path in paths {
+            if matches!(path.extension().and_then(|ext| ext.to_str()), Some("toml" | "json")) {
+                translator = translator.load_file(&path)?;
+            }
+        }
+        info!("Loaded message catalogs for {} locale(s) from {}", translator.catalogs.len(), dir.display());
+        Ok(translator)
+    }
+
+    // One catalog, named for its locale (`de-AT.toml`)
+    pub fn load_file<P: AsRef<Path>>(self, path: P) -> Result<Self, I18nError> {
+        let path = path.as_ref();
+        let display = path.display().to_string();
+        let content = std::fs::read_to_string(path)
+            .map_err(|source| I18nError::Read { path: display.clone(), source })?;
+        let parse_error = |message: String| I18nError::Parse { path: display.clone(), message };
+        let messages: Value = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => serde_json::from_str(&content).map_err(|e| parse_error(e.to_string()))?,
+            _ => toml::from_str(&content).map_err(|e| parse_error(e.to_string()))?,
+        };
+        if !messages.is_object() {
+            return Err(parse_error("expected a table of messages".to_string()));
+        }
+        let locale = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default();
+        Ok(self.add_messages(locale, messages))
+    }
+
+    // Nested messages for `locale`, e.g. `json!({"cart": {"title": "Your cart"}})`, added to any
+    // it already has
+    pub fn add_messages(mut self, locale: &str, messages: Value) -> Self {
+        let catalog = self.catalogs.entry(canonical_locale(locale)).or_default();
+        flatten(&messages, String::new(), catalog);
+        self
+    }
+
+    pub fn default_locale(&self) -> &str {
+        &self.default_locale
+    }
+
+    pub fn locales(&self) -> impl Iterator<Item = &str> {
+        self.catalogs.keys().map(String::as_str)
+    }
+
+    // The available locale for a requested one: itself, or the closest more general one
+    // (`de-AT` falls back to `de`)
+    pub fn resolve(&self, requested: &str) -> Option<&str> {
+        fallback_chain(&canonical_locale(requested))
+            .find_map(|locale| self.catalogs.get_key_value(locale).map(|(locale, _)| locale.as_str()))
+    }
+
+    // `key` in `locale`, falling back to more general locales and then the default one, with
+    // `{name}` placeholders replaced by `args`. A missing key renders as the key itself.
+    pub fn t(&self, locale: &str, key: &str, args: &[(&str, &dyn fmt::Display)]) -> String {
+        let count = args.iter()
+            .find(|(name, _)| *name == "count")
+            .and_then(|(_, value)| value.to_string().trim().parse::<f64>().ok());
+        let locale = canonical_locale(locale);
+        let message = fallback_chain(&locale)
+            .chain(fallback_chain(&self.default_locale))
+            .filter_map(|locale| self.catalogs.get(locale))
+            .find_map(|catalog| lookup(catalog, key, count));
+
+        match message {
+            Some(message) => interpolate(message, args),
+            None => {
+                if self.reported.lock().map(|mut reported| reported.insert(key.to_string())).unwrap_or(false) {
+                    warn!("Missing translation for '{}' (locale {})", key, locale);
+                }
+                key.to_string()
+            }
+        }
+    }
+}
+
+fn flatten(value: &Value, prefix: String, catalog: &mut HashMap<String, String>) {
+    let join = |key: &str| if prefix.is_empty() { key.to_string() } else { format!("{}.{}", prefix, key) };
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                flatten(value, join(key), catalog);
+            }
+        }
+        Value::String(message) => {
+            catalog.insert(prefix, message.clone());
+        }
+        Value::Number(_) | Value::Bool(_) => {
+            catalog.insert(prefix, value.to_string());
+        }
+        Value::Array(_) | Value::Null => {}
+    }
+}
+
+// A plain message, or the plural form for `count`: `zero` (if given) for 0, `one` for 1, and
+// `other` for everything else
+fn lookup<'a>(catalog: &'a HashMap<String, String>, key: &str, count: Option<f64>) -> Option<&'a str> {
+    if let Some(message) = catalog.get(key) {
+        return Some(message);
+    }
+    let count = count?;
+    let form = |name: &str| catalog.get(&format!("{}.{}", key, name)).map(String::as_str);
+    let exact = if count == 0.0 {
+        form("zero")
+    } else if count == 1.0 {
+        form("one")
+    } else {
+        None
+    };
+    exact.or_else(|| form("other"))
+}
+
+// Unknown placeholders are left as they are
+fn interpolate(message: &str, args: &[(&str, &dyn fmt::Display)]) -> String {
+    let mut output = String::with_capacity(message.len());
+    let mut rest = message;
+    while let Some(start) = rest.find('{') {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let value = after.find('}').and_then(|end| {
+            let (_, value) = args.iter().find(|(name, _)| *name == &after[..end])?;
+            Some((value, end))
+        });
+        match value {
+            Some((value, end)) => {
+                output.push_str(&value.to_string());
+                rest = &after[end + 1..];
+            }
+            None => {
+                output.push('{');
+                rest = after;
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+// `de_at` and `DE-at` are both `de-AT`; scripts are title case (`zh-Hant`)
+fn canonical_locale(locale: &str) -> String {
+    locale.trim()
+        .split(['-', '_'])
+        .filter(|part| !part.is_empty())
+        .enumerate()
+        .map(|(i, part)| match part.len() {
+            _ if i == 0 => part.to_ascii_lowercase(),
+            2 => part.to_ascii_uppercase(),
+            4 => part[..1].to_ascii_uppercase() + &part[1..].to_ascii_lowercase(),
+            _ => part.to_ascii_lowercase(),
+        })
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+// `zh-Hant-TW`, `zh-Hant`, `zh`
+fn fallback_chain(locale: &str) -> impl Iterator<Item = &str> {
+    let mut next = Some(locale).filter(|locale| !locale.is_empty());
+    std::iter::from_fn(move || {
+        let current = next?;
+        next = current.rfind('-').map(|i| &current[..i]);
+        Some(current)
+    })
+}
+
+// The languages of an `Accept-Language` header, most preferred first (ties keep header order),
+// without those given `q=0`; malformed entries are skipped
+pub fn parse_accept_language(header: &str) -> Vec<(String, f32)> {
+    let mut languages: Vec<(String, f32)> = header.split(',')
+        .filter_map(|part| {
+            let mut pieces = part.split(';');
+            let tag = pieces.next()?.trim();
+            if tag.is_empty() || !tag.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'*') {
+                return None;
+            }
+            let q = match pieces.find_map(|param| param.trim().strip_prefix("q=")) {
+                Some(q) => q.trim().parse::<f32>().ok()?,
+                None => 1.0,
+            };
+            (q > 0.0).then(|| (tag.to_string(), q.min(1.0)))
+        })
+        .collect();
+    languages.sort_by(|a, b| b.1.total_cmp(&a.1));
+    languages
+}
+
+static GLOBAL_TRANSLATOR: OnceCell<Translator> = OnceCell::new();
+
+pub fn init_translator(translator: Translator) {
+    if GLOBAL_TRANSLATOR.set(translator).is_err() {
+        warn!("Translator already initialized, ignoring new initialization.");
+    }
+}
+
+// Without `init_translator`, an English translator with no messages, so every key renders as is
+pub fn get_translator() -> &'static Translator {
+    GLOBAL_TRANSLATOR.get_or_init(|| Translator::new("en"))
+}
+
+impl Request {
+    // `key` in the request's locale (see LocaleResolver) through the global translator
+    pub fn t(&self, key: &str, args: &[(&str, &dyn fmt::Display)]) -> String {
+        let translator = get_translator();
+        translator.t(self.locale.as_deref().unwrap_or(translator.default_locale()), key, args)
+    }
+}
+
+// Sets `req.locale` to the first locale the global translator has a catalog for, from the
+// `?lang=` query parameter, then the `locale` cookie, then `Accept-Language`, and otherwise the
+// translator's default locale. A language switcher links to `?lang=de` and sets the cookie.
+pub struct LocaleResolver {
+    query_param: String,
+    cookie_name: String,
+}
+
+impl LocaleResolver {
+    pub fn new() -> Self {
+        LocaleResolver {
+            query_param: "lang".to_string(),
+            cookie_name: "locale".to_string(),
+        }
+    }
+
+    pub fn query_param(mut self, name: &str) -> Self {
+        self.query_param = name.to_string();
+        self
+    }
+
+    pub fn cookie_name(mut self, name: &str) -> Self {
+        self.cookie_name = name.to_string();
+        self
+    }
+
+    pub fn resolve(&self, req: &Request, translator: &Translator) -> String {
+        let cookie = req.headers.get_all(hyper::header::COOKIE).iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(';'))
+            .filter_map(|pair| pair.trim().split_once('='))
+            .find(|(name, _)| *name == self.cookie_name)
+            .map(|(_, value)| value.trim_matches('"'));
+        let accept_language = req.headers.get(hyper::header::ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok())
+            .map(parse_accept_language)
+            .unwrap_or_default();
+
+        req.query.get(&self.query_param).map(String::as_str)
+            .into_iter()
+            .chain(cookie)
+            .chain(accept_language.iter().map(|(tag, _)| tag.as_str()))
+            .find_map(|requested| translator.resolve(requested))
+            .unwrap_or(translator.default_locale())
+            .to_string()
+    }
+}
+
+impl Default for LocaleResolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Middleware for LocaleResolver {
+    async fn handle(
+        &self,
+        mut req: Request,
+        next: Arc<dyn Handler>,
+    ) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+        req.locale = Some(self.resolve(&req, get_translator()));
+        next.handle(req).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn translator() -> Translator {
+        Translator::new("en")
+            .add_messages("en", json!({
+                "cart": {
+                    "title": "Your cart",
+                    "greeting": "Hello, {name}!",
+                    "items": {"zero": "Your cart is empty", "one": "{count} item", "other": "{count} items"},
+                },
+                "footer": "Made in {city}",
+            }))
+            .add_messages("de", json!({"cart": {"title": "Dein Warenkorb", "items": {"one": "{count} Artikel", "other": "{count} Artikel"}}}))
+            .add_messages("de_at", json!({"cart": {"title": "Dein Einkaufswagen"}}))
+    }
+
+    #[test]
+    fn falls_back_to_general_then_default_locales() {
+        let t = translator();
+        assert_eq!(t.t("de-AT", "cart.title", &[]), "Dein Einkaufswagen");
+        assert_eq!(t.t("DE_at", "cart.title", &[]), "Dein Einkaufswagen");
+        assert_eq!(t.t("de-CH", "cart.title", &[]), "Dein Warenkorb");
+        assert_eq!(t.t("de-AT", "cart.greeting", &[("name", &"Ada")]), "Hello, Ada!");
+        assert_eq!(t.t("fr", "cart.title", &[]), "Your cart");
+        assert_eq!(t.t("de", "cart.missing", &[]), "cart.missing");
+
+        assert_eq!(t.resolve("de-at"), Some("de-AT"));
+        assert_eq!(t.resolve("de-CH"), Some("de"));
+        assert_eq!(t.resolve("fr"), None);
+    }
+
+    #[test]
+    fn plural_forms_follow_count() {
+        let t = translator();
+        assert_eq!(t.t("en", "cart.items", &[("count", &0)]), "Your cart is empty");
+        assert_eq!(t.t("en", "cart.items", &[("count", &1)]), "1 item");
+        assert_eq!(t.t("en", "cart.items", &[("count", &5)]), "5 items");
+        // No `zero` in German: `other`
+        assert_eq!(t.t("de", "cart.items", &[("count", &0)]), "0 Artikel");
+        // Plurals need a count
+        assert_eq!(t.t("en", "cart.items", &[]), "cart.items");
+    }
+
+    #[test]
+    fn interpolation_leaves_unknown_placeholders() {
+        assert_eq!(interpolate("{a} and {b} {", &[("a", &1)]), "1 and {b} {");
+        assert_eq!(interpolate("{{a}}", &[("a", &"x")]), "{x}");
+        assert_eq!(translator().t("en", "footer", &[]), "Made in {city}");
+    }
+
+    #[test]
+    fn locales_are_canonicalized() {
+        assert_eq!(canonical_locale("de_at"), "de-AT");
+        assert_eq!(canonical_locale(" ZH-hant-tw "), "zh-Hant-TW");
+        assert_eq!(canonical_locale("es-419"), "es-419");
+        assert_eq!(fallback_chain("zh-Hant-TW").collect::<Vec<_>>(), ["zh-Hant-TW", "zh-Hant", "zh"]);
+        assert_eq!(fallback_chain("").count(), 0);
+    }
+
+    #[test]
+    fn accept_language_is_sorted_by_q() {
+        assert_eq!(
+            parse_accept_language("fr;q=0.5, de-AT, en;q=0.8, it;q=0, bad tag, es;q=x, *;q=0.1"),
+            [("de-AT".to_string(), 1.0), ("en".to_string(), 0.8), ("fr".to_string(), 0.5), ("*".to_string(), 0.1)]
+        );
+        assert!(parse_accept_language("").is_empty());
+    }
+
+    async fn request(uri: &str, headers: &[(&str, &str)]) -> Request {
+        let mut builder = hyper::Request::builder().uri(uri);
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        Request::from_hyper(builder.body(hyper::Body::empty()).unwrap()).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn resolver_prefers_query_then_cookie_then_accept_language() {
+        let t = translator();
+        let resolver = LocaleResolver::new();
+        let headers = [("cookie", "theme=dark; locale=de"), ("accept-language", "fr, de-AT;q=0.5")];
+
+        assert_eq!(resolver.resolve(&request("/?lang=de-at", &headers).await, &t), "de-AT");
+        assert_eq!(resolver.resolve(&request("/", &headers).await, &t), "de");
+        assert_eq!(resolver.resolve(&request("/", &headers[1..]).await, &t), "de-AT");
+        // Unavailable choices are skipped
+        assert_eq!(resolver.resolve(&request("/?lang=fr", &headers[1..]).await, &t), "de-AT");
+        assert_eq!(resolver.resolve(&request("/", &[]).await, &t), "en");
+
+        let renamed = LocaleResolver::new().query_param("hl").cookie_name("lang");
+        assert_eq!(renamed.resolve(&request("/?hl=de", &[]).await, &t), "de");
+        assert_eq!(renamed.resolve(&request("/", &[("cookie", "lang=\"de-AT\"")]).await, &t), "de-AT");
+    }
+
+    #[test]
+    fn loads_toml_and_json_catalogs() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("en.toml"), "[nav]\nhome = \"Home\"\nposts = 3\n").unwrap();
+        std::fs::write(dir.path().join("de.json"), r#"{"nav": {"home": "Start"}}"#).unwrap();
+        std::fs::write(dir.path().join("README.md"), "not a catalog").unwrap();
+
+        let t = Translator::load_dir(dir.path(), "en").unwrap();
+        let mut locales: Vec<&str> = t.locales().collect();
+        locales.sort_unstable();
+        assert_eq!(locales, ["de", "en"]);
+        assert_eq!(t.t("de", "nav.home", &[]), "Start");
+        assert_eq!(t.t("de", "nav.posts", &[]), "3");
+
+        std::fs::write(dir.path().join("fr.json"), "[1, 2]").unwrap();
+        assert!(matches!(Translator::load_dir(dir.path(), "en"), Err(I18nError::Parse { .. })));
+        std::fs::write(dir.path().join("fr.json"), "{").unwrap();
+        assert!(matches!(Translator::load_dir(dir.path(), "en"), Err(I18nError::Parse { .. })));
+        assert!(matches!(Translator::load_dir(dir.path().join("missing"), "en"), Err(I18nError::Read { .. })));
+    }
+}
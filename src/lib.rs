@@ -3,6 +3,7 @@ pub mod router;
 pub mod handler;
 pub mod middleware;
 pub mod request;
+pub mod query_string;
 pub mod response;
 pub mod server;
 pub mod static_files;
@@ -14,25 +15,39 @@ pub mod database;
 pub mod file_upload;
 pub mod metrics;
 pub mod session;
+pub mod site;
+pub mod tasks;
 pub mod ui;
 pub mod forms;
+pub mod htmx;
 pub mod api;
 pub mod config;
 pub mod assets;
 pub mod error; // New module export
+pub mod escape;
 pub mod logging; // New module export
+pub mod i18n;
 
 // Optional dev module for development utilities
 #[cfg(feature = "dev")]
 pub mod dev;
 
+#[cfg(feature = "ws")]
+pub mod ws;
+
 pub use app::App;
-pub use router::{Router, Route};
+pub use escape::{escape_attribute, escape_text, escape_url};
+pub use router::{Router, Route, RouteBuilder, TrailingSlash};
 pub use handler::Handler;
-pub use middleware::{Middleware, Logger, Cors};
+pub use htmx::{render_fragment, render_partial_or_full};
+pub use i18n::{get_translator, init_translator, I18nError, LocaleResolver, Translator};
+pub use middleware::{Middleware, Logger, Cors, MethodOverride, Recover, RequestId};
 pub use request::Request;
-pub use response::Response;
+pub use response::{Json, Response};
 pub use server::Server;
+pub use site::{sitemap_from_pages, Favicon, RobotsConfig};
+pub use static_files::StaticFiles;
+pub use tasks::{CancellationToken, TaskScheduler};
 
 // UI exports
 pub use ui::*;
@@ -50,18 +65,25 @@ pub use config::*;
 pub use assets::*;
 
 // Error exports
-pub use error::{AppError, IntoResponse}; // Export AppError and IntoResponse trait
+pub use error::{AppError, ErrorContext, ErrorHandler, ErrorPage, ErrorPages, IntoResponse, ResultExt}; // Export AppError and IntoResponse trait
 
 // Logging exports
-pub use logging::init_logging; // Export init_logging function
+pub use logging::{init_logging, init_logging_with, LogFormat}; // Export init_logging function
+#[cfg(feature = "tracing")]
+pub use logging::{init_tracing, init_tracing_with};
+#[cfg(feature = "tracing")]
+pub use middleware::{TraceContext, TracingMiddleware};
+#[cfg(feature = "ws")]
+pub use ws::{Message, WebSocket, WebSocketHandler};
 
 // Re-export commonly used types
 pub use hyper::{Body, Method, StatusCode};
+pub use mime::{self, Mime};
 pub use serde::{Deserialize, Serialize};
 pub use serde_json::{json, Value};
 pub use async_trait::async_trait;
 
 // Re-export global state getters
-pub use config::{get_config, init_config};
+pub use config::{get_config, init_config, ConfigError};
 pub use database::{get_database, init_database};
-pub use cache::{get_cache, init_cache};
+pub use cache::{get_cache, init_cache, Cache, CacheMiddleware, CacheNamespace, MemoryCache};
@@ -1,14 +1,96 @@
 use env_logger::Env;
+use log::kv::{self, VisitSource};
+use serde_json::{Map, Value};
+use std::io::Write;
 // Removed unused import: use log::LevelFilter;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    // Human-readable lines with millisecond timestamps
+    #[default]
+    Pretty,
+    // One JSON object per line: `level`, `ts`, `target`, `msg` and any key-value fields
+    Json,
+}
+
 pub fn init_logging() {
+    init_logging_with(LogFormat::Pretty);
+}
+
+// Logging stays off when the global config (if already initialized) has `features.logging = false`
+pub fn init_logging_with(format: LogFormat) {
+    if crate::config::try_get_config().is_some_and(|config| !config.features.logging) {
+        return;
+    }
+
     let env = Env::default()
         .filter_or("RUST_LOG", "info")
         .write_style_or("RUST_LOG_STYLE", "always");
 
-    env_logger::Builder::from_env(env)
-        .format_timestamp_millis()
-        .init();
+    let mut builder = env_logger::Builder::from_env(env);
+    match format {
+        LogFormat::Pretty => {
+            builder.format_timestamp_millis();
+        }
+        LogFormat::Json => {
+            builder.format(|buf, record| {
+                let mut line = Map::new();
+                line.insert("level".to_string(), Value::from(record.level().as_str()));
+                line.insert("ts".to_string(), Value::from(chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)));
+                line.insert("target".to_string(), Value::from(record.target()));
+                line.insert("msg".to_string(), Value::from(record.args().to_string()));
+                let _ = record.key_values().visit(&mut JsonFields(&mut line));
+                writeln!(buf, "{}", Value::Object(line))
+            });
+        }
+    }
+    builder.init();
 
     log::info!("Logging initialized.");
-}
\ No newline at end of file
+}
+
+#[cfg(feature = "tracing")]
+pub fn init_tracing() {
+    init_tracing_with(LogFormat::Pretty);
+}
+
+// Installs a `tracing` subscriber instead of env_logger (use one or the other), filtered by
+// `RUST_LOG` like init_logging. `log` records, the crate's own included, become tracing events.
+#[cfg(feature = "tracing")]
+pub fn init_tracing_with(format: LogFormat) {
+    use tracing_subscriber::EnvFilter;
+
+    if crate::config::try_get_config().is_some_and(|config| !config.features.logging) {
+        return;
+    }
+
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+    match format {
+        LogFormat::Pretty => builder.init(),
+        LogFormat::Json => builder.json().init(),
+    }
+
+    tracing::info!("Tracing initialized.");
+}
+
+// Adds a record's key-value fields to the JSON line, keeping numbers and booleans typed
+struct JsonFields<'a>(&'a mut Map<String, Value>);
+
+impl<'kvs> VisitSource<'kvs> for JsonFields<'_> {
+    fn visit_pair(&mut self, key: kv::Key<'kvs>, value: kv::Value<'kvs>) -> Result<(), kv::Error> {
+        let value = if let Some(n) = value.to_u64() {
+            Value::from(n)
+        } else if let Some(n) = value.to_i64() {
+            Value::from(n)
+        } else if let Some(n) = value.to_f64() {
+            Value::from(n)
+        } else if let Some(b) = value.to_bool() {
+            Value::from(b)
+        } else {
+            Value::from(value.to_string())
+        };
+        self.0.insert(key.as_str().to_string(), value);
+        Ok(())
+    }
+}
@@ -35,6 +35,12 @@ impl Metrics {
     }
 }
 
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub struct MetricsMiddleware {
     metrics: Arc<Metrics>,
 }
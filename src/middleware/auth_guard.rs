@@ -1,13 +1,144 @@
 use crate::{Request, Response, Handler};
+use crate::auth::JwtAuth;
 use crate::middleware::Middleware;
 use async_trait::async_trait;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use std::collections::HashMap; // Used for RateLimiter's requests field
-use std::time::Instant; // Used for RateLimiter
+// Moved to `rate_limit`; still importable from here
+pub use crate::middleware::rate_limit::RateLimiter;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    pub user_id: String,
+    pub roles: Vec<String>,
+}
+
+// A source the guard can pull the current user from when `req.user_id` isn't already set
+#[async_trait]
+pub trait IdentityExtractor: Send + Sync {
+    async fn extract(&self, req: &Request) -> Option<Identity>;
+}
+
+#[async_trait]
+impl<F> IdentityExtractor for F
+where
+    F: Fn(&Request) -> Option<Identity> + Send + Sync,
+{
+    async fn extract(&self, req: &Request) -> Option<Identity> {
+        self(req)
+    }
+}
+
+// Reads the user from `req.session` (set by SessionMiddleware); expired sessions are ignored
+pub struct SessionIdentity {
+    pub user_key: String,
+    pub roles_key: String,
+}
+
+impl Default for SessionIdentity {
+    fn default() -> Self {
+        SessionIdentity {
+            user_key: "user_id".to_string(),
+            roles_key: "roles".to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl IdentityExtractor for SessionIdentity {
+    async fn extract(&self, req: &Request) -> Option<Identity> {
+        let session = req.session.as_ref().filter(|session| !session.is_expired())?;
+        let user_id: String = session.get(&self.user_key)?;
+        let roles: Vec<String> = session.get(&self.roles_key).unwrap_or_default();
+        Some(Identity { user_id, roles })
+    }
+}
+
+// Reads the user from a `Bearer` token in the Authorization header
+pub struct JwtIdentity {
+    pub jwt: Arc<JwtAuth>,
+}
+
+#[async_trait]
+impl IdentityExtractor for JwtIdentity {
+    async fn extract(&self, req: &Request) -> Option<Identity> {
+        let token = req.headers
+            .get("authorization")
+            .and_then(|auth| auth.to_str().ok())
+            .and_then(|auth| auth.strip_prefix("Bearer "))?;
+        let claims = self.jwt.verify_token(token).await.ok()?;
+        Some(Identity { user_id: claims.sub, roles: claims.roles })
+    }
+}
+
+// Reads the user id from a header set by a trusted upstream (e.g. an auth proxy)
+pub struct HeaderIdentity {
+    pub user_header: String,
+    pub roles_header: Option<String>,
+}
+
+#[async_trait]
+impl IdentityExtractor for HeaderIdentity {
+    async fn extract(&self, req: &Request) -> Option<Identity> {
+        let user_id = req.headers
+            .get(self.user_header.as_str())
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())?;
+        let roles = self.roles_header.as_ref()
+            .and_then(|name| req.headers.get(name.as_str()))
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.split(',').map(|r| r.trim().to_string()).filter(|r| !r.is_empty()).collect())
+            .unwrap_or_default();
+        Some(Identity { user_id, roles })
+    }
+}
+
+// Which roles imply which, e.g.
+// `RoleHierarchy::new().role("admin", &["editor"]).role("editor", &["viewer"])`
+// makes an admin pass `require_role("viewer")`. Implication is transitive.
+#[derive(Debug, Clone, Default)]
+pub struct RoleHierarchy {
+    implies: HashMap<String, Vec<String>>,
+}
+
+impl RoleHierarchy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn role(mut self, role: &str, implies: &[&str]) -> Self {
+        self.implies.entry(role.to_string())
+            .or_default()
+            .extend(implies.iter().map(|r| r.to_string()));
+        self
+    }
+
+    // The given roles plus every role they imply
+    pub fn expand(&self, roles: &[String]) -> HashSet<String> {
+        let mut expanded: HashSet<String> = HashSet::new();
+        let mut pending: Vec<&String> = roles.iter().collect();
+        while let Some(role) = pending.pop() {
+            // Skipping already-seen roles also makes cycles harmless
+            if expanded.insert(role.clone()) {
+                if let Some(implied) = self.implies.get(role) {
+                    pending.extend(implied);
+                }
+            }
+        }
+        expanded
+    }
+
+    pub fn grants(&self, roles: &[String], required: &str) -> bool {
+        self.expand(roles).contains(required)
+    }
+}
 
 pub struct AuthGuard {
     pub required_roles: Vec<String>,
     pub redirect_url: Option<String>,
+    pub identity_sources: Vec<Arc<dyn IdentityExtractor>>,
+    pub hierarchy: Option<Arc<RoleHierarchy>>,
 }
 
 impl AuthGuard {
@@ -15,6 +146,48 @@ impl AuthGuard {
         AuthGuard {
             required_roles: Vec::new(),
             redirect_url: None,
+            identity_sources: Vec::new(),
+            hierarchy: None,
+        }
+    }
+
+    // Sources are tried in the order they are added
+    pub fn identity_source<E>(mut self, source: E) -> Self
+    where
+        E: IdentityExtractor + 'static,
+    {
+        self.identity_sources.push(Arc::new(source));
+        self
+    }
+
+    #[allow(clippy::wrong_self_convention)]
+    pub fn from_session(self) -> Self {
+        self.identity_source(SessionIdentity::default())
+    }
+
+    #[allow(clippy::wrong_self_convention)]
+    pub fn from_jwt(self, jwt: Arc<JwtAuth>) -> Self {
+        self.identity_source(JwtIdentity { jwt })
+    }
+
+    #[allow(clippy::wrong_self_convention)]
+    pub fn from_header(self, user_header: &str) -> Self {
+        self.identity_source(HeaderIdentity {
+            user_header: user_header.to_string(),
+            roles_header: None,
+        })
+    }
+
+    async fn resolve_identity(&self, req: &mut Request) {
+        if req.user_id.is_some() {
+            return;
+        }
+        for source in &self.identity_sources {
+            if let Some(identity) = source.extract(req).await {
+                req.user_id = Some(identity.user_id);
+                req.user_roles = identity.roles;
+                return;
+            }
         }
     }
 
@@ -27,15 +200,39 @@ impl AuthGuard {
         self.redirect_url = Some(url.to_string());
         self
     }
+
+    // Checks `require_role` against the user's roles and everything they imply
+    pub fn with_hierarchy(mut self, hierarchy: Arc<RoleHierarchy>) -> Self {
+        self.hierarchy = Some(hierarchy);
+        self
+    }
+
+    fn has_required_role(&self, roles: &[String]) -> bool {
+        match &self.hierarchy {
+            Some(hierarchy) => {
+                let expanded = hierarchy.expand(roles);
+                self.required_roles.iter().any(|required| expanded.contains(required))
+            }
+            None => self.required_roles.iter().any(|required| roles.contains(required)),
+        }
+    }
+}
+
+impl Default for AuthGuard {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[async_trait]
 impl Middleware for AuthGuard {
     async fn handle(
         &self,
-        req: Request,
+        mut req: Request,
         next: Arc<dyn Handler>,
     ) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+        self.resolve_identity(&mut req).await;
+
         // Check if user is authenticated
         if req.user_id.is_none() {
             if let Some(redirect_url) = &self.redirect_url {
@@ -48,78 +245,141 @@ impl Middleware for AuthGuard {
         }
 
         // Check required roles
-        if !self.required_roles.is_empty() {
-            let user_has_required_role = self.required_roles.iter()
-                .any(|required_role| req.user_roles.contains(required_role));
-            
-            if !user_has_required_role {
-                return Ok(Response::new()
-                    .status(hyper::StatusCode::FORBIDDEN)
-                    .json(&serde_json::json!({"error": "Insufficient permissions"}))?);
-            }
+        if !self.required_roles.is_empty() && !self.has_required_role(&req.user_roles) {
+            return Ok(Response::new()
+                .status(hyper::StatusCode::FORBIDDEN)
+                .json(&serde_json::json!({"error": "Insufficient permissions"}))?);
         }
 
         next.handle(req).await
     }
 }
 
-pub struct RateLimiter {
-    pub max_requests: u32,
-    pub window_seconds: u64,
-    pub requests: std::sync::Arc<std::sync::Mutex<std::collections::HashMap<String, (u32, std::time::Instant)>>>,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::Session;
+
+    // Answers with the resolved user and roles
+    fn whoami() -> Arc<dyn Handler> {
+        Arc::new(|req: Request| async move {
+            let user = format!("{} {}", req.user_id.clone().unwrap_or_default(), req.user_roles.join(","));
+            Ok::<_, Box<dyn std::error::Error + Send + Sync>>(Response::new().text(&user))
+        })
+    }
 
-impl RateLimiter {
-    pub fn new(max_requests: u32, window_seconds: u64) -> Self {
-        RateLimiter {
-            max_requests,
-            window_seconds,
-            requests: std::sync::Arc::new(std::sync::Mutex::new(std::collections::HashMap::new())),
+    async fn request(headers: &[(&str, &str)]) -> Request {
+        let mut builder = hyper::Request::builder().uri("/dashboard");
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
         }
+        Request::from_hyper(builder.body(hyper::Body::empty()).unwrap()).await.unwrap()
     }
-}
 
-#[async_trait]
-impl Middleware for RateLimiter {
-    async fn handle(
-        &self,
-        req: Request,
-        next: Arc<dyn Handler>,
-    ) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
-        let client_ip = req.headers
-            .get("x-forwarded-for")
-            .or_else(|| req.headers.get("x-real-ip"))
-            .and_then(|v| v.to_str().ok())
-            .unwrap_or("unknown")
-            .to_string();
-
-        let now = Instant::now();
-        
-        // Perform rate limiting logic in a separate, synchronous block
-        let rate_limit_exceeded = {
-            let mut requests_guard = self.requests.lock().unwrap(); // Acquire lock
-            
-            let (count, last_request) = requests_guard.entry(client_ip.clone())
-                .or_insert((0, now));
-
-            // Reset counter if window has passed
-            if now.duration_since(*last_request).as_secs() > self.window_seconds {
-                *count = 0;
-                *last_request = now;
-            }
+    async fn body_text(response: Response) -> String {
+        let bytes = hyper::body::to_bytes(response.body).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
 
-            *count += 1;
+    #[tokio::test]
+    async fn resolves_the_user_from_the_session() {
+        let mut req = request(&[]).await;
+        let mut session = Session::new(chrono::Duration::hours(1));
+        session.set("user_id", "alice").unwrap();
+        session.set("roles", vec!["admin"]).unwrap();
+        req.session = Some(session);
 
-            *count > self.max_requests // Return true if exceeded, false otherwise
-        }; // `requests_guard` is dropped here, releasing the mutex
+        let response = AuthGuard::new().from_session().handle(req, whoami()).await.unwrap();
+        assert_eq!(body_text(response).await, "alice admin");
+    }
 
-        if rate_limit_exceeded {
-            return Ok(Response::new()
-                .status(hyper::StatusCode::TOO_MANY_REQUESTS)
-                .header("Retry-After", &self.window_seconds.to_string())
-                .json(&serde_json::json!({"error": "Rate limit exceeded"}))?);
-        }
+    #[tokio::test]
+    async fn resolves_the_user_from_a_bearer_token() {
+        let jwt = Arc::new(JwtAuth::new("test-secret"));
+        let token = jwt.generate_token("bob", vec!["editor".to_string()]).unwrap();
+        let req = request(&[("authorization", &format!("Bearer {}", token))]).await;
 
-        next.handle(req).await
+        let response = AuthGuard::new().from_jwt(jwt).handle(req, whoami()).await.unwrap();
+        assert_eq!(body_text(response).await, "bob editor");
+    }
+
+    #[tokio::test]
+    async fn sources_are_tried_in_order() {
+        let guard = AuthGuard::new()
+            .from_jwt(Arc::new(JwtAuth::new("test-secret")))
+            .identity_source(HeaderIdentity {
+                user_header: "x-user".to_string(),
+                roles_header: Some("x-roles".to_string()),
+            });
+        let req = request(&[("authorization", "Bearer not-a-token"), ("x-user", "carol"), ("x-roles", "viewer, editor")]).await;
+
+        let response = guard.handle(req, whoami()).await.unwrap();
+        assert_eq!(body_text(response).await, "carol viewer,editor");
+    }
+
+    #[tokio::test]
+    async fn anonymous_requests_get_401_or_the_redirect() {
+        let response = AuthGuard::new().from_session().handle(request(&[]).await, whoami()).await.unwrap();
+        assert_eq!(response.status, hyper::StatusCode::UNAUTHORIZED);
+
+        let response = AuthGuard::new().redirect_to("/login").handle(request(&[]).await, whoami()).await.unwrap();
+        assert!(response.status.is_redirection());
+        assert_eq!(response.headers.get("location").unwrap(), "/login");
+    }
+
+    #[tokio::test]
+    async fn missing_roles_get_403() {
+        let guard = AuthGuard::new().from_header("x-user").require_role("admin");
+        let response = guard.handle(request(&[("x-user", "dave")]).await, whoami()).await.unwrap();
+        assert_eq!(response.status, hyper::StatusCode::FORBIDDEN);
+    }
+
+    fn roles(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn hierarchy_grants_implied_roles_transitively() {
+        let hierarchy = RoleHierarchy::new().role("admin", &["editor"]).role("editor", &["viewer"]);
+        assert!(hierarchy.grants(&roles(&["admin"]), "admin"));
+        assert!(hierarchy.grants(&roles(&["admin"]), "editor"));
+        assert!(hierarchy.grants(&roles(&["admin"]), "viewer"));
+        assert!(hierarchy.grants(&roles(&["editor"]), "viewer"));
+
+        // Implication only runs downwards
+        assert!(!hierarchy.grants(&roles(&["viewer"]), "editor"));
+        assert!(!hierarchy.grants(&roles(&["editor"]), "admin"));
+        assert!(!hierarchy.grants(&roles(&["admin"]), "billing"));
+        assert!(!hierarchy.grants(&[], "viewer"));
+    }
+
+    #[test]
+    fn hierarchy_cycles_terminate() {
+        let hierarchy = RoleHierarchy::new()
+            .role("a", &["b"])
+            .role("b", &["c"])
+            .role("c", &["a"])
+            .role("self", &["self"]);
+        assert_eq!(hierarchy.expand(&roles(&["b"])), ["a", "b", "c"].iter().map(|r| r.to_string()).collect());
+        assert!(hierarchy.grants(&roles(&["c"]), "b"));
+        assert!(!hierarchy.grants(&roles(&["a"]), "d"));
+        assert_eq!(hierarchy.expand(&roles(&["self"])).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn guard_checks_roles_through_the_hierarchy() {
+        let hierarchy = Arc::new(RoleHierarchy::new().role("admin", &["editor"]).role("editor", &["viewer"]));
+        let guard = AuthGuard::new()
+            .identity_source(HeaderIdentity {
+                user_header: "x-user".to_string(),
+                roles_header: Some("x-roles".to_string()),
+            })
+            .require_role("viewer")
+            .with_hierarchy(hierarchy);
+
+        let response = guard.handle(request(&[("x-user", "erin"), ("x-roles", "admin")]).await, whoami()).await.unwrap();
+        assert_eq!(response.status, hyper::StatusCode::OK);
+        let response = guard.handle(request(&[("x-user", "frank"), ("x-roles", "guest")]).await, whoami()).await.unwrap();
+        assert_eq!(response.status, hyper::StatusCode::FORBIDDEN);
     }
 }
@@ -0,0 +1,108 @@
+use crate::middleware::Middleware;
+use crate::request::BodyTooLarge;
+use crate::{Handler, Request, Response};
+use async_trait::async_trait;
+use std::sync::Arc;
+
+// Caps request bodies for the routes it wraps, overriding `server.max_body_size`.
+// A Content-Length over the limit is rejected before the handler runs; bodies without one
+// (chunked) are cut off by Request::json/form/multipart as soon as they cross it.
+pub struct BodyLimit {
+    max_bytes: usize,
+}
+
+impl BodyLimit {
+    pub fn new(max_bytes: usize) -> Self {
+        BodyLimit { max_bytes }
+    }
+
+    fn too_large(&self) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(Response::new()
+            .status(hyper::StatusCode::PAYLOAD_TOO_LARGE)
+            .json(&serde_json::json!({
+                "error": BodyTooLarge { limit: self.max_bytes }.to_string(),
+                "limit": self.max_bytes,
+            }))?)
+    }
+}
+
+#[async_trait]
+impl Middleware for BodyLimit {
+    async fn handle(
+        &self,
+        mut req: Request,
+        next: Arc<dyn Handler>,
+    ) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+        let declared = req.headers.get(hyper::header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse::<u64>().ok());
+        if declared.is_some_and(|length| length > self.max_bytes as u64) {
+            return self.too_large();
+        }
+
+        req.body_limit = Some(self.max_bytes);
+        match next.handle(req).await {
+            Err(e) if e.downcast_ref::<BodyTooLarge>().is_some() => self.too_large(),
+            Err(e) if matches!(e.downcast_ref::<multer::Error>(), Some(multer::Error::StreamSizeExceeded { .. })) => self.too_large(),
+            result => result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    // Reads the JSON body, noting that it ran
+    fn json_handler(ran: Arc<AtomicBool>) -> Arc<dyn Handler> {
+        Arc::new(move |mut req: Request| {
+            let ran = ran.clone();
+            async move {
+                ran.store(true, Ordering::SeqCst);
+                let body = req.json().await?;
+                Ok::<_, Box<dyn std::error::Error + Send + Sync>>(Response::new().text(&body.to_string()))
+            }
+        })
+    }
+
+    async fn request(body: hyper::Body, content_length: Option<usize>) -> Request {
+        let mut builder = hyper::Request::builder().method("POST").uri("/upload");
+        if let Some(length) = content_length {
+            builder = builder.header("content-length", length);
+        }
+        Request::from_hyper(builder.body(body).unwrap()).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn rejects_a_declared_length_over_the_limit_before_the_handler() {
+        let ran = Arc::new(AtomicBool::new(false));
+        let body = "[1,2,3,4,5,6,7,8,9,10]";
+        let req = request(body.into(), Some(body.len())).await;
+
+        let response = BodyLimit::new(8).handle(req, json_handler(ran.clone())).await.unwrap();
+        assert_eq!(response.status, hyper::StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(!ran.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn cuts_off_chunked_bodies_that_cross_the_limit() {
+        let ran = Arc::new(AtomicBool::new(false));
+        let chunks: Vec<Result<&'static str, std::io::Error>> = vec![Ok("[1,2,3,"), Ok("4,5,6,7]")];
+        let req = request(hyper::Body::wrap_stream(futures::stream::iter(chunks)), None).await;
+
+        let response = BodyLimit::new(8).handle(req, json_handler(ran.clone())).await.unwrap();
+        assert_eq!(response.status, hyper::StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(ran.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn passes_bodies_within_the_limit() {
+        let ran = Arc::new(AtomicBool::new(false));
+        let req = request("[1,2]".into(), Some(5)).await;
+
+        let response = BodyLimit::new(8).handle(req, json_handler(ran)).await.unwrap();
+        assert_eq!(response.status, hyper::StatusCode::OK);
+        assert_eq!(hyper::body::to_bytes(response.body).await.unwrap(), "[1,2]");
+    }
+}
@@ -0,0 +1,136 @@
+use crate::middleware::Middleware;
+use crate::{Handler, Request, Response};
+use async_trait::async_trait;
+use hyper::header::CONTENT_TYPE;
+use hyper::Method;
+use std::sync::Arc;
+
+pub const METHOD_OVERRIDE_HEADER: &str = "X-HTTP-Method-Override";
+pub const METHOD_OVERRIDE_FIELD: &str = "_method";
+
+// Lets HTML forms, which can only GET or POST, reach PUT, PATCH and DELETE routes: a POST with an
+// `X-HTTP-Method-Override` header, or a urlencoded form with a `_method` field, e.g.
+//
+//     <input type="hidden" name="_method" value="DELETE">
+//
+// is handled as that method. Other methods, and other verbs in the override, are left alone. The
+// form is parsed into `req.form()`, which handlers can still read afterwards.
+//
+// The Router picks the route before running its own middleware, so this has to wrap the whole
+// app: `Server::new(app, addr).wrap(MethodOverride::new())`.
+pub struct MethodOverride;
+
+impl MethodOverride {
+    pub fn new() -> Self {
+        MethodOverride
+    }
+}
+
+impl Default for MethodOverride {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Only verbs a form can't send; overriding to GET or HEAD would make a POST look safe
+fn allowed(method: &str) -> Option<Method> {
+    match method.trim().to_ascii_uppercase().as_str() {
+        "PUT" => Some(Method::PUT),
+        "PATCH" => Some(Method::PATCH),
+        "DELETE" => Some(Method::DELETE),
+        _ => None,
+    }
+}
+
+// Multipart bodies are left for the handler to stream
+fn is_urlencoded(req: &Request) -> bool {
+    req.headers.get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .is_some_and(|v| v.trim().eq_ignore_ascii_case("application/x-www-form-urlencoded"))
+}
+
+#[async_trait]
+impl Middleware for MethodOverride {
+    async fn handle(
+        &self,
+        mut req: Request,
+        next: Arc<dyn Handler>,
+    ) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+        if req.method == Method::POST {
+            let header = req.headers.get(METHOD_OVERRIDE_HEADER)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string);
+            let requested = match header {
+                Some(method) => Some(method),
+                None if is_urlencoded(&req) => req.form().await?.get(METHOD_OVERRIDE_FIELD).cloned(),
+                None => None,
+            };
+            if let Some(method) = requested.as_deref().and_then(allowed) {
+                req.method = method;
+            }
+        }
+        next.handle(req).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Answers with the method it saw and the form's `title`, read again after the override
+    fn echo_handler() -> Arc<dyn Handler> {
+        Arc::new(|mut req: Request| async move {
+            let method = req.method.to_string();
+            let title = if is_urlencoded(&req) {
+                req.form().await?.get("title").cloned().unwrap_or_default()
+            } else {
+                String::new()
+            };
+            Ok::<_, Box<dyn std::error::Error + Send + Sync>>(Response::new().text(&format!("{} {}", method, title)))
+        })
+    }
+
+    async fn send(method: &str, headers: &[(&str, &str)], body: &str) -> String {
+        let mut builder = hyper::Request::builder().method(method).uri("/posts/1");
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        let req = Request::from_hyper(builder.body(hyper::Body::from(body.to_string())).unwrap()).await.unwrap();
+        let response = MethodOverride::new().handle(req, echo_handler()).await.unwrap();
+        let bytes = hyper::body::to_bytes(response.body).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    const FORM: (&str, &str) = ("content-type", "application/x-www-form-urlencoded");
+
+    #[tokio::test]
+    async fn header_overrides_a_post() {
+        assert_eq!(send("POST", &[(METHOD_OVERRIDE_HEADER, "delete")], "").await, "DELETE ");
+    }
+
+    #[tokio::test]
+    async fn form_field_overrides_a_post_and_the_form_stays_readable() {
+        assert_eq!(send("POST", &[FORM], "_method=put&title=Hello").await, "PUT Hello");
+        assert_eq!(
+            send("POST", &[("content-type", "application/x-www-form-urlencoded; charset=UTF-8")], "title=Hi&_method=PATCH").await,
+            "PATCH Hi"
+        );
+    }
+
+    #[tokio::test]
+    async fn safe_and_unknown_methods_are_ignored() {
+        assert_eq!(send("POST", &[(METHOD_OVERRIDE_HEADER, "GET")], "").await, "POST ");
+        assert_eq!(send("POST", &[(METHOD_OVERRIDE_HEADER, "HEAD")], "").await, "POST ");
+        assert_eq!(send("POST", &[FORM], "_method=get&title=x").await, "POST x");
+        assert_eq!(send("POST", &[FORM], "_method=TRACE").await, "POST ");
+    }
+
+    #[tokio::test]
+    async fn only_posts_are_overridden() {
+        assert_eq!(send("GET", &[(METHOD_OVERRIDE_HEADER, "DELETE")], "").await, "GET ");
+        assert_eq!(send("PUT", &[FORM, (METHOD_OVERRIDE_HEADER, "DELETE")], "_method=delete").await, "PUT ");
+        // Multipart and other bodies are left alone
+        assert_eq!(send("POST", &[("content-type", "text/plain")], "_method=delete").await, "POST ");
+    }
+}
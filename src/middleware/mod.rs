@@ -1,4 +1,5 @@
 use crate::{Request, Response, Handler};
+use crate::tasks::TaskScheduler;
 use async_trait::async_trait;
 use std::sync::Arc;
 
@@ -10,10 +11,19 @@ pub trait Middleware: Send + Sync + 'static {
         req: Request,
         next: Arc<dyn Handler>,
     ) -> Result<Response, Box<dyn std::error::Error + Send + Sync>>;
+
+    // Background work the middleware needs while the server runs, e.g. SessionMiddleware's
+    // expired-session cleanup. `Server` collects it from the router and from `wrap`.
+    fn tasks(&self, scheduler: TaskScheduler) -> TaskScheduler {
+        scheduler
+    }
 }
 
 // Moved from src/middleware.rs
-// Logger middleware
+// Logs one line per request through the `log` crate (target `rustnext::request`), with
+// `method`, `path`, `status`, `duration_ms`, `client_ip` (see `Request::client_ip`) and
+// `request_id` (see RequestId) as key-value fields.
+// 5xx responses and handler errors log at error level, 4xx at warn, the rest at info.
 pub struct Logger;
 
 #[async_trait]
@@ -25,14 +35,39 @@ impl Middleware for Logger {
     ) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
         let start = std::time::Instant::now();
         let method = req.method.clone();
-        let uri = req.uri.clone();
-        
-        let response = next.handle(req).await?;
-        
-        let duration = start.elapsed(); // `duration` is already defined here
-        println!("{} {} {} - {:?}", method, uri, response.status, duration);
-        
-        Ok(response)
+        let path = req.uri.path().to_string();
+        let client_ip = req.client_ip().unwrap_or_else(|| "-".to_string());
+        let request_id = req.request_id.clone();
+
+        let result = next.handle(req).await;
+
+        let duration_ms = start.elapsed().as_millis() as u64;
+        // Errors become a response further out; by default that's a 500
+        let status = match &result {
+            Ok(response) => response.status.as_u16(),
+            Err(e) => crate::error::AppError::from_ref(e.as_ref()).status().as_u16(),
+        };
+        // Outside the RequestId middleware the ID is only on the response
+        let request_id = request_id
+            .or_else(|| {
+                let value = result.as_ref().ok()?.headers.get(request_id::REQUEST_ID_HEADER)?;
+                Some(value.to_str().ok()?.to_string())
+            })
+            .unwrap_or_else(|| "-".to_string());
+        let level = match status {
+            500.. => log::Level::Error,
+            400..=499 => log::Level::Warn,
+            _ => log::Level::Info,
+        };
+        log::log!(
+            target: "rustnext::request",
+            level,
+            method = method.as_str(), path = path.as_str(), status = status, duration_ms = duration_ms, client_ip = client_ip.as_str(),
+            request_id = request_id.as_str();
+            "{} {} {} {}ms", method, path, status, duration_ms
+        );
+
+        result
     }
 }
 
@@ -58,6 +93,12 @@ impl Cors {
     }
 }
 
+impl Default for Cors {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[async_trait]
 impl Middleware for Cors {
     async fn handle(
@@ -73,17 +114,30 @@ impl Middleware for Cors {
                 .status(hyper::StatusCode::OK));
         }
 
-        let mut response = next.handle(req).await?;
-        response.headers.insert("Access-Control-Allow-Origin".to_string(), self.allow_origin.clone());
-        Ok(response)
+        let response = next.handle(req).await?;
+        Ok(response.header("Access-Control-Allow-Origin", &self.allow_origin))
     }
 }
 
 // Existing module declarations
 pub mod auth_guard;
+pub mod body_limit;
+pub mod method_override;
+pub mod rate_limit;
+pub mod recover;
+pub mod request_id;
+#[cfg(feature = "tracing")]
+pub mod trace;
 
 // Export all public middleware components and the trait
-pub use auth_guard::{AuthGuard, RateLimiter};
+pub use auth_guard::{AuthGuard, Identity, IdentityExtractor, SessionIdentity, JwtIdentity, HeaderIdentity};
+pub use body_limit::BodyLimit;
+pub use method_override::MethodOverride;
+pub use rate_limit::{CacheRateLimitStore, MemoryRateLimitStore, Quota, RateLimitStore, RateLimiter};
+pub use recover::Recover;
+pub use request_id::RequestId;
+#[cfg(feature = "tracing")]
+pub use trace::{TraceContext, TracingMiddleware};
 // Removed redundant `pub use super::middleware::...` as they are defined directly in this mod.rs
 // pub use super::middleware::Middleware;
 // pub use super::middleware::Logger;
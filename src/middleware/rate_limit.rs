@@ -0,0 +1,375 @@
+use crate::cache::get_cache;
+use crate::middleware::Middleware;
+use crate::{Handler, Request, Response};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// What a RateLimiter allows each client
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quota {
+    pub max_requests: u32,
+    pub window: Duration,
+    // How many requests a client that has been idle can make at once, where the store supports it
+    pub burst: u32,
+}
+
+impl Quota {
+    // Requests regained per second
+    fn rate(&self) -> f64 {
+        self.max_requests as f64 / self.window.as_secs_f64().max(1.0)
+    }
+}
+
+// Where a RateLimiter keeps its counts: in this process (MemoryRateLimitStore, the default), or
+// somewhere all instances behind a load balancer share (CacheRateLimitStore)
+#[async_trait]
+pub trait RateLimitStore: Send + Sync {
+    // Counts a request from `key`: None if `quota` allows it, otherwise how long until it would
+    async fn hit(&self, key: &str, quota: &Quota) -> Result<Option<Duration>, Box<dyn std::error::Error + Send + Sync>>;
+}
+
+// A token bucket per client: each can burst up to `quota.burst` requests and regains
+// `max_requests` per `window`, one at a time, so there's no window boundary at which twice the
+// limit gets through. Buckets idle long enough to be full again are dropped.
+pub struct MemoryRateLimitStore {
+    state: Mutex<MemoryState>,
+}
+
+struct MemoryState {
+    buckets: HashMap<String, TokenBucket>,
+    last_sweep: Instant,
+}
+
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl MemoryRateLimitStore {
+    pub fn new() -> Self {
+        MemoryRateLimitStore {
+            state: Mutex::new(MemoryState {
+                buckets: HashMap::new(),
+                last_sweep: Instant::now(),
+            }),
+        }
+    }
+
+    // Takes a token from `key`'s bucket, or says how long until one is available
+    fn take(&self, key: &str, quota: &Quota) -> Option<Duration> {
+        let now = Instant::now();
+        let rate = quota.rate();
+        // A bucket that can't hold a whole token would reject everything
+        let capacity = quota.burst.max(1) as f64;
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+
+        // How long an empty bucket takes to fill; one untouched for that long is the same as
+        // none. At most one sweep per such period, so the map only holds recently active clients.
+        let expiry = if rate > 0.0 { Duration::from_secs_f64(capacity / rate) } else { quota.window };
+        if now.duration_since(state.last_sweep) >= expiry {
+            state.buckets.retain(|_, bucket| now.duration_since(bucket.updated) < expiry);
+            state.last_sweep = now;
+        }
+
+        let bucket = state.buckets.entry(key.to_string())
+            .or_insert(TokenBucket { tokens: capacity, updated: now });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(capacity);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            None
+        } else if rate > 0.0 {
+            Some(Duration::from_secs_f64((1.0 - bucket.tokens) / rate))
+        } else {
+            Some(quota.window)
+        }
+    }
+}
+
+impl Default for MemoryRateLimitStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for MemoryRateLimitStore {
+    async fn hit(&self, key: &str, quota: &Quota) -> Result<Option<Duration>, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(self.take(key, quota))
+    }
+}
+
+// Counts in the global Cache (see `init_cache`), so with Redis the limit holds across every
+// instance sharing it. A sliding-window counter: one counter per client and fixed window,
+// bumped with `Cache::increment_and_get` (which reads the previous window's count in the same
+// round trip), and the previous count weighted by how much of it still overlaps the last
+// `window`. That smooths out boundary bursts, though `quota.burst` isn't supported. Rejected
+// requests count too, so a client that keeps retrying stays limited.
+pub struct CacheRateLimitStore {
+    prefix: String,
+}
+
+impl CacheRateLimitStore {
+    pub fn new() -> Self {
+        CacheRateLimitStore {
+            prefix: "rustnext:ratelimit:".to_string(),
+        }
+    }
+
+    // Key prefix in the cache, to keep several limiters apart
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+}
+
+impl Default for CacheRateLimitStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CacheRateLimitStore {
+    // `hit` at `now_ms` since the Unix epoch
+    async fn hit_at(&self, key: &str, quota: &Quota, now_ms: u64) -> Result<Option<Duration>, Box<dyn std::error::Error + Send + Sync>> {
+        let window_ms = quota.window.as_millis().max(1000) as u64;
+        let window_index = now_ms / window_ms;
+        let into_window = (now_ms % window_ms) as f64 / window_ms as f64;
+
+        // Looked up per request rather than at construction, so `init_cache` can come later
+        let cache = get_cache().with_prefix(&self.prefix);
+        // Kept for two windows: the current one, then as the previous one
+        let (current, previous) = cache
+            .increment_and_get(
+                &format!("{}:{}", key, window_index),
+                Duration::from_millis(2 * window_ms),
+                &format!("{}:{}", key, window_index - 1),
+            )
+            .await?;
+        let (current, previous) = (current as f64, previous.unwrap_or(0) as f64);
+
+        let max = quota.max_requests as f64;
+        let estimate = previous * (1.0 - into_window) + current;
+        if estimate <= max {
+            return Ok(None);
+        }
+
+        // Until the estimate leaves room for the retry, which counts too: the previous window's
+        // share fades out over this one, then this one's over the next
+        let window = window_ms as f64 / 1000.0;
+        let remaining = (1.0 - into_window) * window;
+        let fade_previous = if previous > 0.0 { (estimate + 1.0 - max) / previous * window } else { f64::INFINITY };
+        let wait = if fade_previous <= remaining {
+            fade_previous
+        } else if max > 0.0 {
+            remaining + (1.0 - (max - 1.0) / current).max(0.0) * window
+        } else {
+            remaining + window
+        };
+        Ok(Some(Duration::from_secs_f64(wait)))
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for CacheRateLimitStore {
+    async fn hit(&self, key: &str, quota: &Quota) -> Result<Option<Duration>, Box<dyn std::error::Error + Send + Sync>> {
+        // Wall-clock time, so every instance agrees on the window boundaries
+        let now_ms = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64;
+        self.hit_at(key, quota, now_ms).await
+    }
+}
+
+// Limits each client, keyed by `Request::client_ip`, to `max_requests` per `window_seconds`.
+// Counts live in a MemoryRateLimitStore unless `store` says otherwise, e.g.
+//
+//     RateLimiter::new(100, 60).store(CacheRateLimitStore::new())
+//
+// to share the limit between instances through Redis. A rejected request gets a 429 with
+// `Retry-After` set to when the next request will be let through. If the store fails, the
+// request is let through and the error logged.
+pub struct RateLimiter {
+    pub max_requests: u32,
+    pub window_seconds: u64,
+    burst: u32,
+    store: Arc<dyn RateLimitStore>,
+}
+
+impl RateLimiter {
+    pub fn new(max_requests: u32, window_seconds: u64) -> Self {
+        RateLimiter {
+            max_requests,
+            window_seconds,
+            burst: max_requests,
+            store: Arc::new(MemoryRateLimitStore::new()),
+        }
+    }
+
+    // How many requests a client that has been idle can make at once (default `max_requests`,
+    // at least 1)
+    pub fn burst(mut self, burst: u32) -> Self {
+        self.burst = burst.max(1);
+        self
+    }
+
+    pub fn store<S: RateLimitStore + 'static>(mut self, store: S) -> Self {
+        self.store = Arc::new(store);
+        self
+    }
+
+    pub fn quota(&self) -> Quota {
+        Quota {
+            max_requests: self.max_requests,
+            window: Duration::from_secs(self.window_seconds),
+            burst: self.burst,
+        }
+    }
+
+    // Counts a request from `key`: None if it's allowed, otherwise how long until one would be
+    pub async fn check(&self, key: &str) -> Result<Option<Duration>, Box<dyn std::error::Error + Send + Sync>> {
+        self.store.hit(key, &self.quota()).await
+    }
+}
+
+#[async_trait]
+impl Middleware for RateLimiter {
+    async fn handle(
+        &self,
+        req: Request,
+        next: Arc<dyn Handler>,
+    ) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+        // Requests without an address (not from the Server) share one budget
+        let client_ip = req.client_ip().unwrap_or_else(|| "unknown".to_string());
+
+        match self.check(&client_ip).await {
+            Ok(None) => {}
+            Ok(Some(retry_after)) => {
+                // Whole seconds, rounded up so a client that waits that long gets through
+                let retry_after = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+                return Ok(Response::new()
+                    .status(hyper::StatusCode::TOO_MANY_REQUESTS)
+                    .header("Retry-After", retry_after.max(1).to_string())
+                    .json(&serde_json::json!({"error": "Rate limit exceeded"}))?);
+            }
+            Err(e) => log::error!("Rate limit store failed, letting the request through: {}", e),
+        }
+
+        next.handle(req).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ok_handler() -> Arc<dyn Handler> {
+        Arc::new(|_req: Request| async {
+            Ok::<_, Box<dyn std::error::Error + Send + Sync>>(Response::new().text("ok"))
+        })
+    }
+
+    async fn get(limiter: &RateLimiter, ip: &str) -> Response {
+        let mut req = Request::from_hyper(hyper::Request::get("/").body(hyper::Body::empty()).unwrap()).await.unwrap();
+        req.remote_addr = Some(format!("{}:4000", ip).parse().unwrap());
+        limiter.handle(req, ok_handler()).await.unwrap()
+    }
+
+    // One token back every 50ms
+    fn quota(burst: u32) -> Quota {
+        Quota { max_requests: 20, window: Duration::from_secs(1), burst }
+    }
+
+    #[tokio::test]
+    async fn allows_a_burst_then_rejects_with_retry_after() {
+        // A token every 5s
+        let limiter = RateLimiter::new(2, 10);
+
+        assert_eq!(get(&limiter, "192.0.2.1").await.status, hyper::StatusCode::OK);
+        assert_eq!(get(&limiter, "192.0.2.1").await.status, hyper::StatusCode::OK);
+        let response = get(&limiter, "192.0.2.1").await;
+        assert_eq!(response.status, hyper::StatusCode::TOO_MANY_REQUESTS);
+        // Just under 5s, rounded up
+        assert_eq!(response.headers["retry-after"], "5");
+
+        // Other clients have their own bucket
+        assert_eq!(get(&limiter, "192.0.2.2").await.status, hyper::StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn a_zero_burst_still_lets_requests_through() {
+        let limiter = RateLimiter::new(2, 10).burst(0);
+        assert_eq!(limiter.quota().burst, 1);
+        assert_eq!(get(&limiter, "192.0.2.1").await.status, hyper::StatusCode::OK);
+        assert_eq!(get(&limiter, "192.0.2.1").await.status, hyper::StatusCode::TOO_MANY_REQUESTS);
+
+        let store = MemoryRateLimitStore::new();
+        assert_eq!(store.take("a", &quota(0)), None);
+    }
+
+    #[tokio::test]
+    async fn tokens_come_back_one_per_window_over_max_requests() {
+        let store = MemoryRateLimitStore::new();
+        let quota = quota(2);
+
+        assert_eq!(store.take("a", &quota), None);
+        assert_eq!(store.take("a", &quota), None);
+        let wait = store.take("a", &quota).unwrap();
+        assert!(wait <= Duration::from_millis(50) && wait > Duration::from_millis(40), "{:?}", wait);
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(store.take("a", &quota), None);
+        assert!(store.take("a", &quota).is_some());
+    }
+
+    #[tokio::test]
+    async fn idle_buckets_are_swept() {
+        let store = MemoryRateLimitStore::new();
+        // Full again after 50ms
+        let quota = quota(1);
+
+        store.take("idle", &quota);
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        store.take("active", &quota);
+
+        let state = store.state.lock().unwrap();
+        assert_eq!(state.buckets.len(), 1);
+        assert!(state.buckets.contains_key("active"));
+    }
+
+    // 10 requests per 10s, `at_ms` into window 100
+    async fn cache_hit(store: &CacheRateLimitStore, quota: &Quota, at_ms: u64) -> Option<Duration> {
+        store.hit_at("client", quota, 100 * 10_000 + at_ms).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn cache_store_weights_the_previous_window_by_its_overlap() {
+        let store = CacheRateLimitStore::new().prefix("test:weighted:");
+        let quota = Quota { max_requests: 10, window: Duration::from_secs(10), burst: 10 };
+        get_cache().with_prefix("test:weighted:").set("client:99", &8u64, Duration::from_secs(60)).await.unwrap();
+
+        // A quarter in, 8 * 0.75 = 6 of the previous window still counts, leaving room for 4
+        for _ in 0..4 {
+            assert_eq!(cache_hit(&store, &quota, 2_500).await, None);
+        }
+        // 6 + 5 = 11. The previous window's share has to fade by 2 (this request and the retry),
+        // which takes 2 / 8 of a window
+        let wait = cache_hit(&store, &quota, 2_500).await.unwrap();
+        assert!((wait.as_secs_f64() - 2.5).abs() < 1e-9, "{:?}", wait);
+    }
+
+    #[tokio::test]
+    async fn cache_store_waits_into_the_next_window_without_a_previous_count() {
+        let store = CacheRateLimitStore::new().prefix("test:fresh:");
+        let quota = Quota { max_requests: 2, window: Duration::from_secs(10), burst: 2 };
+
+        assert_eq!(cache_hit(&store, &quota, 5_000).await, None);
+        assert_eq!(cache_hit(&store, &quota, 5_000).await, None);
+        // Nothing to fade, so the rest of this window (5s) and then until 3 * (1 - f) + 1 <= 2
+        // in the next, f = 2/3
+        let wait = cache_hit(&store, &quota, 5_000).await.unwrap();
+        assert!((wait.as_secs_f64() - (5.0 + 20.0 / 3.0)).abs() < 1e-9, "{:?}", wait);
+    }
+}
@@ -0,0 +1,146 @@
+use crate::error::AppError;
+use crate::metrics::Metrics;
+use crate::middleware::Middleware;
+use crate::{Handler, Request, Response};
+use async_trait::async_trait;
+use futures::FutureExt;
+use std::any::Any;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+
+// Turns a panic in the middleware and handler it wraps into `AppError::Internal("panic: ..")`,
+// which the App's error handling renders as a 500, instead of a dropped connection. Register it
+// first with `use_middleware` so it covers everything after it. Only the handler's future is
+// guarded; writing the response happens later, in the server.
+pub struct Recover {
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl Recover {
+    pub fn new() -> Self {
+        Recover { metrics: None }
+    }
+
+    // Counts panics as errors. Only needed when MetricsMiddleware runs inside Recover; outside
+    // it, it already counts the error Recover returns.
+    pub fn metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+}
+
+impl Default for Recover {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Middleware for Recover {
+    async fn handle(
+        &self,
+        req: Request,
+        next: Arc<dyn Handler>,
+    ) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+        let method = req.method.clone();
+        let path = req.uri.path().to_string();
+        let request_id = req.request_id.clone();
+
+        match AssertUnwindSafe(next.handle(req)).catch_unwind().await {
+            Ok(result) => result,
+            Err(payload) => {
+                let message = panic_message(payload.as_ref());
+                log::error!(
+                    "Handler panicked on {} {} (request {}): {}",
+                    method, path, request_id.as_deref().unwrap_or("-"), message
+                );
+                if let Some(metrics) = &self.metrics {
+                    *metrics.error_counter.lock().unwrap() += 1;
+                }
+                Err(Box::new(AppError::Internal(format!("panic: {}", message))))
+            }
+        }
+    }
+}
+
+// `panic!` payloads are a `&str` or, when formatted, a `String`
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic payload".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{App, Router};
+
+    async fn get(app: &App, uri: &str) -> Response {
+        let req = hyper::Request::get(uri).header("accept", "application/json").body(hyper::Body::empty()).unwrap();
+        app.handle(Request::from_hyper(req).await.unwrap()).await.unwrap()
+    }
+
+    async fn body_text(response: Response) -> String {
+        let bytes = hyper::body::to_bytes(response.body).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    async fn panics(req: Request) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+        if req.uri.query() == Some("formatted") {
+            panic!("bad id {}", 42);
+        }
+        panic!("boom")
+    }
+
+    fn app(recover: Recover) -> App {
+        let router = Router::new()
+            .use_middleware(recover)
+            .get("/panic", panics)
+            .get("/ok", |_req: Request| async {
+                Ok::<_, Box<dyn std::error::Error + Send + Sync>>(Response::new().text("fine"))
+            });
+        App::new().router(router)
+    }
+
+    #[tokio::test]
+    async fn a_panicking_handler_gets_a_500_and_the_app_keeps_serving() {
+        let app = app(Recover::new());
+
+        let response = get(&app, "/panic").await;
+        assert_eq!(response.status, hyper::StatusCode::INTERNAL_SERVER_ERROR);
+        let body: serde_json::Value = serde_json::from_str(&body_text(response).await).unwrap();
+        // The panic message is only logged
+        assert_eq!(body["error"], "Internal Server Error");
+
+        let response = get(&app, "/ok").await;
+        assert_eq!(response.status, hyper::StatusCode::OK);
+        assert_eq!(body_text(response).await, "fine");
+
+        // And the same route again
+        let response = get(&app, "/panic?formatted").await;
+        assert_eq!(response.status, hyper::StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(!body_text(response).await.contains("bad id 42"));
+    }
+
+    #[tokio::test]
+    async fn panics_are_counted_when_metrics_are_given() {
+        let metrics = Arc::new(Metrics::new());
+        let app = app(Recover::new().metrics(metrics.clone()));
+
+        get(&app, "/panic").await;
+        get(&app, "/ok").await;
+        get(&app, "/panic").await;
+        assert_eq!(*metrics.error_counter.lock().unwrap(), 2);
+    }
+
+    #[test]
+    fn panic_messages_come_from_str_and_string_payloads() {
+        assert_eq!(panic_message(&"static"), "static");
+        assert_eq!(panic_message(&"owned".to_string()), "owned");
+        assert_eq!(panic_message(&7u8), "unknown panic payload");
+    }
+}
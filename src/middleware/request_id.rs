@@ -0,0 +1,123 @@
+use crate::middleware::Middleware;
+use crate::{Handler, Request, Response};
+use async_trait::async_trait;
+use std::sync::Arc;
+
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+// Tags each request with an ID: the incoming `X-Request-Id` if it looks sane, otherwise a new
+// UUID. Handlers read it from `req.request_id`, the response echoes it back, and the Logger
+// middleware includes it in its log line. Register it before (outside) the Logger.
+pub struct RequestId {
+    trust_incoming: bool,
+}
+
+impl RequestId {
+    pub fn new() -> Self {
+        RequestId { trust_incoming: true }
+    }
+
+    // Ignore IDs sent by clients and always generate one, e.g. when not behind a trusted proxy
+    pub fn trust_incoming(mut self, trust: bool) -> Self {
+        self.trust_incoming = trust;
+        self
+    }
+}
+
+impl Default for RequestId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Printable ASCII without spaces and of a reasonable length, so it's safe to log and echo
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= 128 && id.bytes().all(|b| b.is_ascii_graphic())
+}
+
+#[async_trait]
+impl Middleware for RequestId {
+    async fn handle(
+        &self,
+        mut req: Request,
+        next: Arc<dyn Handler>,
+    ) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+        let incoming = req.headers.get(REQUEST_ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(str::trim)
+            .filter(|id| self.trust_incoming && is_valid_id(id));
+        let id = match incoming {
+            Some(id) => id.to_string(),
+            None => uuid::Uuid::new_v4().to_string(),
+        };
+        req.request_id = Some(id.clone());
+
+        let response = next.handle(req).await?;
+        Ok(response.header(REQUEST_ID_HEADER, id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Answers with the ID the handler saw
+    fn echo_handler() -> Arc<dyn Handler> {
+        Arc::new(|req: Request| async move {
+            let id = req.request_id.clone().unwrap_or_default();
+            Ok::<_, Box<dyn std::error::Error + Send + Sync>>(Response::new().text(&id))
+        })
+    }
+
+    // The response's header and the ID the handler saw
+    async fn send(middleware: &RequestId, incoming: Option<&str>) -> (String, String) {
+        let mut builder = hyper::Request::get("/");
+        if let Some(id) = incoming {
+            builder = builder.header(REQUEST_ID_HEADER, id);
+        }
+        let req = Request::from_hyper(builder.body(hyper::Body::empty()).unwrap()).await.unwrap();
+        let response = middleware.handle(req, echo_handler()).await.unwrap();
+        let header = response.headers[REQUEST_ID_HEADER].to_str().unwrap().to_string();
+        let bytes = hyper::body::to_bytes(response.body).await.unwrap();
+        (header, String::from_utf8(bytes.to_vec()).unwrap())
+    }
+
+    fn is_uuid(id: &str) -> bool {
+        uuid::Uuid::parse_str(id).is_ok()
+    }
+
+    #[tokio::test]
+    async fn incoming_ids_are_echoed_and_visible_to_the_handler() {
+        let (header, seen) = send(&RequestId::new(), Some(" req-42.abc ")).await;
+        assert_eq!(header, "req-42.abc");
+        assert_eq!(seen, "req-42.abc");
+    }
+
+    #[tokio::test]
+    async fn missing_or_invalid_ids_are_replaced() {
+        let long = "a".repeat(129);
+        for incoming in [None, Some(""), Some("has space"), Some("tab\there"), Some(long.as_str())] {
+            let (header, seen) = send(&RequestId::new(), incoming).await;
+            assert!(is_uuid(&header), "{:?} -> {}", incoming, header);
+            assert_eq!(header, seen);
+        }
+
+        let longest = "a".repeat(128);
+        assert_eq!(send(&RequestId::new(), Some(&longest)).await.0, longest);
+    }
+
+    #[tokio::test]
+    async fn untrusted_incoming_ids_are_ignored() {
+        let (header, seen) = send(&RequestId::new().trust_incoming(false), Some("req-42")).await;
+        assert_ne!(header, "req-42");
+        assert!(is_uuid(&header));
+        assert_eq!(header, seen);
+    }
+
+    #[test]
+    fn valid_ids_are_printable_ascii() {
+        assert!(is_valid_id("0f8fad5b-d9cb-469f-a165-70867728950e"));
+        assert!(!is_valid_id("naïve"));
+        assert!(!is_valid_id("a\u{7f}"));
+    }
+}
@@ -0,0 +1,285 @@
+use crate::error::AppError;
+use crate::middleware::Middleware;
+use crate::{Handler, Request, Response};
+use async_trait::async_trait;
+use hyper::HeaderMap;
+use std::sync::Arc;
+use tracing::field::Empty;
+use tracing::Instrument;
+
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+pub const TRACE_ID_HEADER: &str = "X-Trace-Id";
+
+// A W3C trace context: `traceparent: 00-<trace id>-<span id>-<flags>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    pub trace_id: String,
+    pub span_id: String,
+    pub sampled: bool,
+}
+
+impl TraceContext {
+    // A new trace
+    pub fn new() -> Self {
+        TraceContext {
+            trace_id: uuid::Uuid::new_v4().simple().to_string(),
+            span_id: new_span_id(),
+            sampled: true,
+        }
+    }
+
+    // Inside TracingMiddleware this is the request's own span, to pass on to downstream calls
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        headers.get(TRACEPARENT_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(Self::parse)
+    }
+
+    // Versions other than 00 are read by their first four fields, as the spec asks
+    pub fn parse(traceparent: &str) -> Option<Self> {
+        let fields: Vec<&str> = traceparent.trim().split('-').collect();
+        let (version, trace_id, span_id, flags) = match fields[..] {
+            [version, trace_id, span_id, flags, ..] => (version, trace_id, span_id, flags),
+            _ => return None,
+        };
+        let valid = is_hex(version, 2) && version != "ff" && (version != "00" || fields.len() == 4)
+            && is_hex(trace_id, 32) && trace_id.bytes().any(|b| b != b'0')
+            && is_hex(span_id, 16) && span_id.bytes().any(|b| b != b'0')
+            && is_hex(flags, 2);
+        if !valid {
+            return None;
+        }
+        Some(TraceContext {
+            trace_id: trace_id.to_string(),
+            span_id: span_id.to_string(),
+            sampled: u8::from_str_radix(flags, 16).ok()? & 1 == 1,
+        })
+    }
+
+    // The same trace with a new span, whose parent is this one
+    pub fn child(&self) -> Self {
+        TraceContext {
+            trace_id: self.trace_id.clone(),
+            span_id: new_span_id(),
+            sampled: self.sampled,
+        }
+    }
+
+    pub fn traceparent(&self) -> String {
+        format!("00-{}-{}-{:02x}", self.trace_id, self.span_id, self.sampled as u8)
+    }
+}
+
+impl Default for TraceContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn new_span_id() -> String {
+    uuid::Uuid::new_v4().simple().to_string()[..16].to_string()
+}
+
+// Lowercase only, per the spec
+fn is_hex(value: &str, len: usize) -> bool {
+    value.len() == len && value.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+// Runs each request in a `tracing` span (target `rustnext::request`) with `http.method`,
+// `http.target`, `http.route` (the matched pattern; ApiRegistry narrows it to its own route),
+// `http.status_code`, `latency_ms`, `trace_id`, `span_id`, `parent_span_id` and `request_id`,
+// and logs one event when the request completes, at the same levels as the Logger.
+//
+// The trace continues the incoming `traceparent`, if any. Handlers see a `traceparent` header
+// naming the request's span, so `TraceContext::from_headers(&req.headers)` is what to forward
+// to downstream services, and the response carries the trace id in `X-Trace-Id` (handler errors
+// only become a response further out, without it).
+pub struct TracingMiddleware;
+
+impl TracingMiddleware {
+    pub fn new() -> Self {
+        TracingMiddleware
+    }
+}
+
+impl Default for TracingMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Middleware for TracingMiddleware {
+    async fn handle(
+        &self,
+        mut req: Request,
+        next: Arc<dyn Handler>,
+    ) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+        let start = std::time::Instant::now();
+        let parent = TraceContext::from_headers(&req.headers);
+        let context = match &parent {
+            Some(parent) => parent.child(),
+            None => TraceContext::new(),
+        };
+
+        let span = tracing::info_span!(
+            target: "rustnext::request",
+            "request",
+            http.method = %req.method,
+            http.target = %req.uri.path(),
+            http.route = Empty,
+            http.status_code = Empty,
+            latency_ms = Empty,
+            trace_id = %context.trace_id,
+            span_id = %context.span_id,
+            parent_span_id = Empty,
+            request_id = Empty,
+        );
+        if let Some(parent) = &parent {
+            span.record("parent_span_id", parent.span_id.as_str());
+        }
+        if let Some(route) = &req.route {
+            span.record("http.route", route.as_str());
+        }
+        if let Some(request_id) = &req.request_id {
+            span.record("request_id", request_id.as_str());
+        }
+        if let Ok(value) = context.traceparent().parse() {
+            req.headers.insert(TRACEPARENT_HEADER, value);
+        }
+
+        let result = next.handle(req).instrument(span.clone()).await;
+
+        // Errors become a response further out; by default that's a 500
+        let status = match &result {
+            Ok(response) => response.status.as_u16(),
+            Err(e) => AppError::from_ref(e.as_ref()).status().as_u16(),
+        };
+        let latency_ms = start.elapsed().as_millis() as u64;
+        span.record("http.status_code", status);
+        span.record("latency_ms", latency_ms);
+        span.in_scope(|| match status {
+            500.. => tracing::error!(target: "rustnext::request", "request failed"),
+            400..=499 => tracing::warn!(target: "rustnext::request", "request completed"),
+            _ => tracing::info!(target: "rustnext::request", "request completed"),
+        });
+
+        Ok(result?.header(TRACE_ID_HEADER, context.trace_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    const INCOMING: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    #[test]
+    fn parses_valid_traceparents_only() {
+        let context = TraceContext::parse(INCOMING).unwrap();
+        assert_eq!(context.trace_id, "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(context.span_id, "00f067aa0ba902b7");
+        assert!(context.sampled);
+        assert_eq!(context.traceparent(), INCOMING);
+
+        assert!(!TraceContext::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00").unwrap().sampled);
+        // Later versions may add fields
+        assert!(TraceContext::parse("01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra").is_some());
+
+        for invalid in [
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6-00f067aa0ba902b7-01",
+            "garbage",
+        ] {
+            assert_eq!(TraceContext::parse(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn child_spans_stay_in_the_trace() {
+        let parent = TraceContext::new();
+        let child = parent.child();
+        assert_eq!(child.trace_id, parent.trace_id);
+        assert_ne!(child.span_id, parent.span_id);
+        assert_eq!(child.span_id.len(), 16);
+        assert!(TraceContext::parse(&child.traceparent()).is_some());
+    }
+
+    // What the fmt subscriber writes, for checking span fields
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    async fn traced(traceparent: Option<&str>) -> (Response, String, String) {
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .with_ansi(false)
+            .finish();
+        let _default = tracing::subscriber::set_default(subscriber);
+
+        let mut builder = hyper::Request::get("/posts/7");
+        if let Some(traceparent) = traceparent {
+            builder = builder.header(TRACEPARENT_HEADER, traceparent);
+        }
+        let mut req = Request::from_hyper(builder.body(hyper::Body::empty()).unwrap()).await.unwrap();
+        req.route = Some("/posts/:id".to_string());
+        let seen = Arc::new(Mutex::new(String::new()));
+        let record = seen.clone();
+        let handler = move |req: Request| {
+            *record.lock().unwrap() = req.headers[TRACEPARENT_HEADER].to_str().unwrap().to_string();
+            async { Ok::<_, Box<dyn std::error::Error + Send + Sync>>(Response::new().status(hyper::StatusCode::NOT_FOUND)) }
+        };
+
+        let response = TracingMiddleware::new().handle(req, Arc::new(handler)).await.unwrap();
+        let log = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        let seen = seen.lock().unwrap().clone();
+        (response, seen, log)
+    }
+
+    #[tokio::test]
+    async fn continues_the_incoming_trace() {
+        let (response, seen, log) = traced(Some(INCOMING)).await;
+        let parent = TraceContext::parse(INCOMING).unwrap();
+        let context = TraceContext::parse(&seen).unwrap();
+        assert_eq!(context.trace_id, parent.trace_id);
+        assert_ne!(context.span_id, parent.span_id);
+        assert_eq!(response.headers[TRACE_ID_HEADER], parent.trace_id.as_str());
+
+        assert!(log.contains("WARN"));
+        for field in [
+            "http.method=GET".to_string(),
+            // Recorded after the span is created, so quoted
+            "http.route=\"/posts/:id\"".to_string(),
+            "http.status_code=404".to_string(),
+            format!("span_id={}", context.span_id),
+            format!("parent_span_id={:?}", parent.span_id),
+        ] {
+            assert!(log.contains(&field), "{} missing from {}", field, log);
+        }
+    }
+
+    #[tokio::test]
+    async fn starts_a_trace_without_one() {
+        let (response, seen, log) = traced(Some("not-a-traceparent")).await;
+        let context = TraceContext::parse(&seen).unwrap();
+        assert_eq!(response.headers[TRACE_ID_HEADER], context.trace_id.as_str());
+        assert!(!log.contains("parent_span_id"));
+    }
+}
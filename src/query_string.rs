@@ -0,0 +1,212 @@
+use serde::de::value::{Error, MapDeserializer, SeqDeserializer};
+use serde::de::{self, DeserializeOwned, IntoDeserializer, Visitor};
+use std::collections::HashMap;
+use url::form_urlencoded;
+
+// Every value of every key, in order: `?tag=a&tag=b` and `?tag[]=a&tag[]=b` both give
+// `tag => [a, b]`. `+` is a space, and a key without `=` has an empty value.
+pub fn parse(query: &str) -> HashMap<String, Vec<String>> {
+    let mut values: HashMap<String, Vec<String>> = HashMap::new();
+    for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+        let key = key.strip_suffix("[]").unwrap_or(&key).to_string();
+        values.entry(key).or_default().push(value.into_owned());
+    }
+    values
+}
+
+// Deserializes parsed values into `T`. Like serde_urlencoded, values are strings that fields
+// parse into numbers, booleans etc.; unlike it, a key given more than once fills a `Vec` field
+// (a single value does too), and an empty value is `None` for an `Option` field.
+pub fn from_values<T: DeserializeOwned>(values: &HashMap<String, Vec<String>>) -> Result<T, Error> {
+    let entries = values.iter().map(|(key, values)| (key.as_str(), Values(values)));
+    T::deserialize(MapDeserializer::new(entries))
+}
+
+struct Values<'a>(&'a [String]);
+
+impl<'a> Values<'a> {
+    // Scalars take the last value, as `Request::query` does
+    fn last(&self) -> &'a str {
+        self.0.last().map_or("", String::as_str)
+    }
+
+    fn parse<T: std::str::FromStr>(&self, kind: &str) -> Result<T, Error> {
+        self.last().trim().parse()
+            .map_err(|_| de::Error::custom(format!("invalid {}: {:?}", kind, self.last())))
+    }
+}
+
+impl<'a> IntoDeserializer<'a, Error> for Values<'a> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident: $ty:ty),* $(,)?) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                visitor.$visit(self.parse::<$ty>(stringify!($ty))?)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Values<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            [_] | [] => visitor.visit_borrowed_str(self.last()),
+            values => visit_seq(values, visitor),
+        }
+    }
+
+    deserialize_parsed! {
+        deserialize_bool => visit_bool: bool,
+        deserialize_i8 => visit_i8: i8,
+        deserialize_i16 => visit_i16: i16,
+        deserialize_i32 => visit_i32: i32,
+        deserialize_i64 => visit_i64: i64,
+        deserialize_u8 => visit_u8: u8,
+        deserialize_u16 => visit_u16: u16,
+        deserialize_u32 => visit_u32: u32,
+        deserialize_u64 => visit_u64: u64,
+        deserialize_f32 => visit_f32: f32,
+        deserialize_f64 => visit_f64: f64,
+        deserialize_char => visit_char: char,
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_borrowed_str(self.last())
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_borrowed_str(self.last())
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.0.iter().all(|value| value.is_empty()) {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visit_seq(self.0, visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    // Unit variants by name, e.g. `?order=desc` into `enum Order { Asc, Desc }` with
+    // `#[serde(rename_all = "lowercase")]`
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_enum(self.last().into_deserializer(), name, variants, visitor)
+    }
+
+    serde::forward_to_deserialize_any! {
+        i128 u128 bytes byte_buf unit unit_struct tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+// Each value on its own, so `?id=1&id=2` fills a `Vec<u32>`
+fn visit_seq<'de, V: Visitor<'de>>(values: &'de [String], visitor: V) -> Result<V::Value, Error> {
+    let items = values.iter().map(|value| Values(std::slice::from_ref(value)));
+    visitor.visit_seq(SeqDeserializer::new(items))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    enum Order {
+        Asc,
+        Desc,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Search {
+        #[serde(default)]
+        tag: Vec<String>,
+        page: Option<u32>,
+        order: Option<Order>,
+        q: Option<String>,
+    }
+
+    fn search(query: &str) -> Result<Search, Error> {
+        from_values(&parse(query))
+    }
+
+    #[test]
+    fn repeated_and_bracketed_keys_fill_a_vec() {
+        assert_eq!(search("tag=a&tag=b").unwrap().tag, ["a", "b"]);
+        assert_eq!(search("tag[]=a&tag[]=b").unwrap().tag, ["a", "b"]);
+        assert_eq!(search("tag[]=a").unwrap().tag, ["a"]);
+        assert_eq!(search("tag=a").unwrap().tag, ["a"]);
+        assert!(search("").unwrap().tag.is_empty());
+
+        let ids: HashMap<String, Vec<u32>> = from_values(&parse("id=1&id=2")).unwrap();
+        assert_eq!(ids["id"], [1, 2]);
+    }
+
+    #[test]
+    fn empty_values_are_none() {
+        let parsed = search("page=&q=").unwrap();
+        assert_eq!(parsed.page, None);
+        assert_eq!(parsed.q, None);
+        assert_eq!(search("page=3&q=a+b").unwrap(), Search {
+            tag: vec![],
+            page: Some(3),
+            order: None,
+            q: Some("a b".to_string()),
+        });
+    }
+
+    #[test]
+    fn scalars_take_the_last_value() {
+        let parsed = search("page=1&page=2&q=x&q=y").unwrap();
+        assert_eq!(parsed.page, Some(2));
+        assert_eq!(parsed.q.as_deref(), Some("y"));
+    }
+
+    #[test]
+    fn unit_variants_are_matched_by_name() {
+        assert_eq!(search("order=desc").unwrap().order, Some(Order::Desc));
+        assert_eq!(search("order=asc").unwrap().order, Some(Order::Asc));
+        assert!(search("order=sideways").is_err());
+    }
+
+    #[test]
+    fn bad_numbers_are_errors() {
+        let err = search("page=two").unwrap_err();
+        assert!(err.to_string().contains("invalid u32: \"two\""), "{}", err);
+        assert!(search("page=-1").is_err());
+    }
+
+    #[tokio::test]
+    async fn request_query_as_fails_with_a_400() {
+        let req = hyper::Request::get("/search?page=two").body(hyper::Body::empty()).unwrap();
+        let req = crate::Request::from_hyper(req).await.unwrap();
+
+        let err = req.query_as::<Search>().unwrap_err();
+        assert_eq!(err.status(), hyper::StatusCode::BAD_REQUEST);
+        assert!(err.message().starts_with("Invalid query string"), "{}", err.message());
+
+        let req = hyper::Request::get("/search?tag=a&tag=b&page=2").body(hyper::Body::empty()).unwrap();
+        let parsed: Search = crate::Request::from_hyper(req).await.unwrap().query_as().unwrap();
+        assert_eq!((parsed.tag, parsed.page), (vec!["a".to_string(), "b".to_string()], Some(2)));
+    }
+}
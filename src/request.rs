@@ -1,8 +1,15 @@
+use hyper::body::{Bytes, HttpBody};
 use hyper::{Body, Request as HyperRequest, Method, Uri};
 use serde_json::Value;
+use serde::de::DeserializeOwned;
+use crate::error::AppError;
 use std::collections::HashMap;
+use std::net::SocketAddr;
 use url::form_urlencoded;
 use multer::Multipart;
+use once_cell::sync::OnceCell;
+
+mod negotiation;
 
 #[derive(Debug)]
 pub struct Request {
@@ -11,19 +18,71 @@ pub struct Request {
     pub headers: hyper::HeaderMap,
     pub body: Option<Body>, // Changed to Option<Body>
     pub params: HashMap<String, String>,
+    // The last value of each query parameter; see `query_all` for repeated ones
     pub query: HashMap<String, String>,
+    // Every value of each query parameter, e.g. `tag => [a, b]` for `?tag=a&tag=b` or `?tag[]=a&tag[]=b`
+    pub query_values: HashMap<String, Vec<String>>,
     pub json_body: Option<Value>,
     pub form_body: Option<HashMap<String, String>>,
+    // Every value of each form field, like `query_values`; filled in alongside `form_body`
+    pub form_values: Option<HashMap<String, Vec<String>>>,
     // Fields used by middleware
     pub user_id: Option<String>,
     pub user_roles: Vec<String>,
     pub session: Option<crate::session::Session>,
+    pub(crate) session_cell: Option<crate::session::SessionCell>,
+    // Overrides `server.max_body_size` for this request (set by BodyLimit, or by a handler
+    // before reading, e.g. to allow large uploads)
+    pub body_limit: Option<usize>,
+    // Set by the RequestId middleware, for correlating a handler's own logs with the request
+    pub request_id: Option<String>,
+    // The pattern of the matched route (e.g. `/items/:id`), set by the Router before its
+    // middleware runs
+    pub route: Option<String>,
+    // The locale to render in, e.g. `de-AT`, set by LocaleResolver; `t` falls back to the
+    // translator's default without it
+    pub locale: Option<String>,
+    // The connection's peer, set by the Server; behind a proxy that's the proxy (see `client_ip`)
+    pub remote_addr: Option<SocketAddr>,
+    // `content_type()`, parsed on first use
+    content_type: OnceCell<Option<mime::Mime>>,
+    // The connection to take over for a WebSocket upgrade, kept by the Server
+    #[cfg(feature = "ws")]
+    pub(crate) upgrade: Option<hyper::upgrade::OnUpgrade>,
+}
+
+// Hands a (possibly modified) `session` back to SessionMiddleware once the handler is done
+impl Drop for Request {
+    fn drop(&mut self) {
+        if let (Some(cell), Some(session)) = (&self.session_cell, self.session.take()) {
+            if let Ok(mut slot) = cell.lock() {
+                *slot = Some(session);
+            }
+        }
+    }
 }
 
+// The body exceeded the request's limit; reported as 413 Payload Too Large
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BodyTooLarge {
+    pub limit: usize,
+}
+
+impl std::fmt::Display for BodyTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Request body exceeds the limit of {} bytes", self.limit)
+    }
+}
+
+impl std::error::Error for BodyTooLarge {}
+
 impl Request {
     pub async fn from_hyper(req: HyperRequest<Body>) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
         let (parts, body) = req.into_parts();
-        let query = Self::parse_query(&parts.uri);
+        let query_values = crate::query_string::parse(parts.uri.query().unwrap_or(""));
+        let query = query_values.iter()
+            .filter_map(|(key, values)| Some((key.clone(), values.last()?.clone())))
+            .collect();
         
         Ok(Request {
             method: parts.method,
@@ -32,17 +91,28 @@ impl Request {
             body: Some(body), // Store body as Some
             params: HashMap::new(),
             query,
+            query_values,
             json_body: None,
             form_body: None,
+            form_values: None,
             user_id: None,
             user_roles: Vec::new(),
             session: None,
+            session_cell: None,
+            body_limit: None,
+            request_id: None,
+            route: None,
+            locale: None,
+            remote_addr: None,
+            content_type: OnceCell::new(),
+            #[cfg(feature = "ws")]
+            upgrade: None,
         })
     }
 
     pub async fn json(&mut self) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
         if self.json_body.is_none() {
-            let body_bytes = hyper::body::to_bytes(self.body.take().unwrap_or_default()).await?; // Take body
+            let body_bytes = self.read_body().await?;
             if !body_bytes.is_empty() {
                 self.json_body = Some(serde_json::from_slice(&body_bytes)?);
             }
@@ -52,24 +122,96 @@ impl Request {
 
     pub async fn form(&mut self) -> Result<&HashMap<String, String>, Box<dyn std::error::Error + Send + Sync>> {
         if self.form_body.is_none() {
-            let body_bytes = hyper::body::to_bytes(self.body.take().unwrap_or_default()).await?; // Take body
+            let body_bytes = self.read_body().await?;
             let body_str = String::from_utf8(body_bytes.to_vec())?;
             let parsed_form: HashMap<String, String> = form_urlencoded::parse(body_str.as_bytes())
                 .into_owned()
                 .collect();
             self.form_body = Some(parsed_form);
+            self.form_values = Some(crate::query_string::parse(&body_str));
         }
         Ok(self.form_body.as_ref().unwrap())
     }
 
-    pub fn multipart(&mut self) -> Result<Multipart, Box<dyn std::error::Error + Send + Sync>> {
+    pub fn multipart(&mut self) -> Result<Multipart<'_>, Box<dyn std::error::Error + Send + Sync>> {
         let content_type = self.headers.get(hyper::header::CONTENT_TYPE)
             .and_then(|v| v.to_str().ok())
             .ok_or("Missing Content-Type header for multipart form")?;
         
         let boundary = multer::parse_boundary(content_type)?;
-        // Create a new Multipart instance, consuming the body
-        Ok(Multipart::new(self.body.take().unwrap_or_default(), boundary))
+        let limit = self.check_content_length()?;
+        // Create a new Multipart instance, consuming the body; multer stops once it passes the limit
+        let constraints = multer::Constraints::new()
+            .size_limit(multer::SizeLimit::new().whole_stream(limit as u64));
+        Ok(Multipart::with_constraints(self.body.take().unwrap_or_default(), boundary, constraints))
+    }
+
+    // The client's address: with `server.trust_proxy`, the address the proxy put in the forwarded
+    // headers (see `forwarded_ip`), otherwise the peer of the connection. None for a Request that
+    // didn't come through the Server and has no usable header.
+    pub fn client_ip(&self) -> Option<String> {
+        if crate::config::get_config().server.trust_proxy {
+            if let Some(ip) = self.forwarded_ip() {
+                return Some(ip.to_string());
+            }
+        }
+        self.remote_addr.map(|addr| addr.ip().to_string())
+    }
+
+    // The last `X-Forwarded-For` hop, which the trusted proxy appended itself; anything before it
+    // came from the client and may be made up. `X-Real-IP` when there's no `X-Forwarded-For`.
+    fn forwarded_ip(&self) -> Option<&str> {
+        self.headers
+            .get("x-forwarded-for")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.rsplit(',').next())
+            .or_else(|| self.headers.get("x-real-ip").and_then(|v| v.to_str().ok()))
+            .map(str::trim)
+            .filter(|ip| !ip.is_empty())
+    }
+
+    // `body_limit`, falling back to `server.max_body_size`
+    pub fn max_body_size(&self) -> usize {
+        self.body_limit.unwrap_or_else(|| crate::config::get_config().server.max_body_size)
+    }
+
+    // Rejects a declared Content-Length over the limit before anything is read
+    fn check_content_length(&self) -> Result<usize, BodyTooLarge> {
+        let limit = self.max_body_size();
+        let declared = self.headers.get(hyper::header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse::<u64>().ok());
+        match declared {
+            Some(length) if length > limit as u64 => Err(BodyTooLarge { limit }),
+            _ => Ok(limit),
+        }
+    }
+
+    // Takes and buffers the body, giving up as soon as it grows past the limit
+    // (chunked bodies have no Content-Length to check up front)
+    async fn read_body(&mut self) -> Result<Bytes, Box<dyn std::error::Error + Send + Sync>> {
+        let limit = self.check_content_length()?;
+        let mut body = self.body.take().unwrap_or_default();
+        let mut buffer = Vec::new();
+        while let Some(chunk) = body.data().await {
+            let chunk = chunk?;
+            if buffer.len() + chunk.len() > limit {
+                return Err(Box::new(BodyTooLarge { limit }));
+            }
+            buffer.extend_from_slice(&chunk);
+        }
+        Ok(Bytes::from(buffer))
+    }
+
+    // Rotates the ID of `req.session` (see `Session::regenerate_id`). SessionMiddleware then
+    // deletes the old store entry and sends a cookie with the new ID. Returns the new ID.
+    pub fn regenerate_session(&mut self) -> Option<String> {
+        let session = self.session.as_mut()?;
+        session.regenerate_id();
+        if let Some(cell) = &self.session_cell {
+            *cell.lock().unwrap() = Some(session.clone());
+        }
+        Some(session.id.clone())
     }
 
     pub fn param(&self, key: &str) -> Option<&String> {
@@ -80,18 +222,52 @@ impl Request {
         self.query.get(key)
     }
 
-    fn parse_query(uri: &Uri) -> HashMap<String, String> {
-        let mut query = HashMap::new();
-        if let Some(query_str) = uri.query() {
-            for pair in query_str.split('&') {
-                if let Some((key, value)) = pair.split_once('=') {
-                    query.insert(
-                        percent_encoding::percent_decode_str(key).decode_utf8_lossy().to_string(),
-                        percent_encoding::percent_decode_str(value).decode_utf8_lossy().to_string(),
-                    );
-                }
-            }
+    // Every value of a repeated parameter, in order
+    pub fn query_all(&self, key: &str) -> &[String] {
+        self.query_values.get(key).map_or(&[], Vec::as_slice)
+    }
+
+    // The whole query string as `T`, e.g. `?page=2&tag=a&tag=b` into
+    // `struct Filter { page: Option<u32>, #[serde(default)] tag: Vec<String> }`; see
+    // `query_string::from_values`. Fails with a 400.
+    pub fn query_as<T: DeserializeOwned>(&self) -> Result<T, AppError> {
+        crate::query_string::from_values(&self.query_values)
+            .map_err(|e| AppError::BadRequest(format!("Invalid query string: {}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn request(headers: &[(&str, &str)]) -> Request {
+        let mut builder = HyperRequest::builder().uri("/");
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
         }
-        query
+        Request::from_hyper(builder.body(Body::empty()).unwrap()).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn forwarded_ip_is_the_hop_the_proxy_appended() {
+        let req = request(&[("X-Forwarded-For", "203.0.113.9, 198.51.100.7")]).await;
+        assert_eq!(req.forwarded_ip(), Some("198.51.100.7"));
+
+        let req = request(&[("X-Forwarded-For", "198.51.100.7"), ("X-Real-IP", "203.0.113.9")]).await;
+        assert_eq!(req.forwarded_ip(), Some("198.51.100.7"));
+
+        let req = request(&[("X-Real-IP", "203.0.113.9")]).await;
+        assert_eq!(req.forwarded_ip(), Some("203.0.113.9"));
+
+        assert_eq!(request(&[]).await.forwarded_ip(), None);
+    }
+
+    #[tokio::test]
+    async fn client_ip_ignores_forwarded_headers_without_trust_proxy() {
+        let mut req = request(&[("X-Forwarded-For", "203.0.113.9")]).await;
+        assert_eq!(req.client_ip(), None);
+
+        req.remote_addr = Some("192.0.2.1:4000".parse().unwrap());
+        assert_eq!(req.client_ip(), Some("192.0.2.1".to_string()));
     }
 }
@@ -0,0 +1,139 @@
+use super::Request;
+use crate::api::negotiation::negotiate;
+use hyper::header::{ACCEPT, CONTENT_TYPE};
+use mime::Mime;
+
+impl Request {
+    // The parsed `Content-Type`, parameters (charset, boundary) included; None when it's missing
+    // or malformed. Parsed on first use, so set the header before calling this if you change it.
+    pub fn content_type(&self) -> Option<&Mime> {
+        self.content_type
+            .get_or_init(|| {
+                self.headers.get(CONTENT_TYPE)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.trim().parse().ok())
+            })
+            .as_ref()
+    }
+
+    // `application/json`, or a `+json` type such as `application/problem+json`
+    pub fn is_json(&self) -> bool {
+        self.content_type().is_some_and(|mime| {
+            mime.type_() == mime::APPLICATION
+                && (mime.subtype() == mime::JSON || mime.suffix() == Some(mime::JSON))
+        })
+    }
+
+    // `application/x-www-form-urlencoded`, for `form()`
+    pub fn is_form(&self) -> bool {
+        self.content_type().is_some_and(|mime| mime.essence_str() == mime::APPLICATION_WWW_FORM_URLENCODED.essence_str())
+    }
+
+    // `multipart/form-data`, for `multipart()`
+    pub fn is_multipart(&self) -> bool {
+        self.content_type().is_some_and(|mime| mime.essence_str() == mime::MULTIPART_FORM_DATA.essence_str())
+    }
+
+    fn accept_header(&self) -> Option<&str> {
+        self.headers.get(ACCEPT).and_then(|v| v.to_str().ok())
+    }
+
+    // Whether `Accept` allows `media_type` (with a q-value above 0). A missing `Accept` allows
+    // anything, and so does one without a single well-formed range.
+    pub fn accepts(&self, media_type: &Mime) -> bool {
+        negotiate(self.accept_header(), &[media_type.essence_str()]).is_some()
+    }
+
+    // The entry of `available` the client accepts with the highest q-value. Each type takes the
+    // q-value of its most specific matching range (`text/html` over `text/*` over `*/*`), and ties
+    // go to the earlier entry, as does a missing `Accept`.
+    pub fn preferred_accept(&self, available: &[Mime]) -> Option<Mime> {
+        let essences: Vec<&str> = available.iter().map(Mime::essence_str).collect();
+        let chosen = negotiate(self.accept_header(), &essences)?;
+        available.iter().find(|mime| mime.essence_str() == chosen).cloned()
+    }
+
+    // A browser rather than an API client: the client prefers HTML to JSON, or sent no `Accept`
+    pub fn wants_html(&self) -> bool {
+        negotiate(self.accept_header(), &["text/html", "application/json"]) == Some("text/html")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn request(headers: &[(&str, &str)]) -> Request {
+        let mut builder = hyper::Request::builder().uri("/");
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        Request::from_hyper(builder.body(hyper::Body::empty()).unwrap()).await.unwrap()
+    }
+
+    async fn with_content_type(content_type: &str) -> Request {
+        request(&[("content-type", content_type)]).await
+    }
+
+    #[tokio::test]
+    async fn content_type_is_parsed_with_its_parameters() {
+        let req = with_content_type("multipart/form-data; boundary=XyZ").await;
+        let mime = req.content_type().unwrap();
+        assert_eq!(mime.essence_str(), "multipart/form-data");
+        assert_eq!(mime.get_param(mime::BOUNDARY).unwrap(), "XyZ");
+        assert!(req.is_multipart() && !req.is_form() && !req.is_json());
+
+        assert!(request(&[]).await.content_type().is_none());
+        assert!(with_content_type("not a type").await.content_type().is_none());
+    }
+
+    #[tokio::test]
+    async fn recognizes_json_and_form_bodies() {
+        assert!(with_content_type("application/json").await.is_json());
+        assert!(with_content_type("Application/JSON; charset=utf-8").await.is_json());
+        assert!(with_content_type("application/problem+json").await.is_json());
+        assert!(!with_content_type("text/json").await.is_json());
+        assert!(!request(&[]).await.is_json());
+
+        assert!(with_content_type("application/x-www-form-urlencoded; charset=utf-8").await.is_form());
+        assert!(!with_content_type("multipart/form-data").await.is_form());
+    }
+
+    #[tokio::test]
+    async fn accepts_follows_q_values() {
+        let req = request(&[("accept", "text/html, application/*;q=0.5, image/png;q=0")]).await;
+        assert!(req.accepts(&mime::TEXT_HTML));
+        assert!(req.accepts(&mime::APPLICATION_JSON));
+        assert!(!req.accepts(&mime::IMAGE_PNG));
+        assert!(!req.accepts(&mime::TEXT_PLAIN));
+
+        // Nothing to go on: anything goes
+        assert!(request(&[]).await.accepts(&mime::IMAGE_PNG));
+        assert!(request(&[("accept", ";;")]).await.accepts(&mime::IMAGE_PNG));
+    }
+
+    #[tokio::test]
+    async fn preferred_accept_picks_the_most_specific_range() {
+        let available = [mime::APPLICATION_JSON, mime::TEXT_HTML, mime::TEXT_PLAIN];
+        let available = &available;
+        let preferred = |accept| async move {
+            request(&[("accept", accept)]).await.preferred_accept(available).map(|mime| mime.to_string())
+        };
+
+        assert_eq!(preferred("text/*;q=0.8, text/plain;q=0.2").await.as_deref(), Some("text/html"));
+        assert_eq!(preferred("*/*;q=0.1, text/plain").await.as_deref(), Some("text/plain"));
+        // Ties go to the earlier entry
+        assert_eq!(preferred("text/html, application/json").await.as_deref(), Some("application/json"));
+        assert_eq!(preferred("image/*").await, None);
+        assert_eq!(request(&[]).await.preferred_accept(available), Some(mime::APPLICATION_JSON));
+    }
+
+    #[tokio::test]
+    async fn wants_html_for_browsers_and_clients_without_accept() {
+        let browser = "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8";
+        assert!(request(&[("accept", browser)]).await.wants_html());
+        assert!(request(&[]).await.wants_html());
+        assert!(!request(&[("accept", "application/json")]).await.wants_html());
+        assert!(!request(&[("accept", "application/json, text/html;q=0.5")]).await.wants_html());
+    }
+}
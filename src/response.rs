@@ -1,20 +1,27 @@
-use hyper::{Body, Response as HyperResponse, StatusCode};
+use crate::escape::{escape_text, escape_url};
+use crate::error::IntoResponse;
+use hyper::header::{HeaderName, HeaderValue, CONTENT_TYPE, LOCATION};
+use hyper::{Body, HeaderMap, Response as HyperResponse, StatusCode};
 use serde::Serialize;
-use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
 
 #[derive(Debug)]
 pub struct Response {
     pub status: StatusCode,
-    pub headers: HashMap<String, String>,
+    pub headers: HeaderMap,
     pub body: Body,
+    // The first header `header`/`append_header` couldn't convert; `into_hyper` returns it
+    invalid_header: Option<InvalidHeader>,
 }
 
 impl Response {
     pub fn new() -> Self {
         Response {
             status: StatusCode::OK,
-            headers: HashMap::new(),
+            headers: HeaderMap::new(),
             body: Body::empty(),
+            invalid_header: None,
         }
     }
 
@@ -23,28 +30,82 @@ impl Response {
         self
     }
 
-    // Modified to accept any type that can be converted into a String
-    pub fn header<V: Into<String>>(mut self, key: &str, value: V) -> Self {
-        self.headers.insert(key.to_string(), value.into());
+    // Sets a header, replacing any values it had, e.g. `.header("Cache-Control", "no-store")`
+    // or `.header(hyper::header::ETAG, etag)`. Names are case-insensitive. An invalid name or
+    // value (say, a line break) doesn't panic: `into_hyper` reports it instead.
+    pub fn header<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: TryInto<HeaderName>,
+        K::Error: Into<hyper::http::Error>,
+        V: TryInto<HeaderValue>,
+        V::Error: Into<hyper::http::Error>,
+    {
+        if let Some((name, value)) = self.convert(key, value) {
+            self.headers.insert(name, value);
+        }
+        self
+    }
+
+    // Adds a value alongside any the header already has, for headers such as Set-Cookie
+    pub fn append_header<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: TryInto<HeaderName>,
+        K::Error: Into<hyper::http::Error>,
+        V: TryInto<HeaderValue>,
+        V::Error: Into<hyper::http::Error>,
+    {
+        if let Some((name, value)) = self.convert(key, value) {
+            self.headers.append(name, value);
+        }
         self
     }
 
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    pub fn headers_mut(&mut self) -> &mut HeaderMap {
+        &mut self.headers
+    }
+
+    fn convert<K, V>(&mut self, key: K, value: V) -> Option<(HeaderName, HeaderValue)>
+    where
+        K: TryInto<HeaderName>,
+        K::Error: Into<hyper::http::Error>,
+        V: TryInto<HeaderValue>,
+        V::Error: Into<hyper::http::Error>,
+    {
+        let result = key.try_into()
+            .map_err(|e| InvalidHeader { name: None, source: e.into() })
+            .and_then(|name| match value.try_into() {
+                Ok(value) => Ok((name, value)),
+                Err(e) => Err(InvalidHeader { name: Some(name), source: e.into() }),
+            });
+        match result {
+            Ok(header) => Some(header),
+            Err(e) => {
+                self.invalid_header.get_or_insert(e);
+                None
+            }
+        }
+    }
+
     pub fn json<T: Serialize>(mut self, data: &T) -> Result<Self, serde_json::Error> {
         let json_str = serde_json::to_string(data)?;
         self.body = Body::from(json_str);
-        self.headers.insert("Content-Type".to_string(), "application/json".to_string());
+        self.headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
         Ok(self)
     }
 
     pub fn text(mut self, text: &str) -> Self {
         self.body = Body::from(text.to_string());
-        self.headers.insert("Content-Type".to_string(), "text/plain".to_string());
+        self.headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/plain"));
         self
     }
 
     pub fn html(mut self, html: &str) -> Self {
         self.body = Body::from(html.to_string());
-        self.headers.insert("Content-Type".to_string(), "text/html".to_string());
+        self.headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/html"));
         self
     }
 
@@ -53,20 +114,82 @@ impl Response {
         self
     }
 
+    // 302 Found. The other redirect statuses have constructors: `Response::see_other(..)` etc.
     pub fn redirect(mut self, location: &str) -> Self {
         self.status = StatusCode::FOUND;
-        self.headers.insert("Location".to_string(), location.to_string());
-        self
+        self.header(LOCATION, location)
     }
 
-    pub fn into_hyper(self) -> HyperResponse<Body> {
-        let mut response = HyperResponse::builder().status(self.status);
-        
-        for (key, value) in self.headers {
-            response = response.header(key, value);
+    // Panics unless `status` is a 3xx
+    pub fn redirect_with(status: StatusCode, location: &str) -> Self {
+        assert!(status.is_redirection(), "redirect status must be 3xx, got {}", status);
+        Response::new().status(status).header(LOCATION, location)
+    }
+
+    // 303: the client GETs `location`, e.g. after a form POST
+    pub fn see_other(location: &str) -> Self {
+        Self::redirect_with(StatusCode::SEE_OTHER, location)
+    }
+
+    // 301
+    pub fn moved_permanently(location: &str) -> Self {
+        Self::redirect_with(StatusCode::MOVED_PERMANENTLY, location)
+    }
+
+    // 307: the client repeats the request, method and body included, at `location`
+    pub fn temporary_redirect(location: &str) -> Self {
+        Self::redirect_with(StatusCode::TEMPORARY_REDIRECT, location)
+    }
+
+    // 308: like 307, but permanent
+    pub fn permanent_redirect(location: &str) -> Self {
+        Self::redirect_with(StatusCode::PERMANENT_REDIRECT, location)
+    }
+
+    // A short HTML page linking to the Location, for clients that don't follow redirects
+    pub fn redirect_page(self) -> Self {
+        let location = match self.headers.get(LOCATION).and_then(|v| v.to_str().ok()) {
+            Some(location) => location.to_string(),
+            None => return self,
+        };
+        self.html(&format!(
+            "<!DOCTYPE html><html><head><title>Redirecting</title></head><body><p>Redirecting to <a href=\"{}\">{}</a>.</p></body></html>",
+            escape_url(&location),
+            escape_text(&location),
+        ))
+    }
+
+    // Fails if a header set through `header`/`append_header` was invalid
+    pub fn into_hyper(self) -> Result<HyperResponse<Body>, InvalidHeader> {
+        if let Some(e) = self.invalid_header {
+            return Err(e);
         }
-        
-        response.body(self.body).unwrap()
+        let mut response = HyperResponse::new(self.body);
+        *response.status_mut() = self.status;
+        *response.headers_mut() = self.headers;
+        Ok(response)
+    }
+}
+
+#[derive(Debug)]
+pub struct InvalidHeader {
+    // None when the name itself was invalid
+    pub name: Option<HeaderName>,
+    pub source: hyper::http::Error,
+}
+
+impl fmt::Display for InvalidHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "invalid value for response header {}: {}", name, self.source),
+            None => write!(f, "invalid response header name: {}", self.source),
+        }
+    }
+}
+
+impl Error for InvalidHeader {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.source)
     }
 }
 
@@ -75,3 +198,115 @@ impl Default for Response {
         Self::new()
     }
 }
+
+impl IntoResponse for Response {
+    fn into_response(self) -> Result<Response, Box<dyn Error + Send + Sync>> {
+        Ok(self)
+    }
+}
+
+// A value serialized as the JSON body, e.g. a handler returning `Json(post)` or
+// `(StatusCode::CREATED, Json(post))`
+#[derive(Debug, Clone)]
+pub struct Json<T>(pub T);
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Result<Response, Box<dyn Error + Send + Sync>> {
+        Ok(Response::new().json(&self.0)?)
+    }
+}
+
+impl IntoResponse for String {
+    fn into_response(self) -> Result<Response, Box<dyn Error + Send + Sync>> {
+        Ok(Response::new().text(&self))
+    }
+}
+
+impl IntoResponse for &str {
+    fn into_response(self) -> Result<Response, Box<dyn Error + Send + Sync>> {
+        Ok(Response::new().text(self))
+    }
+}
+
+impl<T: IntoResponse> IntoResponse for (StatusCode, T) {
+    fn into_response(self) -> Result<Response, Box<dyn Error + Send + Sync>> {
+        Ok(self.1.into_response()?.status(self.0))
+    }
+}
+
+// Errors are passed on as they are, for middleware and the App's error handling to see
+impl<T, E> IntoResponse for Result<T, E>
+where
+    T: IntoResponse,
+    E: Into<Box<dyn Error + Send + Sync>>,
+{
+    fn into_response(self) -> Result<Response, Box<dyn Error + Send + Sync>> {
+        self.map_err(Into::into)?.into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redirect_constructors_set_status_and_location() {
+        let cases = [
+            (Response::new().redirect("/a"), StatusCode::FOUND),
+            (Response::see_other("/a"), StatusCode::SEE_OTHER),
+            (Response::moved_permanently("/a"), StatusCode::MOVED_PERMANENTLY),
+            (Response::temporary_redirect("/a"), StatusCode::TEMPORARY_REDIRECT),
+            (Response::permanent_redirect("/a"), StatusCode::PERMANENT_REDIRECT),
+            (Response::redirect_with(StatusCode::MULTIPLE_CHOICES, "/a"), StatusCode::MULTIPLE_CHOICES),
+        ];
+        for (response, status) in cases {
+            assert_eq!(response.status, status);
+            assert_eq!(response.headers[LOCATION], "/a");
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "redirect status must be 3xx")]
+    fn redirect_with_rejects_other_statuses() {
+        Response::redirect_with(StatusCode::OK, "/a");
+    }
+
+    #[tokio::test]
+    async fn redirect_page_links_to_the_escaped_location() {
+        let response = Response::see_other("/search?q=\"x\"&tag=<b>").redirect_page();
+        assert_eq!(response.status, StatusCode::SEE_OTHER);
+        assert_eq!(response.headers[CONTENT_TYPE], "text/html");
+        let body = hyper::body::to_bytes(response.body).await.unwrap();
+        let html = String::from_utf8(body.to_vec()).unwrap();
+        assert!(html.contains(r#"href="/search?q=&quot;x&quot;&amp;tag=&lt;b&gt;""#));
+        assert!(html.contains(">/search?q=\"x\"&amp;tag=&lt;b&gt;</a>"));
+
+        let response = Response::see_other("javascript:alert(1)").redirect_page();
+        let body = hyper::body::to_bytes(response.body).await.unwrap();
+        assert!(String::from_utf8(body.to_vec()).unwrap().contains(r#"href="unsafe:javascript:alert(1)""#));
+
+        // Nothing to link to
+        let response = Response::new().text("plain").redirect_page();
+        assert_eq!(response.headers[CONTENT_TYPE], "text/plain");
+    }
+
+    #[test]
+    fn invalid_headers_surface_in_into_hyper() {
+        let response = Response::see_other("/a\nb");
+        assert!(response.headers.get(LOCATION).is_none());
+        let err = response.into_hyper().unwrap_err();
+        assert_eq!(err.name, Some(LOCATION));
+        assert!(Response::see_other("/a").into_hyper().is_ok());
+    }
+
+    #[test]
+    fn repeated_set_cookie_headers_survive_into_hyper() {
+        let response = Response::new()
+            .append_header("Set-Cookie", "a=1; Path=/")
+            .append_header("Set-Cookie", "b=2; HttpOnly")
+            .into_hyper()
+            .unwrap();
+        let cookies: Vec<_> = response.headers().get_all(hyper::header::SET_COOKIE).iter().collect();
+        assert_eq!(cookies, ["a=1; Path=/", "b=2; HttpOnly"]);
+    }
+}
@@ -1,11 +1,14 @@
 use crate::{Request, Response, Handler, error::AppError}; // Updated imports
 use crate::middleware::Middleware;
+use crate::tasks::TaskScheduler;
 use async_trait::async_trait;
 use hyper::Method;
-use regex::Regex;
+use once_cell::sync::OnceCell;
+use regex::{Regex, RegexSet, RegexSetBuilder};
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fmt;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 
 #[derive(Clone)]
 pub struct Route {
@@ -14,6 +17,7 @@ pub struct Route {
     pub regex: Regex,
     pub param_names: Vec<String>,
     pub handler: Arc<dyn Handler>,
+    pub middleware: Vec<Arc<dyn Middleware>>,
 }
 
 // Implement Debug manually for Route
@@ -23,55 +27,26 @@ impl fmt::Debug for Route {
             .field("path", &self.path)
             .field("method", &self.method)
             .field("param_names", &self.param_names)
+            .field("middleware", &self.middleware.len())
             .finish()
     }
 }
 impl Route {
     pub fn new(method: Method, path: &str, handler: Arc<dyn Handler>) -> Self {
-        let (regex, param_names) = Self::path_to_regex(path);
+        let (regex, param_names) = compile_path(path);
         Route {
             path: path.to_string(),
             method,
             regex,
             param_names,
             handler,
+            middleware: Vec::new(),
         }
     }
 
-    fn path_to_regex(path: &str) -> (Regex, Vec<String>) {
-        let mut regex_str = String::new();
-        let mut param_names = Vec::new();
-        let mut chars = path.chars().peekable();
-
-        regex_str.push('^');
-
-        while let Some(ch) = chars.next() {
-            match ch {
-                ':' => {
-                    let mut param_name = String::new();
-                    while let Some(&next_ch) = chars.peek() {
-                        if next_ch.is_alphanumeric() || next_ch == '_' {
-                            param_name.push(chars.next().unwrap());
-                        } else {
-                            break;
-                        }
-                    }
-                    param_names.push(param_name);
-                    regex_str.push_str("([^/]+)");
-                }
-                '*' => {
-                    regex_str.push_str("(.*)");
-                }
-                '.' | '+' | '?' | '^' | '$' | '{' | '}' | '[' | ']' | '|' | '(' | ')' | '\\' => {
-                    regex_str.push('\\');
-                    regex_str.push(ch);
-                }
-                _ => regex_str.push(ch),
-            }
-        }
-
-        regex_str.push('$');
-        (Regex::new(&regex_str).unwrap(), param_names)
+    pub fn with_middleware(mut self, middleware: Vec<Arc<dyn Middleware>>) -> Self {
+        self.middleware = middleware;
+        self
     }
 
     pub fn matches(&self, method: &Method, path: &str) -> Option<HashMap<String, String>> {
@@ -93,24 +68,291 @@ impl Route {
     }
 }
 
+// Compiles a route path into an anchored regex plus its parameter names, in capture order:
+// `:name` matches one segment, `*` anything (including `/`, and not captured), and every other
+// character literally
+pub fn compile_path(path: &str) -> (Regex, Vec<String>) {
+    let mut regex_str = String::new();
+    let mut param_names = Vec::new();
+    let mut chars = path.chars().peekable();
+
+    regex_str.push('^');
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            ':' => {
+                let mut param_name = String::new();
+                while let Some(&next_ch) = chars.peek() {
+                    if next_ch.is_alphanumeric() || next_ch == '_' {
+                        param_name.push(chars.next().unwrap());
+                    } else {
+                        break;
+                    }
+                }
+                param_names.push(param_name);
+                regex_str.push_str("([^/]+)");
+            }
+            '*' => {
+                regex_str.push_str(".*");
+            }
+            '.' | '+' | '?' | '^' | '$' | '{' | '}' | '[' | ']' | '|' | '(' | ')' | '\\' => {
+                regex_str.push('\\');
+                regex_str.push(ch);
+            }
+            _ => regex_str.push(ch),
+        }
+    }
+
+    regex_str.push('$');
+    (Regex::new(&regex_str).unwrap(), param_names)
+}
+
+// Finds the routes matching a path in one pass: a RegexSet of the route regexes per method. Only
+// the winner's own regex needs to run again, for its captures.
+pub(crate) struct RouteTable {
+    // Per method: the set, and each of its patterns' position in the order the table was built
+    by_method: HashMap<Method, (RegexSet, Vec<usize>)>,
+}
+
+impl RouteTable {
+    pub(crate) fn new<'a>(routes: impl IntoIterator<Item = (&'a Method, &'a Regex)>) -> Self {
+        let mut grouped: HashMap<Method, (Vec<&str>, Vec<usize>)> = HashMap::new();
+        for (position, (method, regex)) in routes.into_iter().enumerate() {
+            let (patterns, positions) = grouped.entry(method.clone()).or_default();
+            patterns.push(regex.as_str());
+            positions.push(position);
+        }
+        let by_method = grouped.into_iter()
+            .map(|(method, (patterns, positions))| {
+                // Every pattern already compiled on its own
+                let set = RegexSetBuilder::new(patterns)
+                    .size_limit(64 * (1 << 20))
+                    .build()
+                    .expect("route patterns compile");
+                (method, (set, positions))
+            })
+            .collect();
+        RouteTable { by_method }
+    }
+
+    // Positions of `method`'s routes matching `path`, in the order the table was built
+    pub(crate) fn matches(&self, method: &Method, path: &str) -> Vec<usize> {
+        match self.by_method.get(method) {
+            Some((set, positions)) => set.matches(path).into_iter().map(|i| positions[i]).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    pub(crate) fn methods(&self) -> impl Iterator<Item = &Method> {
+        self.by_method.keys()
+    }
+}
+
+// What a request path ending in `/` (other than `/` itself) matches
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TrailingSlash {
+    // Only routes registered with the slash
+    Strict,
+    // Nothing; the client is sent a 308 to the path without it, which keeps the method and body
+    #[default]
+    RedirectToCanonical,
+    // The same routes as the path without it
+    TreatAsEquivalent,
+}
+
+// The canonical form of a request path: duplicate slashes collapsed, `.` and `..` segments
+// resolved, percent-encoded unreserved characters (letters, digits, `-._~`) decoded and other
+// escapes uppercased, so `/a//b/./%7Eme` becomes `/a/b/~me`. Escapes of reserved characters such
+// as `%2F` stay encoded. A trailing slash is kept. Malformed escapes and `..` above the root are
+// rejected as bad requests.
+pub fn normalize_path(path: &str) -> Result<String, AppError> {
+    if !path.starts_with('/') {
+        // `*` (OPTIONS for the whole server) and the like
+        return Ok(path.to_string());
+    }
+
+    let mut segments: Vec<String> = Vec::new();
+    let mut trailing_slash = false;
+    for raw in path.split('/').skip(1) {
+        let segment = normalize_segment(raw)?;
+        trailing_slash = matches!(segment.as_str(), "" | "." | "..");
+        match segment.as_str() {
+            "" | "." => {}
+            ".." => {
+                if segments.pop().is_none() {
+                    return Err(AppError::BadRequest(format!("Path {} climbs above the root", path)));
+                }
+            }
+            _ => segments.push(segment),
+        }
+    }
+
+    let mut normalized = format!("/{}", segments.join("/"));
+    if trailing_slash && !segments.is_empty() {
+        normalized.push('/');
+    }
+    Ok(normalized)
+}
+
+fn normalize_segment(raw: &str) -> Result<String, AppError> {
+    let bytes = raw.as_bytes();
+    let mut segment = String::with_capacity(raw.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let byte = raw.get(i + 1..i + 3)
+                .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| AppError::BadRequest(format!("Malformed percent-encoding in path segment '{}'", raw)))?;
+            if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+                segment.push(byte as char);
+            } else {
+                segment.push_str(&format!("%{:02X}", byte));
+            }
+            i += 3;
+        } else {
+            let end = raw[i..].find('%').map_or(raw.len(), |offset| i + offset);
+            segment.push_str(&raw[i..end]);
+            i = end;
+        }
+    }
+    Ok(segment)
+}
+
+// Normalizes the request's path in place, so handlers and middleware see the path that was
+// matched. Returns a redirect when the trailing slash policy calls for one.
+pub(crate) fn normalize_request(req: &mut Request, policy: TrailingSlash) -> Result<Option<Response>, AppError> {
+    let mut path = normalize_path(req.uri.path())?;
+    if path.len() > 1 && path.ends_with('/') {
+        match policy {
+            TrailingSlash::Strict => {}
+            TrailingSlash::TreatAsEquivalent => {
+                path.pop();
+            }
+            TrailingSlash::RedirectToCanonical => {
+                path.pop();
+                let location = match req.uri.query() {
+                    Some(query) => format!("{}?{}", path, query),
+                    None => path,
+                };
+                return Ok(Some(Response::permanent_redirect(&location)));
+            }
+        }
+    }
+
+    if path != req.uri.path() {
+        let path_and_query = match req.uri.query() {
+            Some(query) => format!("{}?{}", path, query),
+            None => path,
+        };
+        let mut parts = req.uri.clone().into_parts();
+        parts.path_and_query = Some(path_and_query.parse().map_err(|e| AppError::BadRequest(format!("Invalid path: {}", e)))?);
+        req.uri = hyper::Uri::from_parts(parts).map_err(|e| AppError::BadRequest(format!("Invalid path: {}", e)))?;
+    }
+    Ok(None)
+}
+
+// The paths a normalized request path is matched against: itself and, unless trailing slashes
+// are strict, the same path with one, so routes registered as `/about/` keep matching
+pub(crate) fn match_paths(path: &str, policy: TrailingSlash) -> Vec<Cow<'_, str>> {
+    let mut paths = vec![Cow::Borrowed(path)];
+    if policy != TrailingSlash::Strict && path.starts_with('/') && !path.ends_with('/') {
+        paths.push(Cow::Owned(format!("{}/", path)));
+    }
+    paths
+}
+
 pub struct Router {
     routes: Vec<Route>,
     middleware: Vec<Arc<dyn Middleware>>,
+    scoped_middleware: Vec<(String, Arc<dyn Middleware>)>,
+    // HEAD runs the GET handler without sending its body
+    auto_head: bool,
+    // OPTIONS answers 204 with the path's methods in `Allow`
+    auto_options: bool,
+    trailing_slash: TrailingSlash,
+    // Built on first use, and again after routes are added
+    table: OnceCell<RouteTable>,
+    // Each route's handler wrapped in its middleware, composed on first use and reused
+    chains: RwLock<HashMap<ChainKey, Arc<dyn Handler>>>,
 }
 
+// The matched route (None for the 404 handler) and the scoped middleware applying to the path
+type ChainKey = (Option<usize>, Vec<usize>);
+
 impl Router {
     pub fn new() -> Self {
         Router {
             routes: Vec::new(),
             middleware: Vec::new(),
+            scoped_middleware: Vec::new(),
+            auto_head: true,
+            auto_options: true,
+            trailing_slash: TrailingSlash::default(),
+            table: OnceCell::new(),
+            chains: RwLock::new(HashMap::new()),
+        }
+    }
+
+    fn add_route(&mut self, route: Route) {
+        self.routes.push(route);
+        self.table = OnceCell::new();
+        self.chains = RwLock::new(HashMap::new());
+    }
+
+    fn table(&self) -> &RouteTable {
+        self.table.get_or_init(|| RouteTable::new(self.routes.iter().map(|route| (&route.method, &route.regex))))
+    }
+
+    // The first route registered for `method` that matches the (normalized) `path`, with its
+    // parameters. Unlike a request, this matches `path` only as given, whatever the trailing
+    // slash policy.
+    pub fn match_route(&self, method: &Method, path: &str) -> Option<(&Route, HashMap<String, String>)> {
+        self.find_route(method, &[Cow::Borrowed(path)])
+            .map(|(index, params)| (&self.routes[index], params))
+    }
+
+    // Registration order decides between routes matching any of `paths`
+    fn find_route(&self, method: &Method, paths: &[Cow<str>]) -> Option<(usize, HashMap<String, String>)> {
+        let table = self.table();
+        let first = paths.iter().filter_map(|path| table.matches(method, path).first().copied()).min()?;
+        let route = &self.routes[first];
+        paths.iter().find_map(|path| route.matches(method, path)).map(|params| (first, params))
+    }
+
+    // The composed chain for a route, or for the 404 handler
+    fn chain(&self, route: Option<usize>, scoped: Vec<usize>) -> Arc<dyn Handler> {
+        let key = (route, scoped);
+        if let Some(chain) = self.chains.read().unwrap().get(&key) {
+            return chain.clone();
         }
+        let chain = match route {
+            Some(index) => {
+                let route = &self.routes[index];
+                self.compose(route.handler.clone(), &key.1, &route.middleware)
+            }
+            None => self.compose(Arc::new(NotFoundHandler), &key.1, &[]),
+        };
+        self.chains.write().unwrap().entry(key).or_insert(chain).clone()
+    }
+
+    // Global middleware, then scoped (registration order), then the route's, around `handler`
+    fn compose(&self, handler: Arc<dyn Handler>, scoped: &[usize], route_middleware: &[Arc<dyn Middleware>]) -> Arc<dyn Handler> {
+        let chain: Vec<&Arc<dyn Middleware>> = self.middleware.iter()
+            .chain(scoped.iter().map(|&i| &self.scoped_middleware[i].1))
+            .chain(route_middleware.iter())
+            .collect();
+        chain.into_iter().rev().fold(handler, |next, middleware| {
+            let middleware = middleware.clone();
+            Arc::new(MiddlewareHandler { middleware, next })
+        })
     }
 
     pub fn get<H>(mut self, path: &str, handler: H) -> Self
     where
         H: Handler + 'static,
     {
-        self.routes.push(Route::new(Method::GET, path, Arc::new(handler)));
+        self.add_route(Route::new(Method::GET, path, Arc::new(handler)));
         self
     }
 
@@ -118,7 +360,7 @@ impl Router {
     where
         H: Handler + 'static,
     {
-        self.routes.push(Route::new(Method::POST, path, Arc::new(handler)));
+        self.add_route(Route::new(Method::POST, path, Arc::new(handler)));
         self
     }
 
@@ -126,7 +368,7 @@ impl Router {
     where
         H: Handler + 'static,
     {
-        self.routes.push(Route::new(Method::PUT, path, Arc::new(handler)));
+        self.add_route(Route::new(Method::PUT, path, Arc::new(handler)));
         self
     }
 
@@ -134,44 +376,311 @@ impl Router {
     where
         H: Handler + 'static,
     {
-        self.routes.push(Route::new(Method::DELETE, path, Arc::new(handler)));
+        self.add_route(Route::new(Method::DELETE, path, Arc::new(handler)));
+        self
+    }
+
+    // A WebSocket endpoint: a GET route that upgrades the connection and hands it to `handler`
+    // (see `ws::WebSocketHandler`). Plain requests to it get 426 Upgrade Required.
+    #[cfg(feature = "ws")]
+    pub fn ws<H>(self, path: &str, handler: H) -> Self
+    where
+        H: crate::ws::WebSocketHandler,
+    {
+        self.get(path, crate::ws::Upgrade::new(handler))
+    }
+
+    // Replaces the automatic HEAD handling for this path
+    pub fn head<H>(mut self, path: &str, handler: H) -> Self
+    where
+        H: Handler + 'static,
+    {
+        self.add_route(Route::new(Method::HEAD, path, Arc::new(handler)));
+        self
+    }
+
+    // Replaces the automatic OPTIONS handling for this path
+    pub fn options<H>(mut self, path: &str, handler: H) -> Self
+    where
+        H: Handler + 'static,
+    {
+        self.add_route(Route::new(Method::OPTIONS, path, Arc::new(handler)));
+        self
+    }
+
+    // On by default; with it off, HEAD only matches explicit `head` routes
+    pub fn auto_head(mut self, enabled: bool) -> Self {
+        self.auto_head = enabled;
+        self
+    }
+
+    // On by default; with it off, OPTIONS only matches explicit `options` routes (or middleware
+    // such as Cors)
+    pub fn auto_options(mut self, enabled: bool) -> Self {
+        self.auto_options = enabled;
+        self
+    }
+
+    // How paths ending in `/` match; by default `/about/` is redirected to `/about`. Paths are
+    // always normalized first (see `normalize_path`).
+    pub fn trailing_slash(mut self, policy: TrailingSlash) -> Self {
+        self.trailing_slash = policy;
         self
     }
 
+    // Starts a route with its own middleware, e.g.
+    // `router.route("/admin").middleware(AuthGuard::new()).get(handler)`
+    pub fn route(self, path: &str) -> RouteBuilder {
+        RouteBuilder {
+            router: self,
+            path: path.to_string(),
+            middleware: Vec::new(),
+        }
+    }
+
+    // Adds the background tasks of every middleware in the router (global, scoped and per
+    // route), once per middleware instance
+    pub(crate) fn middleware_tasks(&self, mut scheduler: TaskScheduler) -> TaskScheduler {
+        let mut seen: Vec<*const ()> = Vec::new();
+        let all = self.middleware.iter()
+            .chain(self.scoped_middleware.iter().map(|(_, m)| m))
+            .chain(self.routes.iter().flat_map(|route| route.middleware.iter()));
+        for middleware in all {
+            let ptr = Arc::as_ptr(middleware) as *const ();
+            if !seen.contains(&ptr) {
+                seen.push(ptr);
+                scheduler = middleware.tasks(scheduler);
+            }
+        }
+        scheduler
+    }
+
     pub fn use_middleware<M>(mut self, middleware: M) -> Self
     where
         M: Middleware + 'static,
     {
         self.middleware.push(Arc::new(middleware));
+        self.chains = RwLock::new(HashMap::new());
+        self
+    }
+
+    // Mounts middleware for every request under `prefix` (segment-aware:
+    // "/api" covers "/api" and "/api/posts" but not "/apifoo").
+    pub fn use_middleware_at<M>(mut self, prefix: &str, middleware: M) -> Self
+    where
+        M: Middleware + 'static,
+    {
+        let prefix = prefix.trim_end_matches('/');
+        self.scoped_middleware.push((prefix.to_string(), Arc::new(middleware)));
+        self.chains = RwLock::new(HashMap::new());
         self
     }
 
+    pub fn has_route(&self, method: &Method, path: &str) -> bool {
+        let Ok(mut path) = normalize_path(path) else {
+            return false;
+        };
+        if self.trailing_slash != TrailingSlash::Strict && path.len() > 1 && path.ends_with('/') {
+            path.pop();
+        }
+        let path = path.as_str();
+        let paths = match_paths(path, self.trailing_slash);
+        self.find_route(method, &paths).is_some()
+            || (self.auto_head && *method == Method::HEAD && self.has_route(&Method::GET, path))
+            || (self.auto_options && *method == Method::OPTIONS && !self.allowed_methods(path).is_empty())
+    }
+
+    // Methods with a route for `path`, in registration order, plus the automatic HEAD and OPTIONS
+    pub fn allowed_methods(&self, path: &str) -> Vec<Method> {
+        let paths = match_paths(path, self.trailing_slash);
+        let table = self.table();
+        let mut first_routes: Vec<(usize, &Method)> = table.methods()
+            .filter_map(|method| {
+                let first = paths.iter().filter_map(|path| table.matches(method, path).first().copied()).min()?;
+                Some((first, method))
+            })
+            .collect();
+        first_routes.sort_by_key(|(first, _)| *first);
+        let mut methods: Vec<Method> = first_routes.into_iter().map(|(_, method)| method.clone()).collect();
+        if methods.is_empty() {
+            return methods;
+        }
+        if self.auto_head && methods.contains(&Method::GET) && !methods.contains(&Method::HEAD) {
+            methods.push(Method::HEAD);
+        }
+        if self.auto_options && !methods.contains(&Method::OPTIONS) {
+            methods.push(Method::OPTIONS);
+        }
+        methods
+    }
+
     pub async fn handle_request(&self, mut req: Request) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
-        // Find matching route
-        for route in &self.routes {
-            if let Some(params) = route.matches(&req.method, req.uri.path()) {
+        if let Some(redirect) = normalize_request(&mut req, self.trailing_slash)? {
+            return Ok(redirect);
+        }
+        let path = req.uri.path().to_string();
+
+        // Find matching route; unmatched requests still pass through global and
+        // scoped middleware (so e.g. Cors can answer preflight requests) before the 404.
+        let paths = match_paths(&path, self.trailing_slash);
+        let find = |method: &Method| self.find_route(method, &paths);
+        let mut matched = find(&req.method);
+        // HEAD falls back to the GET route, with the body dropped below
+        let head_from_get = matched.is_none() && self.auto_head && req.method == Method::HEAD;
+        if head_from_get {
+            matched = find(&Method::GET);
+        }
+
+        let scoped: Vec<usize> = self.scoped_middleware.iter().enumerate()
+            .filter(|(_, (prefix, _))| path_in_scope(prefix, &path))
+            .map(|(i, _)| i)
+            .collect();
+        let final_handler = match matched {
+            Some((index, params)) => {
                 req.params = params;
-                
-                // Apply middleware chain
-                let handler = route.handler.clone();
-                let final_handler = self.middleware.iter().rev().fold(handler, |next, middleware| {
-                    let middleware = middleware.clone();
-                    Arc::new(MiddlewareHandler { middleware, next })
-                });
-                
-                return final_handler.handle(req).await;
+                req.route = Some(self.routes[index].path.clone());
+                self.chain(Some(index), scoped)
             }
+            // Allow depends on the path, so this chain isn't kept
+            None if self.auto_options && req.method == Method::OPTIONS && !self.allowed_methods(&path).is_empty() => {
+                self.compose(Arc::new(OptionsHandler { allow: self.allowed_methods(&path) }), &scoped, &[])
+            }
+            None => self.chain(None, scoped),
+        };
+
+        let result = final_handler.handle(req).await;
+        match result {
+            Ok(response) if head_from_get => Ok(without_body(response).await?),
+            result => result,
         }
+    }
+}
 
-        // No route found, return 404 error
+// Keeps the headers a GET would send, including its Content-Length
+async fn without_body(mut response: Response) -> Result<Response, hyper::Error> {
+    use hyper::body::HttpBody;
+
+    if !response.headers.contains_key(hyper::header::CONTENT_LENGTH) {
+        // Streamed bodies have to be read to be measured
+        let length = match response.body.size_hint().exact() {
+            Some(length) => length,
+            None => hyper::body::to_bytes(std::mem::take(&mut response.body)).await?.len() as u64,
+        };
+        response.headers.insert(hyper::header::CONTENT_LENGTH, length.into());
+    }
+    response.body = hyper::Body::empty();
+    Ok(response)
+}
+
+// Answers OPTIONS for a path that has routes but no OPTIONS route of its own
+struct OptionsHandler {
+    allow: Vec<Method>,
+}
+
+#[async_trait]
+impl Handler for OptionsHandler {
+    async fn handle(&self, _req: Request) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+        let allow: Vec<&str> = self.allow.iter().map(Method::as_str).collect();
+        Ok(Response::new()
+            .status(hyper::StatusCode::NO_CONTENT)
+            .header("Allow", allow.join(", ")))
+    }
+}
+
+fn path_in_scope(prefix: &str, path: &str) -> bool {
+    if prefix.is_empty() {
+        return true;
+    }
+    match path.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+// Terminal handler for requests that matched no route
+struct NotFoundHandler;
+
+#[async_trait]
+impl Handler for NotFoundHandler {
+    async fn handle(&self, req: Request) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
         Err(Box::new(AppError::NotFound(format!("Route not found: {}", req.uri.path()))))
     }
 }
 
+impl Default for Router {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct RouteBuilder {
+    router: Router,
+    path: String,
+    middleware: Vec<Arc<dyn Middleware>>,
+}
+
+impl RouteBuilder {
+    pub fn middleware<M>(mut self, middleware: M) -> Self
+    where
+        M: Middleware + 'static,
+    {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    pub fn get<H>(self, handler: H) -> Router
+    where
+        H: Handler + 'static,
+    {
+        self.finish(Method::GET, Arc::new(handler))
+    }
+
+    pub fn post<H>(self, handler: H) -> Router
+    where
+        H: Handler + 'static,
+    {
+        self.finish(Method::POST, Arc::new(handler))
+    }
+
+    pub fn put<H>(self, handler: H) -> Router
+    where
+        H: Handler + 'static,
+    {
+        self.finish(Method::PUT, Arc::new(handler))
+    }
+
+    pub fn delete<H>(self, handler: H) -> Router
+    where
+        H: Handler + 'static,
+    {
+        self.finish(Method::DELETE, Arc::new(handler))
+    }
+
+    pub fn head<H>(self, handler: H) -> Router
+    where
+        H: Handler + 'static,
+    {
+        self.finish(Method::HEAD, Arc::new(handler))
+    }
+
+    pub fn options<H>(self, handler: H) -> Router
+    where
+        H: Handler + 'static,
+    {
+        self.finish(Method::OPTIONS, Arc::new(handler))
+    }
+
+    fn finish(self, method: Method, handler: Arc<dyn Handler>) -> Router {
+        let mut router = self.router;
+        router.add_route(Route::new(method, &self.path, handler).with_middleware(self.middleware));
+        router
+    }
+}
+
 // Helper struct to chain middleware
-struct MiddlewareHandler {
-    middleware: Arc<dyn Middleware>,
-    next: Arc<dyn Handler>,
+pub(crate) struct MiddlewareHandler {
+    pub(crate) middleware: Arc<dyn Middleware>,
+    pub(crate) next: Arc<dyn Handler>,
 }
 
 #[async_trait]
@@ -179,4 +688,330 @@ impl Handler for MiddlewareHandler {
     async fn handle(&self, req: Request) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
         self.middleware.handle(req, self.next.clone()).await
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    type Trace = Arc<Mutex<Vec<&'static str>>>;
+
+    // Records its name on the way in
+    struct Tag(&'static str, Trace);
+
+    #[async_trait]
+    impl Middleware for Tag {
+        async fn handle(&self, req: Request, next: Arc<dyn Handler>) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+            self.1.lock().unwrap().push(self.0);
+            next.handle(req).await
+        }
+    }
+
+    // Answers with the matched route and its parameters, e.g. `/users/:id id=7`
+    async fn echo(req: Request) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+        let mut params: Vec<String> = req.params.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+        params.sort();
+        let route = req.route.clone().unwrap_or_default();
+        Ok(Response::new().text(format!("{} {}", route, params.join(" ")).trim_end()))
+    }
+
+    async fn send(router: &Router, method: &str, uri: &str) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+        let req = Request::from_hyper(hyper::Request::builder().method(method).uri(uri).body(hyper::Body::empty()).unwrap())
+            .await
+            .unwrap();
+        router.handle_request(req).await
+    }
+
+    async fn body_text(response: Response) -> String {
+        let bytes = hyper::body::to_bytes(response.body).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn route_middleware_runs_after_global_and_only_for_its_route() {
+        let trace = Trace::default();
+        let router = Router::new()
+            .use_middleware(Tag("global", trace.clone()))
+            .route("/admin")
+            .middleware(Tag("admin", trace.clone()))
+            .middleware(Tag("audit", trace.clone()))
+            .get(echo)
+            .get("/public", echo);
+
+        send(&router, "GET", "/admin").await.unwrap();
+        assert_eq!(*trace.lock().unwrap(), ["global", "admin", "audit"]);
+
+        trace.lock().unwrap().clear();
+        send(&router, "GET", "/public").await.unwrap();
+        assert_eq!(*trace.lock().unwrap(), ["global"]);
+    }
+
+    #[tokio::test]
+    async fn route_builder_registers_the_handler_under_its_path() {
+        let router = Router::new().route("/users/:id").get(echo);
+        let response = send(&router, "GET", "/users/7").await.unwrap();
+        assert_eq!(body_text(response).await, "/users/:id id=7");
+    }
+
+    #[tokio::test]
+    async fn scoped_middleware_covers_whole_segments_under_its_prefix() {
+        let trace = Trace::default();
+        let router = Router::new()
+            .use_middleware_at("/api/", Tag("api", trace.clone()))
+            .get("/api", echo)
+            .get("/api/posts", echo)
+            .get("/apifoo", echo);
+
+        for (path, expected) in [("/api", vec!["api"]), ("/api/posts", vec!["api"]), ("/apifoo", vec![])] {
+            trace.lock().unwrap().clear();
+            send(&router, "GET", path).await.unwrap();
+            assert_eq!(*trace.lock().unwrap(), expected, "{}", path);
+        }
+    }
+
+    #[tokio::test]
+    async fn scoped_middleware_also_sees_unmatched_requests() {
+        let trace = Trace::default();
+        let router = Router::new()
+            .use_middleware(Tag("global", trace.clone()))
+            .use_middleware_at("/api", Tag("api", trace.clone()))
+            .get("/api/posts", echo);
+
+        let error = send(&router, "GET", "/api/missing").await.unwrap_err();
+        assert!(matches!(error.downcast_ref::<AppError>(), Some(AppError::NotFound(_))));
+        assert_eq!(*trace.lock().unwrap(), ["global", "api"]);
+    }
+
+    #[test]
+    fn compile_path_captures_params_and_escapes_literals() {
+        let (regex, params) = compile_path("/users/:user_id/files/:name.json");
+        assert_eq!(params, ["user_id", "name"]);
+        let captures = regex.captures("/users/7/files/report.json").unwrap();
+        assert_eq!((&captures[1], &captures[2]), ("7", "report"));
+        // `.` is literal, and params don't cross segments
+        assert!(!regex.is_match("/users/7/files/reportxjson"));
+        assert!(!regex.is_match("/users/7/8/files/report.json"));
+        // Anchored at both ends
+        assert!(!regex.is_match("/v1/users/7/files/report.json"));
+        assert!(!regex.is_match("/users/7/files/report.json/raw"));
+
+        let (regex, params) = compile_path("/assets/*");
+        assert!(params.is_empty());
+        assert!(regex.is_match("/assets/css/site.css"));
+        assert!(!regex.is_match("/asset"));
+
+        let (regex, _) = compile_path("/search(beta)+");
+        assert!(regex.is_match("/search(beta)+"));
+        assert!(!regex.is_match("/searchbetabeta"));
+    }
+
+    fn is_not_found(result: Result<Response, Box<dyn std::error::Error + Send + Sync>>) -> bool {
+        matches!(result.map(|_| ()).unwrap_err().downcast_ref::<AppError>(), Some(AppError::NotFound(_)))
+    }
+
+    #[tokio::test]
+    async fn head_falls_back_to_get_without_the_body() {
+        let router = Router::new()
+            .get("/posts", echo)
+            .get("/feed", echo)
+            .head("/feed", |_req: Request| async {
+                Ok::<_, Box<dyn std::error::Error + Send + Sync>>(Response::new().header("X-Head", "explicit"))
+            });
+
+        let response = send(&router, "HEAD", "/posts").await.unwrap();
+        assert_eq!(response.status, hyper::StatusCode::OK);
+        assert_eq!(response.headers["content-length"], "6");
+        assert_eq!(body_text(response).await, "");
+
+        let response = send(&router, "HEAD", "/feed").await.unwrap();
+        assert_eq!(response.headers["x-head"], "explicit");
+
+        let router = Router::new().auto_head(false).get("/posts", echo);
+        assert!(is_not_found(send(&router, "HEAD", "/posts").await));
+        assert!(!router.has_route(&Method::HEAD, "/posts"));
+    }
+
+    #[tokio::test]
+    async fn options_lists_the_allowed_methods() {
+        let router = Router::new()
+            .get("/posts/:id", echo)
+            .delete("/posts/:id", echo)
+            .get("/about", echo)
+            .options("/about", |_req: Request| async {
+                Ok::<_, Box<dyn std::error::Error + Send + Sync>>(Response::new().text("custom"))
+            });
+
+        let response = send(&router, "OPTIONS", "/posts/1").await.unwrap();
+        assert_eq!(response.status, hyper::StatusCode::NO_CONTENT);
+        assert_eq!(response.headers["allow"], "GET, DELETE, HEAD, OPTIONS");
+        assert_eq!(router.allowed_methods("/posts/1"), [Method::GET, Method::DELETE, Method::HEAD, Method::OPTIONS]);
+        assert!(router.has_route(&Method::OPTIONS, "/posts/1"));
+
+        assert_eq!(body_text(send(&router, "OPTIONS", "/about").await.unwrap()).await, "custom");
+        assert!(is_not_found(send(&router, "OPTIONS", "/missing").await));
+        assert!(router.allowed_methods("/missing").is_empty());
+
+        let router = Router::new().auto_options(false).get("/posts/:id", echo);
+        assert!(is_not_found(send(&router, "OPTIONS", "/posts/1").await));
+        assert_eq!(router.allowed_methods("/posts/1"), [Method::GET, Method::HEAD]);
+    }
+
+    #[tokio::test]
+    async fn automatic_options_passes_through_scoped_middleware() {
+        let trace = Trace::default();
+        let router = Router::new().use_middleware_at("/api", Tag("cors", trace.clone())).post("/api/posts", echo);
+
+        let response = send(&router, "OPTIONS", "/api/posts").await.unwrap();
+        assert_eq!(response.headers["allow"], "POST, OPTIONS");
+        assert_eq!(*trace.lock().unwrap(), ["cors"]);
+    }
+
+    #[test]
+    fn normalize_path_canonicalizes_paths() {
+        assert_eq!(normalize_path("/a//b/./%7Eme").unwrap(), "/a/b/~me");
+        assert_eq!(normalize_path("/a/b/../c").unwrap(), "/a/c");
+        assert_eq!(normalize_path("/docs/").unwrap(), "/docs/");
+        assert_eq!(normalize_path("/docs/..").unwrap(), "/");
+        assert_eq!(normalize_path("/docs/x/..").unwrap(), "/docs/");
+        assert_eq!(normalize_path("/files/a%2fb%3f").unwrap(), "/files/a%2Fb%3F");
+        assert_eq!(normalize_path("/").unwrap(), "/");
+        assert_eq!(normalize_path("*").unwrap(), "*");
+
+        assert!(matches!(normalize_path("/../etc/passwd"), Err(AppError::BadRequest(_))));
+        assert!(matches!(normalize_path("/bad%zz"), Err(AppError::BadRequest(_))));
+        assert!(matches!(normalize_path("/bad%4"), Err(AppError::BadRequest(_))));
+    }
+
+    #[tokio::test]
+    async fn handlers_see_the_normalized_path() {
+        let router = Router::new().get("/users/:name", echo);
+        let response = send(&router, "GET", "/users//%61da").await.unwrap();
+        assert_eq!(body_text(response).await, "/users/:name name=ada");
+
+        let err = send(&router, "GET", "/users/%zz").await.map(|_| ()).unwrap_err();
+        assert!(matches!(err.downcast_ref::<AppError>(), Some(AppError::BadRequest(_))));
+    }
+
+    #[tokio::test]
+    async fn trailing_slash_policies() {
+        let routes = |policy| Router::new().trailing_slash(policy).get("/about", echo).get("/docs/", echo);
+
+        // Redirected to the canonical path, query kept
+        let router = routes(TrailingSlash::RedirectToCanonical);
+        let response = send(&router, "GET", "/about/?lang=de").await.unwrap();
+        assert_eq!(response.status, hyper::StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(response.headers["location"], "/about?lang=de");
+        // A route registered with the slash is still reachable without it
+        assert_eq!(body_text(send(&router, "GET", "/docs").await.unwrap()).await, "/docs/");
+        assert!(router.has_route(&Method::GET, "/about/"));
+
+        let router = routes(TrailingSlash::TreatAsEquivalent);
+        assert_eq!(body_text(send(&router, "GET", "/about/").await.unwrap()).await, "/about");
+        assert_eq!(body_text(send(&router, "GET", "/docs").await.unwrap()).await, "/docs/");
+
+        let router = routes(TrailingSlash::Strict);
+        assert!(is_not_found(send(&router, "GET", "/about/").await));
+        assert!(is_not_found(send(&router, "GET", "/docs").await));
+        assert_eq!(body_text(send(&router, "GET", "/docs/").await.unwrap()).await, "/docs/");
+        assert!(!router.has_route(&Method::GET, "/about/"));
+    }
+
+    // The route tables of the examples, in registration order
+    const EXAMPLE_ROUTES: &[(&str, &str)] = &[
+        // auth_app
+        ("POST", "/api/login"), ("POST", "/api/refresh"), ("POST", "/api/logout"),
+        // blog_app, enhanced_blog_app
+        ("GET", "/"), ("GET", "/create"), ("GET", "/post/:id"), ("GET", "/about"),
+        ("GET", "/api/posts"), ("POST", "/api/posts"), ("GET", "/assets/*"),
+        // product_catalog_app
+        ("GET", "/products/new"), ("GET", "/products/:id"), ("GET", "/products/:id/edit"),
+        ("GET", "/api/products"), ("GET", "/api/products/:id"), ("POST", "/api/products"),
+        ("POST", "/api/products/:id/update"), ("POST", "/api/products/:id/delete"),
+        // project_dashboard_app
+        ("GET", "/projects/new"), ("GET", "/projects/:id"), ("GET", "/api/projects"), ("POST", "/api/projects"),
+        ("POST", "/api/projects/:id/tasks"), ("POST", "/api/projects/:project_id/tasks/:task_id/toggle"),
+        ("POST", "/api/projects/:project_id/tasks/:task_id/delete"),
+        // todo_app
+        ("POST", "/api/todos"), ("POST", "/api/todos/:id/toggle"), ("DELETE", "/api/todos/:id"),
+    ];
+
+    // Each route's path with its parameters filled in, plus near misses
+    fn probe_paths(routes: &[(&str, &str)]) -> Vec<String> {
+        let mut probes = vec!["/nope".to_string(), "/api".to_string(), "/products/".to_string()];
+        for (_, path) in routes {
+            let filled: Vec<&str> = path.split('/')
+                .map(|segment| match segment {
+                    "*" => "css/site.css",
+                    s if s.starts_with(':') => "42",
+                    s => s,
+                })
+                .collect();
+            let filled = filled.join("/");
+            probes.push(format!("{}/extra", filled.trim_end_matches('/')));
+            probes.push(format!("{}x", filled));
+            probes.push(filled);
+        }
+        probes
+    }
+
+    #[test]
+    fn route_table_matches_like_each_route_regex() {
+        let mut router = Router::new();
+        for (method, path) in EXAMPLE_ROUTES {
+            router.add_route(Route::new(method.parse().unwrap(), path, Arc::new(echo)));
+        }
+
+        let methods = [Method::GET, Method::POST, Method::DELETE, Method::PUT];
+        for path in probe_paths(EXAMPLE_ROUTES) {
+            for method in &methods {
+                let expected = router.routes.iter().find_map(|route| route.matches(method, &path).map(|params| (&route.path, params)));
+                let actual = router.match_route(method, &path).map(|(route, params)| (&route.path, params));
+                assert_eq!(actual, expected, "{} {}", method, path);
+            }
+        }
+        assert_eq!(router.match_route(&Method::GET, "/products/new").unwrap().0.path, "/products/new");
+        assert_eq!(router.match_route(&Method::POST, "/api/projects/1/tasks/2/toggle").unwrap().1["task_id"], "2");
+    }
+
+    #[tokio::test]
+    async fn chains_are_composed_once_and_reused() {
+        let trace = Trace::default();
+        let router = Router::new()
+            .use_middleware(Tag("global", trace.clone()))
+            .use_middleware_at("/users", Tag("scoped", trace.clone()))
+            .route("/users/:id")
+            .middleware(Tag("route", trace.clone()))
+            .get(echo);
+
+        // Parameters are still per request
+        assert_eq!(body_text(send(&router, "GET", "/users/1").await.unwrap()).await, "/users/:id id=1");
+        assert_eq!(body_text(send(&router, "GET", "/users/2").await.unwrap()).await, "/users/:id id=2");
+        assert_eq!(*trace.lock().unwrap(), ["global", "scoped", "route", "global", "scoped", "route"]);
+        assert_eq!(router.chains.read().unwrap().len(), 1);
+        assert!(Arc::ptr_eq(&router.chain(Some(0), vec![0]), &router.chain(Some(0), vec![0])));
+
+        // One chain for the 404s of a scope; automatic OPTIONS chains aren't kept
+        send(&router, "GET", "/users").await.unwrap_err();
+        send(&router, "GET", "/users/1/missing").await.unwrap_err();
+        send(&router, "OPTIONS", "/users/1").await.unwrap();
+        assert_eq!(router.chains.read().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn changes_to_the_router_recompose_the_chains() {
+        let trace = Trace::default();
+        let router = Router::new().get("/posts", echo);
+        send(&router, "GET", "/posts").await.unwrap();
+        assert_eq!(router.chains.read().unwrap().len(), 1);
+
+        let router = router.use_middleware(Tag("late", trace.clone()));
+        assert!(router.chains.read().unwrap().is_empty());
+        send(&router, "GET", "/posts").await.unwrap();
+        assert_eq!(*trace.lock().unwrap(), ["late"]);
+
+        let router = router.get("/drafts", echo);
+        assert!(router.chains.read().unwrap().is_empty());
+        send(&router, "GET", "/drafts").await.unwrap();
+        assert_eq!(*trace.lock().unwrap(), ["late", "late"]);
+    }
+}
@@ -1,42 +1,94 @@
 use crate::{App, Request};
 use crate::handler::Handler;
+use crate::middleware::Middleware;
+use crate::router::MiddlewareHandler;
+use crate::tasks::TaskScheduler;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use hyper::server::conn::AddrStream;
 use hyper::service::{make_service_fn, service_fn};
 use hyper::Server as HyperServer;
 use std::convert::Infallible;
+use std::future::Future;
 use std::net::SocketAddr;
 use std::sync::Arc;
 
 pub struct Server {
-    app: Arc<App>,
+    // The App, possibly wrapped by `wrap`
+    app: Arc<dyn Handler>,
     addr: SocketAddr,
+    tasks: TaskScheduler,
+    shutdown_signal: Option<BoxFuture<'static, ()>>,
 }
 
 impl Server {
-    pub fn new(app: App, addr: SocketAddr) -> Self {
+    pub fn new(mut app: App, addr: SocketAddr) -> Self {
+        let tasks = app.take_tasks();
         Server {
             app: Arc::new(app),
             addr,
+            tasks,
+            shutdown_signal: None,
         }
     }
 
+    // Runs `middleware` around the whole App, static files included; the last one wrapped runs first
+    pub fn wrap<M>(mut self, middleware: M) -> Self
+    where
+        M: Middleware + 'static,
+    {
+        self.tasks = middleware.tasks(std::mem::take(&mut self.tasks));
+        self.app = Arc::new(MiddlewareHandler {
+            middleware: Arc::new(middleware),
+            next: self.app,
+        });
+        self
+    }
+
+    // Stops the server gracefully once `signal` completes (default: Ctrl-C)
+    pub fn with_shutdown_signal<F>(mut self, signal: F) -> Self
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.shutdown_signal = Some(signal.boxed());
+        self
+    }
+
     pub async fn run(self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         let app = self.app.clone();
         
-        let make_svc = make_service_fn(move |_conn| {
+        let make_svc = make_service_fn(move |conn: &AddrStream| {
             let app = app.clone();
+            let remote_addr = conn.remote_addr();
             async move {
                 Ok::<_, Infallible>(service_fn(move |req| {
                     let app = app.clone();
                     async move {
-                        let request = Request::from_hyper(req).await?;
-                        let response = app.handle(request).await?;
-                        Ok::<_, Box<dyn std::error::Error + Send + Sync>>(response.into_hyper())
+                        let mut request = into_request(req).await?;
+                        request.remote_addr = Some(remote_addr);
+                        let response = app.handle(request).await?.into_hyper().unwrap_or_else(|e| {
+                            log::error!("{}", e);
+                            let mut response = hyper::Response::new(hyper::Body::from("Internal Server Error"));
+                            *response.status_mut() = hyper::StatusCode::INTERNAL_SERVER_ERROR;
+                            response
+                        });
+                        Ok::<_, Box<dyn std::error::Error + Send + Sync>>(response)
                     }
                 }))
             }
         });
 
-        let server = HyperServer::bind(&self.addr).serve(make_svc);
+        let shutdown_signal = self.shutdown_signal.unwrap_or_else(|| {
+            async {
+                let _ = tokio::signal::ctrl_c().await;
+            }
+            .boxed()
+        });
+        let server = HyperServer::bind(&self.addr)
+            .serve(make_svc)
+            .with_graceful_shutdown(shutdown_signal);
+
+        let tasks = self.tasks.start();
         
         println!("Server running on http://{}", self.addr);
         
@@ -44,6 +96,24 @@ impl Server {
             eprintln!("Server error: {}", e);
         }
 
+        // In-flight requests are done; stop the background tasks too
+        tasks.shutdown().await;
+
         Ok(())
     }
 }
+
+#[cfg(not(feature = "ws"))]
+async fn into_request(req: hyper::Request<hyper::Body>) -> Result<Request, Box<dyn std::error::Error + Send + Sync>> {
+    Request::from_hyper(req).await
+}
+
+// Keeps hold of the connection of a WebSocket upgrade request; a `Router::ws` route completes it
+#[cfg(feature = "ws")]
+async fn into_request(mut req: hyper::Request<hyper::Body>) -> Result<Request, Box<dyn std::error::Error + Send + Sync>> {
+    let upgrade = crate::ws::is_upgrade_request(req.method(), req.headers())
+        .then(|| hyper::upgrade::on(&mut req));
+    let mut request = Request::from_hyper(req).await?;
+    request.upgrade = upgrade;
+    Ok(request)
+}
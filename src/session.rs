@@ -1,11 +1,12 @@
 use crate::{Request, Response, middleware::Middleware}; // Corrected import path for Middleware
+use crate::tasks::TaskScheduler;
 use async_trait::async_trait;
 use cookie::{Cookie, CookieJar};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Session {
     pub id: String,
     pub data: HashMap<String, serde_json::Value>,
@@ -40,8 +41,19 @@ impl Session {
     pub fn is_expired(&self) -> bool {
         chrono::Utc::now() > self.expires_at
     }
+
+    // Issues a fresh ID, keeping the data and expiry, and returns the old one. Call it on
+    // privilege changes (login, role changes) so a planted session ID stops being useful.
+    pub fn regenerate_id(&mut self) -> String {
+        std::mem::replace(&mut self.id, uuid::Uuid::new_v4().to_string())
+    }
 }
 
+// Shared between SessionMiddleware and the request it passes on. The request writes its
+// `session` back when dropped (and on `regenerate_session`), so the middleware can persist
+// whatever the handler did to it.
+pub(crate) type SessionCell = Arc<std::sync::Mutex<Option<Session>>>;
+
 #[async_trait]
 pub trait SessionStore: Send + Sync {
     async fn get(&self, id: &str) -> Result<Option<Session>, Box<dyn std::error::Error + Send + Sync>>;
@@ -62,6 +74,12 @@ impl MemorySessionStore {
     }
 }
 
+impl Default for MemorySessionStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[async_trait]
 impl SessionStore for MemorySessionStore {
     async fn get(&self, id: &str) -> Result<Option<Session>, Box<dyn std::error::Error + Send + Sync>> {
@@ -89,10 +107,83 @@ impl SessionStore for MemorySessionStore {
     }
 }
 
+// Sessions as JSON under `{prefix}{id}`, expiring with the session via the Redis TTL, so they
+// survive restarts and are shared between instances
+#[cfg(feature = "cache")]
+pub struct RedisSessionStore {
+    client: redis::Client,
+    key_prefix: String,
+}
+
+#[cfg(feature = "cache")]
+impl RedisSessionStore {
+    pub fn new(redis_url: &str) -> Result<Self, redis::RedisError> {
+        Ok(RedisSessionStore {
+            client: redis::Client::open(redis_url)?,
+            key_prefix: "rustnext:session:".to_string(),
+        })
+    }
+
+    pub fn key_prefix(mut self, prefix: &str) -> Self {
+        self.key_prefix = prefix.to_string();
+        self
+    }
+
+    fn key(&self, id: &str) -> String {
+        format!("{}{}", self.key_prefix, id)
+    }
+}
+
+#[cfg(feature = "cache")]
+#[async_trait]
+impl SessionStore for RedisSessionStore {
+    async fn get(&self, id: &str) -> Result<Option<Session>, Box<dyn std::error::Error + Send + Sync>> {
+        use redis::AsyncCommands;
+
+        let mut conn = self.client.get_async_connection().await?;
+        let value: Option<String> = conn.get(self.key(id)).await?;
+        match value {
+            Some(json) => Ok(Some(serde_json::from_str(&json)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn set(&self, session: Session) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        use redis::AsyncCommands;
+
+        let mut conn = self.client.get_async_connection().await?;
+        let key = self.key(&session.id);
+        let ttl = (session.expires_at - chrono::Utc::now()).num_seconds();
+        if ttl <= 0 {
+            // Already expired: make sure no stale copy lingers
+            conn.del::<_, ()>(key).await?;
+            return Ok(());
+        }
+        let json = serde_json::to_string(&session)?;
+        conn.set_ex::<_, _, ()>(key, json, ttl.try_into()?).await?;
+        Ok(())
+    }
+
+    async fn delete(&self, id: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        use redis::AsyncCommands;
+
+        let mut conn = self.client.get_async_connection().await?;
+        conn.del::<_, ()>(self.key(id)).await?;
+        Ok(())
+    }
+
+    // Redis expires session keys on its own
+    async fn cleanup(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        Ok(())
+    }
+}
+
 pub struct SessionMiddleware {
     store: Arc<dyn SessionStore>,
     cookie_name: String,
     session_duration: chrono::Duration,
+    sliding: bool,
+    cleanup_interval: Option<std::time::Duration>,
 }
 
 impl SessionMiddleware {
@@ -101,6 +192,8 @@ impl SessionMiddleware {
             store,
             cookie_name: "rustnext_session".to_string(),
             session_duration: chrono::Duration::hours(24),
+            sliding: false,
+            cleanup_interval: Some(std::time::Duration::from_secs(300)),
         }
     }
 
@@ -113,6 +206,35 @@ impl SessionMiddleware {
         self.session_duration = duration;
         self
     }
+
+    // Sliding expiry: every request pushes `expires_at` to now + duration and sends the cookie
+    // with a matching Max-Age. Off by default, so sessions end `duration` after creation.
+    pub fn sliding(mut self, sliding: bool) -> Self {
+        self.sliding = sliding;
+        self
+    }
+
+    // How often the server removes expired sessions from the store (every 5 minutes by
+    // default); `None` leaves cleanup to the app, e.g. for a store shared by several servers
+    pub fn cleanup_interval(mut self, period: Option<std::time::Duration>) -> Self {
+        self.cleanup_interval = period;
+        self
+    }
+
+    pub fn store(&self) -> Arc<dyn SessionStore> {
+        self.store.clone()
+    }
+}
+
+impl TaskScheduler {
+    // Calls `store.cleanup()` every `period`. SessionMiddleware registers this itself; use it
+    // directly for stores the middleware doesn't own.
+    pub fn session_cleanup(self, store: Arc<dyn SessionStore>, period: std::time::Duration) -> Self {
+        self.every(period, "session-cleanup", move |_token| {
+            let store = store.clone();
+            async move { store.cleanup().await }
+        })
+    }
 }
 
 #[async_trait]
@@ -139,7 +261,7 @@ impl Middleware for SessionMiddleware {
             });
 
         // Load or create session
-        let session = if let Some(id) = session_id {
+        let mut session = if let Some(id) = session_id {
             match self.store.get(&id).await? {
                 Some(session) if !session.is_expired() => session,
                 _ => Session::new(self.session_duration),
@@ -147,26 +269,221 @@ impl Middleware for SessionMiddleware {
         } else {
             Session::new(self.session_duration)
         };
+        if self.sliding {
+            session.expires_at = chrono::Utc::now() + self.session_duration;
+        }
+
+        // Save before the handler runs so handlers writing through the store
+        // (e.g. auth::LoginHandler) aren't overwritten afterwards
+        self.store.set(session.clone()).await?;
 
         // Add session to request
         req.session = Some(session.clone());
+        let cell = SessionCell::default();
+        req.session_cell = Some(cell.clone());
 
         // Process request
-        let mut response = next.handle(req).await?;
+        let response = next.handle(req).await?;
+
+        // Persist the handler's changes to `req.session`. A regenerated ID replaces the old
+        // entry; the handler may already have saved the new one (with more changes) through
+        // the store, so don't overwrite it. Unchanged sessions aren't written again, which
+        // also keeps a session deleted by the handler (logout) deleted.
+        let updated = cell.lock().unwrap().take();
+        let cookie_id = match updated {
+            Some(updated) if updated.id != session.id => {
+                self.store.delete(&session.id).await?;
+                if self.store.get(&updated.id).await?.is_none() {
+                    self.store.set(updated.clone()).await?;
+                }
+                updated.id
+            }
+            Some(updated) => {
+                if updated != session {
+                    self.store.set(updated).await?;
+                }
+                session.id.clone()
+            }
+            None => session.id.clone(),
+        };
 
         // Set session cookie
-        let cookie = Cookie::build(self.cookie_name.clone(), session.id.clone())
+        let mut cookie = Cookie::build(self.cookie_name.clone(), cookie_id)
             .http_only(true)
             .secure(false) // Set to true in production with HTTPS
             .same_site(cookie::SameSite::Lax)
             .path("/")
             .finish();
+        if self.sliding {
+            cookie.set_max_age(cookie::time::Duration::seconds(self.session_duration.num_seconds()));
+        }
+
+        Ok(response.append_header(hyper::header::SET_COOKIE, cookie.to_string()))
+    }
+
+    fn tasks(&self, scheduler: TaskScheduler) -> TaskScheduler {
+        match self.cleanup_interval {
+            Some(period) => scheduler.session_cleanup(self.store.clone(), period),
+            None => scheduler,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{App, Router};
+    use std::time::Duration;
+
+    // Runs `handler` behind `sessions`, sending `session_id` as the cookie if given
+    async fn send(sessions: &SessionMiddleware, session_id: Option<&str>, handler: Arc<dyn crate::Handler>) -> Response {
+        let mut builder = hyper::Request::get("/");
+        if let Some(id) = session_id {
+            builder = builder.header("cookie", format!("theme=dark; rustnext_session={}", id));
+        }
+        let req = Request::from_hyper(builder.body(hyper::Body::empty()).unwrap()).await.unwrap();
+        sessions.handle(req, handler).await.unwrap()
+    }
+
+    fn set_cookie(response: &Response) -> Cookie<'static> {
+        let header = response.headers[hyper::header::SET_COOKIE].to_str().unwrap();
+        Cookie::parse(header.to_string()).unwrap()
+    }
+
+    // Counts visits in the session
+    fn counting_handler() -> Arc<dyn crate::Handler> {
+        Arc::new(|mut req: Request| async move {
+            let session = req.session.as_mut().unwrap();
+            let visits = session.get::<u32>("visits").unwrap_or(0) + 1;
+            session.set("visits", visits)?;
+            Ok::<_, Box<dyn std::error::Error + Send + Sync>>(Response::new().text(&visits.to_string()))
+        })
+    }
+
+    async fn body_text(response: Response) -> String {
+        let bytes = hyper::body::to_bytes(response.body).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn handler_changes_to_the_session_are_persisted() {
+        let store = Arc::new(MemorySessionStore::new());
+        let sessions = SessionMiddleware::new(store.clone());
 
-        response.headers.insert("Set-Cookie".to_string(), cookie.to_string());
+        let response = send(&sessions, None, counting_handler()).await;
+        let id = set_cookie(&response).value().to_string();
+        assert_eq!(body_text(response).await, "1");
+        assert_eq!(store.get(&id).await.unwrap().unwrap().get::<u32>("visits"), Some(1));
+
+        let response = send(&sessions, Some(&id), counting_handler()).await;
+        assert_eq!(set_cookie(&response).value(), id);
+        assert_eq!(body_text(response).await, "2");
+        assert_eq!(store.get(&id).await.unwrap().unwrap().get::<u32>("visits"), Some(2));
+    }
+
+    #[tokio::test]
+    async fn regenerating_the_id_moves_the_session_to_a_new_cookie() {
+        let store = Arc::new(MemorySessionStore::new());
+        let sessions = SessionMiddleware::new(store.clone());
+        let old_id = set_cookie(&send(&sessions, None, counting_handler()).await).value().to_string();
+
+        let login: Arc<dyn crate::Handler> = Arc::new(|mut req: Request| async move {
+            req.session.as_mut().unwrap().set("user_id", "alice")?;
+            let new_id = req.regenerate_session().unwrap();
+            Ok::<_, Box<dyn std::error::Error + Send + Sync>>(Response::new().text(&new_id))
+        });
+        let response = send(&sessions, Some(&old_id), login).await;
+        let new_id = set_cookie(&response).value().to_string();
+
+        assert_ne!(new_id, old_id);
+        assert_eq!(body_text(response).await, new_id);
+        assert!(store.get(&old_id).await.unwrap().is_none());
+        let session = store.get(&new_id).await.unwrap().unwrap();
+        assert_eq!(session.get::<u32>("visits"), Some(1));
+        assert_eq!(session.get::<String>("user_id").as_deref(), Some("alice"));
+
+        // The old ID now only gets a fresh, empty session
+        let response = send(&sessions, Some(&old_id), counting_handler()).await;
+        assert_ne!(set_cookie(&response).value(), old_id);
+        assert_eq!(body_text(response).await, "1");
+    }
+
+    // A stored session created an hour ago that expires in an hour
+    async fn hour_old_session(store: &MemorySessionStore) -> Session {
+        let mut session = Session::new(chrono::Duration::hours(1));
+        session.created_at -= chrono::Duration::hours(1);
+        store.set(session.clone()).await.unwrap();
+        session
+    }
+
+    #[tokio::test]
+    async fn sliding_sessions_push_the_expiry_forward() {
+        let store = Arc::new(MemorySessionStore::new());
+        let sessions = SessionMiddleware::new(store.clone()).duration(chrono::Duration::hours(2)).sliding(true);
+        let session = hour_old_session(&store).await;
+
+        let response = send(&sessions, Some(&session.id), counting_handler()).await;
+        let cookie = set_cookie(&response);
+        assert_eq!(cookie.value(), session.id);
+        assert_eq!(cookie.max_age(), Some(cookie::time::Duration::hours(2)));
+
+        let stored = store.get(&session.id).await.unwrap().unwrap();
+        let remaining = stored.expires_at - chrono::Utc::now();
+        assert!(remaining > chrono::Duration::minutes(119) && remaining <= chrono::Duration::hours(2), "{}", remaining);
+        assert_eq!(stored.get::<u32>("visits"), Some(1));
+    }
+
+    #[tokio::test]
+    async fn fixed_sessions_keep_their_expiry() {
+        let store = Arc::new(MemorySessionStore::new());
+        let sessions = SessionMiddleware::new(store.clone()).duration(chrono::Duration::hours(2));
+        let session = hour_old_session(&store).await;
+
+        let response = send(&sessions, Some(&session.id), counting_handler()).await;
+        let cookie = set_cookie(&response);
+        assert_eq!(cookie.value(), session.id);
+        // A session cookie; the store decides when it ends
+        assert_eq!(cookie.max_age(), None);
+
+        let stored = store.get(&session.id).await.unwrap().unwrap();
+        assert_eq!(stored.expires_at, session.expires_at);
+        assert_eq!(stored.get::<u32>("visits"), Some(1));
+    }
+
+    #[tokio::test]
+    async fn cleanup_removes_only_expired_sessions() {
+        let store = MemorySessionStore::new();
+        let live = Session::new(chrono::Duration::hours(1));
+        let expired = Session::new(chrono::Duration::seconds(-1));
+        store.set(live.clone()).await.unwrap();
+        store.set(expired.clone()).await.unwrap();
+
+        store.cleanup().await.unwrap();
+        assert!(store.get(&live.id).await.unwrap().is_some());
+        assert!(store.get(&expired.id).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn session_middleware_schedules_cleanup() {
+        let store = Arc::new(MemorySessionStore::new());
+        let expired = Session::new(chrono::Duration::seconds(-1));
+        store.set(expired.clone()).await.unwrap();
+
+        let sessions = SessionMiddleware::new(store.clone()).cleanup_interval(Some(Duration::from_millis(10)));
+        let mut app = App::new().router(Router::new().use_middleware(sessions));
+        let running = app.take_tasks().start();
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        running.shutdown().await;
+        assert!(store.get(&expired.id).await.unwrap().is_none());
+    }
 
-        // Save session
-        self.store.set(session).await?;
+    #[test]
+    fn cleanup_can_be_turned_off() {
+        let sessions = SessionMiddleware::new(Arc::new(MemorySessionStore::new()));
+        assert!(!sessions.tasks(TaskScheduler::new()).is_empty());
 
-        Ok(response)
+        let sessions = sessions.cleanup_interval(None);
+        assert!(sessions.tasks(TaskScheduler::new()).is_empty());
     }
 }
@@ -0,0 +1,282 @@
+use crate::escape::escape_text;
+use crate::ui::PageRegistry;
+use crate::Response;
+use hyper::header::{CACHE_CONTROL, CONTENT_TYPE, ETAG};
+use std::borrow::Cow;
+use std::fmt;
+use std::path::PathBuf;
+
+// Favicons aren't fingerprinted, so a week rather than the year fingerprinted assets get
+const FAVICON_MAX_AGE: u64 = 7 * 24 * 3600;
+
+// What `App::favicon` serves at `/favicon.ico`: a file, read on each request so it can change
+// without a restart (browsers rarely ask, given the Cache-Control), or bytes, e.g.
+// `App::new().favicon(include_bytes!("../static/favicon.png"))`
+#[derive(Debug, Clone)]
+pub enum Favicon {
+    File(PathBuf),
+    Bytes(Cow<'static, [u8]>),
+}
+
+impl Favicon {
+    pub async fn response(&self) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+        let content: Cow<'_, [u8]> = match self {
+            Favicon::File(path) => Cow::Owned(tokio::fs::read(path).await?),
+            Favicon::Bytes(bytes) => Cow::Borrowed(bytes),
+        };
+        let content_type = favicon_content_type(&content);
+        Ok(Response::new()
+            .header(CONTENT_TYPE, content_type)
+            .header(CACHE_CONTROL, format!("public, max-age={}", FAVICON_MAX_AGE))
+            .header(ETAG, format!("\"{:x}\"", md5::compute(&content)))
+            .body(hyper::Body::from(content.into_owned())))
+    }
+}
+
+// By content rather than name, since `/favicon.ico` is often really a PNG
+fn favicon_content_type(content: &[u8]) -> &'static str {
+    let text = String::from_utf8_lossy(&content[..content.len().min(256)]);
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    if content.starts_with(b"\x89PNG\r\n\x1a\n") {
+        "image/png"
+    } else if text.starts_with("<svg") || (text.starts_with("<?xml") && text.contains("<svg")) {
+        "image/svg+xml"
+    } else {
+        "image/x-icon"
+    }
+}
+
+impl From<&str> for Favicon {
+    fn from(path: &str) -> Self {
+        Favicon::File(PathBuf::from(path))
+    }
+}
+
+impl From<PathBuf> for Favicon {
+    fn from(path: PathBuf) -> Self {
+        Favicon::File(path)
+    }
+}
+
+impl From<&'static [u8]> for Favicon {
+    fn from(bytes: &'static [u8]) -> Self {
+        Favicon::Bytes(Cow::Borrowed(bytes))
+    }
+}
+
+impl<const N: usize> From<&'static [u8; N]> for Favicon {
+    fn from(bytes: &'static [u8; N]) -> Self {
+        Favicon::Bytes(Cow::Borrowed(bytes))
+    }
+}
+
+impl From<Vec<u8>> for Favicon {
+    fn from(bytes: Vec<u8>) -> Self {
+        Favicon::Bytes(Cow::Owned(bytes))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct RobotsGroup {
+    user_agents: Vec<String>,
+    // (`Allow` or `Disallow`, path)
+    rules: Vec<(&'static str, String)>,
+    crawl_delay: Option<u32>,
+}
+
+// What `App::robots` serves at `/robots.txt` (RFC 9309), e.g.
+//
+//     RobotsConfig::new()
+//         .user_agent("*").disallow("/admin").allow("/admin/public")
+//         .user_agent("BadBot").disallow("/")
+//         .sitemap("https://example.com/sitemap.xml")
+//
+// Each `user_agent` starts a group, unless the current one has no rules or crawl delay yet, in
+// which case the agent joins it. Rules before any `user_agent` are for `*`. A group without rules
+// allows everything, and so does `RobotsConfig::new()` on its own.
+#[derive(Debug, Clone, Default)]
+pub struct RobotsConfig {
+    groups: Vec<RobotsGroup>,
+    sitemaps: Vec<String>,
+}
+
+impl RobotsConfig {
+    pub fn new() -> Self {
+        RobotsConfig::default()
+    }
+
+    pub fn user_agent(mut self, agent: &str) -> Self {
+        let agent = single_line(agent);
+        match self.groups.last_mut() {
+            Some(group) if group.rules.is_empty() && group.crawl_delay.is_none() => group.user_agents.push(agent),
+            _ => self.groups.push(RobotsGroup { user_agents: vec![agent], ..RobotsGroup::default() }),
+        }
+        self
+    }
+
+    pub fn allow(mut self, path: &str) -> Self {
+        self.current_group().rules.push(("Allow", single_line(path)));
+        self
+    }
+
+    pub fn disallow(mut self, path: &str) -> Self {
+        self.current_group().rules.push(("Disallow", single_line(path)));
+        self
+    }
+
+    // Seconds between requests; not part of RFC 9309, but honoured by several crawlers
+    pub fn crawl_delay(mut self, seconds: u32) -> Self {
+        self.current_group().crawl_delay = Some(seconds);
+        self
+    }
+
+    // An absolute URL
+    pub fn sitemap(mut self, url: &str) -> Self {
+        self.sitemaps.push(single_line(url));
+        self
+    }
+
+    fn current_group(&mut self) -> &mut RobotsGroup {
+        if self.groups.is_empty() {
+            self.groups.push(RobotsGroup { user_agents: vec!["*".to_string()], ..RobotsGroup::default() });
+        }
+        self.groups.last_mut().unwrap()
+    }
+}
+
+// A value can't be allowed to start a line of its own
+fn single_line(value: &str) -> String {
+    value.replace(['\r', '\n'], "").trim().to_string()
+}
+
+impl fmt::Display for RobotsConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let allow_all = [RobotsGroup { user_agents: vec!["*".to_string()], ..RobotsGroup::default() }];
+        let groups = if self.groups.is_empty() { &allow_all[..] } else { &self.groups[..] };
+        for (i, group) in groups.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            for agent in &group.user_agents {
+                writeln!(f, "User-agent: {}", agent)?;
+            }
+            if group.rules.is_empty() {
+                writeln!(f, "Disallow:")?;
+            }
+            for (rule, path) in &group.rules {
+                writeln!(f, "{}: {}", rule, path)?;
+            }
+            if let Some(seconds) = group.crawl_delay {
+                writeln!(f, "Crawl-delay: {}", seconds)?;
+            }
+        }
+        if !self.sitemaps.is_empty() {
+            writeln!(f)?;
+        }
+        for url in &self.sitemaps {
+            writeln!(f, "Sitemap: {}", url)?;
+        }
+        Ok(())
+    }
+}
+
+// A sitemap (sitemaps.org protocol) of the registered pages, skipping parameterized paths such
+// as `/posts/:id`, for `App::sitemap`:
+//
+//     let xml = sitemap_from_pages(&*get_page_registry().lock().await, "https://example.com");
+pub fn sitemap_from_pages(registry: &PageRegistry, base_url: &str) -> String {
+    let base_url = base_url.trim_end_matches('/');
+    let mut paths: Vec<&str> = registry.paths()
+        .filter(|path| path.starts_with('/') && !path.contains([':', '*']))
+        .collect();
+    paths.sort_unstable();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+    for path in paths {
+        let loc = format!("{}{}", base_url, path);
+        xml.push_str(&format!("  <url><loc>{}</loc></url>\n", escape_text(&loc)));
+    }
+    xml.push_str("</urlset>\n");
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::{div, Element};
+    use async_trait::async_trait;
+
+    #[test]
+    fn robots_groups_agents_and_rules() {
+        let robots = RobotsConfig::new()
+            .disallow("/admin")
+            .allow("/admin/public")
+            .user_agent("BadBot")
+            .user_agent("WorseBot")
+            .disallow("/")
+            .crawl_delay(10)
+            .sitemap("https://example.com/sitemap.xml");
+        assert_eq!(
+            robots.to_string(),
+            "User-agent: *\nDisallow: /admin\nAllow: /admin/public\n\n\
+             User-agent: BadBot\nUser-agent: WorseBot\nDisallow: /\nCrawl-delay: 10\n\n\
+             Sitemap: https://example.com/sitemap.xml\n"
+        );
+
+        assert_eq!(RobotsConfig::new().to_string(), "User-agent: *\nDisallow:\n");
+        // A rule can't smuggle in a line of its own
+        assert_eq!(
+            RobotsConfig::new().user_agent("*").disallow("/a\nAllow: /secret").to_string(),
+            "User-agent: *\nDisallow: /aAllow: /secret\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn favicons_are_typed_by_content() {
+        let png: &'static [u8] = b"\x89PNG\r\n\x1a\nrest";
+        let response = Favicon::from(png).response().await.unwrap();
+        assert_eq!(response.headers[CONTENT_TYPE], "image/png");
+        assert_eq!(response.headers[CACHE_CONTROL], "public, max-age=604800");
+        assert_eq!(response.headers[ETAG], format!("\"{:x}\"", md5::compute(png)).as_str());
+
+        assert_eq!(favicon_content_type(b"\xef\xbb\xbf <svg xmlns=\"..\"/>"), "image/svg+xml");
+        assert_eq!(favicon_content_type(b"<?xml version=\"1.0\"?>\n<svg/>"), "image/svg+xml");
+        assert_eq!(favicon_content_type(b"\x00\x00\x01\x00"), "image/x-icon");
+        assert_eq!(favicon_content_type(b""), "image/x-icon");
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("favicon.svg");
+        std::fs::write(&path, "<svg/>").unwrap();
+        let response = Favicon::from(path.clone()).response().await.unwrap();
+        assert_eq!(response.headers[CONTENT_TYPE], "image/svg+xml");
+        std::fs::remove_file(&path).unwrap();
+        assert!(Favicon::from(path).response().await.is_err());
+    }
+
+    struct Blank;
+
+    #[async_trait]
+    impl crate::ui::Page for Blank {
+        async fn render(&self, _req: &crate::Request) -> Element {
+            div()
+        }
+    }
+
+    #[test]
+    fn sitemaps_list_the_static_pages() {
+        let mut registry = PageRegistry::new();
+        for path in ["/about", "/", "/posts/:id", "/docs/*", "/search?q=a&b", "fragment"] {
+            registry.register(path, Blank);
+        }
+        assert_eq!(
+            sitemap_from_pages(&registry, "https://example.com/"),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n  \
+             <url><loc>https://example.com/</loc></url>\n  \
+             <url><loc>https://example.com/about</loc></url>\n  \
+             <url><loc>https://example.com/search?q=a&amp;b</loc></url>\n\
+             </urlset>\n"
+        );
+    }
+}
@@ -1,68 +1,344 @@
 use crate::{Request, Response, Handler};
+use crate::ui::{a, div, get_renderer, h1, li, span, text, ul};
 use async_trait::async_trait;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use tokio::fs;
 
 pub struct StaticFiles {
     dir: String,
     prefix: String,
+    // Served for directory paths; None disables index resolution
+    index_file: Option<String>,
+    // `/static/docs` -> `/static/docs/` when it is a directory
+    redirect_directories: bool,
+    directory_listing: bool,
+    // Serve `app.js.br` / `app.js.gz` next to `app.js` to clients that accept them
+    precompressed: bool,
+    // Document (relative to `dir`) served for unknown client-side routes of a single-page app
+    spa_fallback: Option<String>,
 }
 
+// Sidecar extension per content coding, in preference order on equal q-values
+const SIDECARS: [(&str, &str); 2] = [("br", "br"), ("gzip", "gz")];
+
 impl StaticFiles {
     pub fn new(dir: &str, prefix: &str) -> Self {
         StaticFiles {
             dir: dir.to_string(),
             prefix: prefix.to_string(),
+            index_file: Some("index.html".to_string()),
+            redirect_directories: true,
+            directory_listing: false,
+            precompressed: true,
+            spa_fallback: None,
         }
     }
 
-    async fn serve_file(&self, path: &str) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    pub fn index_file(mut self, name: &str) -> Self {
+        self.index_file = Some(name.to_string());
+        self
+    }
+
+    pub fn without_index(mut self) -> Self {
+        self.index_file = None;
+        self
+    }
+
+    pub fn redirect_directories(mut self, redirect: bool) -> Self {
+        self.redirect_directories = redirect;
+        self
+    }
+
+    // Lists directories without an index file (dotfiles hidden). Meant for development.
+    pub fn with_directory_listing(mut self, enabled: bool) -> Self {
+        self.directory_listing = enabled;
+        self
+    }
+
+    pub fn precompressed(mut self, enabled: bool) -> Self {
+        self.precompressed = enabled;
+        self
+    }
+
+    // Serves `document` with a 200 for missing paths, so client-side routing works: only for
+    // GET/HEAD requests preferring HTML, and never for paths with an extension (`/missing.png`
+    // stays a 404). Under `App`, router routes below the same prefix take precedence.
+    pub fn with_spa_fallback(mut self, document: &str) -> Self {
+        self.spa_fallback = Some(document.to_string());
+        self
+    }
+
+    pub fn has_spa_fallback(&self) -> bool {
+        self.spa_fallback.is_some()
+    }
+
+    fn wants_spa_fallback(&self, req: &Request) -> bool {
+        if self.spa_fallback.is_none() || !matches!(req.method, hyper::Method::GET | hyper::Method::HEAD) {
+            return false;
+        }
+        let last_segment = req.uri.path().rsplit('/').next().unwrap_or("");
+        if last_segment.contains('.') {
+            return false;
+        }
+        req.wants_html()
+    }
+
+    fn not_found() -> Response {
+        Response::new()
+            .status(hyper::StatusCode::NOT_FOUND)
+            .text("File not found")
+    }
+
+    // `path` is relative to the prefix; `request_path` is the full URL path for redirects and listings
+    async fn serve_file(
+        &self,
+        path: &str,
+        request_path: &str,
+        accept_encoding: Option<&str>,
+        spa_fallback: bool,
+    ) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
         let file_path = Path::new(&self.dir).join(path.trim_start_matches('/'));
-        
+
         // Security check: prevent directory traversal
         let canonical_dir = std::fs::canonicalize(&self.dir)?;
         let canonical_file = match file_path.canonicalize() {
             Ok(path) => path,
-            Err(_) => {
-                return Ok(Response::new()
-                    .status(hyper::StatusCode::NOT_FOUND)
-                    .text("File not found"));
-            }
+            Err(_) if spa_fallback => return self.serve_spa_fallback(&canonical_dir, accept_encoding).await,
+            Err(_) => return Ok(Self::not_found()),
         };
-        
+
         if !canonical_file.starts_with(&canonical_dir) {
             return Ok(Response::new()
                 .status(hyper::StatusCode::FORBIDDEN)
                 .text("Forbidden"));
         }
 
-        match fs::read(&file_path).await {
+        if canonical_file.is_dir() {
+            return self.serve_directory(&canonical_dir, &canonical_file, request_path, accept_encoding).await;
+        }
+
+        self.send_file(&canonical_dir, &canonical_file, accept_encoding).await
+    }
+
+    async fn serve_directory(
+        &self,
+        canonical_dir: &Path,
+        directory: &Path,
+        request_path: &str,
+        accept_encoding: Option<&str>,
+    ) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+        // Relative links inside an index page only resolve against a trailing slash
+        if !request_path.ends_with('/') {
+            if self.redirect_directories {
+                return Ok(Response::new()
+                    .status(hyper::StatusCode::MOVED_PERMANENTLY)
+                    .header("Location", format!("{}/", request_path)));
+            }
+            if self.index_file.is_none() && !self.directory_listing {
+                return Ok(Self::not_found());
+            }
+        }
+
+        if let Some(index_file) = &self.index_file {
+            // The index itself may be a symlink, so it gets the same containment check
+            if let Ok(index) = directory.join(index_file).canonicalize() {
+                if index.starts_with(canonical_dir) && index.is_file() {
+                    return self.send_file(canonical_dir, &index, accept_encoding).await;
+                }
+            }
+        }
+
+        if self.directory_listing {
+            return self.render_listing(directory, request_path, directory != canonical_dir).await;
+        }
+
+        Ok(Self::not_found())
+    }
+
+    // Existing `(coding, path)` sidecars of `file_path`. They get the same containment check as
+    // the file itself, so a sidecar symlinked out of the directory is ignored.
+    fn sidecars(&self, canonical_dir: &Path, file_path: &Path) -> Vec<(&'static str, PathBuf)> {
+        if !self.precompressed {
+            return Vec::new();
+        }
+        find_sidecars(canonical_dir, file_path)
+    }
+
+    async fn serve_spa_fallback(
+        &self,
+        canonical_dir: &Path,
+        accept_encoding: Option<&str>,
+    ) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+        let document = self.spa_fallback.as_deref().unwrap_or("index.html");
+        match Path::new(&self.dir).join(document).canonicalize() {
+            Ok(document) if document.starts_with(canonical_dir) && document.is_file() => {
+                let response = self.send_file(canonical_dir, &document, accept_encoding).await?;
+                // The shell must pick up new deployments, unlike the hashed assets it loads
+                Ok(response.header("Cache-Control", "no-cache"))
+            }
+            _ => Ok(Self::not_found()),
+        }
+    }
+
+    async fn send_file(
+        &self,
+        canonical_dir: &Path,
+        file_path: &Path,
+        accept_encoding: Option<&str>,
+    ) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+        // The type always comes from the original name, not from `.gz`/`.br`
+        let mime_type = mime_guess::from_path(file_path)
+            .first_or_octet_stream()
+            .to_string();
+
+        let sidecars = self.sidecars(canonical_dir, file_path);
+        let (encoding, served_path) = match pick_sidecar(&sidecars, accept_encoding) {
+            Some((coding, sidecar)) => (Some(coding), sidecar),
+            None => (None, file_path),
+        };
+
+        match fs::read(served_path).await {
             Ok(contents) => {
-                let mime_type = mime_guess::from_path(&file_path)
-                    .first_or_octet_stream()
-                    .to_string();
-                
-                Ok(Response::new()
+                let mut response = Response::new()
                     .header("Content-Type", &mime_type)
-                    .header("Content-Length", &contents.len().to_string())
+                    .header("Content-Length", contents.len().to_string())
                     .header("Cache-Control", "public, max-age=3600") // 1 hour cache
-                    .status(hyper::StatusCode::OK)
-                    .body(hyper::Body::from(contents)))
+                    .status(hyper::StatusCode::OK);
+                if let Some(encoding) = encoding {
+                    response = response.header("Content-Encoding", encoding);
+                }
+                // The body depends on Accept-Encoding whenever there's a variant to choose
+                if !sidecars.is_empty() {
+                    response = response.header("Vary", "Accept-Encoding");
+                }
+                Ok(response.body(hyper::Body::from(contents)))
             }
-            Err(_) => Ok(Response::new()
-                .status(hyper::StatusCode::NOT_FOUND)
-                .text("File not found")),
+            Err(_) => Ok(Self::not_found()),
+        }
+    }
+
+    async fn render_listing(
+        &self,
+        directory: &Path,
+        request_path: &str,
+        has_parent: bool,
+    ) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+        // (name, is_dir, size, modified)
+        let mut entries: Vec<(String, bool, u64, Option<std::time::SystemTime>)> = Vec::new();
+        let mut dir = fs::read_dir(directory).await?;
+        while let Some(entry) = dir.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with('.') {
+                continue;
+            }
+            let metadata = match entry.metadata().await {
+                Ok(metadata) => metadata,
+                Err(_) => continue,
+            };
+            entries.push((name, metadata.is_dir(), metadata.len(), metadata.modified().ok()));
+        }
+        // Directories first, then by name
+        entries.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+        let base = if request_path.ends_with('/') {
+            request_path.to_string()
+        } else {
+            format!("{}/", request_path)
+        };
+
+        let mut list = ul().class("directory-listing");
+        if has_parent {
+            list = list.child(li().child(a().prop("href", "../").child(text("../"))));
+        }
+        for (name, is_dir, size, modified) in entries {
+            let display = if is_dir { format!("{}/", name) } else { name.clone() };
+            let href = format!("{}{}{}", base, urlencoding::encode(&name), if is_dir { "/" } else { "" });
+            let modified = modified
+                .map(|time| chrono::DateTime::<chrono::Utc>::from(time).format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_default();
+            let details = if is_dir {
+                modified
+            } else {
+                format!("{} bytes, {}", size, modified)
+            };
+            list = list.child(
+                li()
+                    .child(a().prop("href", href).child(text(&display)))
+                    .child(span().child(text(&format!(" — {}", details)))),
+            );
+        }
+
+        let title = percent_encoding::percent_decode_str(&base).decode_utf8_lossy().to_string();
+        let page = div()
+            .class("container")
+            .child(h1().child(text(&format!("Index of {}", title))))
+            .child(list);
+        get_renderer().render_to_response(&page)
+    }
+}
+
+// `file.css.br` and `file.css.gz` next to `file.css`, if they exist inside `canonical_dir`
+pub(crate) fn find_sidecars(canonical_dir: &Path, file_path: &Path) -> Vec<(&'static str, PathBuf)> {
+    SIDECARS.iter()
+        .filter_map(|(coding, extension)| {
+            let mut sidecar = file_path.as_os_str().to_owned();
+            sidecar.push(".");
+            sidecar.push(extension);
+            let sidecar = PathBuf::from(sidecar).canonicalize().ok()?;
+            (sidecar.starts_with(canonical_dir) && sidecar.is_file()).then_some((*coding, sidecar))
+        })
+        .collect()
+}
+
+// The sidecar whose coding the client accepts with the highest q-value, if any
+pub(crate) fn pick_sidecar<'a>(
+    sidecars: &'a [(&'static str, PathBuf)],
+    accept_encoding: Option<&str>,
+) -> Option<(&'static str, &'a Path)> {
+    let mut best: Option<(&'static str, &'a Path, f32)> = None;
+    for (coding, sidecar) in sidecars {
+        let q = encoding_quality(accept_encoding, coding);
+        if q > 0.0 && best.is_none_or(|(_, _, best_q)| q > best_q) {
+            best = Some((coding, sidecar, q));
         }
     }
+    best.map(|(coding, sidecar, _)| (coding, sidecar))
+}
+
+// q-value the `Accept-Encoding` header gives `coding`; an explicit entry beats `*`, and a
+// missing header accepts nothing but the identity encoding
+fn encoding_quality(accept_encoding: Option<&str>, coding: &str) -> f32 {
+    let mut wildcard = 0.0;
+    for part in accept_encoding.unwrap_or("").split(',') {
+        let mut pieces = part.split(';');
+        let name = pieces.next().unwrap_or("").trim();
+        let q = pieces
+            .filter_map(|param| param.split_once('='))
+            .find(|(key, _)| key.trim().eq_ignore_ascii_case("q"))
+            .and_then(|(_, value)| value.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+        if name.eq_ignore_ascii_case(coding) || (coding == "gzip" && name.eq_ignore_ascii_case("x-gzip")) {
+            return q;
+        }
+        if name == "*" {
+            wildcard = q;
+        }
+    }
+    wildcard
 }
 
 #[async_trait]
 impl Handler for StaticFiles {
     async fn handle(&self, req: Request) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
         let path = req.uri.path();
-        if path.starts_with(&self.prefix) {
-            let file_path = &path[self.prefix.len()..];
-            self.serve_file(file_path).await
+        if let Some(file_path) = path.strip_prefix(self.prefix.as_str()) {
+            let file_path = percent_encoding::percent_decode_str(file_path).decode_utf8_lossy();
+            let accept_encoding = req.headers.get("accept-encoding").and_then(|v| v.to_str().ok());
+            self.serve_file(&file_path, path, accept_encoding, self.wants_spa_fallback(&req)).await
         } else {
             Ok(Response::new()
                 .status(hyper::StatusCode::NOT_FOUND)
@@ -70,3 +346,237 @@ impl Handler for StaticFiles {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // public/
+    //   index.html, app.css
+    //   docs/index.html
+    //   raw/<script>.txt, raw/.secret
+    fn public_dir() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("index.html"), "<p>home</p>").unwrap();
+        std::fs::write(dir.path().join("app.css"), "body{}").unwrap();
+        std::fs::create_dir(dir.path().join("docs")).unwrap();
+        std::fs::write(dir.path().join("docs/index.html"), "<p>docs</p>").unwrap();
+        std::fs::create_dir(dir.path().join("raw")).unwrap();
+        std::fs::write(dir.path().join("raw/<script>.txt"), "x").unwrap();
+        std::fs::write(dir.path().join("raw/.secret"), "hidden").unwrap();
+        dir
+    }
+
+    fn files(dir: &tempfile::TempDir) -> StaticFiles {
+        StaticFiles::new(dir.path().to_str().unwrap(), "/static")
+    }
+
+    async fn get(files: &StaticFiles, uri: &str, headers: &[(&str, &str)]) -> Response {
+        let mut builder = hyper::Request::builder().uri(uri);
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        let req = Request::from_hyper(builder.body(hyper::Body::empty()).unwrap()).await.unwrap();
+        files.handle(req).await.unwrap()
+    }
+
+    async fn body_text(response: Response) -> String {
+        let bytes = hyper::body::to_bytes(response.body).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn serves_files_with_their_mime_type() {
+        let dir = public_dir();
+        let response = get(&files(&dir), "/static/app.css", &[]).await;
+        assert_eq!(response.status, hyper::StatusCode::OK);
+        assert_eq!(response.headers["content-type"], "text/css");
+        assert_eq!(body_text(response).await, "body{}");
+
+        let response = get(&files(&dir), "/static/missing.css", &[]).await;
+        assert_eq!(response.status, hyper::StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn rejects_paths_escaping_the_directory() {
+        let dir = public_dir();
+        let inner = dir.path().join("inner");
+        std::fs::create_dir(&inner).unwrap();
+        let files = StaticFiles::new(inner.to_str().unwrap(), "/static");
+
+        let response = get(&files, "/static/%2e%2e/app.css", &[]).await;
+        assert_eq!(response.status, hyper::StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn directories_resolve_to_their_index_file() {
+        let dir = public_dir();
+        let response = get(&files(&dir), "/static/docs/", &[]).await;
+        assert_eq!(response.status, hyper::StatusCode::OK);
+        assert_eq!(body_text(response).await, "<p>docs</p>");
+
+        let response = get(&files(&dir), "/static/", &[]).await;
+        assert_eq!(body_text(response).await, "<p>home</p>");
+    }
+
+    #[tokio::test]
+    async fn directories_without_trailing_slash_redirect() {
+        let dir = public_dir();
+        let response = get(&files(&dir), "/static/docs", &[]).await;
+        assert_eq!(response.status, hyper::StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(response.headers["location"], "/static/docs/");
+
+        let response = get(&files(&dir).redirect_directories(false), "/static/docs", &[]).await;
+        assert_eq!(response.status, hyper::StatusCode::OK);
+        assert_eq!(body_text(response).await, "<p>docs</p>");
+    }
+
+    #[tokio::test]
+    async fn custom_and_disabled_index_files() {
+        let dir = public_dir();
+        std::fs::write(dir.path().join("docs/default.htm"), "<p>default</p>").unwrap();
+
+        let response = get(&files(&dir).index_file("default.htm"), "/static/docs/", &[]).await;
+        assert_eq!(body_text(response).await, "<p>default</p>");
+
+        let response = get(&files(&dir).without_index(), "/static/docs/", &[]).await;
+        assert_eq!(response.status, hyper::StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn directory_listing_is_opt_in() {
+        let dir = public_dir();
+        let response = get(&files(&dir), "/static/raw/", &[]).await;
+        assert_eq!(response.status, hyper::StatusCode::NOT_FOUND);
+
+        let response = get(&files(&dir).with_directory_listing(true), "/static/raw/", &[]).await;
+        assert_eq!(response.status, hyper::StatusCode::OK);
+        let html = body_text(response).await;
+        assert!(html.contains("Index of /static/raw/"));
+        assert!(html.contains(r#"href="../""#));
+        assert!(html.contains("/static/raw/%3Cscript%3E.txt"));
+        assert!(html.contains("&lt;script&gt;.txt"));
+        assert!(!html.contains("<script>"));
+        assert!(!html.contains(".secret"));
+    }
+
+    #[tokio::test]
+    async fn index_file_wins_over_listing() {
+        let dir = public_dir();
+        let response = get(&files(&dir).with_directory_listing(true), "/static/docs/", &[]).await;
+        assert_eq!(body_text(response).await, "<p>docs</p>");
+
+        // The root has no parent link
+        let listing = files(&dir).without_index().with_directory_listing(true);
+        let html = body_text(get(&listing, "/static/", &[]).await).await;
+        assert!(html.contains("docs/"));
+        assert!(!html.contains(r#"href="../""#));
+    }
+
+    fn with_sidecars(dir: &tempfile::TempDir) {
+        std::fs::write(dir.path().join("app.css.br"), "brotli").unwrap();
+        std::fs::write(dir.path().join("app.css.gz"), "gzip").unwrap();
+    }
+
+    #[tokio::test]
+    async fn serves_precompressed_sidecars_to_accepting_clients() {
+        let dir = public_dir();
+        with_sidecars(&dir);
+
+        let response = get(&files(&dir), "/static/app.css", &[("accept-encoding", "gzip, br")]).await;
+        assert_eq!(response.headers["content-encoding"], "br");
+        assert_eq!(response.headers["content-type"], "text/css");
+        assert_eq!(response.headers["vary"], "Accept-Encoding");
+        assert_eq!(body_text(response).await, "brotli");
+
+        let response = get(&files(&dir), "/static/app.css", &[("accept-encoding", "gzip")]).await;
+        assert_eq!(response.headers["content-encoding"], "gzip");
+        assert_eq!(body_text(response).await, "gzip");
+
+        let response = get(&files(&dir), "/static/app.css", &[]).await;
+        assert!(response.headers.get("content-encoding").is_none());
+        assert_eq!(response.headers["vary"], "Accept-Encoding");
+        assert_eq!(body_text(response).await, "body{}");
+    }
+
+    #[tokio::test]
+    async fn sidecars_can_be_disabled() {
+        let dir = public_dir();
+        with_sidecars(&dir);
+
+        let response = get(&files(&dir).precompressed(false), "/static/app.css", &[("accept-encoding", "br")]).await;
+        assert!(response.headers.get("content-encoding").is_none());
+        assert!(response.headers.get("vary").is_none());
+        assert_eq!(body_text(response).await, "body{}");
+    }
+
+    #[tokio::test]
+    async fn index_files_use_sidecars_too() {
+        let dir = public_dir();
+        std::fs::write(dir.path().join("docs/index.html.gz"), "gzipped docs").unwrap();
+
+        let response = get(&files(&dir), "/static/docs/", &[("accept-encoding", "gzip")]).await;
+        assert_eq!(response.headers["content-type"], "text/html");
+        assert_eq!(body_text(response).await, "gzipped docs");
+    }
+
+    #[test]
+    fn picks_the_sidecar_with_the_highest_q_value() {
+        let sidecars = vec![("br", PathBuf::from("a.br")), ("gzip", PathBuf::from("a.gz"))];
+        let coding = |header| pick_sidecar(&sidecars, header).map(|(coding, _)| coding);
+
+        assert_eq!(coding(Some("gzip, br")), Some("br"));
+        assert_eq!(coding(Some("br;q=0.5, gzip")), Some("gzip"));
+        assert_eq!(coding(Some("x-gzip")), Some("gzip"));
+        assert_eq!(coding(Some("*")), Some("br"));
+        assert_eq!(coding(Some("*, br;q=0")), Some("gzip"));
+        assert_eq!(coding(Some("identity")), None);
+        assert_eq!(coding(None), None);
+    }
+
+    fn spa(dir: &tempfile::TempDir) -> StaticFiles {
+        StaticFiles::new(dir.path().to_str().unwrap(), "/").with_spa_fallback("index.html")
+    }
+
+    const HTML: (&str, &str) = ("accept", "text/html,application/xhtml+xml,*/*;q=0.8");
+
+    #[tokio::test]
+    async fn spa_fallback_serves_the_shell_for_client_routes() {
+        let dir = public_dir();
+        let response = get(&spa(&dir), "/dashboard/settings", &[HTML]).await;
+        assert_eq!(response.status, hyper::StatusCode::OK);
+        assert_eq!(response.headers["cache-control"], "no-cache");
+        assert_eq!(response.headers["content-type"], "text/html");
+        assert_eq!(body_text(response).await, "<p>home</p>");
+
+        // Existing files are unaffected
+        let response = get(&spa(&dir), "/app.css", &[HTML]).await;
+        assert_eq!(response.headers["cache-control"], "public, max-age=3600");
+        assert_eq!(body_text(response).await, "body{}");
+    }
+
+    #[tokio::test]
+    async fn spa_fallback_skips_assets_non_html_and_non_get_requests() {
+        let dir = public_dir();
+        let response = get(&spa(&dir), "/missing.png", &[HTML]).await;
+        assert_eq!(response.status, hyper::StatusCode::NOT_FOUND);
+
+        let response = get(&spa(&dir), "/dashboard", &[("accept", "application/json")]).await;
+        assert_eq!(response.status, hyper::StatusCode::NOT_FOUND);
+
+        let req = hyper::Request::post("/dashboard").header(HTML.0, HTML.1).body(hyper::Body::empty()).unwrap();
+        let response = spa(&dir).handle(Request::from_hyper(req).await.unwrap()).await.unwrap();
+        assert_eq!(response.status, hyper::StatusCode::NOT_FOUND);
+
+        let response = get(&files(&dir), "/static/dashboard", &[HTML]).await;
+        assert_eq!(response.status, hyper::StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn spa_fallback_with_a_missing_document_is_a_404() {
+        let dir = public_dir();
+        let files = StaticFiles::new(dir.path().to_str().unwrap(), "/").with_spa_fallback("shell.html");
+        let response = get(&files, "/dashboard", &[HTML]).await;
+        assert_eq!(response.status, hyper::StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,246 @@
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+pub use tokio_util::sync::CancellationToken;
+
+pub type TaskResult = Result<(), Box<dyn std::error::Error + Send + Sync>>;
+
+// A task body; it gets the scheduler's token so long runs can stop early on shutdown
+pub type TaskFn = Arc<dyn Fn(CancellationToken) -> BoxFuture<'static, TaskResult> + Send + Sync>;
+
+#[derive(Debug, Clone, Copy)]
+enum Schedule {
+    Every(Duration),
+    After(Duration),
+}
+
+struct Task {
+    name: String,
+    schedule: Schedule,
+    run: TaskFn,
+}
+
+// Periodic and delayed background work, e.g.
+// `TaskScheduler::new().every(Duration::from_secs(60), "evict", |_token| async { ...; Ok(()) })`.
+// Handed to `App::tasks`, it starts with the server and stops when the server shuts down.
+pub struct TaskScheduler {
+    tasks: Vec<Task>,
+    shutdown_timeout: Duration,
+}
+
+impl TaskScheduler {
+    pub fn new() -> Self {
+        TaskScheduler {
+            tasks: Vec::new(),
+            shutdown_timeout: Duration::from_secs(10),
+        }
+    }
+
+    // Runs `task` every `period`, first after one period. A run that overruns delays the next
+    // one rather than overlapping it.
+    pub fn every<F, Fut>(self, period: Duration, name: &str, task: F) -> Self
+    where
+        F: Fn(CancellationToken) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = TaskResult> + Send + 'static,
+    {
+        self.add(name, Schedule::Every(period), task)
+    }
+
+    // Runs `task` once, `delay` after the scheduler starts
+    pub fn after<F, Fut>(self, delay: Duration, name: &str, task: F) -> Self
+    where
+        F: Fn(CancellationToken) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = TaskResult> + Send + 'static,
+    {
+        self.add(name, Schedule::After(delay), task)
+    }
+
+    // How long `RunningTasks::shutdown` waits for in-flight runs before aborting them
+    pub fn shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.shutdown_timeout = timeout;
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    fn add<F, Fut>(mut self, name: &str, schedule: Schedule, task: F) -> Self
+    where
+        F: Fn(CancellationToken) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = TaskResult> + Send + 'static,
+    {
+        self.tasks.push(Task {
+            name: name.to_string(),
+            schedule,
+            run: Arc::new(move |token| task(token).boxed()),
+        });
+        self
+    }
+
+    // Spawns every task on the current runtime. `Server::run` does this for the App's scheduler.
+    pub fn start(self) -> RunningTasks {
+        let token = CancellationToken::new();
+        let handles = self.tasks.into_iter()
+            .map(|task| {
+                let token = token.clone();
+                let name = task.name.clone();
+                (name, tokio::spawn(drive(task, token)))
+            })
+            .collect();
+
+        RunningTasks {
+            token,
+            handles,
+            shutdown_timeout: self.shutdown_timeout,
+        }
+    }
+}
+
+impl Default for TaskScheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+async fn drive(task: Task, token: CancellationToken) {
+    match task.schedule {
+        Schedule::After(delay) => {
+            tokio::select! {
+                _ = token.cancelled() => {}
+                _ = tokio::time::sleep(delay) => run_once(&task, &token).await,
+            }
+        }
+        Schedule::Every(period) => {
+            let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                tokio::select! {
+                    _ = token.cancelled() => break,
+                    _ = interval.tick() => run_once(&task, &token).await,
+                }
+            }
+        }
+    }
+}
+
+// A failing or panicking run is logged and doesn't stop later runs or other tasks
+async fn run_once(task: &Task, token: &CancellationToken) {
+    match AssertUnwindSafe((task.run)(token.clone())).catch_unwind().await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => log::error!("Task '{}' failed: {}", task.name, e),
+        Err(panic) => {
+            let message = panic.downcast_ref::<&str>().map(|s| s.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            log::error!("Task '{}' panicked: {}", task.name, message);
+        }
+    }
+}
+
+pub struct RunningTasks {
+    token: CancellationToken,
+    handles: Vec<(String, JoinHandle<()>)>,
+    shutdown_timeout: Duration,
+}
+
+impl RunningTasks {
+    // Cancelled when shutdown begins
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    // Cancels the token and waits for in-flight runs; whatever is still running after the
+    // shutdown timeout is aborted
+    pub async fn shutdown(self) {
+        self.token.cancel();
+        let deadline = tokio::time::Instant::now() + self.shutdown_timeout;
+        for (name, mut handle) in self.handles {
+            if tokio::time::timeout_at(deadline, &mut handle).await.is_err() {
+                log::warn!("Task '{}' did not stop within {:?}; aborting it", name, self.shutdown_timeout);
+                handle.abort();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn counter_task(count: &Arc<AtomicUsize>) -> impl Fn(CancellationToken) -> BoxFuture<'static, TaskResult> + Send + Sync + 'static {
+        let count = count.clone();
+        move |_token| {
+            count.fetch_add(1, Ordering::SeqCst);
+            async { Ok(()) }.boxed()
+        }
+    }
+
+    #[tokio::test]
+    async fn periodic_tasks_run_until_shutdown() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let running = TaskScheduler::new()
+            .every(Duration::from_millis(10), "tick", counter_task(&count))
+            .start();
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        running.shutdown().await;
+        let runs = count.load(Ordering::SeqCst);
+        assert!(runs >= 2, "ran {} times", runs);
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(count.load(Ordering::SeqCst), runs);
+    }
+
+    #[tokio::test]
+    async fn delayed_tasks_are_skipped_when_shut_down_first() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let running = TaskScheduler::new()
+            .after(Duration::from_secs(60), "later", counter_task(&count))
+            .start();
+
+        running.shutdown().await;
+        assert_eq!(count.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn a_panicking_run_does_not_stop_the_task() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let runs = count.clone();
+        let running = TaskScheduler::new()
+            .every(Duration::from_millis(10), "flaky", move |_token| {
+                let run = runs.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    if run == 0 {
+                        panic!("first run fails");
+                    }
+                    Ok(())
+                }
+            })
+            .start();
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        running.shutdown().await;
+        assert!(count.load(Ordering::SeqCst) >= 2);
+    }
+
+    #[tokio::test]
+    async fn shutdown_aborts_runs_past_the_timeout() {
+        let running = TaskScheduler::new()
+            .after(Duration::ZERO, "stuck", |_token| async {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                Ok(())
+            })
+            .shutdown_timeout(Duration::from_millis(20))
+            .start();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        tokio::time::timeout(Duration::from_secs(1), running.shutdown()).await
+            .expect("shutdown should give up after the timeout");
+    }
+}
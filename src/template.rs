@@ -1,23 +1,418 @@
+use crate::escape::escape_attribute;
 use crate::Response;
 use serde::Serialize;
+use serde_json::Value;
+use std::borrow::Cow;
+use std::collections::HashMap;
 
+// A parsed template: literal text and `{{ }}` tags
+#[derive(Clone, Debug)]
+enum Node {
+    Text(String),
+    // `{{ path }}` is escaped, `{{{ path }}}` inserted as is
+    Var { path: String, raw: bool },
+    // `{{#each path}}`, `{{#if path}}` or `{{#unless path}}`, with an optional `{{else}}` branch
+    Block { kind: BlockKind, path: String, body: Vec<Node>, else_body: Vec<Node> },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum BlockKind {
+    Each,
+    If,
+    Unless,
+}
+
+impl BlockKind {
+    fn name(self) -> &'static str {
+        match self {
+            BlockKind::Each => "each",
+            BlockKind::If => "if",
+            BlockKind::Unless => "unless",
+        }
+    }
+}
+
+// Mustache-style templates: `{{ name }}` inserts an HTML-escaped value from the data (see
+// `escape::escape_attribute`, safe in text and quoted attributes; URLs from users need
+// `escape::escape_url` first), `{{{ name }}}` inserts it unescaped (trusted content only).
+// Names may be dotted paths (`user.name`, `items.0`); missing values render as nothing.
+//
+// Blocks: `{{#each items}}..{{/each}}` renders once per array item (or object value), with the
+// item's fields in scope and the item itself as `this`, plus `@index` (and `@key` for objects).
+// Names not found on the item are looked up in the enclosing scopes. `{{#if x}}..{{else}}..{{/if}}`
+// and `#unless` branch on truthiness: missing, null, false, 0, "" and [] are false.
+// `#each` over nothing renders its `{{else}}` branch.
 #[derive(Clone, Debug)]
 pub struct TemplateEngine {
-    // Simple template engine without handlebars dependency
+    templates: HashMap<String, Vec<Node>>,
 }
 
 impl TemplateEngine {
     pub fn new() -> Self {
-        TemplateEngine {}
+        TemplateEngine {
+            templates: HashMap::new(),
+        }
+    }
+
+    // Reads and parses the file once; `render(name, ..)` uses the cached copy
+    pub fn register_template_file(&mut self, name: &str, path: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read template '{}' from {}: {}", name, path, e))?;
+        self.register_template(name, &source)
     }
 
-    pub fn register_template_file(&mut self, _name: &str, _path: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        // Simple implementation without handlebars
+    pub fn register_template(&mut self, name: &str, source: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let nodes = parse(source).map_err(|e| format!("Template '{}': {}", name, e))?;
+        self.templates.insert(name.to_string(), nodes);
         Ok(())
     }
 
-    pub fn render<T: Serialize>(&self, template: &str, _data: &T) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
-        // Simple template rendering
-        Ok(Response::new().html(&format!("<html><body>{}</body></html>", template)))
+    pub fn has_template(&self, name: &str) -> bool {
+        self.templates.contains_key(name)
+    }
+
+    // `template` is a registered name, or else the template source itself
+    pub fn render_to_string<T: Serialize>(&self, template: &str, data: &T) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let data = serde_json::to_value(data)?;
+        let parsed;
+        let nodes = match self.templates.get(template) {
+            Some(nodes) => nodes,
+            None => {
+                parsed = parse(template)?;
+                &parsed
+            }
+        };
+        let mut out = String::new();
+        let mut scopes = vec![Scope { value: &data, index: None, key: None }];
+        render_nodes(nodes, &mut scopes, &mut out);
+        Ok(out)
+    }
+
+    pub fn render<T: Serialize>(&self, template: &str, data: &T) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(Response::new().html(&self.render_to_string(template, data)?))
+    }
+}
+
+impl Default for TemplateEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+enum Token<'a> {
+    Text(&'a str),
+    Var { path: &'a str, raw: bool },
+    Open(BlockKind, &'a str),
+    Else,
+    Close(&'a str),
+}
+
+// Splits the source into text and tags, each with its byte offset for error messages
+fn tokenize(source: &str) -> Result<Vec<(Token<'_>, usize)>, String> {
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    while let Some(found) = source[pos..].find("{{") {
+        let start = pos + found;
+        if start > pos {
+            tokens.push((Token::Text(&source[pos..start]), pos));
+        }
+        let (raw, open, close) = if source[start..].starts_with("{{{") {
+            (true, "{{{", "}}}")
+        } else {
+            (false, "{{", "}}")
+        };
+        let inner_start = start + open.len();
+        let end = source[inner_start..]
+            .find(close)
+            .ok_or_else(|| format!("unclosed `{}` at byte {}", open, start))?;
+        let tag = source[inner_start..inner_start + end].trim();
+        pos = inner_start + end + close.len();
+
+        let token = if let Some(rest) = tag.strip_prefix('#') {
+            let (name, arg) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            let kind = match name {
+                "each" => BlockKind::Each,
+                "if" => BlockKind::If,
+                "unless" => BlockKind::Unless,
+                other => return Err(format!("unknown block `#{}` at byte {}", other, start)),
+            };
+            if arg.trim().is_empty() {
+                return Err(format!("`#{}` needs a name at byte {}", name, start));
+            }
+            Token::Open(kind, arg.trim())
+        } else if let Some(name) = tag.strip_prefix('/') {
+            // `else` isn't a block, and as a block end it would pass for `{{else}}`
+            if name.trim() == "else" {
+                return Err(format!("`{{{{/else}}}}` at byte {} closes nothing; use `{{{{else}}}}`", start));
+            }
+            Token::Close(name.trim())
+        } else if tag == "else" {
+            Token::Else
+        } else {
+            Token::Var { path: tag, raw }
+        };
+        tokens.push((token, start));
+    }
+    if pos < source.len() {
+        tokens.push((Token::Text(&source[pos..]), pos));
+    }
+
+    Ok(tokens)
+}
+
+fn parse(source: &str) -> Result<Vec<Node>, String> {
+    let tokens = tokenize(source)?;
+    let mut pos = 0;
+    let (nodes, end) = parse_nodes(&tokens, &mut pos)?;
+    match end {
+        None => Ok(nodes),
+        Some(("else", offset)) => Err(format!("`{{{{else}}}}` outside a block at byte {}", offset)),
+        Some((name, offset)) => Err(format!("`{{{{/{}}}}}` without an open block at byte {}", name, offset)),
+    }
+}
+
+// The tag that ended a run of nodes: `else` or a closing tag's name, with its byte offset
+type BlockEnd<'a> = Option<(&'a str, usize)>;
+
+// Parses until the end of input or a tag ending the current block, which is returned for the
+// caller to check
+fn parse_nodes<'a>(tokens: &[(Token<'a>, usize)], pos: &mut usize) -> Result<(Vec<Node>, BlockEnd<'a>), String> {
+    let mut nodes = Vec::new();
+
+    while let Some((token, offset)) = tokens.get(*pos) {
+        *pos += 1;
+        match token {
+            Token::Text(text) => nodes.push(Node::Text(text.to_string())),
+            Token::Var { path, raw } => nodes.push(Node::Var { path: path.to_string(), raw: *raw }),
+            Token::Else => return Ok((nodes, Some(("else", *offset)))),
+            Token::Close(name) => return Ok((nodes, Some((name, *offset)))),
+            Token::Open(kind, path) => {
+                let (body, mut end) = parse_nodes(tokens, pos)?;
+                let mut else_body = Vec::new();
+                if let Some(("else", _)) = end {
+                    let (nodes, else_end) = parse_nodes(tokens, pos)?;
+                    else_body = nodes;
+                    end = else_end;
+                }
+                match end {
+                    Some((name, _)) if name == kind.name() => {}
+                    Some((name, offset)) => {
+                        return Err(format!("`{{{{/{}}}}}` at byte {} doesn't close `#{}`", name, offset, kind.name()));
+                    }
+                    None => return Err(format!("`#{} {}` at byte {} is never closed", kind.name(), path, offset)),
+                }
+                nodes.push(Node::Block {
+                    kind: *kind,
+                    path: path.to_string(),
+                    body,
+                    else_body,
+                });
+            }
+        }
+    }
+
+    Ok((nodes, None))
+}
+
+// One level of data in scope: the root, then one per `#each` item
+struct Scope<'a> {
+    value: &'a Value,
+    index: Option<usize>,
+    key: Option<&'a str>,
+}
+
+fn render_nodes<'a>(nodes: &[Node], scopes: &mut Vec<Scope<'a>>, out: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Var { path, raw } => {
+                let value = display_value(resolve(scopes, path).as_deref());
+                if *raw {
+                    out.push_str(&value);
+                } else {
+                    out.push_str(&escape_attribute(&value));
+                }
+            }
+            Node::Block { kind: BlockKind::Each, path, body, else_body } => {
+                let items: Vec<Scope<'a>> = match lookup(scopes, path) {
+                    Some(Value::Array(items)) => items.iter()
+                        .enumerate()
+                        .map(|(i, value)| Scope { value, index: Some(i), key: None })
+                        .collect(),
+                    Some(Value::Object(map)) => map.iter()
+                        .enumerate()
+                        .map(|(i, (key, value))| Scope { value, index: Some(i), key: Some(key.as_str()) })
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                if items.is_empty() {
+                    render_nodes(else_body, scopes, out);
+                }
+                for item in items {
+                    scopes.push(item);
+                    render_nodes(body, scopes, out);
+                    scopes.pop();
+                }
+            }
+            Node::Block { kind, path, body, else_body } => {
+                let truthy = is_truthy(resolve(scopes, path).as_deref());
+                let branch = if truthy == (*kind == BlockKind::If) { body } else { else_body };
+                render_nodes(branch, scopes, out);
+            }
+        }
+    }
+}
+
+// Like `lookup`, plus the `@index`/`@key` of the innermost `#each`
+fn resolve<'a>(scopes: &[Scope<'a>], path: &str) -> Option<Cow<'a, Value>> {
+    let innermost = scopes.last()?;
+    match path {
+        "@index" => innermost.index.map(|i| Cow::Owned(Value::from(i))),
+        "@key" => innermost.key.map(|k| Cow::Owned(Value::from(k))),
+        _ => lookup(scopes, path).map(Cow::Borrowed),
+    }
+}
+
+// `this` (or `.`) is the innermost item and `this.name` a field of it. Other dotted paths walk
+// objects by key and arrays by index, starting from the innermost scope that has the first part.
+fn lookup<'a>(scopes: &[Scope<'a>], path: &str) -> Option<&'a Value> {
+    let innermost = scopes.last()?.value;
+    if path == "this" || path == "." {
+        return Some(innermost);
+    }
+    if let Some(rest) = path.strip_prefix("this.") {
+        return walk(innermost, rest);
+    }
+    let first = path.split('.').next().unwrap_or(path);
+    scopes.iter()
+        .rev()
+        .find(|scope| scope.value.get(first).is_some())
+        .and_then(|scope| walk(scope.value, path))
+}
+
+fn walk<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(value, |value, key| match value {
+        Value::Object(map) => map.get(key),
+        Value::Array(items) => key.parse::<usize>().ok().and_then(|i| items.get(i)),
+        _ => None,
+    })
+}
+
+fn is_truthy(value: Option<&Value>) -> bool {
+    match value {
+        None | Some(Value::Null) | Some(Value::Bool(false)) => false,
+        Some(Value::Number(n)) => n.as_f64() != Some(0.0),
+        Some(Value::String(s)) => !s.is_empty(),
+        Some(Value::Array(items)) => !items.is_empty(),
+        _ => true,
+    }
+}
+
+fn display_value(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => s.clone(),
+        Some(other) => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn render(template: &str, data: Value) -> String {
+        TemplateEngine::new().render_to_string(template, &data).unwrap()
+    }
+
+    fn parse_error(template: &str) -> String {
+        parse(template).unwrap_err()
+    }
+
+    #[test]
+    fn variables_are_escaped_unless_triple_braced() {
+        let data = json!({"html": "<b title=\"x\">Tom & 'Jerry'</b>"});
+        assert_eq!(
+            render("{{ html }}", data.clone()),
+            "&lt;b title=&quot;x&quot;&gt;Tom &amp; &#x27;Jerry&#x27;&lt;/b&gt;"
+        );
+        assert_eq!(render("{{{ html }}}", data), "<b title=\"x\">Tom & 'Jerry'</b>");
+    }
+
+    #[test]
+    fn missing_keys_render_empty() {
+        let data = json!({"user": {"name": "Ann", "tags": ["a"]}, "none": null});
+        assert_eq!(render("[{{ missing }}][{{ user.age }}][{{ user.tags.5 }}][{{ none }}]", data.clone()), "[][][][]");
+        assert_eq!(render("{{ user.name }} {{ user.tags.0 }}", data.clone()), "Ann a");
+        assert_eq!(render("{{#each missing}}x{{/each}}{{#if missing}}y{{/if}}", data), "");
+    }
+
+    #[test]
+    fn nested_each_sees_outer_scopes_and_loop_variables() {
+        let data = json!({
+            "sep": ":",
+            "groups": [
+                {"name": "a", "items": ["x", "y"]},
+                {"name": "b", "items": ["z"]},
+            ],
+        });
+        assert_eq!(
+            render("{{#each groups}}{{#each items}}{{ name }}{{ sep }}{{ @index }}={{ this }} {{/each}}{{/each}}", data),
+            "a:0=x a:1=y b:0=z "
+        );
+
+        let data = json!({"prices": {"apple": 1, "pear": 2}, "currency": "EUR"});
+        assert_eq!(
+            render("{{#each prices}}{{ @index }}.{{ @key }}={{ this }} {{ currency }};{{/each}}", data),
+            "0.apple=1 EUR;1.pear=2 EUR;"
+        );
+    }
+
+    #[test]
+    fn each_over_nothing_renders_else() {
+        let template = "{{#each items}}{{ this }}{{else}}none{{/each}}";
+        assert_eq!(render(template, json!({"items": []})), "none");
+        assert_eq!(render(template, json!({})), "none");
+        assert_eq!(render(template, json!({"items": [1, 2]})), "12");
+    }
+
+    #[test]
+    fn if_and_unless_branch_on_truthiness() {
+        let template = "{{#if x}}yes{{else}}no{{/if}}/{{#unless x}}yes{{else}}no{{/unless}}";
+        for falsy in [json!(null), json!(false), json!(0), json!(""), json!([])] {
+            assert_eq!(render(template, json!({"x": falsy})), "no/yes");
+        }
+        for truthy in [json!(true), json!(1), json!("a"), json!([0]), json!({})] {
+            assert_eq!(render(template, json!({"x": truthy})), "yes/no");
+        }
+        assert_eq!(render(template, json!({})), "no/yes");
+        assert_eq!(render("{{#if x}}only{{/if}}", json!({"x": false})), "");
+    }
+
+    #[test]
+    fn malformed_templates_are_parse_errors() {
+        assert_eq!(parse_error("a {{ name"), "unclosed `{{` at byte 2");
+        assert_eq!(parse_error("{{{ name }}"), "unclosed `{{{` at byte 0");
+        assert_eq!(parse_error("{{#with x}}{{/with}}"), "unknown block `#with` at byte 0");
+        assert_eq!(parse_error("{{#if}}{{/if}}"), "`#if` needs a name at byte 0");
+        assert_eq!(parse_error("{{#each items}}x"), "`#each items` at byte 0 is never closed");
+        assert_eq!(parse_error("{{#if a}}{{#each b}}{{/if}}"), "`{{/if}}` at byte 20 doesn't close `#each`");
+        assert_eq!(parse_error("{{#if a}}{{else}}{{/each}}"), "`{{/each}}` at byte 17 doesn't close `#if`");
+        assert_eq!(parse_error("x{{/if}}"), "`{{/if}}` without an open block at byte 1");
+        assert_eq!(parse_error("x{{else}}"), "`{{else}}` outside a block at byte 1");
+    }
+
+    #[test]
+    fn slash_else_is_not_else() {
+        assert_eq!(parse_error("{{#if a}}x{{/else}}y{{/if}}"), "`{{/else}}` at byte 10 closes nothing; use `{{else}}`");
+    }
+
+    #[test]
+    fn registration_errors_name_the_template() {
+        let mut engine = TemplateEngine::new();
+        let err = engine.register_template("page", "{{#if a}}").unwrap_err();
+        assert_eq!(err.to_string(), "Template 'page': `#if a` at byte 0 is never closed");
+        assert!(!engine.has_template("page"));
     }
 }
@@ -1,6 +1,9 @@
-use crate::ui::Element;
+use crate::config::get_config;
+use crate::ui::{div, p, text, Element};
+use serde::de::DeserializeOwned;
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use once_cell::sync::OnceCell;
 use tokio::sync::Mutex;
 use async_trait::async_trait;
@@ -8,6 +11,114 @@ use async_trait::async_trait;
 #[async_trait]
 pub trait Component: Send + Sync {
     async fn render(&self, props: &HashMap<String, Value>) -> Element;
+
+    // `render`, for components that can fail; only TypedComponents do, on props that don't fit
+    async fn try_render(&self, props: &HashMap<String, Value>) -> Result<Element, RenderError> {
+        Ok(self.render(props).await)
+    }
+}
+
+#[derive(Debug)]
+pub enum RenderError {
+    NotRegistered { name: String },
+    // A TypedComponent's props didn't deserialize
+    InvalidProps { component: String, source: serde_json::Error },
+}
+
+impl RenderError {
+    pub fn component(&self) -> &str {
+        match self {
+            RenderError::NotRegistered { name } => name,
+            RenderError::InvalidProps { component, .. } => component,
+        }
+    }
+
+    // The error boundary: what to show in place of the component. In development (see
+    // `Config::is_development`) a card naming the component and the error; otherwise the error is
+    // logged and the `set_render_fallback` element, an empty `div` by default, takes its place.
+    // Fits `unwrap_or_else`:
+    //
+    //     registry.render("card", &props).await.unwrap_or_else(RenderError::into_element)
+    pub fn into_element(self) -> Element {
+        log::error!("{}", self);
+        if get_config().is_development() {
+            div()
+                .class("component-error")
+                .child(p().child(Element::new("strong").child(text(&format!("Component \"{}\" failed to render", self.component())))))
+                .child(p().child(text(&self.to_string())))
+        } else {
+            RENDER_FALLBACK.get().map_or_else(div, |fallback| fallback(&self))
+        }
+    }
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderError::NotRegistered { name } => write!(f, "Component {} is not registered", name),
+            RenderError::InvalidProps { component, source } => write!(f, "Invalid props for component {}: {}", component, source),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RenderError::InvalidProps { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+static RENDER_FALLBACK: OnceCell<fn(&RenderError) -> Element> = OnceCell::new();
+
+// What `RenderError::into_element` shows outside development, e.g.
+// `set_render_fallback(|_| div().class("unavailable").child(text("Unavailable")))`
+pub fn set_render_fallback(fallback: fn(&RenderError) -> Element) {
+    if RENDER_FALLBACK.set(fallback).is_err() {
+        log::warn!("Render fallback already set, ignoring new fallback.");
+    }
+}
+
+// A Component that receives its props already deserialized, e.g.
+//
+//     #[derive(Deserialize)]
+//     struct CardProps { title: String, #[serde(default)] subtitle: Option<String> }
+//
+//     #[async_trait]
+//     impl TypedComponent for Card {
+//         type Props = CardProps;
+//
+//         async fn render(&self, props: CardProps) -> Element { .. }
+//     }
+//
+// or `typed_component!(Card, props: CardProps => ..)`. Every TypedComponent is a Component, so
+// it registers with `register_component!` as usual. Props that don't fit are a
+// `RenderError::InvalidProps`.
+#[async_trait]
+pub trait TypedComponent: Send + Sync {
+    type Props: DeserializeOwned + Send;
+
+    async fn render(&self, props: Self::Props) -> Element;
+}
+
+#[async_trait]
+impl<T: TypedComponent> Component for T {
+    async fn render(&self, props: &HashMap<String, Value>) -> Element {
+        self.try_render(props).await.unwrap_or_else(RenderError::into_element)
+    }
+
+    async fn try_render(&self, props: &HashMap<String, Value>) -> Result<Element, RenderError> {
+        let value = Value::Object(props.iter().map(|(key, value)| (key.clone(), value.clone())).collect());
+        match serde_json::from_value::<T::Props>(value) {
+            Ok(props) => Ok(TypedComponent::render(self, props).await),
+            Err(source) => {
+                let component = std::any::type_name::<T>();
+                let component = component.rsplit("::").next().unwrap_or(component).to_string();
+                Err(RenderError::InvalidProps { component, source })
+            }
+        }
+    }
 }
 
 pub struct ComponentRegistry {
@@ -28,12 +139,34 @@ impl ComponentRegistry {
         self.components.insert(name.to_string(), Box::new(component));
     }
 
-    pub async fn render(&self, name: &str, props: &HashMap<String, Value>) -> Option<Element> {
-        if let Some(component) = self.components.get(name) {
-            Some(component.render(props).await)
-        } else {
-            None
+    pub async fn render(&self, name: &str, props: &HashMap<String, Value>) -> Result<Element, RenderError> {
+        match self.components.get(name) {
+            Some(component) => component.try_render(props).await,
+            None => Err(RenderError::NotRegistered { name: name.to_string() }),
+        }
+    }
+
+    // Renders the component, then fills its `Element::slot` markers from `slots` by name. Slots
+    // left out render their default (or nothing); names the component has no slot for are logged
+    // and dropped.
+    pub async fn render_with_slots(
+        &self,
+        name: &str,
+        props: &HashMap<String, Value>,
+        slots: HashMap<String, Vec<Element>>,
+    ) -> Result<Element, RenderError> {
+        let element = self.render(name, props).await?;
+        let declared: HashSet<&str> = element.slot_names().into_iter().collect();
+        for slot in slots.keys().filter(|slot| !declared.contains(slot.as_str())) {
+            log::warn!("Component {} has no slot named {:?}", name, slot);
         }
+        Ok(element.fill_slots(&slots))
+    }
+}
+
+impl Default for ComponentRegistry {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -49,8 +182,25 @@ macro_rules! component {
         pub struct $name;
         
         #[async_trait]
-        impl crate::ui::Component for $name {
-            async fn render(&self, $props: &std::collections::HashMap<String, serde_json::Value>) -> crate::ui::Element {
+        impl $crate::ui::Component for $name {
+            async fn render(&self, $props: &std::collections::HashMap<String, serde_json::Value>) -> $crate::ui::Element {
+                $body
+            }
+        }
+    };
+}
+
+// `typed_component!(Card, props: CardProps => div().child(text(&props.title)))`
+#[macro_export]
+macro_rules! typed_component {
+    ($name:ident, $props:ident: $props_type:ty => $body:expr) => {
+        pub struct $name;
+
+        #[async_trait]
+        impl $crate::ui::TypedComponent for $name {
+            type Props = $props_type;
+
+            async fn render(&self, $props: $props_type) -> $crate::ui::Element {
                 $body
             }
         }
@@ -62,9 +212,137 @@ macro_rules! register_component {
     ($name:expr, $component_struct:ident) => {
         // This macro now expands to an async block that returns a Future
         async {
-            let mut registry = crate::ui::get_component_registry().lock().await; // Removed .unwrap()
+            let mut registry = $crate::ui::get_component_registry().lock().await; // Removed .unwrap()
             registry.register($name, $component_struct {});
             Ok::<(), Box<dyn std::error::Error + Send + Sync>>(()) // Return a Result
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::{get_renderer, h2};
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[derive(Deserialize)]
+    pub struct CardProps {
+        title: String,
+        #[serde(default)]
+        subtitle: Option<String>,
+    }
+
+    crate::typed_component!(Card, props: CardProps => {
+        let card = div().class("card").child(h2().child(text(&props.title)));
+        match props.subtitle {
+            Some(subtitle) => card.child(p().child(text(&subtitle))),
+            None => card,
+        }
+    });
+
+    fn props(value: Value) -> HashMap<String, Value> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn html(element: &Element) -> String {
+        get_renderer().render_to_html(element)
+    }
+
+    fn registry() -> ComponentRegistry {
+        let mut registry = ComponentRegistry::new();
+        registry.register("card", Card);
+        registry
+    }
+
+    #[tokio::test]
+    async fn typed_component_receives_deserialized_props() {
+        let registry = registry();
+
+        let element = registry.render("card", &props(json!({"title": "Hello"}))).await.unwrap();
+        assert_eq!(html(&element), "<div class=\"card\"><h2>Hello</h2></div>");
+
+        let element = registry.render("card", &props(json!({"title": "Hello", "subtitle": "World"}))).await.unwrap();
+        assert_eq!(html(&element), "<div class=\"card\"><h2>Hello</h2><p>World</p></div>");
+    }
+
+    #[tokio::test]
+    async fn props_that_dont_fit_are_invalid_props() {
+        let registry = registry();
+
+        for value in [json!({}), json!({"title": 7})] {
+            match registry.render("card", &props(value)).await {
+                Err(error @ RenderError::InvalidProps { .. }) => {
+                    assert_eq!(error.component(), "Card");
+                    assert!(error.to_string().starts_with("Invalid props for component Card: "));
+                    assert!(std::error::Error::source(&error).is_some());
+                }
+                other => panic!("expected InvalidProps, got {:?}", other.map(|element| html(&element))),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn typed_component_is_a_component() {
+        let element = Component::render(&Card, &props(json!({"title": "Direct"}))).await;
+        assert_eq!(html(&element), "<div class=\"card\"><h2>Direct</h2></div>");
+    }
+
+    crate::component!(Layout, _props => {
+        div()
+            .child(Element::new("aside").child(Element::slot("sidebar").child(text("Nothing here yet"))))
+            .child(Element::new("main").child(Element::slot("main")))
+    });
+
+    #[tokio::test]
+    async fn render_with_slots_fills_by_name() {
+        let mut registry = registry();
+        registry.register("layout", Layout);
+        let slots = HashMap::from([
+            ("main".to_string(), vec![p().child(text("Body"))]),
+            ("footer".to_string(), vec![p().child(text("Dropped"))]),
+        ]);
+
+        let element = registry.render_with_slots("layout", &HashMap::new(), slots).await.unwrap();
+        assert_eq!(html(&element), "<div><aside>Nothing here yet</aside><main><p>Body</p></main></div>");
+
+        let missing = registry.render_with_slots("missing", &HashMap::new(), HashMap::new()).await;
+        assert!(matches!(missing, Err(RenderError::NotRegistered { name }) if name == "missing"));
+    }
+
+    #[test]
+    fn not_registered_names_the_component() {
+        let error = RenderError::NotRegistered { name: "card".to_string() };
+        assert_eq!(error.component(), "card");
+        assert_eq!(error.to_string(), "Component card is not registered");
+        assert!(std::error::Error::source(&error).is_none());
+    }
+
+    #[test]
+    fn into_element_shows_the_error_in_development_only() {
+        set_render_fallback(|_| div().class("unavailable"));
+        let element = RenderError::NotRegistered { name: "card".to_string() }.into_element();
+
+        // The global config may be development or not, depending on the environment tests run in
+        if get_config().is_development() {
+            assert_eq!(
+                html(&element),
+                "<div class=\"component-error\"><p><strong>Component \"card\" failed to render</strong></p>\
+                 <p>Component card is not registered</p></div>"
+            );
+        } else {
+            assert_eq!(html(&element), "<div class=\"unavailable\"></div>");
+        }
+    }
+
+    #[tokio::test]
+    async fn typed_component_render_falls_back_to_the_error_boundary() {
+        let element = Component::render(&Card, &props(json!({"subtitle": "No title"}))).await;
+        let expected = match registry().render("card", &props(json!({}))).await {
+            Err(error) => error.into_element(),
+            Ok(_) => panic!("expected InvalidProps"),
+        };
+        assert_eq!(html(&element), html(&expected));
+        assert!(!html(&element).contains("No title"));
+    }
+}
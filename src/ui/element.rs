@@ -1,6 +1,9 @@
 use std::collections::HashMap;
 use serde_json::Value;
 
+// Tag of `Element::slot` markers; not a valid HTML tag, so it can't clash with a real element
+pub const SLOT_TAG: &str = "_slot";
+
 #[derive(Debug, Clone)]
 pub struct Element {
     pub tag: String,
@@ -50,6 +53,57 @@ impl Element {
         self.children.extend(children);
         self
     }
+
+    // A named insertion point in a layout component's output, filled by
+    // `ComponentRegistry::render_with_slots`:
+    //
+    //     div()
+    //         .child(Element::slot("sidebar").child(text("Nothing here yet")))
+    //         .child(main().child(Element::slot("main")))
+    //
+    // Its own children are the default, shown when the caller provides nothing for the slot. The
+    // marker renders as its contents alone, without a tag of its own.
+    pub fn slot(name: &str) -> Self {
+        Element::new(SLOT_TAG).prop("name", name)
+    }
+
+    // The name, if this is a slot marker
+    pub fn slot_name(&self) -> Option<&str> {
+        if self.tag != SLOT_TAG {
+            return None;
+        }
+        self.props.get("name").and_then(Value::as_str)
+    }
+
+    // The names of the slot markers in this tree, in document order
+    pub fn slot_names(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        self.collect_slot_names(&mut names);
+        names
+    }
+
+    fn collect_slot_names<'a>(&'a self, names: &mut Vec<&'a str>) {
+        if let Some(name) = self.slot_name() {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        for child in &self.children {
+            child.collect_slot_names(names);
+        }
+    }
+
+    // Replaces the contents of each slot marker that `slots` has elements for, keeping the
+    // default otherwise. The inserted elements aren't searched for slots of their own, so a nested
+    // layout's slots are left for its own render.
+    pub fn fill_slots(mut self, slots: &HashMap<String, Vec<Element>>) -> Self {
+        if let Some(content) = self.slot_name().and_then(|name| slots.get(name)) {
+            self.children = content.clone();
+            return self;
+        }
+        self.children = self.children.into_iter().map(|child| child.fill_slots(slots)).collect();
+        self
+    }
 }
 
 // Helper functions for common elements
@@ -137,3 +191,46 @@ pub fn text(content: &str) -> Element {
 pub fn label() -> Element {
     Element::new("label")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout() -> Element {
+        div()
+            .child(Element::slot("sidebar").child(text("Nothing here yet")))
+            .child(main().child(Element::slot("main")).child(Element::slot("sidebar")))
+    }
+
+    #[test]
+    fn slot_names_are_in_document_order_without_duplicates() {
+        assert_eq!(layout().slot_names(), vec!["sidebar", "main"]);
+        assert!(div().child(p()).slot_names().is_empty());
+        assert_eq!(Element::slot("main").slot_name(), Some("main"));
+        assert_eq!(div().prop("name", "main").slot_name(), None);
+    }
+
+    #[test]
+    fn fill_slots_replaces_contents_and_keeps_defaults() {
+        let slots = HashMap::from([("main".to_string(), vec![p().child(text("Body"))])]);
+        let filled = layout().fill_slots(&slots);
+
+        let sidebar = &filled.children[0];
+        assert_eq!(sidebar.children[0].text.as_deref(), Some("Nothing here yet"));
+        let main_slot = &filled.children[1].children[0];
+        assert_eq!(main_slot.slot_name(), Some("main"));
+        assert_eq!(main_slot.children.len(), 1);
+        assert_eq!(main_slot.children[0].tag, "p");
+    }
+
+    #[test]
+    fn inserted_elements_are_not_searched_for_slots() {
+        let nested = section().child(Element::slot("main").child(text("Inner default")));
+        let slots = HashMap::from([("main".to_string(), vec![nested])]);
+        let filled = div().child(Element::slot("main")).fill_slots(&slots);
+
+        let inner = &filled.children[0].children[0].children[0];
+        assert_eq!(inner.slot_name(), Some("main"));
+        assert_eq!(inner.children[0].text.as_deref(), Some("Inner default"));
+    }
+}
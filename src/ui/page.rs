@@ -32,6 +32,10 @@ impl PageRegistry {
         self.pages.insert(path.to_string(), Box::new(page));
     }
 
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.pages.keys().map(String::as_str)
+    }
+
     pub async fn render_page(&self, path: &str, req: &Request) -> Option<Element> {
         if let Some(page) = self.pages.get(path) {
             Some(page.render(req).await)
@@ -41,6 +45,12 @@ impl PageRegistry {
     }
 }
 
+impl Default for PageRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 static GLOBAL_PAGE_REGISTRY: OnceCell<Mutex<PageRegistry>> = OnceCell::new();
 
 pub fn get_page_registry() -> &'static Mutex<PageRegistry> {
@@ -53,8 +63,8 @@ macro_rules! page {
         pub struct $name;
         
         #[async_trait]
-        impl crate::ui::Page for $name {
-            async fn render(&self, $req: &crate::Request) -> crate::ui::Element {
+        impl $crate::ui::Page for $name {
+            async fn render(&self, $req: &$crate::Request) -> $crate::ui::Element {
                 $body
             }
         }
@@ -66,7 +76,7 @@ macro_rules! register_page {
     ($path:expr, $page_struct:ident) => {
         // This macro now expands to an async block that returns a Future
         async {
-            let mut registry = crate::ui::get_page_registry().lock().await; // Removed .unwrap()
+            let mut registry = $crate::ui::get_page_registry().lock().await; // Removed .unwrap()
             registry.register($path, $page_struct {});
             Ok::<(), Box<dyn std::error::Error + Send + Sync>>(()) // Return a Result
         }
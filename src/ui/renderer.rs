@@ -1,4 +1,5 @@
-use crate::ui::Element;
+use crate::escape::{escape_attribute, escape_text, escape_url, is_url_attribute};
+use crate::ui::{Element, SLOT_TAG};
 use crate::Response;
 use serde_json::Value;
 use once_cell::sync::OnceCell; // New import
@@ -14,11 +15,17 @@ impl Renderer {
         match element.tag.as_str() {
             "text" => {
                 if let Some(text) = &element.text {
-                    html_escape::encode_text(text).to_string()
+                    escape_text(text).into_owned()
                 } else {
                     String::new()
                 }
             }
+            // Slot markers contribute their contents only
+            SLOT_TAG => element.children.iter().map(|child| self.render_to_html(child)).collect(),
+            tag if !is_valid_tag_name(tag) => {
+                log::warn!("Skipping element with invalid tag name {:?}", tag);
+                String::new()
+            }
             _ => {
                 let mut html = format!("<{}", element.tag);
                 let mut inner_html_content: Option<String> = None; // New: To hold raw HTML content
@@ -29,6 +36,8 @@ impl Renderer {
                         if let Value::String(s) = value {
                             inner_html_content = Some(s.clone());
                         }
+                    } else if !is_valid_attribute_name(key) {
+                        log::warn!("Skipping attribute with invalid name {:?} on <{}>", key, element.tag);
                     } else {
                         let attr_value = match value {
                             Value::String(s) => s.clone(),
@@ -36,7 +45,8 @@ impl Renderer {
                             Value::Bool(b) => b.to_string(),
                             _ => value.to_string(),
                         };
-                        html.push_str(&format!(" {}=\"{}\"", key, html_escape::encode_double_quoted_attribute(&attr_value)));
+                        let escaped = if is_url_attribute(key) { escape_url(&attr_value) } else { escape_attribute(&attr_value) };
+                        html.push_str(&format!(" {}=\"{}\"", key, escaped));
                     }
                 }
                 
@@ -282,6 +292,18 @@ impl Renderer {
             font-size: 0.9rem;
         }}
 
+        /* Components that failed to render, in development */
+        .component-error {{
+            color: #721c24;
+            background-color: #f8d7da;
+            border: 2px dashed #dc3545;
+            padding: 0.75rem 1.25rem;
+            margin-bottom: 1rem;
+            border-radius: 6px;
+            font-family: monospace;
+            font-size: 0.9rem;
+        }}
+
         /* Responsive adjustments */
         @media (max-width: 768px) {{
             .container {{ padding: 15px; }}
@@ -303,9 +325,73 @@ impl Renderer {
     }
 }
 
+impl Default for Renderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Tag names must start with an ASCII letter and contain only ASCII letters,
+// digits or '-' (custom elements), so nothing can break out of the `<tag>` markup.
+fn is_valid_tag_name(tag: &str) -> bool {
+    let mut chars = tag.chars();
+    match chars.next() {
+        Some(first) if first.is_ascii_alphabetic() => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '-')
+        }
+        _ => false,
+    }
+}
+
+// Anything but whitespace, quotes, `>`, `/`, `=` and control characters, which would end the
+// name or the tag early
+fn is_valid_attribute_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.chars().all(|c| !c.is_whitespace() && !c.is_control() && !matches!(c, '"' | '\'' | '>' | '/' | '=' | '<'))
+}
+
 // Global renderer instance using once_cell
 static GLOBAL_RENDERER: OnceCell<Renderer> = OnceCell::new();
 
 pub fn get_renderer() -> &'static Renderer {
     GLOBAL_RENDERER.get_or_init(Renderer::new)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::{div, text};
+
+    #[test]
+    fn skips_elements_with_invalid_tag_names() {
+        let element = div()
+            .child(Element::new("img src=x onerror=alert(1)"))
+            .child(Element::new("1h"))
+            .child(Element::new("my-widget").child(text("ok")));
+
+        assert_eq!(get_renderer().render_to_html(&element), "<div><my-widget>ok</my-widget></div>");
+    }
+
+    #[test]
+    fn skips_attributes_with_invalid_names() {
+        let element = div().prop("data-id", 7).prop("x onclick", "alert(1)").prop("a\"b", "c");
+        assert_eq!(get_renderer().render_to_html(&element), "<div data-id=\"7\"></div>");
+    }
+
+    #[test]
+    fn tag_names_must_start_with_a_letter() {
+        assert!(is_valid_tag_name("h1"));
+        assert!(is_valid_tag_name("x-foo-2"));
+        assert!(!is_valid_tag_name(""));
+        assert!(!is_valid_tag_name("-x"));
+        assert!(!is_valid_tag_name("div>"));
+        assert!(!is_valid_tag_name("div/"));
+    }
+
+    #[test]
+    fn slot_markers_render_as_their_contents() {
+        let element = div().child(Element::slot("main").child(text("a")).child(Element::new("b").child(text("c"))));
+        assert_eq!(get_renderer().render_to_html(&element), "<div>a<b>c</b></div>");
+        assert_eq!(get_renderer().render_to_html(&Element::slot("empty")), "");
+    }
+}
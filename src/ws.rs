@@ -0,0 +1,113 @@
+use crate::{AppError, Handler, Request, Response};
+use async_trait::async_trait;
+use hyper::header::{HeaderMap, HeaderValue, CONNECTION, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_KEY, SEC_WEBSOCKET_VERSION, UPGRADE};
+use hyper::upgrade::Upgraded;
+use hyper::StatusCode;
+use std::future::Future;
+use std::sync::Arc;
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::Role;
+use tokio_tungstenite::WebSocketStream;
+
+pub use tokio_tungstenite::tungstenite::Message;
+
+// The connection handed to a `Router::ws` handler; it's a Stream of incoming `Message`s and a
+// Sink for outgoing ones (see futures' StreamExt/SinkExt)
+pub type WebSocket = WebSocketStream<Upgraded>;
+
+// What a `Router::ws` route runs once the connection is upgraded, usually an async closure, e.g.
+//
+//     Router::new().ws("/live/:room", |mut socket: WebSocket, req: Request| async move {
+//         while let Some(Ok(message)) = socket.next().await {
+//             if message.is_text() && socket.send(message).await.is_err() {
+//                 break;
+//             }
+//         }
+//     })
+//
+// `req` is the upgrade request, with its params, session, user etc. The connection closes when
+// the handler returns.
+#[async_trait]
+pub trait WebSocketHandler: Send + Sync + 'static {
+    async fn handle(&self, socket: WebSocket, req: Request);
+}
+
+#[async_trait]
+impl<F, Fut> WebSocketHandler for F
+where
+    F: Fn(WebSocket, Request) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    async fn handle(&self, socket: WebSocket, req: Request) {
+        self(socket, req).await
+    }
+}
+
+// `Upgrade: websocket` on a GET; the Server keeps hold of such connections so they can be
+// upgraded once routed
+pub(crate) fn is_upgrade_request(method: &hyper::Method, headers: &HeaderMap) -> bool {
+    method == hyper::Method::GET && header_has_token(headers, UPGRADE, "websocket")
+}
+
+// Comma-separated, case-insensitive tokens, e.g. `Connection: keep-alive, Upgrade`
+fn header_has_token(headers: &HeaderMap, name: hyper::header::HeaderName, token: &str) -> bool {
+    headers.get_all(name).iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|value| value.trim().eq_ignore_ascii_case(token))
+}
+
+// The Handler behind a `Router::ws` route: checks the handshake (RFC 6455 section 4.2), answers
+// 101 Switching Protocols and runs the WebSocketHandler on the upgraded connection in its own task
+pub(crate) struct Upgrade<H> {
+    handler: Arc<H>,
+}
+
+impl<H: WebSocketHandler> Upgrade<H> {
+    pub(crate) fn new(handler: H) -> Self {
+        Upgrade { handler: Arc::new(handler) }
+    }
+}
+
+#[async_trait]
+impl<H: WebSocketHandler> Handler for Upgrade<H> {
+    async fn handle(&self, mut req: Request) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+        if !is_upgrade_request(&req.method, &req.headers) || !header_has_token(&req.headers, CONNECTION, "upgrade") {
+            return Ok(Response::new()
+                .status(StatusCode::UPGRADE_REQUIRED)
+                .header(UPGRADE, "websocket")
+                .header(CONNECTION, "Upgrade")
+                .text("Expected a WebSocket upgrade"));
+        }
+        if req.headers.get(SEC_WEBSOCKET_VERSION).map(HeaderValue::as_bytes) != Some(b"13") {
+            return Ok(Response::new()
+                .status(StatusCode::UPGRADE_REQUIRED)
+                .header(SEC_WEBSOCKET_VERSION, "13")
+                .text("Unsupported WebSocket version"));
+        }
+        let accept = match req.headers.get(SEC_WEBSOCKET_KEY) {
+            Some(key) => derive_accept_key(key.as_bytes()),
+            None => return Err(Box::new(AppError::BadRequest("Missing Sec-WebSocket-Key".to_string()))),
+        };
+        // Only the Server can upgrade; a request built any other way has no connection to take over
+        let on_upgrade = req.upgrade.take()
+            .ok_or_else(|| AppError::Internal("Connection can't be upgraded".to_string()))?;
+
+        let handler = self.handler.clone();
+        tokio::spawn(async move {
+            match on_upgrade.await {
+                Ok(upgraded) => {
+                    let socket = WebSocketStream::from_raw_socket(upgraded, Role::Server, None).await;
+                    handler.handle(socket, req).await;
+                }
+                Err(e) => log::warn!("WebSocket upgrade failed: {}", e),
+            }
+        });
+
+        Ok(Response::new()
+            .status(StatusCode::SWITCHING_PROTOCOLS)
+            .header(UPGRADE, "websocket")
+            .header(CONNECTION, "Upgrade")
+            .header(SEC_WEBSOCKET_ACCEPT, accept))
+    }
+}